@@ -0,0 +1,53 @@
+// Compute the build id of the compiler, which identifies the code that data stored on disk was produced with.
+//
+// The id is a hash of everything the layout of that data depends on: the sources of the compiler and its macros, and the exact versions of its dependencies.
+// Unlike the package version, it changes with every change to the compiler, committed or not,
+// and it is the same for every binary built from the same sources, so the LSP server can use metadata written by the compiler.
+
+use std::{
+    env,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use xxhash_rust::xxh3::Xxh3;
+
+const INPUTS: &[&str] = &["src", "crates", "build.rs", "Cargo.toml", "Cargo.lock"];
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) {
+    if path.is_dir() {
+        let Ok(entries) = fs::read_dir(path) else { return };
+        for entry in entries.flatten() {
+            collect_files(&entry.path(), files);
+        }
+    } else if path.is_file() {
+        files.push(path.to_path_buf());
+    }
+}
+
+fn main() {
+    let root = PathBuf::from(env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR is set by cargo"));
+
+    let mut files = Vec::new();
+    for input in INPUTS {
+        println!("cargo:rerun-if-changed={input}");
+        collect_files(&root.join(input), &mut files);
+    }
+    // Sort by the relative path with `/` separators, so the id does not depend on the directory order or the platform
+    let mut files: Vec<(String, PathBuf)> = files.into_iter()
+        .map(|path| (path.strip_prefix(&root).unwrap_or(&path).to_string_lossy().replace('\\', "/"), path))
+        .collect();
+    files.sort();
+
+    let mut hasher = Xxh3::new();
+    for (rel_path, path) in &files {
+        let content = fs::read(path).unwrap_or_default();
+        // Prefix each part with its length, so different splits of the same bytes don't result in the same id
+        hasher.update(&(rel_path.len() as u64).to_le_bytes());
+        hasher.update(rel_path.as_bytes());
+        hasher.update(&(content.len() as u64).to_le_bytes());
+        hasher.update(&content);
+    }
+
+    println!("cargo:rustc-env=XENON_BUILD_ID={}-{:016x}", env::var("CARGO_PKG_VERSION").unwrap_or_default(), hasher.digest());
+}
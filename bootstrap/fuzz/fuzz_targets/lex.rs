@@ -0,0 +1,9 @@
+#![no_main]
+
+use bootstrap_fuzz::Tables;
+use libfuzzer_sys::fuzz_target;
+
+// Raw bytes are decoded like a source file, so invalid sequences and other encodings are fuzzed as well
+fuzz_target!(|data: &[u8]| {
+    Tables::new().lex_bytes(data);
+});
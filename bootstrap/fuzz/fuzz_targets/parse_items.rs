@@ -0,0 +1,11 @@
+#![no_main]
+
+use bootstrap_fuzz::{SourceFile, Tables};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|file: SourceFile| {
+    let source = file.to_string();
+    let tables = Tables::new();
+    let tokens = tables.lex(&source);
+    tables.parse(&tokens);
+});
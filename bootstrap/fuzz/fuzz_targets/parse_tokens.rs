@@ -0,0 +1,10 @@
+#![no_main]
+
+use bootstrap_fuzz::Vocabulary;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let vocabulary = Vocabulary::new();
+    let tokens = vocabulary.token_stream(data);
+    vocabulary.tables.parse(&tokens);
+});
@@ -0,0 +1,850 @@
+// Structure-aware generation of syntactically plausible Xenon source.
+//
+// The types mirror a subset of the grammar and derive `Arbitrary`, so the fuzzer mutates the structure of a file instead of its bytes.
+// Generated code does not need to be valid, it only needs to get past the lexer often enough for the parser to be exercised.
+
+use std::fmt::{self, Write};
+
+use arbitrary::Arbitrary;
+
+/// Maximum nesting of types, expressions and patterns, deeper nodes are replaced by a leaf
+const MAX_DEPTH: u32 = 12;
+
+const NAMES: &[&str] = &["a", "b", "c", "foo", "bar", "Foo", "Bar", "T", "U", "Trait", "self", "Self", "super"];
+const PRIM_TYPES: &[&str] = &["i8", "i32", "u8", "u64", "usize", "f32", "f64", "bool", "char", "str", "cstr"];
+const LITERALS: &[&str] = &["0", "1", "42", "0b1010", "0o17", "0xCAFE", "1.5", "2.0:f32", "3:u8", "'c'", "\"str\"", "true", "false"];
+const BINARY_OPS: &[&str] = &["+", "-", "*", "/", "%", "==", "!=", "<", "<=", ">", ">=", "&&", "||", "&", "|", "^", "<<", ">>", "=", "+=", "-=", "..", "..=", "in", "!in"];
+const PREFIX_OPS: &[&str] = &["-", "!", "&", "&mut ", "+", "~"];
+const POSTFIX_OPS: &[&str] = &["?", "!", "^"];
+
+#[derive(Arbitrary, Debug, Clone, Copy)]
+pub struct Name(u8);
+
+#[derive(Arbitrary, Debug)]
+pub struct Path {
+    pub leading_dot: bool,
+    pub first:       Name,
+    pub rest:        Vec<Name>,
+}
+
+#[derive(Arbitrary, Debug)]
+pub struct SourceFile {
+    pub module_attrs: Vec<Attribute>,
+    pub items:        Vec<Item>,
+}
+
+#[derive(Arbitrary, Debug)]
+pub struct Attribute {
+    pub name: Name,
+    pub args: Vec<Name>,
+}
+
+#[derive(Arbitrary, Debug)]
+pub enum Visibility {
+    Priv,
+    Pub,
+    PubPackage,
+    PubLib,
+}
+
+#[derive(Arbitrary, Debug)]
+pub struct Generics {
+    pub params: Vec<Name>,
+}
+
+#[derive(Arbitrary, Debug)]
+pub struct Item {
+    pub attrs: Vec<Attribute>,
+    pub vis:   Visibility,
+    pub kind:  ItemKind,
+}
+
+#[derive(Arbitrary, Debug)]
+pub enum ItemKind {
+    Use(Path),
+    Mod { name: Name, items: Option<Vec<Item>> },
+    Fn(Function),
+    Struct { is_mut: bool, is_record: bool, name: Name, generics: Generics, fields: Vec<(Name, Type)> },
+    TupleStruct { name: Name, fields: Vec<Type> },
+    Union { name: Name, fields: Vec<(Name, Type)> },
+    Enum { name: Name, variants: Vec<Variant> },
+    TypeAlias { is_distinct: bool, name: Name, ty: Type },
+    Const { name: Name, ty: Option<Type>, value: Expr },
+    Static { is_mut: bool, is_tls: bool, name: Name, ty: Type, value: Expr },
+    Trait { is_unsafe: bool, is_sealed: bool, name: Name, generics: Generics, items: Vec<Function> },
+    Impl { ty: Type, trait_path: Option<Path>, items: Vec<Function> },
+}
+
+#[derive(Arbitrary, Debug)]
+pub enum Variant {
+    Unit(Name),
+    Tuple(Name, Vec<Type>),
+    Struct(Name, Vec<(Name, Type)>),
+}
+
+#[derive(Arbitrary, Debug)]
+pub struct Function {
+    pub is_const:  bool,
+    pub is_unsafe: bool,
+    pub name:      Name,
+    pub generics:  Generics,
+    pub receiver:  Option<Receiver>,
+    pub params:    Vec<(Pattern, Type)>,
+    pub ret:       Option<Type>,
+    pub body:      Option<Block>,
+}
+
+#[derive(Arbitrary, Debug)]
+pub enum Receiver {
+    Value,
+    Ref,
+    RefMut,
+    Typed(Type),
+}
+
+#[derive(Arbitrary, Debug)]
+pub enum Type {
+    Prim(u8),
+    Path(Path),
+    Unit,
+    Never,
+    Ref { is_mut: bool, ty: Box<Type> },
+    Ptr { is_mut: bool, ty: Box<Type> },
+    Slice(Box<Type>),
+    Array { len: u8, ty: Box<Type> },
+    Tuple(Vec<Type>),
+    Optional(Box<Type>),
+    Fn { params: Vec<Type>, ret: Option<Box<Type>> },
+    Record(Vec<(Name, Type)>),
+    Dyn(Path),
+    Impl(Path),
+}
+
+#[derive(Arbitrary, Debug)]
+pub struct Block {
+    pub stmts: Vec<Stmt>,
+    pub tail:  Option<Box<Expr>>,
+}
+
+#[derive(Arbitrary, Debug)]
+pub enum Stmt {
+    Let { pattern: Pattern, ty: Option<Type>, value: Option<Expr> },
+    Decl { is_mut: bool, name: Name, value: Expr },
+    Expr(Expr),
+    Defer(Block),
+    Item(Box<Item>),
+}
+
+#[derive(Arbitrary, Debug)]
+pub enum Expr {
+    Literal(u8),
+    Path(Path),
+    Underscore,
+    Unit,
+    Prefix { op: u8, expr: Box<Expr> },
+    Postfix { op: u8, expr: Box<Expr> },
+    Binary { op: u8, lhs: Box<Expr>, rhs: Box<Expr> },
+    Paren(Box<Expr>),
+    Tuple(Vec<Expr>),
+    Array(Vec<Expr>),
+    Call { func: Box<Expr>, args: Vec<Expr> },
+    Method { receiver: Box<Expr>, name: Name, args: Vec<Expr> },
+    Field { expr: Box<Expr>, name: Name },
+    Index { expr: Box<Expr>, index: Box<Expr> },
+    Struct { path: Path, fields: Vec<(Name, Expr)> },
+    Cast { expr: Box<Expr>, ty: Type },
+    Block(Block),
+    Unsafe(Block),
+    If { cond: Box<Expr>, then: Block, otherwise: Option<Block> },
+    IfLet { pattern: Pattern, value: Box<Expr>, then: Block },
+    Loop { label: Option<Name>, body: Block },
+    While { cond: Box<Expr>, body: Block },
+    For { pattern: Pattern, iter: Box<Expr>, body: Block },
+    Match { scrutinee: Box<Expr>, arms: Vec<MatchArm> },
+    Closure { params: Vec<Name>, body: Box<Expr> },
+    Return(Option<Box<Expr>>),
+    Break(Option<Name>),
+    Continue,
+}
+
+#[derive(Arbitrary, Debug)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard:   Option<Expr>,
+    pub body:    Expr,
+}
+
+#[derive(Arbitrary, Debug)]
+pub enum Pattern {
+    Wildcard,
+    Rest,
+    Name { is_ref: bool, is_mut: bool, name: Name },
+    Literal(u8),
+    Range { start: u8, end: u8, inclusive: bool },
+    Path(Path),
+    EnumMember(Name),
+    Tuple(Vec<Pattern>),
+    Slice(Vec<Pattern>),
+    TupleStruct(Path, Vec<Pattern>),
+    Struct(Path, Vec<(Name, Pattern)>),
+    Reference { is_mut: bool, pattern: Box<Pattern> },
+    Alternative(Vec<Pattern>),
+    TypeCheck(Type),
+}
+
+// =============================================================================================================================
+
+fn pick<'a>(list: &[&'a str], idx: u8) -> &'a str {
+    list[idx as usize % list.len()]
+}
+
+/// Writes generated nodes as source, keeping track of the nesting depth
+struct Printer<'a> {
+    out:    &'a mut dyn Write,
+    depth:  u32,
+    indent: u32,
+}
+
+impl Printer<'_> {
+    fn newline(&mut self) -> fmt::Result {
+        writeln!(self.out)?;
+        for _ in 0..self.indent {
+            write!(self.out, "    ")?;
+        }
+        Ok(())
+    }
+
+    fn too_deep(&self) -> bool {
+        self.depth >= MAX_DEPTH
+    }
+
+    fn nested(&mut self, f: impl FnOnce(&mut Self) -> fmt::Result) -> fmt::Result {
+        self.depth += 1;
+        let res = f(self);
+        self.depth -= 1;
+        res
+    }
+
+    fn list<T>(&mut self, items: &[T], sep: &str, mut f: impl FnMut(&mut Self, &T) -> fmt::Result) -> fmt::Result {
+        for (idx, item) in items.iter().enumerate() {
+            if idx != 0 {
+                write!(self.out, "{sep}")?;
+            }
+            f(self, item)?;
+        }
+        Ok(())
+    }
+
+    fn name(&mut self, name: Name) -> fmt::Result {
+        write!(self.out, "{}", pick(NAMES, name.0))
+    }
+
+    fn path(&mut self, path: &Path) -> fmt::Result {
+        if path.leading_dot {
+            write!(self.out, ".")?;
+        }
+        self.name(path.first)?;
+        for name in &path.rest {
+            write!(self.out, ".")?;
+            self.name(*name)?;
+        }
+        Ok(())
+    }
+
+    fn attrs(&mut self, attrs: &[Attribute], is_mod: bool) -> fmt::Result {
+        for attr in attrs {
+            write!(self.out, "{}", if is_mod { "@!" } else { "@" })?;
+            self.name(attr.name)?;
+            if !attr.args.is_empty() {
+                write!(self.out, "(")?;
+                self.list(&attr.args, ", ", |p, arg| p.name(*arg))?;
+                write!(self.out, ")")?;
+            }
+            self.newline()?;
+        }
+        Ok(())
+    }
+
+    fn generics(&mut self, generics: &Generics) -> fmt::Result {
+        if generics.params.is_empty() {
+            return Ok(());
+        }
+        write!(self.out, "[")?;
+        self.list(&generics.params, ", ", |p, param| p.name(*param))?;
+        write!(self.out, "]")
+    }
+
+    fn fields(&mut self, fields: &[(Name, Type)]) -> fmt::Result {
+        self.list(fields, ", ", |p, (name, ty)| {
+            p.name(*name)?;
+            write!(p.out, ": ")?;
+            p.ty(ty)
+        })
+    }
+
+    fn item(&mut self, item: &Item) -> fmt::Result {
+        self.attrs(&item.attrs, false)?;
+        match item.vis {
+            Visibility::Priv       => {},
+            Visibility::Pub        => write!(self.out, "pub ")?,
+            Visibility::PubPackage => write!(self.out, "pub(package) ")?,
+            Visibility::PubLib     => write!(self.out, "pub(lib) ")?,
+        }
+
+        match &item.kind {
+            ItemKind::Use(path) => {
+                write!(self.out, "use :.")?;
+                self.path(path)?;
+                write!(self.out, ";")?;
+            },
+            ItemKind::Mod { name, items } => {
+                write!(self.out, "mod ")?;
+                self.name(*name)?;
+                match items {
+                    Some(items) if !self.too_deep() => {
+                        write!(self.out, " {{")?;
+                        self.indent += 1;
+                        self.nested(|p| {
+                            for item in items {
+                                p.newline()?;
+                                p.item(item)?;
+                            }
+                            Ok(())
+                        })?;
+                        self.indent -= 1;
+                        self.newline()?;
+                        write!(self.out, "}}")?;
+                    },
+                    _ => write!(self.out, ";")?,
+                }
+            },
+            ItemKind::Fn(func) => self.function(func)?,
+            ItemKind::Struct { is_mut, is_record, name, generics, fields } => {
+                if *is_mut {
+                    write!(self.out, "mut ")?;
+                }
+                if *is_record {
+                    write!(self.out, "record ")?;
+                }
+                write!(self.out, "struct ")?;
+                self.name(*name)?;
+                self.generics(generics)?;
+                write!(self.out, " {{ ")?;
+                self.fields(fields)?;
+                write!(self.out, " }}")?;
+            },
+            ItemKind::TupleStruct { name, fields } => {
+                write!(self.out, "struct ")?;
+                self.name(*name)?;
+                write!(self.out, "(")?;
+                self.list(fields, ", ", |p, ty| p.ty(ty))?;
+                write!(self.out, ");")?;
+            },
+            ItemKind::Union { name, fields } => {
+                write!(self.out, "union ")?;
+                self.name(*name)?;
+                write!(self.out, " {{ ")?;
+                self.fields(fields)?;
+                write!(self.out, " }}")?;
+            },
+            ItemKind::Enum { name, variants } => {
+                write!(self.out, "enum ")?;
+                self.name(*name)?;
+                write!(self.out, " {{ ")?;
+                self.list(variants, ", ", |p, variant| match variant {
+                    Variant::Unit(name) => p.name(*name),
+                    Variant::Tuple(name, types) => {
+                        p.name(*name)?;
+                        write!(p.out, "(")?;
+                        p.list(types, ", ", |p, ty| p.ty(ty))?;
+                        write!(p.out, ")")
+                    },
+                    Variant::Struct(name, fields) => {
+                        p.name(*name)?;
+                        write!(p.out, "{{ ")?;
+                        p.fields(fields)?;
+                        write!(p.out, " }}")
+                    },
+                })?;
+                write!(self.out, " }}")?;
+            },
+            ItemKind::TypeAlias { is_distinct, name, ty } => {
+                if *is_distinct {
+                    write!(self.out, "distinct ")?;
+                }
+                write!(self.out, "type ")?;
+                self.name(*name)?;
+                write!(self.out, " = ")?;
+                self.ty(ty)?;
+                write!(self.out, ";")?;
+            },
+            ItemKind::Const { name, ty, value } => {
+                write!(self.out, "const ")?;
+                self.name(*name)?;
+                if let Some(ty) = ty {
+                    write!(self.out, ": ")?;
+                    self.ty(ty)?;
+                }
+                write!(self.out, " = ")?;
+                self.expr(value)?;
+                write!(self.out, ";")?;
+            },
+            ItemKind::Static { is_mut, is_tls, name, ty, value } => {
+                if *is_mut {
+                    write!(self.out, "mut ")?;
+                }
+                if *is_tls {
+                    write!(self.out, "tls ")?;
+                }
+                write!(self.out, "static ")?;
+                self.name(*name)?;
+                write!(self.out, ": ")?;
+                self.ty(ty)?;
+                write!(self.out, " = ")?;
+                self.expr(value)?;
+                write!(self.out, ";")?;
+            },
+            ItemKind::Trait { is_unsafe, is_sealed, name, generics, items } => {
+                if *is_unsafe {
+                    write!(self.out, "unsafe ")?;
+                }
+                if *is_sealed {
+                    write!(self.out, "sealed ")?;
+                }
+                write!(self.out, "trait ")?;
+                self.name(*name)?;
+                self.generics(generics)?;
+                self.assoc_items(items)?;
+            },
+            ItemKind::Impl { ty, trait_path, items } => {
+                write!(self.out, "impl ")?;
+                self.ty(ty)?;
+                if let Some(path) = trait_path {
+                    write!(self.out, " as ")?;
+                    self.path(path)?;
+                }
+                self.assoc_items(items)?;
+            },
+        }
+        self.newline()
+    }
+
+    fn assoc_items(&mut self, items: &[Function]) -> fmt::Result {
+        write!(self.out, " {{")?;
+        self.indent += 1;
+        for func in items {
+            self.newline()?;
+            self.function(func)?;
+        }
+        self.indent -= 1;
+        self.newline()?;
+        write!(self.out, "}}")
+    }
+
+    fn function(&mut self, func: &Function) -> fmt::Result {
+        if func.is_const {
+            write!(self.out, "const ")?;
+        }
+        if func.is_unsafe {
+            write!(self.out, "unsafe ")?;
+        }
+        write!(self.out, "fn ")?;
+        self.name(func.name)?;
+        self.generics(&func.generics)?;
+        write!(self.out, "(")?;
+        if let Some(receiver) = &func.receiver {
+            match receiver {
+                Receiver::Value  => write!(self.out, "self")?,
+                Receiver::Ref    => write!(self.out, "&self")?,
+                Receiver::RefMut => write!(self.out, "&mut self")?,
+                Receiver::Typed(ty) => {
+                    write!(self.out, "self: ")?;
+                    self.ty(ty)?;
+                },
+            }
+            if !func.params.is_empty() {
+                write!(self.out, ", ")?;
+            }
+        }
+        self.list(&func.params, ", ", |p, (pattern, ty)| {
+            p.pattern(pattern)?;
+            write!(p.out, ": ")?;
+            p.ty(ty)
+        })?;
+        write!(self.out, ")")?;
+        if let Some(ret) = &func.ret {
+            write!(self.out, " -> ")?;
+            self.ty(ret)?;
+        }
+        match &func.body {
+            Some(body) => {
+                write!(self.out, " ")?;
+                self.block(body)
+            },
+            None => write!(self.out, ";"),
+        }
+    }
+
+    fn ty(&mut self, ty: &Type) -> fmt::Result {
+        if self.too_deep() {
+            return write!(self.out, "i32");
+        }
+        self.nested(|p| match ty {
+            Type::Prim(idx) => write!(p.out, "{}", pick(PRIM_TYPES, *idx)),
+            Type::Path(path) => p.path(path),
+            Type::Unit => write!(p.out, "()"),
+            Type::Never => write!(p.out, "!"),
+            Type::Ref { is_mut, ty } => {
+                write!(p.out, "{}", if *is_mut { "&mut " } else { "&" })?;
+                p.ty(ty)
+            },
+            Type::Ptr { is_mut, ty } => {
+                write!(p.out, "{}", if *is_mut { "^mut " } else { "^" })?;
+                p.ty(ty)
+            },
+            Type::Slice(ty) => {
+                write!(p.out, "[]")?;
+                p.ty(ty)
+            },
+            Type::Array { len, ty } => {
+                write!(p.out, "[{len}]")?;
+                p.ty(ty)
+            },
+            Type::Tuple(types) => {
+                write!(p.out, "(")?;
+                p.list(types, ", ", |p, ty| p.ty(ty))?;
+                write!(p.out, ")")
+            },
+            Type::Optional(ty) => {
+                write!(p.out, "?")?;
+                p.ty(ty)
+            },
+            Type::Fn { params, ret } => {
+                write!(p.out, "fn(")?;
+                p.list(params, ", ", |p, ty| p.ty(ty))?;
+                write!(p.out, ")")?;
+                if let Some(ret) = ret {
+                    write!(p.out, " -> ")?;
+                    p.ty(ret)?;
+                }
+                Ok(())
+            },
+            Type::Record(fields) => {
+                write!(p.out, "{{ ")?;
+                p.fields(fields)?;
+                write!(p.out, " }}")
+            },
+            Type::Dyn(path) => {
+                write!(p.out, "dyn ")?;
+                p.path(path)
+            },
+            Type::Impl(path) => {
+                write!(p.out, "impl ")?;
+                p.path(path)
+            },
+        })
+    }
+
+    fn block(&mut self, block: &Block) -> fmt::Result {
+        write!(self.out, "{{")?;
+        self.indent += 1;
+        if !self.too_deep() {
+            self.nested(|p| {
+                for stmt in &block.stmts {
+                    p.newline()?;
+                    p.stmt(stmt)?;
+                }
+                if let Some(tail) = &block.tail {
+                    p.newline()?;
+                    p.expr(tail)?;
+                }
+                Ok(())
+            })?;
+        }
+        self.indent -= 1;
+        self.newline()?;
+        write!(self.out, "}}")
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> fmt::Result {
+        match stmt {
+            Stmt::Let { pattern, ty, value } => {
+                write!(self.out, "let ")?;
+                self.pattern(pattern)?;
+                if let Some(ty) = ty {
+                    write!(self.out, ": ")?;
+                    self.ty(ty)?;
+                }
+                if let Some(value) = value {
+                    write!(self.out, " = ")?;
+                    self.expr(value)?;
+                }
+                write!(self.out, ";")
+            },
+            Stmt::Decl { is_mut, name, value } => {
+                if *is_mut {
+                    write!(self.out, "mut ")?;
+                }
+                self.name(*name)?;
+                write!(self.out, " := ")?;
+                self.expr(value)?;
+                write!(self.out, ";")
+            },
+            Stmt::Expr(expr) => {
+                self.expr(expr)?;
+                write!(self.out, ";")
+            },
+            Stmt::Defer(block) => {
+                write!(self.out, "defer ")?;
+                self.block(block)
+            },
+            Stmt::Item(item) => self.item(item),
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> fmt::Result {
+        if self.too_deep() {
+            return write!(self.out, "0");
+        }
+        self.nested(|p| match expr {
+            Expr::Literal(idx) => write!(p.out, "{}", pick(LITERALS, *idx)),
+            Expr::Path(path) => p.path(path),
+            Expr::Underscore => write!(p.out, "_"),
+            Expr::Unit => write!(p.out, "()"),
+            Expr::Prefix { op, expr } => {
+                write!(p.out, "{}", pick(PREFIX_OPS, *op))?;
+                p.expr(expr)
+            },
+            Expr::Postfix { op, expr } => {
+                p.expr(expr)?;
+                write!(p.out, "{}", pick(POSTFIX_OPS, *op))
+            },
+            Expr::Binary { op, lhs, rhs } => {
+                p.expr(lhs)?;
+                write!(p.out, " {} ", pick(BINARY_OPS, *op))?;
+                p.expr(rhs)
+            },
+            Expr::Paren(expr) => {
+                write!(p.out, "(")?;
+                p.expr(expr)?;
+                write!(p.out, ")")
+            },
+            Expr::Tuple(exprs) => {
+                write!(p.out, "(")?;
+                p.list(exprs, ", ", |p, expr| p.expr(expr))?;
+                write!(p.out, ",)")
+            },
+            Expr::Array(exprs) => {
+                write!(p.out, "[")?;
+                p.list(exprs, ", ", |p, expr| p.expr(expr))?;
+                write!(p.out, "]")
+            },
+            Expr::Call { func, args } => {
+                p.expr(func)?;
+                write!(p.out, "(")?;
+                p.list(args, ", ", |p, arg| p.expr(arg))?;
+                write!(p.out, ")")
+            },
+            Expr::Method { receiver, name, args } => {
+                p.expr(receiver)?;
+                write!(p.out, ".")?;
+                p.name(*name)?;
+                write!(p.out, "(")?;
+                p.list(args, ", ", |p, arg| p.expr(arg))?;
+                write!(p.out, ")")
+            },
+            Expr::Field { expr, name } => {
+                p.expr(expr)?;
+                write!(p.out, ".")?;
+                p.name(*name)
+            },
+            Expr::Index { expr, index } => {
+                p.expr(expr)?;
+                write!(p.out, "[")?;
+                p.expr(index)?;
+                write!(p.out, "]")
+            },
+            Expr::Struct { path, fields } => {
+                p.path(path)?;
+                write!(p.out, "{{ ")?;
+                p.list(fields, ", ", |p, (name, expr)| {
+                    p.name(*name)?;
+                    write!(p.out, ": ")?;
+                    p.expr(expr)
+                })?;
+                write!(p.out, " }}")
+            },
+            Expr::Cast { expr, ty } => {
+                p.expr(expr)?;
+                write!(p.out, " as ")?;
+                p.ty(ty)
+            },
+            Expr::Block(block) => p.block(block),
+            Expr::Unsafe(block) => {
+                write!(p.out, "unsafe ")?;
+                p.block(block)
+            },
+            Expr::If { cond, then, otherwise } => {
+                write!(p.out, "if ")?;
+                p.expr(cond)?;
+                write!(p.out, " ")?;
+                p.block(then)?;
+                if let Some(otherwise) = otherwise {
+                    write!(p.out, " else ")?;
+                    p.block(otherwise)?;
+                }
+                Ok(())
+            },
+            Expr::IfLet { pattern, value, then } => {
+                write!(p.out, "if let ")?;
+                p.pattern(pattern)?;
+                write!(p.out, " = ")?;
+                p.expr(value)?;
+                write!(p.out, " ")?;
+                p.block(then)
+            },
+            Expr::Loop { label, body } => {
+                if let Some(label) = label {
+                    write!(p.out, ":")?;
+                    p.name(*label)?;
+                    write!(p.out, ": ")?;
+                }
+                write!(p.out, "loop ")?;
+                p.block(body)
+            },
+            Expr::While { cond, body } => {
+                write!(p.out, "while ")?;
+                p.expr(cond)?;
+                write!(p.out, " ")?;
+                p.block(body)
+            },
+            Expr::For { pattern, iter, body } => {
+                write!(p.out, "for ")?;
+                p.pattern(pattern)?;
+                write!(p.out, " in ")?;
+                p.expr(iter)?;
+                write!(p.out, " ")?;
+                p.block(body)
+            },
+            Expr::Match { scrutinee, arms } => {
+                write!(p.out, "match ")?;
+                p.expr(scrutinee)?;
+                write!(p.out, " {{")?;
+                p.indent += 1;
+                for arm in arms {
+                    p.newline()?;
+                    p.pattern(&arm.pattern)?;
+                    if let Some(guard) = &arm.guard {
+                        write!(p.out, " if ")?;
+                        p.expr(guard)?;
+                    }
+                    write!(p.out, " => ")?;
+                    p.expr(&arm.body)?;
+                    write!(p.out, ",")?;
+                }
+                p.indent -= 1;
+                p.newline()?;
+                write!(p.out, "}}")
+            },
+            Expr::Closure { params, body } => {
+                write!(p.out, "|")?;
+                p.list(params, ", ", |p, param| p.name(*param))?;
+                write!(p.out, "| ")?;
+                p.expr(body)
+            },
+            Expr::Return(expr) => {
+                write!(p.out, "return")?;
+                if let Some(expr) = expr {
+                    write!(p.out, " ")?;
+                    p.expr(expr)?;
+                }
+                Ok(())
+            },
+            Expr::Break(label) => {
+                write!(p.out, "break")?;
+                if let Some(label) = label {
+                    write!(p.out, " :")?;
+                    p.name(*label)?;
+                }
+                Ok(())
+            },
+            Expr::Continue => write!(p.out, "continue"),
+        })
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) -> fmt::Result {
+        if self.too_deep() {
+            return write!(self.out, "_");
+        }
+        self.nested(|p| match pattern {
+            Pattern::Wildcard => write!(p.out, "_"),
+            Pattern::Rest => write!(p.out, ".."),
+            Pattern::Name { is_ref, is_mut, name } => {
+                if *is_ref {
+                    write!(p.out, "ref ")?;
+                }
+                if *is_mut {
+                    write!(p.out, "mut ")?;
+                }
+                p.name(*name)
+            },
+            Pattern::Literal(idx) => write!(p.out, "{}", pick(LITERALS, *idx)),
+            Pattern::Range { start, end, inclusive } => write!(p.out, "{start}{}{end}", if *inclusive { "..=" } else { ".." }),
+            Pattern::Path(path) => p.path(path),
+            Pattern::EnumMember(name) => {
+                write!(p.out, ".")?;
+                p.name(*name)
+            },
+            Pattern::Tuple(patterns) => {
+                write!(p.out, "(")?;
+                p.list(patterns, ", ", |p, pattern| p.pattern(pattern))?;
+                write!(p.out, ")")
+            },
+            Pattern::Slice(patterns) => {
+                write!(p.out, "[")?;
+                p.list(patterns, ", ", |p, pattern| p.pattern(pattern))?;
+                write!(p.out, "]")
+            },
+            Pattern::TupleStruct(path, patterns) => {
+                p.path(path)?;
+                write!(p.out, "(")?;
+                p.list(patterns, ", ", |p, pattern| p.pattern(pattern))?;
+                write!(p.out, ")")
+            },
+            Pattern::Struct(path, fields) => {
+                p.path(path)?;
+                write!(p.out, "{{ ")?;
+                p.list(fields, ", ", |p, (name, pattern)| {
+                    p.name(*name)?;
+                    write!(p.out, ": ")?;
+                    p.pattern(pattern)
+                })?;
+                write!(p.out, " }}")
+            },
+            Pattern::Reference { is_mut, pattern } => {
+                write!(p.out, "{}", if *is_mut { "&mut " } else { "&" })?;
+                p.pattern(pattern)
+            },
+            Pattern::Alternative(patterns) => {
+                write!(p.out, "(")?;
+                p.list(patterns, " | ", |p, pattern| p.pattern(pattern))?;
+                write!(p.out, ")")
+            },
+            Pattern::TypeCheck(ty) => {
+                write!(p.out, "is ")?;
+                p.ty(ty)
+            },
+        })
+    }
+}
+
+impl fmt::Display for SourceFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut printer = Printer { out: f, depth: 0, indent: 0 };
+        printer.attrs(&self.module_attrs, true)?;
+        for item in &self.items {
+            printer.item(item)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+// Shared setup for the fuzz targets
+
+use bootstrap::{
+    ast::{Ast, Parser},
+    common::{NameTable, SpanRegistry},
+    lexer::{self, Lexer, PuncutationTable, TokenMetadata, TokenStore},
+    literals::LiteralTable,
+};
+
+mod gen;
+pub use gen::*;
+
+/// Minimal state needed to run the lexer and parser
+pub struct Tables {
+    pub literals: LiteralTable,
+    pub names:    NameTable,
+    pub puncts:   PuncutationTable,
+    pub spans:    SpanRegistry,
+}
+
+impl Tables {
+    pub fn new() -> Self {
+        Self {
+            literals: LiteralTable::new(),
+            names: NameTable::new(),
+            puncts: PuncutationTable::new(),
+            spans: SpanRegistry::new(),
+        }
+    }
+
+    pub fn lex(&self, source: &str) -> TokenStore {
+        let mut lexer = Lexer::new("fuzz.xn", source, &self.literals, &self.names, &self.puncts, &self.spans);
+        lexer.lex();
+        lexer.tokens
+    }
+
+    /// Decode `bytes` like a file read from disk, including BOM detection and transcoding, and lex the result if it could be decoded
+    pub fn lex_bytes(&self, bytes: &[u8]) -> Option<TokenStore> {
+        let file = lexer::SourceFile::from_bytes(bytes.to_vec());
+        let source = file.decode("fuzz.xn", &self.spans).ok()?;
+        Some(self.lex(&source))
+    }
+
+    pub fn parse(&self, tokens: &TokenStore) -> Ast {
+        let mut parser = Parser::new(tokens, &self.names, &self.spans);
+        parser.parse();
+        parser.ast
+    }
+}
+
+impl Default for Tables {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// =============================================================================================================================
+
+/// Source containing at least one of every kind of token, which is lexed to get the tokens arbitrary token streams are built from
+const VOCABULARY: &str = r#"
+b8 b16 b32 b64 bool char char7 char8 char16 char32 f16 f32 f64 f128 i8 i16 i32 i64 i128 isize
+str str7 str8 str16 str32 u8 u16 u32 u64 u128 usize
+as as? as! assert bitfield break const constraint continue cstr defer do dyn else enum errdefer !in !is extern false
+fallthrough fn for if impl is in let loop match mod move mut pub ref return self Self static struct throw trait true
+try try! type union unsafe use when where while async await yield
+assign associativity distinct flag get higher_than infix invar lib lower_than op opaque override package post postfix
+pre precedence prefix property record sealed set super tls
+a b c Foo Bar T
+0 1 42 0x1F 1.5 'c' "str" 3:u8
+. .. ... ..= ; @ @! : := , ! ^ & ? ?. | = && * -> <- => + - / % < > <= >= == != += -= ~ ||
+( ) { } [ ]
+_
+"#;
+
+/// Tokens to build arbitrary token streams from
+pub struct Vocabulary {
+    pub tables: Tables,
+    tokens:     TokenStore,
+}
+
+impl Vocabulary {
+    pub fn new() -> Self {
+        let tables = Tables::new();
+        let tokens = tables.lex(VOCABULARY);
+        Self { tables, tokens }
+    }
+
+    /// Build a token stream, each index selects a token from the vocabulary
+    pub fn token_stream(&self, indices: &[u8]) -> TokenStore {
+        let mut tokens = TokenStore::new(&self.tables.names);
+        let count = self.tokens.tokens.len();
+        for &idx in indices {
+            let idx = idx as usize % count;
+            tokens.push(self.tokens.tokens[idx], TokenMetadata {
+                span_id: self.tokens.metadata[idx].span_id,
+                meta_elems: Vec::new(),
+                raw_text: self.tokens.metadata[idx].raw_text.clone(),
+            });
+        }
+        tokens
+    }
+}
+
+impl Default for Vocabulary {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,14 +1,17 @@
 #![allow(unused)]
 
 use std::{
+    any::Any,
     fmt::{self, write, Write as _},
     io::{Stdout, Write},
     marker::PhantomData,
-    ops::{Index, IndexMut},
+    ops::{Deref, Index, IndexMut},
     path::{self, PathBuf},
     sync::Arc,
 };
 
+use serde::{Deserialize, Serialize};
+
 use crate::{
     common::{IndentLogger, NameId, NameTable, OpType, PrecedenceAssocKind, SpanId},
     lexer::{Punctuation, PunctuationId, PuncutationTable, StrongKeyword, TokenStore, WeakKeyword},
@@ -22,9 +25,14 @@ pub use parser::{Parser, ParserErr};
 mod visitor;
 pub use visitor::{Visitor, helpers};
 
+mod syntax_view;
+pub use syntax_view::*;
+
+mod serialize;
+
 pub mod passes;
 
-#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug, Serialize, Deserialize)]
 pub struct NodeId(usize);
 
 impl NodeId {
@@ -45,18 +53,20 @@ pub(super) trait AstNodeParseHelper {
     fn set_node_id(&mut self, node_id: NodeId);
 }
 
-pub trait AstNode {
+pub trait AstNode: Any + Send + Sync {
     fn span(&self) -> SpanId;
     fn node_id(&self) -> NodeId;
     fn log(&self, logger: &mut AstLogger);
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct AstNodeMeta {
     pub span:      SpanId,
     pub first_tok: u32,
     pub last_tok:  u32,
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum IdenName {
     Name(NameId, SpanId),
     Disambig{
@@ -67,6 +77,7 @@ pub enum IdenName {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Identifier {
     pub span:     SpanId,
     pub name:     IdenName,
@@ -96,6 +107,7 @@ impl Identifier {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum SimplePathStartKind {
     None,
     Inferred,
@@ -103,6 +115,7 @@ pub enum SimplePathStartKind {
     Super,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct SimplePathStart {
     pub span: SpanId,
     pub kind: SimplePathStartKind
@@ -119,6 +132,7 @@ impl SimplePathStart {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct SimplePath {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -156,6 +170,7 @@ impl AstNodeParseHelper for SimplePath {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum PathStart {
     None,
     SelfTy(SpanId),
@@ -174,6 +189,7 @@ impl PathStart {
     }
 }
  
+#[derive(Serialize, Deserialize)]
 pub enum PathSegment {
     Iden(Identifier),
     Disambig(AstNodeRef<TypePath>, Identifier, SpanId),
@@ -185,6 +201,7 @@ impl PathSegment {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct TypePath {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -217,6 +234,7 @@ impl AstNodeParseHelper for TypePath {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct ExprPath {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -249,6 +267,7 @@ impl AstNodeParseHelper for ExprPath {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct TraitPathFnEnd {
     pub span:      SpanId,
     pub name:      NameId,
@@ -277,6 +296,7 @@ impl TraitPathFnEnd {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct TraitPath {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -311,6 +331,7 @@ impl AstNodeParseHelper for TraitPath {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Block {
     pub span:       SpanId,
     pub node_id:    NodeId,
@@ -345,6 +366,7 @@ impl AstNodeParseHelper for Block {
 
 // =============================================================================================================================
 
+#[derive(Serialize, Deserialize)]
 pub enum Item {
     Module(AstNodeRef<ModuleItem>),
     Use(AstNodeRef<UseItem>),
@@ -366,6 +388,7 @@ pub enum Item {
     OpUse(AstNodeRef<OpUse>),
     Precedence(AstNodeRef<Precedence>),
     PrecedenceUse(AstNodeRef<PrecedenceUse>),
+    Error(AstNodeRef<ErrorNode>),
 }
 
 impl AstNode for Item {
@@ -391,6 +414,7 @@ impl AstNode for Item {
             Item::OpUse(item)         => item.span(),
             Item::Precedence(item)    => item.span(),
             Item::PrecedenceUse(item) => item.span(),
+            Item::Error(item)         => item.span(),
         }
     }
     
@@ -416,6 +440,7 @@ impl AstNode for Item {
             Item::OpUse(item)         => item.node_id(),
             Item::Precedence(item)    => item.node_id(),
             Item::PrecedenceUse(item) => item.node_id(),
+            Item::Error(item)         => item.node_id(),
         }
     }
 
@@ -441,10 +466,39 @@ impl AstNode for Item {
             Self::OpUse(item)         => logger.log_node_ref(item),
             Self::Precedence(item)    => logger.log_node_ref(item),
             Self::PrecedenceUse(item) => logger.log_node_ref(item),
+            Self::Error(item)         => logger.log_node_ref(item),
         }
     }
 }
 
+// Placeholder for tokens that were skipped while recovering from a parse error
+#[derive(Serialize, Deserialize)]
+pub struct ErrorNode {
+    pub span:    SpanId,
+    pub node_id: NodeId,
+}
+
+impl AstNode for ErrorNode {
+    fn span(&self) -> SpanId {
+        self.span
+    }
+
+    fn node_id(&self) -> NodeId {
+        self.node_id
+    }
+
+    fn log(&self, logger: &mut AstLogger) {
+        logger.prefixed_logln("Error")
+    }
+}
+
+impl AstNodeParseHelper for ErrorNode {
+    fn set_node_id(&mut self, node_id: NodeId) {
+        self.node_id = node_id;
+    }
+}
+
+#[derive(Serialize, Deserialize)]
 pub enum ExternItem {
     Function(AstNodeRef<Function>),
     Static(AstNodeRef<Static>),
@@ -473,6 +527,7 @@ impl AstNode for ExternItem {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct ModuleItem {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -509,6 +564,7 @@ impl AstNodeParseHelper for ModuleItem {
 }
 
 
+#[derive(Serialize, Deserialize)]
 pub struct UseItem {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -556,6 +612,7 @@ impl AstNodeParseHelper for UseItem {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum UsePath {
     SelfPath{
         span:    SpanId,
@@ -669,6 +726,7 @@ impl AstNodeParseHelper for UsePath {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Function {
     pub span:         SpanId,
     pub node_id:      NodeId,
@@ -744,6 +802,7 @@ impl FnReceiver {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct FnParam {
     pub span:        SpanId,
     pub names:       Vec<FnParamName>,
@@ -763,6 +822,7 @@ impl FnParam {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct FnParamName {
     pub span:    SpanId,
     pub attrs:   Vec<AstNodeRef<Attribute>>,
@@ -783,6 +843,7 @@ impl FnParamName {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum FnReturn {
     Type{
         span: SpanId,
@@ -812,6 +873,7 @@ impl FnReturn {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Method {
     pub span:         SpanId,
     pub node_id:      NodeId,
@@ -864,6 +926,7 @@ impl AstNodeParseHelper for Method {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum FnReceiver {
     SelfReceiver{
         span:    SpanId,
@@ -877,6 +940,7 @@ pub enum FnReceiver {
     },
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct TypeAlias {
     pub span:     SpanId,
     pub node_id:  NodeId,
@@ -914,6 +978,7 @@ impl AstNodeParseHelper for TypeAlias {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct DistinctType {
     pub span:     SpanId,
     pub node_id:  NodeId,
@@ -951,6 +1016,7 @@ impl AstNodeParseHelper for DistinctType {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct OpaqueType {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -985,6 +1051,7 @@ impl AstNodeParseHelper for OpaqueType {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum Struct {
     Regular {
         span:         SpanId,
@@ -1083,6 +1150,7 @@ impl AstNodeParseHelper for Struct {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum RegStructField {
     Field {
         span:   SpanId,
@@ -1131,6 +1199,7 @@ impl RegStructField {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct TupleStructField {
     pub span:  SpanId,
     pub attrs: Vec<AstNodeRef<Attribute>>,
@@ -1154,6 +1223,7 @@ impl TupleStructField {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Union {
     pub span:         SpanId,
     pub node_id:      NodeId,
@@ -1200,6 +1270,7 @@ impl AstNode for Union {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct UnionField {
     pub span:   SpanId,
     pub attrs:  Vec<AstNodeRef<Attribute>>,
@@ -1222,6 +1293,7 @@ impl UnionField {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum Enum {
     Adt {
         span:         SpanId,
@@ -1299,6 +1371,7 @@ impl AstNodeParseHelper for Enum {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum EnumVariant {
     Struct {
         span:         SpanId,
@@ -1360,6 +1433,7 @@ impl EnumVariant {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct FlagEnumVariant {
     pub span:         SpanId,
     pub attrs:        Vec<AstNodeRef<Attribute>>,
@@ -1378,6 +1452,7 @@ impl FlagEnumVariant {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Bitfield {
     pub span:         SpanId,
     pub node_id:      NodeId,
@@ -1431,6 +1506,7 @@ impl AstNodeParseHelper for Bitfield {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum BitfieldField {
     Field {
         span:   SpanId,
@@ -1492,6 +1568,7 @@ impl BitfieldField {
 }
 
 
+#[derive(Serialize, Deserialize)]
 pub struct Const {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -1527,6 +1604,7 @@ impl AstNodeParseHelper for Const {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum Static {
     Static {
         span:    SpanId,
@@ -1618,6 +1696,7 @@ impl AstNodeParseHelper for Static {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Property {
     pub span:      SpanId,
     pub node_id:   NodeId,
@@ -1667,6 +1746,7 @@ impl AstNodeParseHelper for Property {
 
 //--------------------------------------------------------------
 
+#[derive(Serialize, Deserialize)]
 pub struct Trait {
     pub span:        SpanId,
     pub node_id:     NodeId,
@@ -1712,6 +1792,7 @@ impl AstNodeParseHelper for Trait {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum TraitItem {
     Function(AstNodeRef<TraitFunction>),
     Method(AstNodeRef<TraitMethod>),
@@ -1752,6 +1833,7 @@ impl AstNode for TraitItem {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct TraitFunction {
     pub span:         SpanId,
     pub node_id:      NodeId,
@@ -1805,6 +1887,7 @@ impl AstNodeParseHelper for TraitFunction {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct TraitMethod {
     pub span:         SpanId,
     pub node_id:      NodeId,
@@ -1859,6 +1942,7 @@ impl AstNodeParseHelper for TraitMethod {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct TraitTypeAlias {
     pub span:         SpanId,
     pub node_id:      NodeId,
@@ -1899,6 +1983,7 @@ impl AstNodeParseHelper for TraitTypeAlias {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct TraitConst {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -1935,6 +2020,7 @@ impl AstNodeParseHelper for TraitConst {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct TraitProperty {
     pub span:      SpanId,
     pub node_id:   NodeId,
@@ -1996,6 +2082,7 @@ impl AstNodeParseHelper for TraitProperty {
 
 //--------------------------------------------------------------
 
+#[derive(Serialize, Deserialize)]
 pub struct Impl {
     pub span:         SpanId,
     pub node_id:      NodeId,
@@ -2045,6 +2132,7 @@ impl AstNodeParseHelper for Impl {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum ImplItem {
     Function(AstNodeRef<Function>),
     Method(AstNodeRef<Method>),
@@ -2091,6 +2179,7 @@ impl AstNode for ImplItem {
 
 //--------------------------------------------------------------
 
+#[derive(Serialize, Deserialize)]
 pub struct ExternBlock {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -2129,6 +2218,7 @@ impl AstNodeParseHelper for ExternBlock {
 
 //--------------------------------------------------------------
 
+#[derive(Serialize, Deserialize)]
 pub enum OpSet {
     Base {
         span:       SpanId,
@@ -2203,6 +2293,7 @@ impl AstNodeParseHelper for OpSet {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum OpElem {
     Def {
         span:    SpanId,
@@ -2236,6 +2327,7 @@ impl OpElem {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct OpUse {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -2283,6 +2375,7 @@ impl AstNodeParseHelper for OpUse {
 
 //--------------------------------------------------------------
 
+#[derive(Serialize, Deserialize)]
 pub struct PrecedenceAssociativity {
     pub span: SpanId,
     pub kind: PrecedenceAssocKind
@@ -2298,6 +2391,7 @@ impl fmt::Display for PrecedenceAssociativity {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Precedence {
     pub span:          SpanId,
     pub node_id:       NodeId,
@@ -2348,6 +2442,7 @@ impl AstNodeParseHelper for Precedence {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct PrecedenceUse {
     pub span:        SpanId,
     pub node_id:     NodeId,
@@ -2395,6 +2490,7 @@ impl AstNodeParseHelper for PrecedenceUse {
 
 // =============================================================================================================================
 
+#[derive(Serialize, Deserialize)]
 pub enum Stmt {
     Empty(AstNodeRef<EmptyStmt>),
     Item(Item),
@@ -2402,6 +2498,7 @@ pub enum Stmt {
     Defer(AstNodeRef<Defer>),
     ErrDefer(AstNodeRef<ErrDefer>),
     Expr(AstNodeRef<ExprStmt>),
+    Error(AstNodeRef<ErrorNode>),
 }
 
 impl AstNode for Stmt {
@@ -2413,6 +2510,7 @@ impl AstNode for Stmt {
             Stmt::Defer(item)    => item.span(),
             Stmt::ErrDefer(item) => item.span(),
             Stmt::Expr(item)     => item.span(),
+            Stmt::Error(item)    => item.span(),
         }
     }
 
@@ -2424,6 +2522,7 @@ impl AstNode for Stmt {
             Stmt::Defer(item)    => item.node_id(),
             Stmt::ErrDefer(item) => item.node_id(),
             Stmt::Expr(item)     => item.node_id(),
+            Stmt::Error(item)    => item.node_id(),
         }
     }
 
@@ -2435,10 +2534,12 @@ impl AstNode for Stmt {
             Self::Defer(defer)       => logger.log_node_ref(defer),
             Self::ErrDefer(errdefer) => logger.log_node_ref(errdefer),
             Self::Expr(expr)         => logger.log_node_ref(expr),
+            Self::Error(item)        => logger.log_node_ref(item),
         }
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct EmptyStmt {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -2464,6 +2565,7 @@ impl AstNodeParseHelper for EmptyStmt {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum VarDecl {
     Named {
         span:    SpanId,
@@ -2535,6 +2637,7 @@ impl AstNodeParseHelper for VarDecl {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Defer {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -2566,6 +2669,7 @@ impl AstNodeParseHelper for Defer {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct ErrDefer {
     pub span:     SpanId,
     pub node_id:  NodeId,
@@ -2603,12 +2707,14 @@ impl AstNodeParseHelper for ErrDefer {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct ErrDeferReceiver {
     pub span:   SpanId,
     pub is_mut: bool,
     pub name:   NameId,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct ExprStmt {
     pub span:     SpanId,
     pub node_id:  NodeId,
@@ -2643,7 +2749,7 @@ impl AstNodeParseHelper for ExprStmt {
 
 // =============================================================================================================================
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum Expr {
     Literal(AstNodeRef<LiteralExpr>),
     // Can include a sequence of field accesses
@@ -2824,11 +2930,13 @@ impl AstNode for Expr {
 }
 
 
+#[derive(Serialize, Deserialize)]
 pub enum LiteralValue {
     Lit(LiteralId),
     Bool(bool),
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum LiteralOp {
     Name(NameId),
     Primitive(PrimitiveType),
@@ -2850,6 +2958,7 @@ impl LiteralOp {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct LiteralExpr {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -2887,6 +2996,7 @@ impl AstNodeParseHelper for LiteralExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum PathExpr {
     Path {
         span:    SpanId,
@@ -2938,6 +3048,7 @@ impl AstNodeParseHelper for PathExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct UnitExpr {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -2963,7 +3074,7 @@ impl AstNodeParseHelper for UnitExpr {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum BlockExprKind {
     Normal,
     Unsafe,
@@ -2973,6 +3084,7 @@ pub enum BlockExprKind {
     Labeled{ label: NameId }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct BlockExpr {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -3016,6 +3128,7 @@ impl AstNodeParseHelper for BlockExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct PrefixExpr {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -3047,6 +3160,7 @@ impl AstNodeParseHelper for PrefixExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct PostfixExpr {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -3078,6 +3192,7 @@ impl AstNodeParseHelper for PostfixExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct InfixExpr {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -3111,6 +3226,7 @@ impl AstNodeParseHelper for InfixExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct ParenExpr {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -3140,6 +3256,7 @@ impl AstNodeParseHelper for ParenExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct InplaceExpr {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -3171,13 +3288,14 @@ impl AstNodeParseHelper for InplaceExpr {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum TypeCastKind {
     Normal,
     Try,
     Unwrap,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct TypeCastExpr {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -3216,6 +3334,7 @@ impl AstNodeParseHelper for TypeCastExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct TypeCheckExpr {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -3249,6 +3368,7 @@ impl AstNodeParseHelper for TypeCheckExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct TupleExpr {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -3283,6 +3403,7 @@ impl AstNodeParseHelper for TupleExpr {
 }
 
 // Add support for [expr; size]
+#[derive(Serialize, Deserialize)]
 pub enum ArrayExpr {
     Slice {
         span:    SpanId,
@@ -3340,6 +3461,7 @@ impl AstNodeParseHelper for ArrayExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum StructArg {
     Expr{
         span: SpanId,
@@ -3370,6 +3492,7 @@ impl StructArg {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct StructExpr {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -3402,6 +3525,7 @@ impl AstNodeParseHelper for StructExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct IndexExpr {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -3436,6 +3560,7 @@ impl AstNodeParseHelper for IndexExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct TupleIndexExpr {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -3467,6 +3592,7 @@ impl AstNodeParseHelper for TupleIndexExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum FnArg {
     Expr{
         span: SpanId,
@@ -3492,6 +3618,7 @@ impl FnArg {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct FnCallExpr {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -3524,6 +3651,7 @@ impl AstNodeParseHelper for FnCallExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct MethodCallExpr {
     pub span:           SpanId,
     pub node_id:        NodeId,
@@ -3559,6 +3687,7 @@ impl AstNodeParseHelper for MethodCallExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct FieldAccessExpr {
     pub span:           SpanId,
     pub node_id:        NodeId,
@@ -3592,6 +3721,7 @@ impl AstNodeParseHelper for FieldAccessExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct ClosureExpr {
     pub span:     SpanId,
     pub node_id:  NodeId,
@@ -3612,7 +3742,11 @@ impl AstNode for ClosureExpr {
 
     fn log(&self, logger: &mut AstLogger) {
         logger.log_ast_node("Closure Expression", |logger| {
-
+            logger.prefixed_log_fmt(format_args!("Is Moved: {}\n", self.is_moved));
+            logger.log_indented_slice("Params", &self.params, |logger, param| param.log(logger));
+            logger.log_opt(&self.ret, |logger, ret| ret.log(logger));
+            logger.set_last_at_indent();
+            logger.log_indented_node("Body", &self.body);
         });
     }
 }
@@ -3623,6 +3757,7 @@ impl AstNodeParseHelper for ClosureExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct FullRangeExpr {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -3648,6 +3783,7 @@ impl AstNodeParseHelper for FullRangeExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct IfExpr {
     pub span:      SpanId,
     pub node_id:   NodeId,
@@ -3682,6 +3818,7 @@ impl AstNodeParseHelper for IfExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct LetBindingExpr {
     pub span:      SpanId,
     pub node_id:   NodeId,
@@ -3713,6 +3850,7 @@ impl AstNodeParseHelper for LetBindingExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct LoopExpr {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -3746,6 +3884,7 @@ impl AstNodeParseHelper for LoopExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct WhileExpr {
     pub span:      SpanId,
     pub node_id:   NodeId,
@@ -3786,6 +3925,7 @@ impl AstNodeParseHelper for WhileExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct DoWhileExpr {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -3821,6 +3961,7 @@ impl AstNodeParseHelper for DoWhileExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct ForExpr {
     pub span:      SpanId,
     pub node_id:   NodeId,
@@ -3861,6 +4002,7 @@ impl AstNodeParseHelper for ForExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct MatchExpr {
     pub span:      SpanId,
     pub node_id:   NodeId,
@@ -3897,6 +4039,7 @@ impl AstNodeParseHelper for MatchExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct MatchBranch {
     pub span:     SpanId,
     pub label:    Option<NameId>,
@@ -3919,6 +4062,7 @@ impl MatchBranch {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct BreakExpr {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -3952,6 +4096,7 @@ impl AstNodeParseHelper for BreakExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct ContinueExpr {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -3982,6 +4127,7 @@ impl AstNodeParseHelper for ContinueExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct FallthroughExpr {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -4012,6 +4158,7 @@ impl AstNodeParseHelper for FallthroughExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct ReturnExpr {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -4041,6 +4188,7 @@ impl AstNodeParseHelper for ReturnExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct UnderscoreExpr {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -4066,6 +4214,7 @@ impl AstNodeParseHelper for UnderscoreExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct ThrowExpr {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -4094,6 +4243,7 @@ impl AstNodeParseHelper for ThrowExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct CommaExpr {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -4122,6 +4272,7 @@ impl AstNodeParseHelper for CommaExpr {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct WhenExpr {
     pub span:      SpanId,
     pub node_id:   NodeId,
@@ -4158,6 +4309,7 @@ impl AstNodeParseHelper for WhenExpr {
 
 // =============================================================================================================================
 
+#[derive(Serialize, Deserialize)]
 pub enum Pattern {
     Literal(AstNodeRef<LiteralPattern>),
     Identifier(AstNodeRef<IdentifierPattern>),
@@ -4238,6 +4390,7 @@ impl AstNode for Pattern {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct LiteralPattern {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -4272,6 +4425,7 @@ impl AstNodeParseHelper for LiteralPattern {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct IdentifierPattern {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -4307,6 +4461,7 @@ impl AstNodeParseHelper for IdentifierPattern {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct PathPattern {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -4336,6 +4491,7 @@ impl AstNodeParseHelper for PathPattern {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct WildcardPattern {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -4361,6 +4517,7 @@ impl AstNodeParseHelper for WildcardPattern {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct RestPattern {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -4386,6 +4543,7 @@ impl AstNodeParseHelper for RestPattern {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum RangePattern {
     Exclusive{
         span:    SpanId,
@@ -4477,6 +4635,7 @@ impl AstNodeParseHelper for RangePattern {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct ReferencePattern {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -4508,6 +4667,7 @@ impl AstNodeParseHelper for ReferencePattern {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum StructPattern {
     Inferred {
         span:    SpanId,
@@ -4562,6 +4722,7 @@ impl AstNodeParseHelper for StructPattern {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum StructPatternField {
     Named {
         span:    SpanId,
@@ -4611,6 +4772,7 @@ impl StructPatternField {
 }
 
 // TODO: doesn't seem to get parsed, check this out
+#[derive(Serialize, Deserialize)]
 pub enum TupleStructPattern {
     Inferred {
         span:     SpanId,
@@ -4666,6 +4828,7 @@ impl AstNodeParseHelper for TupleStructPattern {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct TuplePattern {
     pub span:     SpanId,
     pub node_id:  NodeId,
@@ -4695,6 +4858,7 @@ impl AstNodeParseHelper for TuplePattern {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct GroupedPattern {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -4724,6 +4888,7 @@ impl AstNodeParseHelper for GroupedPattern {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct SlicePattern {
     pub span:     SpanId,
     pub node_id:  NodeId,
@@ -4753,6 +4918,7 @@ impl AstNodeParseHelper for SlicePattern {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct EnumMemberPattern {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -4781,6 +4947,7 @@ impl AstNodeParseHelper for EnumMemberPattern {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct AlternativePattern {
     pub span:     SpanId,
     pub node_id:  NodeId,
@@ -4810,6 +4977,7 @@ impl AstNodeParseHelper for AlternativePattern {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct TypeCheckPattern {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -4841,6 +5009,7 @@ impl AstNodeParseHelper for TypeCheckPattern {
 
 // =============================================================================================================================
 
+#[derive(Serialize, Deserialize)]
 pub enum Type {
     Paren(AstNodeRef<ParenthesizedType>),
     Primitive(AstNodeRef<PrimitiveType>),
@@ -4857,6 +5026,8 @@ pub enum Type {
     Fn(AstNodeRef<FnType>),
     Record(AstNodeRef<RecordType>),
     EnumRecord(AstNodeRef<EnumRecordType>),
+    TraitObject(AstNodeRef<TraitObjectType>),
+    ImplTrait(AstNodeRef<ImplTraitType>),
 }
 
 impl AstNode for Type {
@@ -4877,6 +5048,8 @@ impl AstNode for Type {
             Type::Fn(ty)          => ty.span(),
             Type::Record(ty)      => ty.span(),
             Type::EnumRecord(ty)  => ty.span(),
+            Type::TraitObject(ty) => ty.span(),
+            Type::ImplTrait(ty)   => ty.span(),
         }
     }
 
@@ -4897,6 +5070,8 @@ impl AstNode for Type {
             Type::Fn(ty)          => ty.node_id(),
             Type::Record(ty)      => ty.node_id(),
             Type::EnumRecord(ty)  => ty.node_id(),
+            Type::TraitObject(ty) => ty.node_id(),
+            Type::ImplTrait(ty)   => ty.node_id(),
         }
     }
 
@@ -4917,10 +5092,13 @@ impl AstNode for Type {
             Type::Fn(ty)          => logger.log_node_ref(ty),
             Type::Record(ty)      => logger.log_node_ref(ty),
             Type::EnumRecord(ty)  => logger.log_node_ref(ty),
+            Type::TraitObject(ty) => logger.log_node_ref(ty),
+            Type::ImplTrait(ty)   => logger.log_node_ref(ty),
         }
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct ParenthesizedType {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -4950,6 +5128,7 @@ impl AstNodeParseHelper for ParenthesizedType {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct PrimitiveType {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -5007,6 +5186,7 @@ impl AstNodeParseHelper for PrimitiveType {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct UnitType {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -5032,6 +5212,7 @@ impl AstNodeParseHelper for UnitType {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct NeverType {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -5057,6 +5238,7 @@ impl AstNodeParseHelper for NeverType {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct PathType {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -5085,6 +5267,7 @@ impl AstNodeParseHelper for PathType {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct TupleType {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -5113,6 +5296,7 @@ impl AstNodeParseHelper for TupleType {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct ArrayType {
     pub span:     SpanId,
     pub node_id:  NodeId,
@@ -5146,6 +5330,7 @@ impl AstNodeParseHelper for ArrayType {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct SliceType {
     pub span:     SpanId,
     pub node_id:  NodeId,
@@ -5177,6 +5362,7 @@ impl AstNodeParseHelper for SliceType {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct StringSliceType {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -5213,6 +5399,7 @@ impl AstNodeParseHelper for StringSliceType {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct PointerType {
     pub span:     SpanId,
     pub node_id:  NodeId,
@@ -5251,6 +5438,7 @@ impl AstNodeParseHelper for PointerType {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct ReferenceType {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -5283,6 +5471,7 @@ impl AstNodeParseHelper for ReferenceType {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct OptionalType {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -5312,6 +5501,7 @@ impl AstNodeParseHelper for OptionalType {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct FnType {
     pub span:      SpanId,
     pub node_id:   NodeId,
@@ -5360,6 +5550,7 @@ impl AstNodeParseHelper for FnType {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct RecordType {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -5389,6 +5580,7 @@ impl AstNodeParseHelper for RecordType {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct EnumRecordType {
     pub span:     SpanId,
     pub node_id:  NodeId,
@@ -5418,8 +5610,69 @@ impl AstNodeParseHelper for EnumRecordType {
     }
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct TraitObjectType {
+    pub span:    SpanId,
+    pub node_id: NodeId,
+    pub bounds:  AstNodeRef<TraitBounds>,
+}
+
+impl AstNode for TraitObjectType {
+    fn span(&self) -> SpanId {
+        self.span
+    }
+
+    fn node_id(&self) -> NodeId {
+        self.node_id
+    }
+
+    fn log(&self, logger: &mut AstLogger) {
+        logger.log_ast_node("Trait Object Type", |logger| {
+            logger.set_last_at_indent();
+            logger.log_indented_node_ref("Bounds", &self.bounds);
+        });
+    }
+}
+
+impl AstNodeParseHelper for TraitObjectType {
+    fn set_node_id(&mut self, node_id: NodeId) {
+        self.node_id = node_id;
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ImplTraitType {
+    pub span:    SpanId,
+    pub node_id: NodeId,
+    pub bounds:  AstNodeRef<TraitBounds>,
+}
+
+impl AstNode for ImplTraitType {
+    fn span(&self) -> SpanId {
+        self.span
+    }
+
+    fn node_id(&self) -> NodeId {
+        self.node_id
+    }
+
+    fn log(&self, logger: &mut AstLogger) {
+        logger.log_ast_node("Impl Trait Type", |logger| {
+            logger.set_last_at_indent();
+            logger.log_indented_node_ref("Bounds", &self.bounds);
+        });
+    }
+}
+
+impl AstNodeParseHelper for ImplTraitType {
+    fn set_node_id(&mut self, node_id: NodeId) {
+        self.node_id = node_id;
+    }
+}
+
 // =============================================================================================================================
 
+#[derive(Serialize, Deserialize)]
 pub enum GenericParam {
     Type(AstNodeRef<GenericTypeParam>),
     TypeSpec(AstNodeRef<GenericTypeSpec>),
@@ -5442,6 +5695,7 @@ impl AstNode for GenericParam {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct GenericTypeParam {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -5476,6 +5730,7 @@ impl AstNodeParseHelper for GenericTypeParam {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct GenericTypeSpec {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -5505,6 +5760,7 @@ impl AstNodeParseHelper for GenericTypeSpec {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct GenericConstParam {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -5539,6 +5795,7 @@ impl AstNodeParseHelper for GenericConstParam {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct GenericConstSpec {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -5568,6 +5825,7 @@ impl AstNodeParseHelper for GenericConstSpec {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum GenericParamPackDesc {
     Type(SpanId),
     TypeBounds(SpanId, Vec<GenericTypeBound>),
@@ -5584,6 +5842,7 @@ impl GenericParamPackDesc {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum GenericParamPackDef {
     Type(Type),
     Expr(AstNodeRef<BlockExpr>),
@@ -5598,6 +5857,7 @@ impl GenericParamPackDef {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct GenericParamPack {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -5635,6 +5895,7 @@ impl AstNodeParseHelper for GenericParamPack {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct GenericParams {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -5664,6 +5925,7 @@ impl AstNodeParseHelper for GenericParams {
 }
 
 
+#[derive(Serialize, Deserialize)]
 pub struct WhereClause {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -5698,6 +5960,7 @@ impl AstNodeParseHelper for WhereClause {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum WhereBound {
     Type {
         span:   SpanId,
@@ -5734,6 +5997,7 @@ impl WhereBound {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum GenericTypeBound {
     Type(AstNodeRef<TypePath>),
 }
@@ -5758,12 +6022,14 @@ impl AstNode for GenericTypeBound {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum GenericArg {
     Type(Type),
     Value(AstNodeRef<BlockExpr>),
     TypeOrValue(NameId),
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct GenericArgs {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -5792,6 +6058,7 @@ impl AstNodeParseHelper for GenericArgs {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct TraitBounds {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -5800,11 +6067,11 @@ pub struct TraitBounds {
 
 impl AstNode for TraitBounds {
     fn span(&self) -> SpanId {
-        SpanId::INVALID
+        self.span
     }
 
     fn node_id(&self) -> NodeId {
-        NodeId::default()
+        self.node_id
     }
 
     fn log(&self, logger: &mut AstLogger) {
@@ -5828,6 +6095,7 @@ impl AstNodeParseHelper for TraitBounds {
 
 // =============================================================================================================================
 
+#[derive(Serialize, Deserialize)]
 pub enum Visibility {
     Pub {
         span:    SpanId,
@@ -5899,6 +6167,7 @@ impl AstNodeParseHelper for Visibility {
 
 // =============================================================================================================================
 
+#[derive(Serialize, Deserialize)]
 pub struct Attribute {
     pub span:    SpanId,
     pub node_id: NodeId,
@@ -5933,6 +6202,7 @@ impl AstNodeParseHelper for Attribute {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum AttribMeta {
     Simple {
         path:    AstNodeRef<SimplePath>,
@@ -5973,6 +6243,7 @@ impl AttribMeta {
 
 // =============================================================================================================================
 
+#[derive(Serialize, Deserialize)]
 pub struct Contract {
 
 }
@@ -6017,7 +6288,22 @@ impl AstNodeParseHelper for Contract {
 //     }
 // }
 
-pub type AstNodeRef<T> = Arc<T>;
+/// Shared reference to a node in the AST
+pub struct AstNodeRef<T>(Arc<T>);
+
+impl<T> Clone for AstNodeRef<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Deref for AstNodeRef<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
 
 
 
@@ -6042,13 +6328,13 @@ impl Ast {
         }
     }
 
-    pub fn add_node<T: AstNode + AstNodeParseHelper + 'static>(&mut self, mut node: T, meta: AstNodeMeta) -> AstNodeRef<T> {
+    pub(crate) fn add_node<T: AstNode + AstNodeParseHelper + 'static>(&mut self, mut node: T, meta: AstNodeMeta) -> AstNodeRef<T> {
         let idx = self.nodes.len();
         node.set_node_id(NodeId(idx));
         let node_ref = Arc::new(node);
         self.nodes.push(node_ref.clone());
         self.meta.push(meta);
-        node_ref
+        AstNodeRef(node_ref)
     }
 
     pub fn log(&self, names: &NameTable, literals: &LiteralTable, puncts: &PuncutationTable) {
@@ -8,7 +8,7 @@ use std::{
 };
 
 use crate::{
-    ast::*, common::{NameId, NameTable, Span, SpanRegistry}, error_warning::ParseErrorCode, lexer::{OpenCloseSymbol, Punctuation, PunctuationId, StrongKeyword, Token, TokenMetadata, TokenStore, WeakKeyword}, literals::LiteralId
+    ast::*, common::{Diagnostic, NameId, NameTable, Span, SpanRegistry}, error_warning::ParseErrorCode, lexer::{OpenCloseSymbol, Punctuation, PunctuationId, StrongKeyword, Token, TokenMetadata, TokenStore, WeakKeyword}, literals::LiteralId
 };
 
 use super::*;
@@ -19,6 +19,26 @@ pub struct ParserErr {
 }
 
 
+impl ParserErr {
+    pub fn to_diagnostic(&self, tokens: &TokenStore) -> Diagnostic {
+        // Errors at the end of the file point to the last token
+        let span = tokens.metadata.get(self.tok_idx)
+            .or(tokens.metadata.last())
+            .map_or(SpanId::INVALID, |meta| meta.span_id);
+
+        let diag = Diagnostic::error(&self.err);
+        match &self.err {
+            ParseErrorCode::NotEnoughTokens =>
+                diag.with_primary(span, "unexpected end of file after this"),
+            ParseErrorCode::FoundButExpected { expected, .. } =>
+                diag.with_primary(span, format!("expected `{}`", expected.as_display_str())),
+            ParseErrorCode::UnexpectedFor { for_reason, .. } =>
+                diag.with_primary(span, format!("unexpected token for {for_reason}")),
+            _ => diag.with_primary(span, ""),
+        }
+    }
+}
+
 impl fmt::Display for ParserErr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.err)
@@ -32,6 +52,14 @@ pub struct ParserFrame {
     token_id: u32,
 }
 
+// Parser state to return to when recovering from an error
+#[derive(Clone, Copy)]
+struct RecoveryPoint {
+    token_idx:   usize,
+    frame_depth: usize,
+    scope_depth: usize,
+}
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub enum ExprParseMode {
     AllowComma,
@@ -46,7 +74,7 @@ pub struct Parser<'a> {
     token_store:    &'a TokenStore,
     token_idx:      usize,
 
-    spans:          &'a mut SpanRegistry,
+    spans:          &'a SpanRegistry,
 
     frames:         Vec<ParserFrame>,
     last_frame:     ParserFrame,
@@ -54,10 +82,11 @@ pub struct Parser<'a> {
 
     names:          &'a NameTable,
     pub ast:        Ast,
+    pub errors:     Vec<ParserErr>,
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(token_store: &'a TokenStore, names: &'a NameTable, spans: &'a mut SpanRegistry) -> Self {
+    pub fn new(token_store: &'a TokenStore, names: &'a NameTable, spans: &'a SpanRegistry) -> Self {
         Self {
             token_store,
             token_idx: 0,
@@ -70,19 +99,118 @@ impl<'a> Parser<'a> {
 
             names,
             ast: Ast::new(),
+            errors: Vec::new(),
         }
     }
 
 }
 
 impl Parser<'_> {
-    pub fn parse(&mut self) -> Result<(), ParserErr> {
+    /// Parse all items, any errors are collected in `errors`
+    pub fn parse(&mut self) {
         while self.token_idx != self.token_store.tokens.len() {
-            let item = self.parse_item(None)?;
-            self.ast.items.push(item);
+            let point = self.recovery_point();
+            match self.parse_item(None) {
+                Ok(item) => self.ast.items.push(item),
+                Err(err) => {
+                    self.add_error(err);
+                    let node = self.recover(point, false);
+                    self.ast.items.push(Item::Error(node));
+                },
+            }
         }
+    }
 
-        Ok(())
+    fn add_error(&mut self, err: ParserErr) {
+        // Error tokens were already reported by the lexer
+        if self.token_store.tokens.get(err.tok_idx) != Some(&Token::Error) {
+            self.errors.push(err);
+        }
+    }
+
+    fn recovery_point(&self) -> RecoveryPoint {
+        RecoveryPoint {
+            token_idx: self.token_idx,
+            frame_depth: self.frames.len(),
+            scope_depth: self.scope_stack.len(),
+        }
+    }
+
+    /// Skip tokens until the next synchronization point and return an error node covering all tokens since `point`.
+    /// 
+    /// Synchronization happens at the depth of the item or statement that failed, either after a `;`, after a closing `}`,
+    /// before the closing symbol of the enclosing scope, or before the start of a new item (or statement when `in_block` is set)
+    fn recover(&mut self, point: RecoveryPoint, in_block: bool) -> AstNodeRef<ErrorNode> {
+        // Scopes opened before the error may not be tracked in the scope stack, so calculate the depth from the tokens
+        let mut depth = 0;
+        for tok in &self.token_store.tokens[point.token_idx..self.token_idx] {
+            match tok {
+                Token::OpenSymbol(_)  => depth += 1,
+                Token::CloseSymbol(_) => depth -= 1,
+                _ => {},
+            }
+        }
+
+        while let Some(peek) = self.try_peek() {
+            match peek {
+                Token::OpenSymbol(_) => depth += 1,
+                Token::CloseSymbol(sym) => {
+                    if depth == 0 && self.token_idx != point.token_idx {
+                        break;
+                    }
+                    depth = (depth - 1).max(0);
+                    if depth == 0 && sym == OpenCloseSymbol::Brace {
+                        self.consume_single();
+                        break;
+                    }
+                },
+                Token::Punctuation(Punctuation::Semicolon) if depth == 0 => {
+                    self.consume_single();
+                    break;
+                },
+                // Always skip at least 1 token, so recovery cannot get stuck
+                _ if depth == 0 && self.token_idx != point.token_idx && Self::is_sync_start(peek, in_block) => break,
+                _ => {},
+            }
+            self.consume_single();
+        }
+
+        self.frames.truncate(point.frame_depth);
+        self.scope_stack.truncate(point.scope_depth);
+
+        // Error at the end of the file without any tokens left to skip
+        let first_tok = point.token_idx.min(self.token_store.metadata.len() - 1);
+        let last_tok = self.token_idx.max(first_tok + 1) - 1;
+        let begin = self.token_store.metadata[first_tok].span_id;
+        let end = self.token_store.metadata[last_tok].span_id;
+        let span = self.spans.combine_spans(begin, end);
+
+        self.frames.push(ParserFrame { span: begin, token_id: first_tok as u32 });
+        self.add_node(ErrorNode {
+            span,
+            node_id: NodeId::default(),
+        })
+    }
+
+    fn is_sync_start(token: Token, in_block: bool) -> bool {
+        match token {
+            Token::StrongKw(StrongKeyword::Bitfield) |
+            Token::StrongKw(StrongKeyword::Enum)     |
+            Token::StrongKw(StrongKeyword::Fn)       |
+            Token::StrongKw(StrongKeyword::Impl)     |
+            Token::StrongKw(StrongKeyword::Mod)      |
+            Token::StrongKw(StrongKeyword::Pub)      |
+            Token::StrongKw(StrongKeyword::Static)   |
+            Token::StrongKw(StrongKeyword::Struct)   |
+            Token::StrongKw(StrongKeyword::Trait)    |
+            Token::StrongKw(StrongKeyword::Type)     |
+            Token::StrongKw(StrongKeyword::Union)    |
+            Token::StrongKw(StrongKeyword::Use)      => true,
+            Token::StrongKw(StrongKeyword::Defer)    |
+            Token::StrongKw(StrongKeyword::ErrDefer) |
+            Token::StrongKw(StrongKeyword::Let)      => in_block,
+            _ => false,
+        }
     }
 
     fn try_peek(&self) -> Option<Token> {
@@ -263,10 +391,11 @@ impl Parser<'_> {
     }
 
     fn push_meta_frame(&mut self) {
-        let token_meta = &self.token_store.metadata[self.token_idx];
+        // Frames can be started at the end of the file, when the source is incomplete
+        let span = self.token_store.metadata.get(self.token_idx).map_or(SpanId::INVALID, |meta| meta.span_id);
 
         self.frames.push(ParserFrame {
-            span:  token_meta.span_id,
+            span,
             token_id: self.token_idx as u32,
         })
     }
@@ -299,7 +428,7 @@ impl Parser<'_> {
     }
 
     pub fn get_cur_span(&self) -> SpanId {
-        self.token_store.metadata[self.token_idx].span_id
+        self.token_store.metadata.get(self.token_idx).map_or(SpanId::INVALID, |meta| meta.span_id)
     }
 
     pub fn get_span_to_current(&mut self, begin: SpanId) -> SpanId {
@@ -404,7 +533,7 @@ impl Parser<'_> {
                 self.consume_punct(Punctuation::Colon)?;
                 let ty = self.parse_type()?;
                 self.consume_punct(Punctuation::Colon)?;
-                self.end_scope();
+                self.end_scope()?;
                 Ok(PathStart::Typed(ty))
             },
             Token::StrongKw(kw) if kw.is_primitive_type() => {
@@ -550,14 +679,14 @@ impl Parser<'_> {
             Token::WeakKw(WeakKeyword::Flag)         => self.parse_enum(attrs, vis),
             Token::WeakKw(WeakKeyword::Sealed)       => self.parse_trait(attrs, vis),
             Token::WeakKw(WeakKeyword::Tls)          => self.parse_static_item(attrs, vis).map(|item| Item::Static(item)),
-            Token::WeakKw(WeakKeyword::Precedence)   => if self.try_peek() == Some(Token::StrongKw(StrongKeyword::Use)) {
+            Token::WeakKw(WeakKeyword::Precedence)   => if self.try_peek_at(1) == Some(Token::StrongKw(StrongKeyword::Use)) {
                 self.parse_precedence_use(attrs, vis)
             } else {
                 self.parse_precedence(attrs, vis)
             },
             Token::StrongKw(StrongKeyword::Type)     |
             Token::WeakKw(WeakKeyword::Distinct)     => self.parse_type_item(attrs, vis),
-            Token::WeakKw(WeakKeyword::Op)           => if self.try_peek() == Some(Token::StrongKw(StrongKeyword::Use)) {
+            Token::WeakKw(WeakKeyword::Op)           => if self.try_peek_at(1) == Some(Token::StrongKw(StrongKeyword::Use)) {
                 self.parse_op_use(attrs, vis)
             } else {
                 self.parse_op_set(attrs, vis)
@@ -856,9 +985,11 @@ impl Parser<'_> {
 
         let where_clause = self.parse_where_clause()?;
 
-        let contracts = if self.peek()? != Token::OpenSymbol(OpenCloseSymbol::Brace) {
+        // Trait functions without a body end in a `;`
+        let is_contract_end = |tok| matches!(tok, Token::OpenSymbol(OpenCloseSymbol::Brace) | Token::Punctuation(Punctuation::Semicolon));
+        let contracts = if !is_contract_end(self.peek()?) {
             let mut contracts = Vec::new();
-            while self.peek()? != Token::OpenSymbol(OpenCloseSymbol::Brace) {
+            while !is_contract_end(self.peek()?) {
                 contracts.push(self.parse_contract()?)
             }
             contracts
@@ -1724,7 +1855,7 @@ impl Parser<'_> {
         let where_clause = self.parse_where_clause()?;
 
         let mut assoc_items = Vec::new();
-        self.begin_scope(OpenCloseSymbol::Brace);
+        self.begin_scope(OpenCloseSymbol::Brace)?;
         while !self.try_end_scope() {
             assoc_items.push(self.parse_trait_item()?);
         }
@@ -1795,9 +1926,11 @@ impl Parser<'_> {
 
         let where_clause = self.parse_where_clause()?;
 
-        let contracts = if self.peek()? != Token::OpenSymbol(OpenCloseSymbol::Brace) {
+        // Trait functions without a body end in a `;`
+        let is_contract_end = |tok| matches!(tok, Token::OpenSymbol(OpenCloseSymbol::Brace) | Token::Punctuation(Punctuation::Semicolon));
+        let contracts = if !is_contract_end(self.peek()?) {
             let mut contracts = Vec::new();
-            while self.peek()? != Token::OpenSymbol(OpenCloseSymbol::Brace) {
+            while !is_contract_end(self.peek()?) {
                 contracts.push(self.parse_contract()?)
             }
             contracts
@@ -2009,7 +2142,7 @@ impl Parser<'_> {
         let where_clause = self.parse_where_clause()?;
 
         let mut assoc_items = Vec::new();
-        self.begin_scope(OpenCloseSymbol::Brace);
+        self.begin_scope(OpenCloseSymbol::Brace)?;
         while !self.try_end_scope() {
             assoc_items.push(self.parse_impl_item()?);
         }
@@ -2104,7 +2237,7 @@ impl Parser<'_> {
         let abi = self.consume_lit()?;
         
         let mut items = Vec::new();
-        self.begin_scope(OpenCloseSymbol::Brace);
+        self.begin_scope(OpenCloseSymbol::Brace)?;
         while !self.try_end_scope() {
             items.push(self.parse_extern_item()?);
         }
@@ -2257,6 +2390,7 @@ impl Parser<'_> {
         } else {
             Vec::new()
         };
+        self.consume_punct(Punctuation::Semicolon)?;
 
         let span = self.get_span_to_current(begin);
         Ok(Item::OpUse(self.add_node(OpUse {
@@ -2373,6 +2507,7 @@ impl Parser<'_> {
         } else {
             Vec::new()
         };
+        self.consume_punct(Punctuation::Semicolon)?;
 
         let span = self.get_span_to_current(begin);
         Ok(Item::PrecedenceUse(self.add_node(PrecedenceUse {
@@ -2394,7 +2529,17 @@ impl Parser<'_> {
 
         let mut stmts = Vec::new();
         while !self.try_end_scope() {
-            stmts.push(self.parse_stmt(true)?);
+            let point = self.recovery_point();
+            match self.parse_stmt(true) {
+                Ok(stmt) => stmts.push(stmt),
+                // Nothing left to recover, so let the error bubble up
+                Err(err) if self.try_peek().is_none() => return Err(err),
+                Err(err) => {
+                    self.add_error(err);
+                    let node = self.recover(point, true);
+                    stmts.push(Stmt::Error(node));
+                },
+            }
         }
 
         let final_expr = if let Some(Stmt::Expr(stmt)) = stmts.last() {
@@ -2821,7 +2966,13 @@ impl Parser<'_> {
                     }))
                 }
                 
-                Token::OpenSymbol(OpenCloseSymbol::Brace)   => self.parse_struct_expr(expr, mode != ExprParseMode::NoStructLit)?,
+                // When struct expressions are not allowed, the brace is not part of this expression, e.g. the block of a `match`
+                Token::OpenSymbol(OpenCloseSymbol::Brace) if mode == ExprParseMode::NoStructLit => {
+                    let expr = self.parse_struct_expr(expr, false)?;
+                    self.pop_meta_frame();
+                    break expr
+                },
+                Token::OpenSymbol(OpenCloseSymbol::Brace)   => self.parse_struct_expr(expr, true)?,
                 Token::OpenSymbol(OpenCloseSymbol::Bracket) => self.parse_index_expr(expr)?,
                 Token::OpenSymbol(OpenCloseSymbol::Paren)   => self.parse_call_expression(expr)?,
 
@@ -3216,6 +3367,7 @@ impl Parser<'_> {
         self.begin_scope(OpenCloseSymbol::Bracket)?;
         let is_opt = self.try_consume(Token::Punctuation(Punctuation::Question));
         let index = self.parse_expr(ExprParseMode::AllowComma)?;
+        self.end_scope()?;
 
         let span = self.get_span_to_current(begin);
         Ok(Expr::Index(self.add_node(IndexExpr {
@@ -4045,12 +4197,30 @@ impl Parser<'_> {
     fn parse_type(&mut self) -> Result<Type, ParserErr> {
         let peek = self.peek()?;
         match peek {
-            Token::StrongKw(StrongKeyword::Dyn) => todo!(),
-            Token::StrongKw(StrongKeyword::Impl) => todo!(),
-            _ => self.parse_type_no_bounds()
+            Token::StrongKw(StrongKeyword::Dyn)  => self.parse_trait_object_type(),
+            Token::StrongKw(StrongKeyword::Impl) => self.parse_impl_trait_type(),
+            _                                    => self.parse_type_no_bounds()
         }
     }
 
+    fn parse_trait_object_type(&mut self) -> Result<Type, ParserErr> {
+        self.push_meta_frame();
+        let begin = self.get_cur_span();
+        self.consume_strong_kw(StrongKeyword::Dyn)?;
+        let bounds = self.parse_trait_bounds()?;
+        let span = self.get_span_to_current(begin);
+        Ok(Type::TraitObject(self.add_node(TraitObjectType { span, node_id: NodeId::default(), bounds })))
+    }
+
+    fn parse_impl_trait_type(&mut self) -> Result<Type, ParserErr> {
+        self.push_meta_frame();
+        let begin = self.get_cur_span();
+        self.consume_strong_kw(StrongKeyword::Impl)?;
+        let bounds = self.parse_trait_bounds()?;
+        let span = self.get_span_to_current(begin);
+        Ok(Type::ImplTrait(self.add_node(ImplTraitType { span, node_id: NodeId::default(), bounds })))
+    }
+
     fn parse_type_no_bounds(&mut self) -> Result<Type, ParserErr> {
         self.push_meta_frame();
         
@@ -4160,7 +4330,7 @@ impl Parser<'_> {
         let peek = self.peek()?;
         match peek {
             Token::CloseSymbol(OpenCloseSymbol::Bracket) => {
-                self.end_scope();
+                self.end_scope()?;
                 let ty = self.parse_type_no_bounds()?;
                 let span = self.get_span_to_current(begin);
                 Ok(Type::Slice(self.add_node(SliceType { span, node_id: NodeId::default(), sentinel: None, ty })))
@@ -4182,7 +4352,7 @@ impl Parser<'_> {
                 };
                 self.end_scope()?;
                 let is_mut = self.try_consume(Token::StrongKw(StrongKeyword::Mut));
-                let ty = self.parse_type_no_bounds()?;
+                let ty = self.parse_type()?;
                 let span = self.get_span_to_current(begin);
                 Ok(Type::Pointer(self.add_node(PointerType { span, node_id: NodeId::default(), is_multi: true, is_mut, sentinel, ty })))
             },
@@ -4205,7 +4375,7 @@ impl Parser<'_> {
         let begin = self.get_cur_span();
         self.consume_punct(Punctuation::Caret)?;
         let is_mut = self.try_consume(Token::StrongKw(StrongKeyword::Mut));
-        let ty = self.parse_type_no_bounds()?;
+        let ty = self.parse_type()?;
         let span = self.get_span_to_current(begin);
         Ok(Type::Pointer(self.add_node(PointerType { span, node_id: NodeId::default(), is_multi: false, is_mut, sentinel: None, ty })))
     }
@@ -4214,7 +4384,7 @@ impl Parser<'_> {
         let begin = self.get_cur_span();
         self.consume_punct(Punctuation::Ampersand)?;
         let is_mut = self.try_consume(Token::StrongKw(StrongKeyword::Mut));
-        let ty = self.parse_type_no_bounds()?;
+        let ty = self.parse_type()?;
         let span = self.get_span_to_current(begin);
         Ok(Type::Ref(self.add_node(ReferenceType { span, node_id: NodeId::default(), is_mut, ty })))
     }
@@ -4611,6 +4781,7 @@ impl Parser<'_> {
     }
 
     fn parse_trait_bounds(&mut self) -> Result<AstNodeRef<TraitBounds>, ParserErr> {
+        self.push_meta_frame();
         let begin = self.get_cur_span();
         let bounds = self.parse_punct_separated(Punctuation::Ampersand, Self::parse_trait_path)?;
 
@@ -4775,4 +4946,118 @@ impl Parser<'_> {
         }
         Ok(values)
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ast::{Ast, Item, Stmt}, common::{NameTable, SpanRegistry}, error_warning::ParseErrorCode, lexer::{Lexer, PuncutationTable}, literals::LiteralTable};
+
+    use super::Parser;
+
+    /// Parse a source and return the ast and the codes of all errors
+    fn parse(source: &str) -> (Ast, Vec<ParseErrorCode>) {
+        let literals = LiteralTable::new();
+        let names = NameTable::new();
+        let punctuation = PuncutationTable::new();
+        let spans = SpanRegistry::new();
+        let mut lexer = Lexer::new("", source, &literals, &names, &punctuation, &spans);
+        lexer.lex();
+        assert!(lexer.errors.is_empty());
+
+        let mut parser = Parser::new(&lexer.tokens, &names, &spans);
+        parser.parse();
+        let Parser { ast, errors, .. } = parser;
+        (ast, errors.into_iter().map(|err| err.err).collect())
+    }
+
+    fn fn_body_stmts(item: &Item) -> &[Stmt] {
+        let Item::Function(func) = item else { panic!("expected a function") };
+        &func.body.as_ref().unwrap().stmts
+    }
+
+    #[test]
+    fn test_recover_item() {
+        let source = r#"
+fn a() {}
+fn b(: i32) {}
+fn c() {}
+"#;
+        let (ast, errors) = parse(source);
+
+        // The broken function is skipped up to and including its closing brace
+        assert_eq!(errors.len(), 1);
+        assert_eq!(ast.items.len(), 3);
+        assert!(matches!(ast.items[0], Item::Function(_)));
+        assert!(matches!(ast.items[1], Item::Error(_)));
+        assert!(matches!(ast.items[2], Item::Function(_)));
+    }
+
+    #[test]
+    fn test_recover_closing_delimiter() {
+        let source = r#"
+struct A {
+    a: i32,
+    b:
+}
+struct B {}
+"#;
+        let (ast, errors) = parse(source);
+
+        // Recovery stops after the closing brace of the scope the error was in
+        assert_eq!(errors.len(), 1);
+        assert_eq!(ast.items.len(), 2);
+        assert!(matches!(ast.items[0], Item::Error(_)));
+        assert!(matches!(ast.items[1], Item::Struct(_)));
+    }
+
+    #[test]
+    fn test_recover_stmt() {
+        let source = r#"
+fn a() {
+    let x = ;
+    let y = 1;
+    let z = 2 2;
+    let w = 3;
+}
+fn b() {}
+"#;
+        let (ast, errors) = parse(source);
+
+        // Each broken statement is replaced by an error node, and the statements after it are still parsed
+        assert_eq!(errors.len(), 2);
+        assert_eq!(ast.items.len(), 2);
+        let stmts = fn_body_stmts(&ast.items[0]);
+        assert_eq!(stmts.len(), 4);
+        assert!(matches!(stmts[0], Stmt::Error(_)));
+        assert!(matches!(stmts[1], Stmt::VarDecl(_)));
+        assert!(matches!(stmts[2], Stmt::Error(_)));
+        assert!(matches!(stmts[3], Stmt::VarDecl(_)));
+        assert!(matches!(ast.items[1], Item::Function(_)));
+    }
+
+    #[test]
+    fn test_recover_unclosed_block() {
+        let source = r#"
+fn a() {
+    let x = 
+fn b() {}
+"#;
+        let (ast, errors) = parse(source);
+
+        // The statement recovers at `fn`, but the block of `a` is never closed, so the whole function becomes an error
+        assert_eq!(errors.len(), 2);
+        assert_eq!(ast.items.len(), 1);
+        assert!(matches!(ast.items[0], Item::Error(_)));
+    }
+
+    #[test]
+    fn test_recover_end_of_file() {
+        let (ast, errors) = parse("fn a() {");
+
+        // Nothing is left to skip, but the error is still reported once
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(errors[0], ParseErrorCode::NotEnoughTokens));
+        assert_eq!(ast.items.len(), 1);
+        assert!(matches!(ast.items[0], Item::Error(_)));
+    }
 }
\ No newline at end of file
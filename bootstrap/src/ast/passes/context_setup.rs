@@ -1,6 +1,6 @@
 use crate::{ast::*, common::Scope};
 
-use super::{Context, ContextNodeData, ModuleContextData};
+use super::{AstPass, Context, ContextNodeData, ModuleContextData};
 
 
 
@@ -17,6 +17,11 @@ impl<'a> ContextSetup<'a> {
     }
 }
 
+impl AstPass for ContextSetup<'_> {
+    const NAME: &'static str = "Context Setup";
+    const DEPENDENCIES: &'static [&'static str] = &[];
+}
+
 impl Visitor for ContextSetup<'_> {
     fn visit_module(&mut self, node_id: &AstNodeRef<ModuleItem>) where Self: Sized {
         let node = self.ctx.get_node_for_mut(node_id);
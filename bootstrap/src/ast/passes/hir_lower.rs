@@ -10,7 +10,7 @@ use crate::{
     literals::{LiteralId, LiteralTable},
 };
 
-use super::{AstError, Context};
+use super::{AstError, AstPass, Context, ModulePathResolution, ModuleScopePass};
 
 // TODO: node tracking for nodes that don't have a `node_id`
 
@@ -38,6 +38,8 @@ pub struct AstToHirLowering<'a> {
 
     gen_args_stack:     Vec<Box<hir::GenericArgs>>,
     trait_bounds_stack: Vec<Box<hir::TraitBounds>>,
+    /// `impl Trait` types that are not yet known to be in an allowed position
+    impl_trait_types:   Vec<NodeId>,
 
     #[allow(unused)]
     contract_stack:     Vec<Box<hir::Contract>>,
@@ -94,6 +96,7 @@ impl<'a> AstToHirLowering<'a> {
 
             gen_args_stack:     Vec::new(),
             trait_bounds_stack: Vec::new(),
+            impl_trait_types:   Vec::new(),
 
             contract_stack:     Vec::new(),
 
@@ -289,26 +292,7 @@ impl AstToHirLowering<'_> {
             let Some(ret_ty) = &return_ty else { unreachable!() };
             let hir::Type::Tuple(hir::TupleType{ types, .. }) = ret_ty.as_ref() else { unreachable!() };
 
-            let mut ret_exprs = Vec::new();
-            for (names, _) in vars {
-                for (name, span) in names {
-                    ret_exprs.push(Box::new(hir::Expr::Path(hir::PathExpr::Named {
-                        span: *span,
-                        node_id: body.node_id,
-                        start: hir::PathStart::None,
-                        iden: hir::Identifier {
-                            span: *span,
-                            name: hir::IdenName::Name { name: *name, span: *span },
-                            gen_args: None,
-                        },
-                    })));
-                }
-            }
-            let ret_tup_expr = Box::new(hir::Expr::Tuple(hir::TupleExpr {
-                span: *span,
-                node_id: body.node_id,
-                exprs: ret_exprs,
-            }));
+            let ret_tup_expr = self.convert_named_ret_expr(*span, vars, body.node_id);
             self.named_ret_expr = Some(ret_tup_expr.clone());
             self.visit_block(body);
             self.named_ret_expr = None;
@@ -341,6 +325,29 @@ impl AstToHirLowering<'_> {
         }
     }
 
+    fn convert_named_ret_expr(&self, span: SpanId, vars: &[(Vec<(NameId, SpanId)>, Type)], node_id: NodeId) -> Box<hir::Expr> {
+        let mut ret_exprs = Vec::new();
+        for (names, _) in vars {
+            for (name, span) in names {
+                ret_exprs.push(Box::new(hir::Expr::Path(hir::PathExpr::Named {
+                    span: *span,
+                    node_id,
+                    start: hir::PathStart::None,
+                    iden: hir::Identifier {
+                        span: *span,
+                        name: hir::IdenName::Name { name: *name, span: *span },
+                        gen_args: None,
+                    },
+                })));
+            }
+        }
+        Box::new(hir::Expr::Tuple(hir::TupleExpr {
+            span,
+            node_id,
+            exprs: ret_exprs,
+        }))
+    }
+
     fn convert_reg_struct_field(&mut self, field: &RegStructField) -> (Vec<hir::StructField>, Vec<hir::StructUse>) {
         let mut fields = Vec::new();
         let mut uses = Vec::new();
@@ -985,6 +992,63 @@ impl AstToHirLowering<'_> {
         }
     }
 
+    /// Lower argument-position `impl Trait` types to anonymous generic type parameters, the `impl Trait` type in a return type is kept as an abstract return type
+    fn lower_impl_trait_types(&mut self, node_id: NodeId, params: &mut [hir::FnParam], return_ty: Option<&hir::Type>, generics: &mut Option<Box<hir::GenericParams>>, where_clause: &mut Option<Box<hir::WhereClause>>) {
+        if let Some(hir::Type::ImplTrait(ty)) = return_ty {
+            self.impl_trait_types.retain(|id| *id != ty.node_id);
+        }
+
+        for param in params {
+            let ty = match param {
+                hir::FnParam::Param { ty, .. }    |
+                hir::FnParam::Opt { ty, .. }      |
+                hir::FnParam::Variadic { ty, .. } => ty,
+            };
+            let hir::Type::ImplTrait(impl_ty) = &**ty else { continue };
+            let span = impl_ty.span;
+            let impl_node_id = impl_ty.node_id;
+
+            let loc = self.spans[span];
+            let name = self.names.add(&format!("__impl_trait_{}_{}", loc.row, loc.column));
+
+            // Parameters with multiple names share the same type, so they also share the same generic parameter
+            let generics = generics.get_or_insert_with(|| Box::new(hir::GenericParams {
+                span,
+                node_id,
+                params: Vec::new(),
+                pack: None,
+            }));
+            let is_new = !generics.params.iter().any(|param| matches!(param, hir::GenericParam::Type(param) if param.name == name));
+            if is_new {
+                self.impl_trait_types.retain(|id| *id != impl_node_id);
+
+                generics.params.push(hir::GenericParam::Type(hir::GenericTypeParam {
+                    span,
+                    name,
+                    def: None,
+                    ctx: hir::GenericParamContext::new(),
+                }));
+
+                let hir::Type::ImplTrait(impl_ty) = *std::mem::replace(ty, Box::new(hir::PathType::from_name(name, span, impl_node_id))) else { unreachable!() };
+                let bound = hir::WhereBound::Type {
+                    span,
+                    ty: Box::new(hir::PathType::from_name(name, span, impl_node_id)),
+                    bounds: impl_ty.bounds.bounds,
+                };
+                match where_clause {
+                    Some(where_clause) => where_clause.bounds.push(bound),
+                    None => *where_clause = Some(Box::new(hir::WhereClause {
+                        span,
+                        node_id,
+                        bounds: vec![bound],
+                    })),
+                }
+            } else {
+                **ty = hir::PathType::from_name(name, span, impl_node_id);
+            }
+        }
+    }
+
     fn convert_path_start(&mut self, start: &PathStart) -> hir::PathStart {
         match start {
             PathStart::None           => hir::PathStart::None,
@@ -1024,6 +1088,11 @@ impl AstToHirLowering<'_> {
 
 // =============================================================================================================================
 
+impl AstPass for AstToHirLowering<'_> {
+    const NAME: &'static str = "AST to HIR lowering";
+    const DEPENDENCIES: &'static [&'static str] = &[ModuleScopePass::NAME, ModulePathResolution::NAME];
+}
+
 impl Visitor for AstToHirLowering<'_> {
     fn visit(&mut self, ast: &Ast) where Self: Sized {
         // First item is always within the file's scope, so we can get it from there
@@ -1053,6 +1122,7 @@ impl Visitor for AstToHirLowering<'_> {
             Item::OpUse(item)         => self.ctx.get_node_for(item),
             Item::Precedence(item)    => self.ctx.get_node_for(item),
             Item::PrecedenceUse(item) => self.ctx.get_node_for(item),
+            Item::Error(item)         => self.ctx.get_node_for(item),
         };
         self.file_scope = ast_ctx.module_scope.clone();
 
@@ -1065,6 +1135,13 @@ impl Visitor for AstToHirLowering<'_> {
         for item in &ast.items {
             self.visit_item(item);
         }
+
+        for node_id in std::mem::take(&mut self.impl_trait_types) {
+            self.ctx.add_error(AstError {
+                node_id,
+                err: AstErrorCode::ImplTraitNotAllowed,
+            });
+        }
     }
 
     fn visit_simple_path(&mut self, node: &AstNodeRef<SimplePath>) where Self: Sized {
@@ -1241,7 +1318,7 @@ impl Visitor for AstToHirLowering<'_> {
             },
         });
 
-        let params = self.convert_fn_params(&node.params, node.node_id());
+        let mut params = self.convert_fn_params(&node.params, node.node_id());
         let abi = self.convert_abi(node.abi, node.node_id());
         let vis = self.get_vis(node.vis.as_ref());
         let attrs = self.get_attribs(&node.attrs);
@@ -1256,7 +1333,9 @@ impl Visitor for AstToHirLowering<'_> {
             }
             (None, None)
         } else {
-            self.convert_generic_params(node.generics.as_ref(), node.where_clause.as_ref())
+            let (mut generics, mut where_clause) = self.convert_generic_params(node.generics.as_ref(), node.where_clause.as_ref());
+            self.lower_impl_trait_types(node.node_id, &mut params, return_ty.as_deref(), &mut generics, &mut where_clause);
+            (generics, where_clause)
         };
 
         let node_ctx = self.ctx.get_node_for(node);
@@ -1318,13 +1397,14 @@ impl Visitor for AstToHirLowering<'_> {
             },
         });
 
-        let params = self.convert_fn_params(&node.params, node.node_id());
+        let mut params = self.convert_fn_params(&node.params, node.node_id());
         let receiver = self.convert_fn_receiver(&node.receiver);
         let vis = self.get_vis(node.vis.as_ref());
         let attrs = self.get_attribs(&node.attrs);
         let body = self.convert_fn_body(&node.body, node.returns.as_ref(), return_ty.as_ref());
 
-        let (generics, where_clause) = self.convert_generic_params(node.generics.as_ref(), node.where_clause.as_ref());
+        let (mut generics, mut where_clause) = self.convert_generic_params(node.generics.as_ref(), node.where_clause.as_ref());
+        self.lower_impl_trait_types(node.node_id, &mut params, return_ty.as_deref(), &mut generics, &mut where_clause);
 
         self.hir.add_method(self.trait_impl_scope.clone(), self.file_scope.clone(), hir::Method {
             span: node.span,
@@ -1770,7 +1850,7 @@ impl Visitor for AstToHirLowering<'_> {
     //--------------------------------------------------------------
 
     fn visit_trait(&mut self, node: &AstNodeRef<Trait>) where Self: Sized {
-        helpers::visit_trait(self, node, false);
+        helpers::visit_trait(self, node, false, false);
 
         let vis = self.get_vis(node.vis.as_ref());
         let attrs = self.get_attribs(&node.attrs);
@@ -1823,7 +1903,7 @@ impl Visitor for AstToHirLowering<'_> {
             },
         });
 
-        let params = self.convert_fn_params(&node.params, node.node_id());
+        let mut params = self.convert_fn_params(&node.params, node.node_id());
         let attrs = self.get_attribs(&node.attrs);
 
         let body = node.body.as_ref().map(|body| if let Some(FnReturn::Named{ span, vars }) = &node.returns {
@@ -1846,26 +1926,7 @@ impl Visitor for AstToHirLowering<'_> {
             let Some(ret_ty) = &return_ty else { unreachable!() };
             let hir::Type::Tuple(hir::TupleType{ types, .. }) = ret_ty.as_ref() else { unreachable!() };
 
-            let mut ret_exprs = Vec::new();
-            for (names, _) in vars {
-                for (name, span) in names {
-                    ret_exprs.push(Box::new(hir::Expr::Path(hir::PathExpr::Named {
-                        span: *span,
-                        node_id: node.node_id,
-                        start: hir::PathStart::None,
-                        iden: hir::Identifier {
-                            span: *span,
-                            name: hir::IdenName::Name{ name: *name, span: *span },
-                            gen_args: None,
-                        },
-                    })));
-                }
-            }
-            let ret_tup_expr = Box::new(hir::Expr::Tuple(hir::TupleExpr {
-                span: *span,
-                node_id: node.node_id,
-                exprs: ret_exprs,
-            }));
+            let ret_tup_expr = self.convert_named_ret_expr(*span, vars, node.node_id);
             self.named_ret_expr = Some(ret_tup_expr.clone());
             self.visit_block(body);
             self.named_ret_expr = None;
@@ -1897,7 +1958,8 @@ impl Visitor for AstToHirLowering<'_> {
             Box::new(self.block_stack.pop().unwrap())
         });
 
-        let (generics, where_clause) = self.convert_generic_params(node.generics.as_ref(), node.where_clause.as_ref());
+        let (mut generics, mut where_clause) = self.convert_generic_params(node.generics.as_ref(), node.where_clause.as_ref());
+        self.lower_impl_trait_types(node.node_id, &mut params, return_ty.as_deref(), &mut generics, &mut where_clause);
 
         self.hir.add_trait_function(self.trait_impl_scope.clone(), self.file_scope.clone(), hir::TraitFunction {
             span: node.span,
@@ -1937,12 +1999,13 @@ impl Visitor for AstToHirLowering<'_> {
             },
         });
 
-        let params = self.convert_fn_params(&node.params, node.node_id());
+        let mut params = self.convert_fn_params(&node.params, node.node_id());
         let receiver = self.convert_fn_receiver(&node.receiver);
         let attrs = self.get_attribs(&node.attrs);
         let body = node.body.as_ref().map(|body| self.convert_fn_body(body, node.returns.as_ref(), return_ty.as_ref()));
 
-        let (generics, where_clause) = self.convert_generic_params(node.generics.as_ref(), node.where_clause.as_ref());
+        let (mut generics, mut where_clause) = self.convert_generic_params(node.generics.as_ref(), node.where_clause.as_ref());
+        self.lower_impl_trait_types(node.node_id, &mut params, return_ty.as_deref(), &mut generics, &mut where_clause);
 
         self.hir.add_trait_method(self.trait_impl_scope.clone(), self.file_scope.clone(), hir::TraitMethod {
             span: node.span,
@@ -3274,19 +3337,111 @@ impl Visitor for AstToHirLowering<'_> {
     }
 
     fn visit_closure_expr(&mut self, node: &AstNodeRef<ClosureExpr>) where Self: Sized {
-        helpers::visit_closure_expr(self, node);
+        for param in &node.params {
+            helpers::visit_fn_param(self, param);
+        }
+        if let Some(ret) = &node.ret {
+            helpers::visit_fn_return(self, ret);
+        }
 
-        let body = self.expr_stack.pop().unwrap();
+        // A `return` in the closure returns from the closure, not from the surrounding function
+        let ret_tup_expr = match &node.ret {
+            Some(FnReturn::Named{ span, vars }) => Some(self.convert_named_ret_expr(*span, vars, node.node_id)),
+            _ => None,
+        };
+        let outer_ret_expr = mem::replace(&mut self.named_ret_expr, ret_tup_expr.clone());
+        self.visit_expr(&node.body);
+        self.named_ret_expr = outer_ret_expr;
+
+        let mut body = self.expr_stack.pop().unwrap();
+
+        let ret = node.ret.as_ref().map(|ret| match ret {
+            FnReturn::Type{ .. } => self.type_stack.pop().unwrap(),
+            FnReturn::Named{ span, vars } => {
+                let mut types = Vec::new();
+                for _ in vars {
+                    types.push(self.type_stack.pop().unwrap());
+                }
+                types.reverse();
+                Box::new(hir::Type::Tuple(hir::TupleType {
+                    span: *span,
+                    node_id: node.node_id,
+                    types,
+                    ctx: hir::TypeContext::new(),
+                }))
+            },
+        });
 
-        // TODO``
+        let params = self.convert_fn_params(&node.params, node.node_id);
+
+        if let (Some(FnReturn::Named{ vars, .. }), Some(ret_tup_expr), Some(ret_ty)) = (&node.ret, ret_tup_expr, &ret) {
+            // convert:
+            //
+            // ```
+            // || -> (a, b: u32) { ... /* body */ }
+            // ```
+            // to:
+            // ```
+            // || -> (u32, u32) {
+            //     let mut a: u32;
+            //     let mut b: u32;
+            //     ... // body
+            //     (a, b)
+            // }
+            // ```
+            let hir::Type::Tuple(hir::TupleType{ types, .. }) = ret_ty.as_ref() else { unreachable!() };
+
+            let mut stmts = Vec::new();
+            for (idx, (names, _)) in vars.iter().enumerate() {
+                for (name, span) in names {
+                    stmts.push(Box::new(hir::Stmt::UninitVarDecl(hir::UninitVarDecl {
+                        span: *span,
+                        node_id: node.node_id,
+                        attrs: Vec::new(),
+                        is_mut: true,
+                        name: *name,
+                        ty: types[idx].clone(),
+                        allow_du: false,
+                    })));
+                }
+            }
+
+            body = match *body {
+                hir::Expr::Block(hir::BlockExpr { kind: hir::BlockKind::Normal, mut block, .. }) => {
+                    stmts.append(&mut block.stmts);
+                    block.stmts = stmts;
+                    if block.expr.is_none() {
+                        block.expr = Some(ret_tup_expr);
+                    }
+                    Box::new(hir::Expr::Block(hir::BlockExpr {
+                        span: block.span,
+                        node_id: node.node_id,
+                        kind: hir::BlockKind::Normal,
+                        block,
+                    }))
+                },
+                body => Box::new(hir::Expr::Block(hir::BlockExpr {
+                    span: body.span(),
+                    node_id: node.node_id,
+                    kind: hir::BlockKind::Normal,
+                    block: hir::Block {
+                        span: body.span(),
+                        stmts,
+                        expr: Some(Box::new(body)),
+                        ctx: hir::BlockContext::new(),
+                    },
+                })),
+            };
+        }
 
         self.push_expr(hir::Expr::Closure(hir::ClosureExpr {
             span: node.span,
             node_id: node.node_id,
             is_moved: node.is_moved,
-            params: todo!(),
-            ret: todo!(),
-            body: todo!(),
+            params,
+            ret,
+            body,
+            ctx: hir::ClosureContext::new(),
         }))
     }
 
@@ -4408,6 +4563,27 @@ impl Visitor for AstToHirLowering<'_> {
         }))
     }
 
+    fn visit_trait_object_type(&mut self, node: &AstNodeRef<TraitObjectType>) where Self: Sized {
+        let bounds = Box::new(self.convert_trait_bounds(&node.bounds));
+        self.push_type(hir::Type::TraitObject(hir::TraitObjectType {
+            span: node.span,
+            node_id: node.node_id,
+            bounds,
+            ctx: hir::TypeContext::new(),
+        }))
+    }
+
+    fn visit_impl_trait_type(&mut self, node: &AstNodeRef<ImplTraitType>) where Self: Sized {
+        let bounds = Box::new(self.convert_trait_bounds(&node.bounds));
+        self.impl_trait_types.push(node.node_id);
+        self.push_type(hir::Type::ImplTrait(hir::ImplTraitType {
+            span: node.span,
+            node_id: node.node_id,
+            bounds,
+            ctx: hir::TypeContext::new(),
+        }))
+    }
+
     // =============================================================
 
     fn visit_visibility(&mut self, node: &AstNodeRef<Visibility>) where Self: Sized {
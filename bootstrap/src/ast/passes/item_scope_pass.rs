@@ -1,4 +1,4 @@
-use super::Context;
+use super::{AstPass, Context, ContextSetup};
 use crate::{ast::*, common::{NameTable, Scope}};
 
 
@@ -19,6 +19,11 @@ impl<'a> ModuleScopePass<'a> {
     }
 }
 
+impl AstPass for ModuleScopePass<'_> {
+    const NAME: &'static str = "Module Scoping";
+    const DEPENDENCIES: &'static [&'static str] = &[ContextSetup::NAME];
+}
+
 impl Visitor for ModuleScopePass<'_> {
     fn visit_item(&mut self, item: &Item) where Self: Sized {
         let ctx = self.ctx.get_node_for_index_mut(item.node_id().index());
@@ -1,10 +1,11 @@
 use std::{fmt, path::PathBuf, sync::Arc};
 use crate::{
     ast::{Ast, AstNodeRef, AstNode, NodeId},
-    common::{LibraryPath, PrecedenceDAG, RootSymbolTable, Scope},
+    common::{Diagnostic, LibraryPath, PassInfo, PrecedenceDAG, RootSymbolTable, Scope, SpanId},
     error_warning::AstErrorCode
 };
 use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
 
 mod context_setup;
 pub use context_setup::*;
@@ -21,27 +22,58 @@ pub use module_symbol_generation::*;
 mod hir_lower;
 pub use hir_lower::*;
 
+/// Pass over the AST, passes run in the order of `AST_PASSES`
+pub trait AstPass {
+    const NAME: &'static str;
+    /// Passes that need to run before this pass
+    const DEPENDENCIES: &'static [&'static str];
+}
+
+const fn pass_info<T: AstPass>() -> PassInfo {
+    PassInfo { name: T::NAME, dependencies: T::DEPENDENCIES }
+}
+
+/// All AST passes, in the order they run, the HIR lowering is always the last pass
+pub const AST_PASSES: &[PassInfo] = &[
+    pass_info::<ContextSetup>(),
+    pass_info::<ModuleScopePass>(),
+    pass_info::<ModuleAttributeResolver>(),
+    pass_info::<ModulePathResolution>(),
+    pass_info::<AstToHirLowering>(),
+];
+
 pub struct AstError {
     node_id: NodeId,
     err:     AstErrorCode,
 }
 
+impl AstError {
+    pub fn to_diagnostic(&self, ast: &Ast) -> Diagnostic {
+        let span = ast.nodes.get(self.node_id.index())
+            .map_or(SpanId::INVALID, |node| node.span());
+        Diagnostic::error(&self.err).with_primary(span, "")
+    }
+}
+
 impl fmt::Display for AstError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.err)
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct ModuleContextData {
     path:     Option<PathBuf>,
     sym_path: Scope, //< Not really a scope, but good enough for now
 }
 
+#[derive(Serialize, Deserialize)]
 pub enum ContextNodeData {
     None,
     Module(ModuleContextData),
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct ContextNode {
     pub module_scope: Scope,
     pub data:         ContextNodeData,
@@ -76,6 +108,17 @@ impl Context {
         let mut ctxs = Vec::with_capacity(ast.nodes.len());
         ctxs.resize_with(ast.nodes.len(), || ContextNode::new());
 
+        Self::with_nodes(lib_path, syms, mod_root, ctxs, precedences)
+    }
+
+    /// Create a context from nodes of an earlier run of the per-file passes, e.g. loaded from the incremental cache
+    pub fn with_nodes(
+        lib_path: LibraryPath,
+        syms: Arc<RwLock<RootSymbolTable>>,
+        mod_root: Scope,
+        ctxs: Vec<ContextNode>,
+        precedences: Arc<RwLock<PrecedenceDAG>>,
+    ) -> Self {
         Self {
             lib_path,
             errors: Mutex::new(Vec::new()),
@@ -86,6 +129,10 @@ impl Context {
         }
     }
 
+    pub fn nodes(&self) -> &[ContextNode] {
+        &self.ctxs
+    }
+
     pub fn get_node_for_index(&self, id: usize) -> &ContextNode {
         assert!(id < self.ctxs.len());
         &self.ctxs[id]
@@ -5,25 +5,33 @@ use crate::{
     literals::{Literal, LiteralTable}
 };
 
-use super::{AstError, Context, ContextNodeData};
+use super::{AstError, AstPass, Context, ContextNodeData, ModuleScopePass};
 
 pub struct ModuleAttributeResolver<'a> {
     ctx:          &'a mut Context,
     lit_table:    &'a LiteralTable,
     path_name_id: NameId,
+    doc_name_id:  NameId,
 }
 
 impl<'a> ModuleAttributeResolver<'a> {
     pub fn new(ctx: &'a mut Context, name_table: &NameTable, lit_table: &'a LiteralTable) -> Self {
         let path_name_id = name_table.get_id_for_str("path");
+        let doc_name_id = name_table.get_id_for_str("doc");
         Self {
             ctx,
             lit_table,
             path_name_id,
+            doc_name_id,
         }
     }
 }
 
+impl AstPass for ModuleAttributeResolver<'_> {
+    const NAME: &'static str = "Module Attribute Resolve";
+    const DEPENDENCIES: &'static [&'static str] = &[ModuleScopePass::NAME];
+}
+
 impl Visitor for ModuleAttributeResolver<'_> {
 
     // Limit visiting only to root accessible items
@@ -35,6 +43,11 @@ impl Visitor for ModuleAttributeResolver<'_> {
 
     fn visit_module(&mut self, node: &AstNodeRef<ModuleItem>) where Self: Sized {
         for attr in &node.attrs {
+            // Documentation is handled by the doc generator
+            if attr.path.names.len() == 1 && attr.path.names[0].0 == self.doc_name_id {
+                continue;
+            }
+
             for meta in &attr.metas {
                 match meta {
                     AttribMeta::Simple { .. } => {
@@ -95,8 +108,7 @@ impl Visitor for ModuleAttributeResolver<'_> {
                     },
                 }
             }
-
-            helpers::visit_module(self, node);
         }
+        helpers::visit_module(self, node);
     }
 }
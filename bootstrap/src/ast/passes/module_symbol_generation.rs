@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::{
     ast::*,
@@ -6,7 +6,7 @@ use crate::{
     error_warning::AstErrorCode
 };
 
-use super::{AstError, Context, ContextNodeData};
+use super::{AstError, AstPass, Context, ContextNodeData, ModuleAttributeResolver};
 
 pub struct ModulePathResolution<'a> {
     ctx:             &'a mut Context,
@@ -14,6 +14,8 @@ pub struct ModulePathResolution<'a> {
     base_path:       PathBuf,
     path_stack:      Vec<PathBuf>,
     pub collected_paths: Vec<(PathBuf, Scope)>,
+    /// Paths that were checked for a module file, and whether they were a file, the result of the pass is only valid while these don't change
+    pub probed_paths:    Vec<(PathBuf, bool)>,
 }
 
 impl<'a> ModulePathResolution<'a> {
@@ -24,8 +26,20 @@ impl<'a> ModulePathResolution<'a> {
             base_path,
             path_stack: Vec::new(),
             collected_paths: Vec::new(),
+            probed_paths: Vec::new(),
         }
     }
+
+    fn is_file(&mut self, path: &Path) -> bool {
+        let is_file = path.is_file();
+        self.probed_paths.push((path.to_path_buf(), is_file));
+        is_file
+    }
+}
+
+impl AstPass for ModulePathResolution<'_> {
+    const NAME: &'static str = "Module Symbol Generation + Path Collection";
+    const DEPENDENCIES: &'static [&'static str] = &[ModuleAttributeResolver::NAME];
 }
 
 impl Visitor for ModulePathResolution<'_> {
@@ -127,14 +141,14 @@ impl Visitor for ModulePathResolution<'_> {
             let mut err_paths = Vec::new();
             if is_mod_path {
                 path.set_extension("xn");
-                if !path.is_file() {
+                if !self.is_file(&path) {
                     err_paths.push(path.to_str().unwrap().to_string());
                     path.set_extension("");
 
 
                     path.push("mod.xn");   
 
-                    if !path.is_file() {
+                    if !self.is_file(&path) {
                         self.ctx.add_error(AstError {
                             node_id: node.node_id(),
                             err: AstErrorCode::InvalidModulePath { paths: err_paths },
@@ -145,11 +159,11 @@ impl Visitor for ModulePathResolution<'_> {
             } else {
                 path.push(mod_name);
                 path.set_extension("xn");
-                if !path.is_file() {
+                if !self.is_file(&path) {
                     err_paths.push(path.to_str().unwrap().to_string());
                     path.set_extension("");
                     path.push("mod.xn");
-                    if !path.is_file() {
+                    if !self.is_file(&path) {
                         err_paths.push(path.to_str().unwrap().to_string());
                         self.ctx.add_error(AstError {
                             node_id: node.node_id(),
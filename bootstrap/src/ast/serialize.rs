@@ -0,0 +1,167 @@
+// Serialization of the AST for the incremental cache.
+//
+// Nodes are stored in the order they were added to the AST, and references to nodes are stored as the index of the node.
+// The parser adds the children of a node before the node itself, so all nodes a node references have already been loaded when it is loaded.
+
+use std::{any::Any, fmt, path::PathBuf, sync::Arc};
+
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{incremental, lexer::TokenStore};
+
+use super::*;
+
+impl<T> Serialize for AstNodeRef<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let index = incremental::node_index(Arc::as_ptr(&self.0) as *const ()).map_err(ser::Error::custom)?;
+        index.serialize(serializer)
+    }
+}
+
+impl<'de, T: Any + Send + Sync> Deserialize<'de> for AstNodeRef<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let index = u32::deserialize(deserializer)?;
+        incremental::loaded_node(index).map(AstNodeRef).map_err(de::Error::custom)
+    }
+}
+
+macro_rules! node_kinds {
+    ($($kind:ident),* $(,)?) => {
+        #[derive(Serialize)]
+        enum NodeData<'a> {
+            $($kind(&'a $kind),)*
+        }
+
+        impl<'a> NodeData<'a> {
+            fn new(node: &'a dyn AstNode) -> Option<Self> {
+                let node: &dyn Any = node;
+                $(
+                    if let Some(node) = node.downcast_ref::<$kind>() {
+                        return Some(NodeData::$kind(node));
+                    }
+                )*
+                None
+            }
+        }
+
+        #[derive(Deserialize)]
+        enum OwnedNodeData {
+            $($kind($kind),)*
+        }
+
+        impl OwnedNodeData {
+            fn into_node(self) -> (Arc<dyn AstNode>, Arc<dyn Any + Send + Sync>) {
+                match self {
+                    $(
+                        OwnedNodeData::$kind(node) => {
+                            let node = Arc::new(node);
+                            (node.clone(), node)
+                        },
+                    )*
+                }
+            }
+        }
+    };
+}
+
+node_kinds! {
+    SimplePath, TypePath, ExprPath, TraitPath, Block, Item, ErrorNode, ExternItem, ModuleItem, UseItem, UsePath,
+    Function, Method, TypeAlias, DistinctType, OpaqueType, Struct, Union, Enum, Bitfield, Const, Static, Property,
+    Trait, TraitItem, TraitFunction, TraitMethod, TraitTypeAlias, TraitConst, TraitProperty, Impl, ImplItem,
+    ExternBlock, OpSet, OpUse, Precedence, PrecedenceUse, Stmt, EmptyStmt, VarDecl, Defer, ErrDefer, ExprStmt, Expr,
+    LiteralExpr, PathExpr, UnitExpr, BlockExpr, PrefixExpr, PostfixExpr, InfixExpr, ParenExpr, InplaceExpr,
+    TypeCastExpr, TypeCheckExpr, TupleExpr, ArrayExpr, StructExpr, IndexExpr, TupleIndexExpr, FnCallExpr,
+    MethodCallExpr, FieldAccessExpr, ClosureExpr, FullRangeExpr, IfExpr, LetBindingExpr, LoopExpr, WhileExpr,
+    DoWhileExpr, ForExpr, MatchExpr, BreakExpr, ContinueExpr, FallthroughExpr, ReturnExpr, UnderscoreExpr, ThrowExpr,
+    CommaExpr, WhenExpr, Pattern, LiteralPattern, IdentifierPattern, PathPattern, WildcardPattern, RestPattern,
+    RangePattern, ReferencePattern, StructPattern, TupleStructPattern, TuplePattern, GroupedPattern, SlicePattern,
+    EnumMemberPattern, AlternativePattern, TypeCheckPattern, Type, ParenthesizedType, PrimitiveType, UnitType,
+    NeverType, PathType, TupleType, ArrayType, SliceType, StringSliceType, PointerType, ReferenceType, OptionalType,
+    FnType, RecordType, EnumRecordType, TraitObjectType, ImplTraitType, GenericParam, GenericTypeParam,
+    GenericTypeSpec, GenericConstParam, GenericConstSpec, GenericParamPack, GenericParams, WhereClause,
+    GenericTypeBound, GenericArgs, TraitBounds, Visibility, Attribute, Contract
+}
+
+struct NodesRef<'a>(&'a [Arc<dyn AstNode>]);
+
+impl Serialize for NodesRef<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        for (idx, node) in self.0.iter().enumerate() {
+            incremental::register_node_address(Arc::as_ptr(node) as *const (), idx as u32).map_err(ser::Error::custom)?;
+        }
+        serializer.collect_seq(self.0.iter().map(|node| NodeData::new(&**node)))
+    }
+}
+
+struct Nodes(Vec<Arc<dyn AstNode>>);
+
+impl<'de> Deserialize<'de> for Nodes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct NodesVisitor;
+
+        impl<'de> de::Visitor<'de> for NodesVisitor {
+            type Value = Nodes;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a sequence of AST nodes")
+            }
+
+            fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut nodes = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+                // Each node needs to be registered before the next one is loaded, as it may reference it
+                while let Some(data) = seq.next_element::<Option<OwnedNodeData>>()? {
+                    let data = data.ok_or_else(|| de::Error::custom("unknown kind of AST node"))?;
+                    let (node, any) = data.into_node();
+                    incremental::add_loaded_node(any).map_err(de::Error::custom)?;
+                    nodes.push(node);
+                }
+                Ok(Nodes(nodes))
+            }
+        }
+
+        deserializer.deserialize_seq(NodesVisitor)
+    }
+}
+
+#[derive(Serialize)]
+struct AstRef<'a> {
+    file:   &'a PathBuf,
+    nodes:  NodesRef<'a>,
+    meta:   &'a [AstNodeMeta],
+    items:  &'a [Item],
+    tokens: &'a TokenStore,
+}
+
+#[derive(Deserialize)]
+struct OwnedAst {
+    file:   PathBuf,
+    nodes:  Nodes,
+    meta:   Vec<AstNodeMeta>,
+    items:  Vec<Item>,
+    tokens: TokenStore,
+}
+
+impl Serialize for Ast {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        AstRef {
+            file: &self.file,
+            nodes: NodesRef(&self.nodes),
+            meta: &self.meta,
+            items: &self.items,
+            tokens: &self.tokens,
+        }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Ast {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let OwnedAst { file, nodes, meta, items, tokens } = OwnedAst::deserialize(deserializer)?;
+        Ok(Self {
+            file,
+            nodes: nodes.0,
+            meta,
+            items,
+            tokens,
+        })
+    }
+}
@@ -0,0 +1,161 @@
+use std::ops::Range;
+
+use crate::{
+    common::{NameTable, SpanId},
+    lexer::{MetaElem, PuncutationTable, Token},
+};
+
+use super::{Ast, NodeId};
+
+/// Token in a `SyntaxView`, together with its exact source text and the trivia in front of it
+pub struct SyntaxToken<'a> {
+    pub token:          Token,
+    pub span:           SpanId,
+    pub text:           &'a str,
+    pub leading_trivia: &'a [MetaElem],
+}
+
+/// Lossless view over the syntax of an `Ast`.
+///
+/// Each node can be looked at as the tokens it was parsed from, including all whitespace and comments (trivia) in and around it.
+pub struct SyntaxView<'a> {
+    ast:    &'a Ast,
+    names:  &'a NameTable,
+    puncts: &'a PuncutationTable,
+}
+
+impl<'a> SyntaxView<'a> {
+    pub fn new(ast: &'a Ast, names: &'a NameTable, puncts: &'a PuncutationTable) -> Self {
+        Self { ast, names, puncts }
+    }
+
+    /// Get the range of tokens a node was parsed from, `None` if the node does not cover any tokens
+    pub fn token_range(&self, node: NodeId) -> Option<Range<usize>> {
+        let meta = self.ast.meta.get(node.index())?;
+        let range = meta.first_tok as usize..(meta.last_tok as usize).min(self.ast.tokens.tokens.len());
+        (meta.span != SpanId::INVALID && !range.is_empty()).then_some(range)
+    }
+
+    pub fn token(&self, idx: usize) -> Option<SyntaxToken<'a>> {
+        let tokens = &self.ast.tokens;
+        let meta = tokens.metadata.get(idx)?;
+        Some(SyntaxToken {
+            token: tokens.tokens[idx],
+            span: meta.span_id,
+            text: tokens.token_text(idx, self.names, self.puncts),
+            leading_trivia: &meta.meta_elems,
+        })
+    }
+
+    /// Iterate over all tokens of a node, the leading trivia of the first token is part of `leading_trivia` of the node
+    pub fn tokens(&self, node: NodeId) -> impl Iterator<Item = SyntaxToken<'a>> + '_ {
+        self.token_range(node)
+            .unwrap_or_default()
+            .filter_map(|idx| self.token(idx))
+    }
+
+    /// Trivia in front of the first token of a node
+    pub fn leading_trivia(&self, node: NodeId) -> &'a [MetaElem] {
+        match self.token_range(node) {
+            Some(range) => &self.ast.tokens.metadata[range.start].meta_elems,
+            None        => &[],
+        }
+    }
+
+    /// Trivia after the last token of a node, up to the first line break, e.g. a comment at the end of the same line
+    pub fn trailing_trivia(&self, node: NodeId) -> &'a [MetaElem] {
+        let Some(range) = self.token_range(node) else {
+            return &[];
+        };
+
+        let tokens = &self.ast.tokens;
+        let elems = match tokens.metadata.get(range.end) {
+            Some(meta) => &meta.meta_elems,
+            None       => &tokens.tail_meta_elems,
+        };
+        let end = elems.iter()
+            .position(|elem| matches!(elem, MetaElem::Whitespace(ws) if ws.contains(['\n', '\u{0085}'])))
+            .unwrap_or(elems.len());
+        &elems[..end]
+    }
+
+    /// Get the exact source text of a node, including any trivia between its tokens, but without leading and trailing trivia.
+    /// `None` is returned if the node does not cover any tokens.
+    pub fn text(&self, node: NodeId) -> Option<String> {
+        let range = self.token_range(node)?;
+        let mut out = String::new();
+        for idx in range.clone() {
+            let token = self.token(idx)?;
+            if idx != range.start {
+                for elem in token.leading_trivia {
+                    elem.write_source(&mut out);
+                }
+            }
+            out.push_str(token.text);
+        }
+        Some(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ast::{Ast, Item, Parser}, common::{NameTable, SpanRegistry}, lexer::{Lexer, MetaElem, PuncutationTable}, literals::LiteralTable};
+
+    use super::SyntaxView;
+
+    fn parse(source: &str, names: &NameTable, puncts: &PuncutationTable) -> Ast {
+        let literals = LiteralTable::new();
+        let spans = SpanRegistry::new();
+        let mut lexer = Lexer::new("", source, &literals, names, puncts, &spans);
+        lexer.lex();
+        assert!(lexer.errors.is_empty());
+        let Lexer { tokens, .. } = lexer;
+
+        let mut parser = Parser::new(&tokens, names, &spans);
+        parser.parse();
+        let Parser { mut ast, errors, .. } = parser;
+        assert!(errors.is_empty());
+        ast.tokens = tokens;
+        ast
+    }
+
+    #[test]
+    fn test_node_text_and_trivia() {
+        let source = "// leading\nfn a(x: i32) -> i32 { x /* inner */ + 1 } // trailing\nfn b() {}\n";
+        let names = NameTable::new();
+        let puncts = PuncutationTable::new();
+        let ast = parse(source, &names, &puncts);
+        let view = SyntaxView::new(&ast, &names, &puncts);
+
+        let Item::Function(func) = &ast.items[0] else { panic!("expected a function") };
+        assert_eq!(view.text(func.node_id).as_deref(), Some("fn a(x: i32) -> i32 { x /* inner */ + 1 }"));
+        assert_eq!(view.leading_trivia(func.node_id), &[
+            MetaElem::LineComment(" leading".to_string()),
+            MetaElem::Whitespace("\n".to_string()),
+        ]);
+        assert_eq!(view.trailing_trivia(func.node_id), &[
+            MetaElem::Whitespace(" ".to_string()),
+            MetaElem::LineComment(" trailing".to_string()),
+        ]);
+
+        let tokens = view.tokens(func.node_id).map(|tok| tok.text).collect::<Vec<_>>();
+        assert_eq!(tokens, ["fn", "a", "(", "x", ":", "i32", ")", "->", "i32", "{", "x", "+", "1", "}"]);
+
+        // Trailing trivia at the end of the file comes from the tail of the token store
+        let Item::Function(func) = &ast.items[1] else { panic!("expected a function") };
+        assert_eq!(view.text(func.node_id).as_deref(), Some("fn b() {}"));
+        assert!(view.trailing_trivia(func.node_id).is_empty());
+    }
+
+    #[test]
+    fn test_node_text_keeps_source_spelling() {
+        let source = "const A: u32 = 0x1F;";
+        let names = NameTable::new();
+        let puncts = PuncutationTable::new();
+        let ast = parse(source, &names, &puncts);
+        let view = SyntaxView::new(&ast, &names, &puncts);
+
+        let Item::Const(item) = &ast.items[0] else { panic!("expected a constant") };
+        assert_eq!(view.text(item.node_id).as_deref(), Some(source));
+    }
+}
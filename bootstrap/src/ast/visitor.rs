@@ -56,6 +56,9 @@ pub trait Visitor {
         helpers::visit_use_path(self, node);
     }
 
+    fn visit_error(&mut self, node: &AstNodeRef<ErrorNode>) where Self: Sized {
+    }
+
     fn visit_function(&mut self, node: &AstNodeRef<Function>) where Self: Sized {
         helpers::visit_function(self, node, true, true);
     }
@@ -123,7 +126,7 @@ pub trait Visitor {
 //--------------------------------------------------------------
 
     fn visit_trait(&mut self, node: &AstNodeRef<Trait>) where Self: Sized {
-        helpers::visit_trait(self, node, true);
+        helpers::visit_trait(self, node, true, true);
     }
     
     fn visit_trait_function(&mut self, node: &AstNodeRef<TraitFunction>) where Self: Sized {
@@ -477,6 +480,14 @@ pub trait Visitor {
         helpers::visit_enum_record_type(self, node);
     }
 
+    fn visit_trait_object_type(&mut self, node: &AstNodeRef<TraitObjectType>) where Self: Sized {
+        helpers::visit_trait_object_type(self, node);
+    }
+
+    fn visit_impl_trait_type(&mut self, node: &AstNodeRef<ImplTraitType>) where Self: Sized {
+        helpers::visit_impl_trait_type(self, node);
+    }
+
 // =============================================================================================================================
 
     fn visit_visibility(&mut self, node: &AstNodeRef<Visibility>) where Self: Sized {
@@ -608,6 +619,7 @@ pub mod helpers {
             Item::OpUse(node)         => visitor.visit_op_use(node),
             Item::Precedence(node)    => visitor.visit_precedence(node),
             Item::PrecedenceUse(node) => visitor.visit_precedence_use(node),
+            Item::Error(node)         => visitor.visit_error(node),
         }
     }
 
@@ -1133,7 +1145,7 @@ pub mod helpers {
 //--------------------------------------------------------------
 // <T: Visitor>(visitor: &mut T, node: &AstNodeRef<>)
 
-    pub fn visit_trait<T: Visitor>(visitor: &mut T, node: &AstNodeRef<Trait>, do_generics: bool) {
+    pub fn visit_trait<T: Visitor>(visitor: &mut T, node: &AstNodeRef<Trait>, do_generics: bool, do_assoc: bool) {
         for attr in &node.attrs {
             visitor.visit_attribute(attr);
         }
@@ -1153,8 +1165,10 @@ pub mod helpers {
                 visitor.visit_where_clause(where_clause);
             }
         }
-        for item in &node.assoc_items {
-            visitor.visit_trait_item(item);
+        if do_assoc {
+            for item in &node.assoc_items {
+                visitor.visit_trait_item(item);
+            }
         }
     }
 
@@ -1403,6 +1417,7 @@ pub mod helpers {
             Stmt::Defer(node)    => visitor.visit_defer(node),
             Stmt::ErrDefer(node) => visitor.visit_err_defer(node),
             Stmt::Expr(node)     => visitor.visit_expr_stmt(node),
+            Stmt::Error(node)    => visitor.visit_error(node),
         }
     }
 
@@ -1841,6 +1856,8 @@ pub mod helpers {
             Type::Fn(ty)          => visitor.visit_fn_type(ty),
             Type::Record(ty)      => visitor.visit_record_type(ty),
             Type::EnumRecord(ty)  => visitor.visit_enum_record_type(ty),
+            Type::TraitObject(ty) => visitor.visit_trait_object_type(ty),
+            Type::ImplTrait(ty)   => visitor.visit_impl_trait_type(ty),
         }
     }
 
@@ -1909,6 +1926,14 @@ pub mod helpers {
         }
     }
 
+    pub fn visit_trait_object_type<T: Visitor>(visitor: &mut T, node: &AstNodeRef<TraitObjectType>) {
+        visitor.visit_trait_bounds(&node.bounds);
+    }
+
+    pub fn visit_impl_trait_type<T: Visitor>(visitor: &mut T, node: &AstNodeRef<ImplTraitType>) {
+        visitor.visit_trait_bounds(&node.bounds);
+    }
+
 // =============================================================================================================================
 
     pub fn visit_visibility<T: Visitor>(visitor: &mut T, node: &AstNodeRef<Visibility>) {
@@ -0,0 +1,308 @@
+use std::{
+    collections::HashMap,
+    fs,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+};
+
+use bootstrap::{
+    common::{Scope, SpanId, Symbol, SymbolRef},
+    frontend::{FrontEnd, FrontEndFile},
+    hir::{self, Visitor as _},
+    lexer::Token,
+};
+
+/// Item in the HIR with the symbol it defines
+pub struct Definition {
+    pub sym:  SymbolRef,
+    pub span: SpanId,
+}
+
+/// Result of running the compiler front-end over a single library
+pub struct Analysis {
+    pub front_end:   FrontEnd,
+    pub definitions: Vec<Definition>,
+}
+
+impl Analysis {
+    /// Find the root file of the library a file is part of, which is the closest `lib.xn` or `main.xn` in the file's directory or above it.
+    /// If there is none, the file is its own root.
+    pub fn find_root(file: &Path) -> PathBuf {
+        for dir in file.ancestors().skip(1) {
+            for root_name in ["lib.xn", "main.xn"] {
+                let root = dir.join(root_name);
+                if root.is_file() {
+                    return root;
+                }
+            }
+
+            // Don't leave the directory tree that contains the sources
+            let has_sources = fs::read_dir(dir).is_ok_and(|entries| entries.flatten().any(|entry| entry.path().extension().is_some_and(|ext| ext == "xn")));
+            if !has_sources {
+                break;
+            }
+        }
+        file.to_path_buf()
+    }
+
+    /// Run all front-end passes on the library starting at `root`, with its dependencies and core library, the sources in `overlay` are used instead of the files on disk
+    pub fn run(root: &Path, overlay: &HashMap<PathBuf, String>) -> Self {
+        let mut front_end = FrontEnd::for_root(root, overlay);
+
+        let mut collector = DefinitionCollector { definitions: Vec::new() };
+        collector.visit(&mut front_end.hir, hir::VisitFlags::all());
+
+        Self {
+            front_end,
+            definitions: collector.definitions,
+        }
+    }
+
+    pub fn file(&self, path: &Path) -> Option<&FrontEndFile> {
+        self.front_end.file(path)
+    }
+
+    pub fn span_location(&self, span: SpanId) -> Option<(PathBuf, usize, usize)> {
+        self.front_end.span_location(span)
+    }
+
+    /// Get the index of the token at a byte offset in a file
+    pub fn token_at(&self, file: &FrontEndFile, offset: usize) -> Option<usize> {
+        let spans = self.front_end.ctx.spans.read();
+        let tokens = &file.ast.tokens;
+        (0..tokens.tokens.len()).find(|&idx| {
+            spans.get(tokens.metadata[idx].span_id)
+                .is_some_and(|span| span.byte_offset as usize <= offset && offset <= (span.byte_offset + span.byte_len) as usize)
+        })
+    }
+
+    /// Find the symbol the name at a byte offset in a file refers to
+    pub fn symbol_at(&self, file: &FrontEndFile, offset: usize) -> Option<SymbolRef> {
+        let tokens = &file.ast.tokens;
+        let idx = self.token_at(file, offset)?;
+        let Token::Name(_) = tokens.tokens[idx] else {
+            return None;
+        };
+
+        // Collect the path up to the name, e.g. `a.b.c` when hovering `c`
+        let names = self.front_end.ctx.names.read();
+        let mut path = Vec::new();
+        let mut cur = idx;
+        loop {
+            let Token::Name(name) = tokens.tokens[cur] else { break };
+            path.push(names[name].to_string());
+            if cur < 2 || tokens.tokens[cur - 1] != Token::Punctuation(bootstrap::lexer::Punctuation::Dot) {
+                break;
+            }
+            cur -= 2;
+        }
+        path.reverse();
+        drop(names);
+
+        let innermost = self.definitions.iter()
+            .filter_map(|def| {
+                let (def_path, start, end) = self.span_location(def.span)?;
+                (def_path == file.path && start <= offset && offset <= end).then_some((def, end - start))
+            })
+            .min_by_key(|(_, len)| *len)
+            .map(|(def, _)| def);
+
+        // Names of definitions themselves refer to the definition
+        if let Some(def) = innermost {
+            if path.len() == 1 && def.sym.read().path().iden().name == path[0] {
+                return Some(def.sym.clone());
+            }
+        }
+
+        if !self.front_end.has_errors {
+            let cur_scope = match innermost {
+                Some(def) => def.sym.read().path().scope().clone(),
+                None => file.scope.clone(),
+            };
+            let mut sym_path = Scope::new();
+            for name in &path {
+                sym_path.push(name.clone());
+            }
+
+            // Lookups can still panic on symbols that were only partially processed
+            let syms = self.front_end.ctx.syms.read();
+            let uses = self.front_end.ctx.uses.read();
+            let found = panic::catch_unwind(AssertUnwindSafe(|| syms.get_symbol_with_uses(&uses, &cur_scope, None, &sym_path)));
+            if let Ok(Ok(sym)) = found {
+                return Some(sym);
+            }
+        }
+
+        // Fall back to any definition with the same name, so this keeps working when the library has errors
+        let name = path.last()?;
+        self.definitions.iter()
+            .find(|def| def.sym.read().path().iden().name == *name)
+            .map(|def| def.sym.clone())
+    }
+
+    /// Get the span of the name of a definition, `None` if the name can't be found in the item, e.g. for an impl
+    pub fn definition_name_span(&self, def: &Definition) -> Option<SpanId> {
+        let (path, start, end) = self.span_location(def.span)?;
+        let file = self.file(&path)?;
+        let name = def.sym.read().path().iden().name.clone();
+
+        let spans = self.front_end.ctx.spans.read();
+        let names = self.front_end.ctx.names.read();
+        let tokens = &file.ast.tokens;
+        (0..tokens.tokens.len())
+            .filter(|&idx| matches!(tokens.tokens[idx], Token::Name(name_id) if names[name_id] == name))
+            .map(|idx| tokens.metadata[idx].span_id)
+            .find(|span_id| spans.get(*span_id).is_some_and(|span| start <= span.byte_offset as usize && (span.byte_offset as usize) < end))
+    }
+
+    pub fn hover_text(sym: &Symbol) -> String {
+        format!("{} `{}`", sym.kind_str(), sym.path())
+    }
+}
+
+/// Collect the symbols of all items in the HIR, together with the span of their item
+struct DefinitionCollector {
+    definitions: Vec<Definition>,
+}
+
+impl DefinitionCollector {
+    fn add(&mut self, span: SpanId, sym: &Option<SymbolRef>) {
+        if let Some(sym) = sym {
+            self.definitions.push(Definition { sym: sym.clone(), span });
+        }
+    }
+}
+
+impl hir::Visitor for DefinitionCollector {
+    fn visit_function(&mut self, node: &mut hir::Function, ctx: &mut hir::FunctionContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_extern_function_no_body(&mut self, node: &mut hir::ExternFunctionNoBody, ctx: &mut hir::FunctionContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_type_alias(&mut self, node: &mut hir::TypeAlias, ctx: &mut hir::TypeAliasContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_distinct_type(&mut self, node: &mut hir::DistinctType, ctx: &mut hir::TypeAliasContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_opaque_type(&mut self, node: &mut hir::OpaqueType, ctx: &mut hir::TypeAliasContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_struct(&mut self, node: &mut hir::Struct, ctx: &mut hir::StructContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_tuple_struct(&mut self, node: &mut hir::TupleStruct, ctx: &mut hir::StructContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_unit_struct(&mut self, node: &mut hir::UnitStruct, ctx: &mut hir::StructContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_union(&mut self, node: &mut hir::Union, ctx: &mut hir::UnionContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_adt_enum(&mut self, node: &mut hir::AdtEnum, ctx: &mut hir::AdtEnumContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_flag_enum(&mut self, node: &mut hir::FlagEnum, ctx: &mut hir::FlagEnumContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_bitfield(&mut self, node: &mut hir::Bitfield, ctx: &mut hir::BitfieldContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_const(&mut self, node: &mut hir::Const, ctx: &mut hir::ConstContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_static(&mut self, node: &mut hir::Static, ctx: &mut hir::StaticContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_tls_static(&mut self, node: &mut hir::TlsStatic, ctx: &mut hir::StaticContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_extern_static(&mut self, node: &mut hir::ExternStatic, ctx: &mut hir::StaticContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_trait(&mut self, node: &mut hir::Trait, ctx: &mut hir::TraitContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_trait_function(&mut self, _trait_ref: hir::Ref<hir::Trait>, _trait_ctx: hir::Ref<hir::TraitContext>, node: &mut hir::TraitFunction, ctx: &mut hir::FunctionContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_trait_method(&mut self, _trait_ref: hir::Ref<hir::Trait>, _trait_ctx: hir::Ref<hir::TraitContext>, node: &mut hir::TraitMethod, ctx: &mut hir::FunctionContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_trait_type_alias(&mut self, _trait_ref: hir::Ref<hir::Trait>, _trait_ctx: hir::Ref<hir::TraitContext>, node: &mut hir::TraitTypeAlias, ctx: &mut hir::TypeAliasContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_trait_const(&mut self, _trait_ref: hir::Ref<hir::Trait>, _trait_ctx: hir::Ref<hir::TraitContext>, node: &mut hir::TraitConst, ctx: &mut hir::ConstContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_trait_property(&mut self, _trait_ref: hir::Ref<hir::Trait>, _trait_ctx: hir::Ref<hir::TraitContext>, node: &mut hir::TraitProperty, ctx: &mut hir::PropertyContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_impl(&mut self, node: &mut hir::Impl, ctx: &mut hir::ImplContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_impl_function(&mut self, _impl_ref: hir::Ref<hir::Impl>, _impl_ctx: hir::Ref<hir::ImplContext>, node: &mut hir::Function, ctx: &mut hir::FunctionContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_method(&mut self, _impl_ref: hir::Ref<hir::Impl>, _impl_ctx: hir::Ref<hir::ImplContext>, node: &mut hir::Method, ctx: &mut hir::FunctionContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_impl_type_alias(&mut self, _impl_ref: hir::Ref<hir::Impl>, _impl_ctx: hir::Ref<hir::ImplContext>, node: &mut hir::TypeAlias, ctx: &mut hir::TypeAliasContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_impl_const(&mut self, _impl_ref: hir::Ref<hir::Impl>, _impl_ctx: hir::Ref<hir::ImplContext>, node: &mut hir::Const, ctx: &mut hir::ConstContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_impl_static(&mut self, _impl_ref: hir::Ref<hir::Impl>, _impl_ctx: hir::Ref<hir::ImplContext>, node: &mut hir::Static, ctx: &mut hir::StaticContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_impl_tls_static(&mut self, _impl_ref: hir::Ref<hir::Impl>, _impl_ctx: hir::Ref<hir::ImplContext>, node: &mut hir::TlsStatic, ctx: &mut hir::StaticContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_property(&mut self, _impl_ref: hir::Ref<hir::Impl>, _impl_ctx: hir::Ref<hir::ImplContext>, node: &mut hir::Property, ctx: &mut hir::PropertyContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_op_set(&mut self, node: &mut hir::OpSet, ctx: &mut hir::OpSetContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_operator(&mut self, _op_set_ref: hir::Ref<hir::OpSet>, _op_set_ctx: hir::Ref<hir::OpSetContext>, node: &mut hir::Operator, ctx: &mut hir::OperatorContext) {
+        self.add(node.span, &ctx.sym);
+    }
+
+    fn visit_precedence(&mut self, node: &mut hir::Precedence, ctx: hir::Ref<hir::PrecedenceContext>) {
+        self.add(node.span, &ctx.read().sym);
+    }
+}
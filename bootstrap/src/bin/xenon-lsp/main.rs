@@ -0,0 +1,574 @@
+// Language server for Xenon, speaking LSP over stdio.
+//
+// Every change to a document re-runs the compiler front-end over the library the document is part of,
+// the results are kept around to answer hover, go-to-definition, and document symbol requests.
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+    notification::{DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument, Notification as _, PublishDiagnostics},
+    request::{DocumentSymbolRequest, GotoDefinition, HoverRequest, Request as _},
+    DiagnosticRelatedInformation, DiagnosticSeverity, DocumentSymbol, DocumentSymbolResponse, GotoDefinitionResponse, Hover, HoverContents,
+    HoverProviderCapability, InitializeParams, Location, MarkupContent, MarkupKind, NumberOrString, OneOf, Position, PublishDiagnosticsParams, Range,
+    ServerCapabilities, SymbolKind, TextDocumentSyncCapability, TextDocumentSyncKind, Uri,
+};
+
+use bootstrap::common::{Diagnostic, Severity, SpanId, Symbol};
+
+mod analysis;
+use analysis::*;
+
+fn main() -> Result<(), Box<dyn Error + Sync + Send>> {
+    // stdout is used for the protocol, so all logging needs to go to stderr
+    eprintln!("Starting Xenon language server");
+
+    let (connection, io_threads) = Connection::stdio();
+    serve(connection)?;
+    // The writer thread only stops once the connection is dropped, which happens when `serve` returns
+    io_threads.join()?;
+
+    eprintln!("Shutting down Xenon language server");
+    Ok(())
+}
+
+/// Initialize the connection and handle messages until the client shuts the server down
+fn serve(connection: Connection) -> Result<(), Box<dyn Error + Sync + Send>> {
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    };
+    let init_params = connection.initialize(serde_json::to_value(capabilities)?)?;
+    let _init_params: InitializeParams = serde_json::from_value(init_params)?;
+
+    let mut server = Server::new(connection);
+    server.run()
+}
+
+struct Server {
+    connection: Connection,
+    /// Text of all documents that are open in the client
+    documents:  HashMap<PathBuf, String>,
+    /// Latest analysis of each library, by root file
+    analyses:   HashMap<PathBuf, Analysis>,
+    /// Files that diagnostics were published for, by root file, so they can be cleared when they are fixed
+    published:  HashMap<PathBuf, Vec<PathBuf>>,
+}
+
+impl Server {
+    fn new(connection: Connection) -> Self {
+        Self {
+            connection,
+            documents: HashMap::new(),
+            analyses: HashMap::new(),
+            published: HashMap::new(),
+        }
+    }
+
+    fn run(&mut self) -> Result<(), Box<dyn Error + Sync + Send>> {
+        while let Ok(msg) = self.connection.receiver.recv() {
+            match msg {
+                Message::Request(req) => {
+                    if self.connection.handle_shutdown(&req)? {
+                        return Ok(());
+                    }
+                    self.handle_request(req)?;
+                },
+                Message::Notification(not) => self.handle_notification(not)?,
+                Message::Response(_) => {},
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_request(&mut self, req: Request) -> Result<(), Box<dyn Error + Sync + Send>> {
+        let Request { id, method, params } = req;
+        let resp = match panic::catch_unwind(AssertUnwindSafe(|| self.request_result(&method, params))) {
+            Ok(Ok(Some(result))) => Response::new_ok(id, result),
+            Ok(Ok(None))         => Response::new_err(id, ErrorCode::MethodNotFound as i32, format!("Unsupported request: {method}")),
+            Ok(Err(err))         => Response::new_err(id, ErrorCode::InvalidParams as i32, err.to_string()),
+            Err(_)               => Response::new_err(id, ErrorCode::InternalError as i32, format!("Handling '{method}' panicked")),
+        };
+        self.connection.sender.send(Message::Response(resp))?;
+        Ok(())
+    }
+
+    /// Get the result of a request, `None` if the request is not supported
+    fn request_result(&mut self, method: &str, params: serde_json::Value) -> serde_json::Result<Option<serde_json::Value>> {
+        let result = match method {
+            HoverRequest::METHOD => {
+                let params: lsp_types::HoverParams = serde_json::from_value(params)?;
+                let doc = params.text_document_position_params;
+                serde_json::to_value(self.hover(&doc.text_document.uri, doc.position))?
+            },
+            GotoDefinition::METHOD => {
+                let params: lsp_types::GotoDefinitionParams = serde_json::from_value(params)?;
+                let doc = params.text_document_position_params;
+                serde_json::to_value(self.goto_definition(&doc.text_document.uri, doc.position))?
+            },
+            DocumentSymbolRequest::METHOD => {
+                let params: lsp_types::DocumentSymbolParams = serde_json::from_value(params)?;
+                serde_json::to_value(self.document_symbols(&params.text_document.uri))?
+            },
+            _ => return Ok(None),
+        };
+        Ok(Some(result))
+    }
+
+    fn handle_notification(&mut self, not: Notification) -> Result<(), Box<dyn Error + Sync + Send>> {
+        match not.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: lsp_types::DidOpenTextDocumentParams = serde_json::from_value(not.params)?;
+                if let Some(path) = uri_to_path(&params.text_document.uri) {
+                    self.documents.insert(path.clone(), params.text_document.text);
+                    self.analyze(&path)?;
+                }
+            },
+            DidChangeTextDocument::METHOD => {
+                let params: lsp_types::DidChangeTextDocumentParams = serde_json::from_value(not.params)?;
+                // Only full document sync is supported, so the last change contains the whole document
+                if let (Some(path), Some(change)) = (uri_to_path(&params.text_document.uri), params.content_changes.into_iter().last()) {
+                    self.documents.insert(path.clone(), change.text);
+                    self.analyze(&path)?;
+                }
+            },
+            DidSaveTextDocument::METHOD => {
+                let params: lsp_types::DidSaveTextDocumentParams = serde_json::from_value(not.params)?;
+                if let Some(path) = uri_to_path(&params.text_document.uri) {
+                    self.analyze(&path)?;
+                }
+            },
+            DidCloseTextDocument::METHOD => {
+                let params: lsp_types::DidCloseTextDocumentParams = serde_json::from_value(not.params)?;
+                if let Some(path) = uri_to_path(&params.text_document.uri) {
+                    self.documents.remove(&path);
+                }
+            },
+            _ => {},
+        }
+        Ok(())
+    }
+
+    /// Re-analyze the library a file is part of, and publish its diagnostics
+    fn analyze(&mut self, path: &Path) -> Result<(), Box<dyn Error + Sync + Send>> {
+        let root = Analysis::find_root(path);
+
+        // The front-end can still panic on unsupported syntax, which should not take down the server
+        let documents = &self.documents;
+        let analysis = match panic::catch_unwind(AssertUnwindSafe(|| Analysis::run(&root, documents))) {
+            Ok(analysis) => analysis,
+            Err(_) => {
+                eprintln!("Analysis of '{}' panicked, keeping the previous results", root.display());
+                let Some(uri) = path_to_uri(path) else { return Ok(()) };
+                let diagnostic = lsp_types::Diagnostic {
+                    severity: Some(DiagnosticSeverity::ERROR),
+                    source: Some("xenon".to_string()),
+                    message: "Internal compiler error while analyzing this library, results may be outdated".to_string(),
+                    ..Default::default()
+                };
+                let params = PublishDiagnosticsParams { uri, diagnostics: vec![diagnostic], version: None };
+                self.connection.sender.send(Message::Notification(Notification::new(PublishDiagnostics::METHOD.to_string(), params)))?;
+                self.published.entry(root).or_default().push(path.to_path_buf());
+                return Ok(());
+            },
+        };
+
+        let mut by_file: HashMap<PathBuf, Vec<lsp_types::Diagnostic>> = HashMap::new();
+        for file in &analysis.front_end.files {
+            by_file.insert(file.path.clone(), Vec::new());
+        }
+        for old_file in self.published.remove(&root).unwrap_or_default() {
+            by_file.entry(old_file).or_default();
+        }
+        for diag in &analysis.front_end.diagnostics {
+            let file = analysis.span_location(diag.primary_span()).map_or_else(|| root.clone(), |(path, _, _)| path);
+            by_file.entry(file).or_default().push(to_lsp_diagnostic(&analysis, diag));
+        }
+
+        let mut published = Vec::new();
+        for (file, diagnostics) in by_file {
+            let Some(uri) = path_to_uri(&file) else { continue };
+            if !diagnostics.is_empty() {
+                published.push(file);
+            }
+            let params = PublishDiagnosticsParams { uri, diagnostics, version: None };
+            let not = Notification::new(PublishDiagnostics::METHOD.to_string(), params);
+            self.connection.sender.send(Message::Notification(not))?;
+        }
+        self.published.insert(root.clone(), published);
+        self.analyses.insert(root, analysis);
+        Ok(())
+    }
+
+    /// Get the analysis that contains a file, analyzing its library if that has not happened yet
+    fn analysis_for(&mut self, path: &Path) -> Option<&Analysis> {
+        let root = Analysis::find_root(path);
+        if !self.analyses.get(&root).is_some_and(|analysis| analysis.file(path).is_some()) {
+            self.analyze(path).ok()?;
+        }
+        self.analyses.get(&root)
+    }
+
+    fn hover(&mut self, uri: &Uri, pos: Position) -> Option<Hover> {
+        let path = uri_to_path(uri)?;
+        let analysis = self.analysis_for(&path)?;
+        let file = analysis.file(&path)?;
+        let offset = position_to_offset(&file.source, pos);
+
+        let sym = analysis.symbol_at(file, offset)?;
+        let value = Analysis::hover_text(&sym.read());
+
+        let idx = analysis.token_at(file, offset)?;
+        let range = span_to_range(analysis, file.ast.tokens.metadata[idx].span_id).map(|(_, range)| range);
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent { kind: MarkupKind::Markdown, value }),
+            range,
+        })
+    }
+
+    fn goto_definition(&mut self, uri: &Uri, pos: Position) -> Option<GotoDefinitionResponse> {
+        let path = uri_to_path(uri)?;
+        let analysis = self.analysis_for(&path)?;
+        let file = analysis.file(&path)?;
+        let offset = position_to_offset(&file.source, pos);
+
+        let sym = analysis.symbol_at(file, offset)?;
+        // Modules are defined by their file
+        if let Symbol::Module(module) = &*sym.read() {
+            let uri = path_to_uri(&module.file_path)?;
+            return Some(GotoDefinitionResponse::Scalar(Location { uri, range: Range::default() }));
+        }
+
+        let def = analysis.definitions.iter().find(|def| Arc::ptr_eq(&def.sym, &sym))?;
+        let span = analysis.definition_name_span(def).unwrap_or(def.span);
+        let (def_path, range) = span_to_range(analysis, span)?;
+        Some(GotoDefinitionResponse::Scalar(Location { uri: path_to_uri(&def_path)?, range }))
+    }
+
+    fn document_symbols(&mut self, uri: &Uri) -> Option<DocumentSymbolResponse> {
+        let path = uri_to_path(uri)?;
+        let analysis = self.analysis_for(&path)?;
+
+        let mut symbols = Vec::new();
+        for def in &analysis.definitions {
+            let Some((def_path, range)) = span_to_range(analysis, def.span) else { continue };
+            if def_path != path {
+                continue;
+            }
+            let selection_range = analysis.definition_name_span(def)
+                .and_then(|span| span_to_range(analysis, span))
+                .map_or(range, |(_, range)| range);
+
+            let sym = def.sym.read();
+            #[allow(deprecated)]
+            symbols.push(DocumentSymbol {
+                name: sym.path().iden().to_string(),
+                detail: Some(sym.kind_str().to_string()),
+                kind: symbol_kind(&sym),
+                tags: None,
+                deprecated: None,
+                range,
+                selection_range,
+                children: None,
+            });
+        }
+
+        Some(DocumentSymbolResponse::Nested(nest_symbols(symbols)))
+    }
+}
+
+/// Nest document symbols by their ranges, e.g. so methods are children of their impl
+fn nest_symbols(mut symbols: Vec<DocumentSymbol>) -> Vec<DocumentSymbol> {
+    // Sort by start, with outer symbols in front of inner symbols starting at the same position
+    symbols.sort_by(|a, b| a.range.start.cmp(&b.range.start).then(b.range.end.cmp(&a.range.end)));
+
+    let contains = |outer: &DocumentSymbol, inner: &DocumentSymbol| outer.range.start <= inner.range.start && inner.range.end <= outer.range.end;
+
+    let mut roots = Vec::new();
+    let mut stack: Vec<DocumentSymbol> = Vec::new();
+    for sym in symbols {
+        while stack.last().is_some_and(|top| !contains(top, &sym)) {
+            let done = stack.pop().unwrap();
+            match stack.last_mut() {
+                Some(parent) => parent.children.get_or_insert_with(Vec::new).push(done),
+                None         => roots.push(done),
+            }
+        }
+        stack.push(sym);
+    }
+    while let Some(done) = stack.pop() {
+        match stack.last_mut() {
+            Some(parent) => parent.children.get_or_insert_with(Vec::new).push(done),
+            None         => roots.push(done),
+        }
+    }
+    roots
+}
+
+fn symbol_kind(sym: &Symbol) -> SymbolKind {
+    match sym {
+        Symbol::Module(_)       => SymbolKind::MODULE,
+        Symbol::Precedence(_)   => SymbolKind::CONSTANT,
+        Symbol::Function(_)     => SymbolKind::FUNCTION,
+        Symbol::TypeAlias(_)    |
+        Symbol::DistinctType(_) |
+        Symbol::OpaqueType(_)   => SymbolKind::TYPE_PARAMETER,
+        Symbol::Struct(_)       |
+        Symbol::Union(_)        |
+        Symbol::Bitfield(_)     => SymbolKind::STRUCT,
+        Symbol::AdtEnum(_)      |
+        Symbol::FlagEnum(_)     => SymbolKind::ENUM,
+        Symbol::Const(_)        => SymbolKind::CONSTANT,
+        Symbol::Static(_)       => SymbolKind::VARIABLE,
+        Symbol::Property(_)     => SymbolKind::PROPERTY,
+        Symbol::Trait(_)        => SymbolKind::INTERFACE,
+        Symbol::Impl(_)         => SymbolKind::NAMESPACE,
+        Symbol::TypeGeneric(_)  |
+        Symbol::ValueGeneric(_) => SymbolKind::TYPE_PARAMETER,
+        Symbol::OpSet(_)        => SymbolKind::INTERFACE,
+        Symbol::Operator(_)     => SymbolKind::OPERATOR,
+    }
+}
+
+fn to_lsp_diagnostic(analysis: &Analysis, diag: &Diagnostic) -> lsp_types::Diagnostic {
+    let range = span_to_range(analysis, diag.primary_span()).map_or(Range::default(), |(_, range)| range);
+
+    let related_information = diag.labels.iter()
+        .filter(|label| !label.is_primary)
+        .filter_map(|label| {
+            let (path, range) = span_to_range(analysis, label.span)?;
+            Some(DiagnosticRelatedInformation {
+                location: Location { uri: path_to_uri(&path)?, range },
+                message: label.message.clone(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut message = diag.message.clone();
+    if let Some(label) = diag.labels.iter().find(|label| label.is_primary && !label.message.is_empty()) {
+        message.push_str(&format!("\n{}", label.message));
+    }
+    for note in &diag.notes {
+        message.push_str(&format!("\nnote: {note}"));
+    }
+    for help in &diag.help {
+        message.push_str(&format!("\nhelp: {help}"));
+    }
+
+    lsp_types::Diagnostic {
+        range,
+        severity: Some(match diag.severity {
+            Severity::Error   => DiagnosticSeverity::ERROR,
+            Severity::Warning => DiagnosticSeverity::WARNING,
+        }),
+        code: diag.code.map(|code| NumberOrString::String(format!("E{code:04}"))),
+        source: Some("xenon".to_string()),
+        message,
+        related_information: (!related_information.is_empty()).then_some(related_information),
+        ..Default::default()
+    }
+}
+
+// =============================================================================================================================
+
+fn span_to_range(analysis: &Analysis, span: SpanId) -> Option<(PathBuf, Range)> {
+    let (path, start, end) = analysis.span_location(span)?;
+    let file = analysis.file(&path)?;
+    Some((path, Range {
+        start: offset_to_position(&file.source, start),
+        end: offset_to_position(&file.source, end),
+    }))
+}
+
+/// Convert a byte offset to a position, which uses utf-16 code units for the column
+fn offset_to_position(source: &str, offset: usize) -> Position {
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    Position {
+        line: before.matches('\n').count() as u32,
+        character: before[line_start..].encode_utf16().count() as u32,
+    }
+}
+
+fn position_to_offset(source: &str, pos: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..pos.line {
+        match source[line_start..].find('\n') {
+            Some(idx) => line_start += idx + 1,
+            None => return source.len(),
+        }
+    }
+
+    let mut units = 0;
+    for (idx, ch) in source[line_start..].char_indices() {
+        if units >= pos.character as usize || ch == '\n' {
+            return line_start + idx;
+        }
+        units += ch.len_utf16();
+    }
+    source.len()
+}
+
+fn uri_to_path(uri: &Uri) -> Option<PathBuf> {
+    let path = uri.as_str().strip_prefix("file://")?;
+
+    // Decode percent-encoded bytes
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        if bytes[idx] == b'%' && idx + 2 < bytes.len() {
+            if let Ok(byte) = u8::from_str_radix(&path[idx + 1..idx + 3], 16) {
+                decoded.push(byte);
+                idx += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[idx]);
+        idx += 1;
+    }
+    Some(PathBuf::from(String::from_utf8(decoded).ok()?))
+}
+
+fn path_to_uri(path: &Path) -> Option<Uri> {
+    let mut uri = String::from("file://");
+    for byte in path.to_str()?.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => uri.push(byte as char),
+            _ => uri.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    Uri::from_str(&uri).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, thread, time::Duration};
+
+    use lsp_server::{Connection, Message, Notification, Request, RequestId, Response};
+    use lsp_types::{
+        notification::{DidOpenTextDocument, Exit, Initialized, Notification as _, PublishDiagnostics},
+        request::{GotoDefinition, HoverRequest, Initialize, Request as _, Shutdown},
+        GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams, InitializeParams, InitializeResult, Position,
+        PublishDiagnosticsParams, TextDocumentIdentifier, TextDocumentItem, TextDocumentPositionParams, Uri,
+    };
+
+    use super::{path_to_uri, serve};
+
+    const TIMEOUT: Duration = Duration::from_secs(60);
+
+    /// Client side of an in-memory connection to the server
+    struct Client {
+        connection: Connection,
+        next_id:    i32,
+    }
+
+    impl Client {
+        fn request<T: serde::Serialize>(&mut self, method: &str, params: T) -> Response {
+            self.next_id += 1;
+            let id = RequestId::from(self.next_id);
+            self.connection.sender.send(Message::Request(Request::new(id.clone(), method.to_string(), params))).unwrap();
+
+            loop {
+                match self.connection.receiver.recv_timeout(TIMEOUT).expect("no response from the server") {
+                    Message::Response(resp) if resp.id == id => return resp,
+                    _ => {},
+                }
+            }
+        }
+
+        fn notify<T: serde::Serialize>(&self, method: &str, params: T) {
+            self.connection.sender.send(Message::Notification(Notification::new(method.to_string(), params))).unwrap();
+        }
+
+        fn wait_for_diagnostics(&self, uri: &Uri) -> PublishDiagnosticsParams {
+            loop {
+                if let Message::Notification(not) = self.connection.receiver.recv_timeout(TIMEOUT).expect("no diagnostics from the server") {
+                    if not.method == PublishDiagnostics::METHOD {
+                        let params: PublishDiagnosticsParams = serde_json::from_value(not.params).unwrap();
+                        if params.uri == *uri {
+                            return params;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn position_params(uri: &Uri, line: u32, character: u32) -> TextDocumentPositionParams {
+        TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier { uri: uri.clone() },
+            position: Position { line, character },
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let source = "struct Point {\n    x: i32,\n}\n\nfn origin(p: Point) {}\n";
+
+        let dir = std::env::temp_dir().join(format!("xenon-lsp-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("lib.xn");
+        fs::write(&path, source).unwrap();
+        let uri = path_to_uri(&path).unwrap();
+
+        let (server, connection) = Connection::memory();
+        let server = thread::spawn(move || serve(server).map_err(|err| err.to_string()));
+        let mut client = Client { connection, next_id: 0 };
+
+        let resp = client.request(Initialize::METHOD, InitializeParams::default());
+        let init: InitializeResult = serde_json::from_value(resp.result.expect("initialize failed")).unwrap();
+        assert!(init.capabilities.hover_provider.is_some());
+        assert!(init.capabilities.definition_provider.is_some());
+        client.notify(Initialized::METHOD, serde_json::json!({}));
+
+        client.notify(DidOpenTextDocument::METHOD, lsp_types::DidOpenTextDocumentParams {
+            text_document: TextDocumentItem { uri: uri.clone(), language_id: "xenon".to_string(), version: 1, text: source.to_string() },
+        });
+        let diags = client.wait_for_diagnostics(&uri);
+        assert!(diags.diagnostics.is_empty(), "unexpected diagnostics: {:?}", diags.diagnostics);
+
+        // Hover the use of `Point` in the parameter
+        let resp = client.request(HoverRequest::METHOD, HoverParams {
+            text_document_position_params: position_params(&uri, 4, 15),
+            work_done_progress_params: Default::default(),
+        });
+        let hover: Hover = serde_json::from_value(resp.result.expect("hover failed")).unwrap();
+        let HoverContents::Markup(contents) = hover.contents else { panic!("expected markup") };
+        assert!(contents.value.starts_with("struct `") && contents.value.ends_with("Point`"), "unexpected hover: {}", contents.value);
+        let range = hover.range.expect("hover without a range");
+        assert_eq!((range.start, range.end), (Position::new(4, 13), Position::new(4, 18)));
+
+        // Go to the definition of `Point`, which is the name in the struct
+        let resp = client.request(GotoDefinition::METHOD, GotoDefinitionParams {
+            text_document_position_params: position_params(&uri, 4, 15),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        });
+        let def: GotoDefinitionResponse = serde_json::from_value(resp.result.expect("definition failed")).unwrap();
+        let GotoDefinitionResponse::Scalar(location) = def else { panic!("expected a single location") };
+        assert_eq!(location.uri, uri);
+        assert_eq!((location.range.start, location.range.end), (Position::new(0, 7), Position::new(0, 12)));
+
+        let resp = client.request(Shutdown::METHOD, ());
+        assert!(resp.error.is_none());
+        client.notify(Exit::METHOD, ());
+        server.join().unwrap().unwrap();
+
+        _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,17 +1,45 @@
-use clap::Parser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum ErrorFormat {
+    /// Human readable diagnostics with source snippets
+    Human,
+    /// One json object per diagnostic, per line, on stdout. Any other output is written to stderr
+    Json,
+}
 
 #[derive(Parser, Debug)]
-#[command(name = "Xenon bootstrap compiler")]
+#[command(name = "Xenon bootstrap compiler", args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct Cli {
-    pub input_files:                 Vec<String>,
+    #[command(subcommand)]
+    pub command:                     Option<Command>,
+
+    /// Package manifest or the directory containing it, all artifacts in the package are built.
+    /// A single `.xn` file can also be passed, which is then built as the root of a library, or of a binary if it is named `main.xn`
+    pub input:                       Option<String>,
+
+    #[arg(long, value_enum, default_value_t = ErrorFormat::Human)]
+    pub error_format:                ErrorFormat,
 
+    /// Number of threads used to lex and parse files and to run HIR passes, defaults to the number of available cores
+    #[arg(short, long)]
+    pub jobs:                        Option<usize>,
+
+    /// Skip NFC normalization of names and punctuation, for sources that are known to be normalized
     #[arg(long)]
-    pub group:                       Option<String>,
+    pub skip_normalization:          bool,
+
+    /// Don't load or store the lexed, parsed and processed files in the incremental cache, which is stored in `target/incremental` next to the package manifest
     #[arg(long)]
-    pub package:                     String,
+    pub no_incremental:              bool,
 
+    /// Core library that is implicitly imported into every library, either its package or its prebuilt metadata.
+    /// By default, the core library named by the `core` key of the package manifest is used, when there is one
+    #[arg(long)]
+    pub core:                        Option<String>,
+    /// Don't implicitly import the core library into any library
     #[arg(long)]
-    pub library:                     Option<String>,
+    pub no_core:                     bool,
 
     #[arg(long)]
     pub print_lex_output:            bool,
@@ -55,9 +83,93 @@ pub struct Cli {
     #[arg(long)]
     pub print_var_info:              bool,
 
+    /// Stop after the given AST or HIR pass, passes are selected by their name as printed by `--pass-timings`, ignoring case, whitespace and punctuation.
+    /// Libraries of dependencies are always fully processed
+    #[arg(long, value_name = "PASS")]
+    pub stop_after:                  Option<String>,
+    /// Print the HIR pseudo-code after the given pass, can be passed multiple times
+    #[arg(long, value_name = "PASS")]
+    pub dump_hir_after:              Vec<String>,
+    /// Don't run the given pass, nor any pass that depends on it, can be passed multiple times
+    #[arg(long, value_name = "PASS")]
+    pub skip:                        Vec<String>,
+    /// Check the invariants of the HIR after every pass, to catch a pass that breaks them at the pass itself
+    #[arg(long)]
+    pub verify_hir:                  bool,
+
     #[arg(long)]
     pub timings:                     bool,
 
     #[arg(long)]
     pub pass_timings:                bool,
+    /// Write a Chrome trace of the time spent per file in each phase and in each pass, together with the compiler stats, to the given file
+    #[arg(long, value_name = "PATH")]
+    pub timings_json:                Option<String>,
+}
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Format source files
+    Fmt(FmtArgs),
+    /// Generate documentation for a library
+    Doc(DocArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct FmtArgs {
+    #[arg(required = true)]
+    pub files:        Vec<String>,
+
+    /// Don't write the formatted files, but exit with an error if any file is not formatted
+    #[arg(long)]
+    pub check:        bool,
+
+    #[arg(long, default_value_t = 4)]
+    pub indent_width: usize,
+    #[arg(long)]
+    pub hard_tabs:    bool,
+    #[arg(long, default_value_t = 120)]
+    pub max_width:    usize,
+    /// Don't realign hand aligned members of `op trait` and `precedence` blocks, and arms of `match` expressions
+    #[arg(long)]
+    pub no_align:     bool,
+
+    #[arg(long, value_enum, default_value_t = ErrorFormat::Human)]
+    pub error_format: ErrorFormat,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum DocFormat {
+    Html,
+    Markdown,
+}
+
+#[derive(Args, Debug)]
+pub struct DocArgs {
+    /// Package manifest or the directory containing it, all libraries in the package are documented.
+    /// The root file of a single library, i.e. `lib.xn`, can also be passed
+    #[arg(default_value = ".")]
+    pub input:            String,
+
+    /// Directory the documentation is written to, in a sub-directory named after the library
+    #[arg(long, default_value = "doc")]
+    pub out_dir:          String,
+    #[arg(long, value_enum, default_value_t = DocFormat::Html)]
+    pub format:           DocFormat,
+
+    /// Also document items that are not `pub`
+    #[arg(long)]
+    pub document_private: bool,
+    /// Also document items marked with `@doc(hidden)`
+    #[arg(long)]
+    pub document_hidden:  bool,
+
+    /// Core library that is implicitly imported into the documented libraries, overriding the one named by the package manifest
+    #[arg(long)]
+    pub core:             Option<String>,
+    /// Don't implicitly import the core library
+    #[arg(long)]
+    pub no_core:          bool,
+
+    #[arg(long, value_enum, default_value_t = ErrorFormat::Human)]
+    pub error_format:     ErrorFormat,
 }
\ No newline at end of file
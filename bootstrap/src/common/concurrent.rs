@@ -0,0 +1,193 @@
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    hash::{BuildHasher, Hash, RandomState},
+    sync::{atomic::{AtomicUsize, Ordering}, OnceLock},
+};
+use parking_lot::Mutex;
+
+const FIRST_SEGMENT_BITS: u32 = 5;
+const NUM_SEGMENTS: usize = (usize::BITS - FIRST_SEGMENT_BITS) as usize;
+
+/// Vector of write-once slots, which can be pushed to and set from multiple threads at the same time without locking.
+///
+/// Elements are stored in segments of increasing size which are never reallocated, so references to elements stay valid while new elements are added.
+pub struct OnceVec<T> {
+    len:      AtomicUsize,
+    segments: [OnceLock<Box<[OnceLock<T>]>>; NUM_SEGMENTS],
+}
+
+impl<T> OnceVec<T> {
+    pub fn new() -> Self {
+        Self {
+            len: AtomicUsize::new(0),
+            segments: [const { OnceLock::new() }; NUM_SEGMENTS],
+        }
+    }
+
+    /// Add an element, returning its index
+    pub fn push(&self, value: T) -> usize {
+        let idx = self.len.fetch_add(1, Ordering::Relaxed);
+        // The index is unique, so the slot can't be set yet
+        _ = self.slot(idx).set(value);
+        idx
+    }
+
+    /// Set the element at `idx` if it isn't set yet, otherwise `value` is returned
+    pub fn set(&self, idx: usize, value: T) -> Result<(), T> {
+        let res = self.slot(idx).set(value);
+        self.len.fetch_max(idx + 1, Ordering::Relaxed);
+        res
+    }
+
+    /// Get the element at `idx`, `None` is returned when it hasn't been set (yet)
+    pub fn get(&self, idx: usize) -> Option<&T> {
+        let (segment, offset) = Self::location(idx)?;
+        self.segments[segment].get()?[offset].get()
+    }
+
+    /// Number of slots, including those that are reserved by a `push` that is still in progress
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        (0..self.len()).filter_map(|idx| self.get(idx))
+    }
+
+    fn slot(&self, idx: usize) -> &OnceLock<T> {
+        let (segment, offset) = Self::location(idx).expect("OnceVec index out of range");
+        let segment = self.segments[segment].get_or_init(|| {
+            let len = 1 << (segment as u32 + FIRST_SEGMENT_BITS);
+            (0..len).map(|_| OnceLock::new()).collect()
+        });
+        &segment[offset]
+    }
+
+    /// Segment `n` contains `2^(n + FIRST_SEGMENT_BITS)` elements
+    fn location(idx: usize) -> Option<(usize, usize)> {
+        let biased = idx.checked_add(1 << FIRST_SEGMENT_BITS)?;
+        let bits = usize::BITS - 1 - biased.leading_zeros();
+        let segment = (bits - FIRST_SEGMENT_BITS) as usize;
+        Some((segment, biased - (1 << bits)))
+    }
+}
+
+impl<T> Default for OnceVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// =============================================================
+
+const NUM_SHARDS: usize = 32;
+
+/// Deduplicating storage which can be added to from multiple threads.
+///
+/// Lookups are split over shards by hash, so threads only contend when they add values that end up in the same shard.
+/// The index of a value depends on the order in which values are added, so it is not deterministic when multiple threads add values.
+pub struct Interner<T> {
+    values: OnceVec<T>,
+    shards: [Mutex<HashMap<T, u32>>; NUM_SHARDS],
+    hasher: RandomState,
+}
+
+impl<T: Clone + Hash + Eq> Interner<T> {
+    pub fn new() -> Self {
+        Self {
+            values: OnceVec::new(),
+            shards: std::array::from_fn(|_| Mutex::new(HashMap::new())),
+            hasher: RandomState::new(),
+        }
+    }
+
+    /// Get the index of `value`, adding it when it isn't interned yet
+    pub fn add<Q>(&self, value: &Q) -> u32 where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = T> + ?Sized,
+    {
+        let mut shard = self.shard(value).lock();
+        if let Some(idx) = shard.get(value) {
+            return *idx;
+        }
+
+        let value = value.to_owned();
+        let idx = self.values.push(value.clone()) as u32;
+        shard.insert(value, idx);
+        idx
+    }
+
+    /// Get the index of `value`, if it is interned
+    pub fn find<Q>(&self, value: &Q) -> Option<u32> where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.shard(value).lock().get(value).copied()
+    }
+
+    pub fn get(&self, idx: u32) -> Option<&T> {
+        self.values.get(idx as usize)
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    fn shard<Q: Hash + ?Sized>(&self, value: &Q) -> &Mutex<HashMap<T, u32>> {
+        &self.shards[self.hasher.hash_one(value) as usize % NUM_SHARDS]
+    }
+}
+
+impl<T: Clone + Hash + Eq> Default for Interner<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_once_vec_segments() {
+        let vec = OnceVec::new();
+        for idx in 0..1000 {
+            assert_eq!(vec.push(idx), idx);
+        }
+        assert!((0..1000).all(|idx| vec.get(idx) == Some(&idx)));
+        assert_eq!(vec.get(1000), None);
+        assert_eq!(vec.get(usize::MAX), None);
+
+        assert_eq!(vec.set(5000, 5000), Ok(()));
+        assert_eq!(vec.set(5000, 0), Err(0));
+        assert_eq!(vec.len(), 5001);
+        assert_eq!(vec.iter().count(), 1001);
+    }
+
+    #[test]
+    fn test_interner_threads() {
+        let interner = Interner::<String>::new();
+        let ids: Vec<Vec<u32>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..4).map(|_| scope.spawn(|| {
+                (0..500).map(|idx| interner.add(format!("name{idx}").as_str())).collect()
+            })).collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+
+        assert_eq!(interner.len(), 500);
+        assert!(ids.iter().all(|thread_ids| *thread_ids == ids[0]));
+        for (idx, id) in ids[0].iter().enumerate() {
+            assert_eq!(interner.get(*id).map(String::as_str), Some(format!("name{idx}").as_str()));
+            assert_eq!(interner.find(format!("name{idx}").as_str()), Some(*id));
+        }
+    }
+}
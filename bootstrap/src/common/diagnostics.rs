@@ -0,0 +1,594 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error_warning::{ErrorCode, ErrorMessage};
+
+use super::{Span, SpanId, SpanRegistry};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error   => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DiagnosticLabel {
+    pub span:       SpanId,
+    pub message:    String,
+    pub is_primary: bool,
+}
+
+/// A single diagnostic, shared by all compiler phases
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code:     Option<u16>,
+    pub message:  String,
+    pub labels:   Vec<DiagnosticLabel>,
+    pub notes:    Vec<String>,
+    pub help:     Vec<String>,
+}
+
+#[allow(unused)]
+impl Diagnostic {
+    pub fn new(severity: Severity, message: String) -> Self {
+        Self {
+            severity,
+            code: None,
+            message,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: Vec::new(),
+        }
+    }
+
+    pub fn from_code<T: ErrorCode>(severity: Severity, code: &T) -> Self {
+        Self {
+            code: Some(code.code()),
+            ..Self::new(severity, ErrorMessage(code).to_string())
+        }
+    }
+
+    pub fn error<T: ErrorCode>(code: &T) -> Self {
+        Self::from_code(Severity::Error, code)
+    }
+
+    pub fn warning<T: ErrorCode>(code: &T) -> Self {
+        Self::from_code(Severity::Warning, code)
+    }
+
+    /// Add the primary label, labels with an invalid span are ignored
+    pub fn with_primary(self, span: SpanId, message: impl Into<String>) -> Self {
+        self.with_label(span, message.into(), true)
+    }
+
+    /// Add a secondary label, labels with an invalid span are ignored
+    pub fn with_secondary(self, span: SpanId, message: impl Into<String>) -> Self {
+        self.with_label(span, message.into(), false)
+    }
+
+    fn with_label(mut self, span: SpanId, message: String, is_primary: bool) -> Self {
+        if span != SpanId::INVALID {
+            self.labels.push(DiagnosticLabel { span, message, is_primary });
+        }
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help.push(help.into());
+        self
+    }
+
+    pub fn primary_span(&self) -> SpanId {
+        self.labels.iter()
+            .find(|label| label.is_primary)
+            .map_or(SpanId::INVALID, |label| label.span)
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+// =============================================================
+
+/// Label location resolved against the source text of its file.
+/// Lines are 0-based, columns are 0-based display columns (tabs are expanded).
+struct ResolvedLabel<'a> {
+    label:      &'a DiagnosticLabel,
+    start_line: usize,
+    start_col:  usize,
+    end_line:   usize,
+    end_col:    usize,
+}
+
+struct SourceLines<'a> {
+    source:      &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> SourceLines<'a> {
+    const TAB_WIDTH: usize = 4;
+
+    fn new(source: &'a str) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(source.match_indices('\n').map(|(idx, _)| idx + 1));
+        Self { source, line_starts }
+    }
+
+    fn line_of(&self, byte: usize) -> usize {
+        self.line_starts.partition_point(|&start| start <= byte) - 1
+    }
+
+    fn line_text(&self, line: usize) -> &'a str {
+        let start = self.line_starts[line];
+        let end = self.line_starts.get(line + 1).copied().unwrap_or(self.source.len());
+        self.source[start..end].trim_end_matches(['\n', '\r'])
+    }
+
+    fn floor_char_boundary(&self, mut byte: usize) -> usize {
+        byte = byte.min(self.source.len());
+        while !self.source.is_char_boundary(byte) {
+            byte -= 1;
+        }
+        byte
+    }
+
+    fn display_width(s: &str) -> usize {
+        s.chars().map(|ch| if ch == '\t' { Self::TAB_WIDTH } else { 1 }).sum()
+    }
+
+    fn display_col(&self, line: usize, byte: usize) -> usize {
+        let start = self.line_starts[line];
+        let text = &self.source[start..byte];
+        Self::display_width(text.trim_end_matches(['\n', '\r']))
+    }
+
+    fn resolve<'b>(&self, label: &'b DiagnosticLabel, span: &Span) -> ResolvedLabel<'b> {
+        let start = self.floor_char_boundary(span.byte_offset as usize);
+        let end = self.floor_char_boundary((span.byte_offset + span.byte_len) as usize).max(start);
+
+        let start_line = self.line_of(start);
+        // A span ending in a newline should not spill over onto the next line
+        let end_line = if end > start {
+            self.line_of(self.floor_char_boundary(end - 1))
+        } else {
+            start_line
+        };
+
+        ResolvedLabel {
+            label,
+            start_line,
+            start_col: self.display_col(start_line, start),
+            end_line,
+            end_col: self.display_col(end_line, end),
+        }
+    }
+}
+
+pub struct FormatDiagnostic<'a> {
+    registry: &'a SpanRegistry,
+    diag:     &'a Diagnostic,
+}
+
+impl<'a> FormatDiagnostic<'a> {
+    /// Maximum number of lines a multi-line label shows before only its first and last line are shown
+    const MAX_LABEL_LINES: usize = 4;
+
+    pub fn new(registry: &'a SpanRegistry, diag: &'a Diagnostic) -> Self {
+        Self { registry, diag }
+    }
+
+    fn fmt_file(&self, f: &mut fmt::Formatter<'_>, file_id: u32, lines: Option<&SourceLines>, labels: &[&DiagnosticLabel], gutter: usize, is_first: bool) -> fmt::Result {
+        let file = self.registry.get_file(file_id);
+        let arrow = if is_first { "-->" } else { ":::" };
+
+        let first = &self.registry[labels[0].span];
+        writeln!(f, "{:gutter$}{arrow} {file}:{}:{}", "", first.row, first.column)?;
+
+        let Some(lines) = lines else {
+            for label in labels {
+                if !label.message.is_empty() {
+                    writeln!(f, "{:gutter$} = {}", "", label.message)?;
+                }
+            }
+            return Ok(());
+        };
+
+        let mut resolved: Vec<_> = labels.iter()
+            .map(|label| lines.resolve(label, &self.registry[label.span]))
+            .collect();
+        resolved.sort_by_key(|res| (res.start_line, res.start_col));
+
+        let mut shown_lines = Vec::new();
+        for res in &resolved {
+            if res.end_line - res.start_line < Self::MAX_LABEL_LINES {
+                shown_lines.extend(res.start_line..=res.end_line);
+            } else {
+                shown_lines.push(res.start_line);
+                shown_lines.push(res.end_line);
+            }
+        }
+        shown_lines.sort_unstable();
+        shown_lines.dedup();
+
+        writeln!(f, "{:gutter$} |", "")?;
+        let mut prev_line = None;
+        for line in shown_lines {
+            if prev_line.is_some_and(|prev| line > prev + 1) {
+                writeln!(f, "{:gutter$}...", "")?;
+            }
+            prev_line = Some(line);
+
+            let text = lines.line_text(line);
+            writeln!(f, "{:>gutter$} | {}", line + 1, text.replace('\t', "    "))?;
+
+            for res in resolved.iter().filter(|res| res.start_line <= line && line <= res.end_line) {
+                let begin = if line == res.start_line { res.start_col } else { 0 };
+                let end = if line == res.end_line { res.end_col } else { SourceLines::display_width(text) };
+                let len = end.saturating_sub(begin).max(1);
+                let marker = if res.label.is_primary { "^" } else { "-" };
+
+                write!(f, "{:gutter$} | {:begin$}{}", "", "", marker.repeat(len))?;
+                if line == res.end_line && !res.label.message.is_empty() {
+                    write!(f, " {}", res.label.message)?;
+                }
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for FormatDiagnostic<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let diag = self.diag;
+        match diag.code {
+            Some(code) => writeln!(f, "{}[E{code:04}]: {}", diag.severity, diag.message)?,
+            None       => writeln!(f, "{}: {}", diag.severity, diag.message)?,
+        }
+
+        // Primary labels first, so their file is shown first
+        let mut labels: Vec<_> = diag.labels.iter()
+            .filter(|label| self.registry.get(label.span).is_some())
+            .collect();
+        labels.sort_by_key(|label| !label.is_primary);
+
+        let mut files = Vec::new();
+        for label in &labels {
+            let file_id = self.registry[label.span].file_id;
+            if !files.iter().any(|(id, _)| *id == file_id) {
+                files.push((file_id, self.registry.get_file_source(file_id).map(SourceLines::new)));
+            }
+        }
+
+        let mut max_line = 0;
+        for label in &labels {
+            let span = &self.registry[label.span];
+            let (_, lines) = files.iter().find(|(id, _)| *id == span.file_id).unwrap();
+            let line = match lines {
+                Some(lines) => lines.resolve(label, span).end_line + 1,
+                None        => span.row_end.max(span.row) as usize,
+            };
+            max_line = max_line.max(line);
+        }
+        let gutter = max_line.to_string().len();
+
+        for (idx, (file_id, lines)) in files.iter().enumerate() {
+            let file_labels: Vec<_> = labels.iter()
+                .copied()
+                .filter(|label| self.registry[label.span].file_id == *file_id)
+                .collect();
+            self.fmt_file(f, *file_id, lines.as_ref(), &file_labels, gutter, idx == 0)?;
+        }
+
+        if !diag.notes.is_empty() || !diag.help.is_empty() {
+            if !files.is_empty() {
+                writeln!(f, "{:gutter$} |", "")?;
+            }
+            for note in &diag.notes {
+                writeln!(f, "{:gutter$} = note: {note}", "")?;
+            }
+            for help in &diag.help {
+                writeln!(f, "{:gutter$} = help: {help}", "")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// =============================================================
+
+/// Formats a diagnostic as a single line json object, used by `--error-format=json`
+pub struct FormatDiagnosticJson<'a> {
+    registry: &'a SpanRegistry,
+    diag:     &'a Diagnostic,
+}
+
+/// Location of a span, all fields are `null` when the span is unknown
+#[derive(Serialize)]
+struct JsonLocation<'a> {
+    file:         Option<&'a str>,
+    byte_start:   Option<u64>,
+    byte_end:     Option<u64>,
+    line_start:   Option<u32>,
+    column_start: Option<u32>,
+    line_end:     Option<u32>,
+    column_end:   Option<u32>,
+}
+
+#[derive(Serialize)]
+struct JsonLabel<'a> {
+    #[serde(flatten)]
+    location:   JsonLocation<'a>,
+    is_primary: bool,
+    label:      &'a str,
+}
+
+#[derive(Serialize)]
+struct JsonDiagnostic<'a> {
+    severity: String,
+    code:     Option<String>,
+    message:  &'a str,
+    #[serde(flatten)]
+    location: JsonLocation<'a>,
+    spans:    Vec<JsonLabel<'a>>,
+    notes:    &'a [String],
+    help:     &'a [String],
+}
+
+impl<'a> FormatDiagnosticJson<'a> {
+    pub fn new(registry: &'a SpanRegistry, diag: &'a Diagnostic) -> Self {
+        Self { registry, diag }
+    }
+
+    fn location(&self, span_id: SpanId) -> JsonLocation<'a> {
+        let Some(span) = self.registry.get(span_id) else {
+            return JsonLocation {
+                file: None,
+                byte_start: None,
+                byte_end: None,
+                line_start: None,
+                column_start: None,
+                line_end: None,
+                column_end: None,
+            };
+        };
+
+        JsonLocation {
+            file: Some(self.registry.get_file(span.file_id)),
+            byte_start: Some(span.byte_offset),
+            byte_end: Some(span.byte_offset + span.byte_len),
+            line_start: Some(span.row),
+            column_start: Some(span.column),
+            line_end: Some(span.row_end.max(span.row)),
+            column_end: Some(span.column_end),
+        }
+    }
+}
+
+impl fmt::Display for FormatDiagnosticJson<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let diag = self.diag;
+        let spans = diag.labels.iter()
+            .filter(|label| self.registry.get(label.span).is_some())
+            .map(|label| JsonLabel {
+                location: self.location(label.span),
+                is_primary: label.is_primary,
+                label: &label.message,
+            })
+            .collect();
+
+        let json = JsonDiagnostic {
+            severity: diag.severity.to_string(),
+            code: diag.code.map(|code| format!("E{code:04}")),
+            message: &diag.message,
+            location: self.location(diag.primary_span()),
+            spans,
+            notes: &diag.notes,
+            help: &diag.help,
+        };
+        let json = serde_json::to_string(&json).map_err(|_| fmt::Error)?;
+        f.write_str(&json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Add a span covering `text` in `source`, the first occurrence is used
+    fn add_span(registry: &SpanRegistry, file: &str, source: &str, text: &str) -> SpanId {
+        let start = source.find(text).unwrap();
+        let end = start + text.len();
+        let row = source[..start].matches('\n').count() as u32 + 1;
+        let column = (start - source[..start].rfind('\n').map_or(0, |idx| idx + 1)) as u32 + 1;
+        let row_end = source[..end].matches('\n').count() as u32 + 1;
+        let column_end = (end - source[..end].rfind('\n').map_or(0, |idx| idx + 1)) as u32 + 1;
+
+        registry.add_span(file, Span {
+            file_id: 0,
+            row,
+            column,
+            char_offset: start as u64,
+            byte_offset: start as u64,
+            char_len: text.len() as u64,
+            byte_len: text.len() as u64,
+            row_end,
+            column_end,
+        })
+    }
+
+    #[test]
+    fn test_format_single_label() {
+        let source = "fn foo() {\n    let a = b;\n}\n";
+        let registry = SpanRegistry::new();
+        registry.set_file_source("main.xn", source);
+        let span = add_span(&registry, "main.xn", source, "b");
+
+        let mut diag = Diagnostic::new(Severity::Error, "unknown name".to_string())
+            .with_primary(span, "not found in this scope")
+            .with_help("declare `b` before using it");
+        diag.code = Some(12);
+
+        assert_eq!(FormatDiagnostic::new(&registry, &diag).to_string(),
+"error[E0012]: unknown name
+ --> main.xn:2:13
+  |
+2 |     let a = b;
+  |             ^ not found in this scope
+  |
+  = help: declare `b` before using it
+");
+    }
+
+    #[test]
+    fn test_format_multiple_labels() {
+        let source = "let a = 1;\nlet b = 2;\nlet c = 3;\nlet a = 4;\n";
+        let registry = SpanRegistry::new();
+        registry.set_file_source("main.xn", source);
+        let first = add_span(&registry, "main.xn", source, "a = 1");
+        let second = add_span(&registry, "main.xn", source, "a = 4");
+
+        // The primary label is shown, even though it comes after the secondary label in the source
+        let diag = Diagnostic::new(Severity::Warning, "shadowed variable".to_string())
+            .with_secondary(first, "first declared here")
+            .with_primary(second, "shadowed here")
+            .with_note("shadowing is allowed");
+
+        assert_eq!(FormatDiagnostic::new(&registry, &diag).to_string(),
+"warning: shadowed variable
+ --> main.xn:4:5
+  |
+1 | let a = 1;
+  |     ----- first declared here
+ ...
+4 | let a = 4;
+  |     ^^^^^ shadowed here
+  |
+  = note: shadowing is allowed
+");
+    }
+
+    #[test]
+    fn test_format_multi_line_label() {
+        let source = "fn foo() {\n\ta;\n\tb;\n\tc;\n\td;\n}\n";
+        let registry = SpanRegistry::new();
+        registry.set_file_source("main.xn", source);
+        let span = add_span(&registry, "main.xn", source, &source[..source.len() - 1]);
+
+        // Long labels only show their first and last line, tabs are expanded
+        let diag = Diagnostic::new(Severity::Error, "bad function".to_string())
+            .with_primary(span, "");
+        assert_eq!(FormatDiagnostic::new(&registry, &diag).to_string(),
+"error: bad function
+ --> main.xn:1:1
+  |
+1 | fn foo() {
+  | ^^^^^^^^^^
+ ...
+6 | }
+  | ^
+");
+
+        let span = add_span(&registry, "main.xn", source, "\ta;\n\tb;");
+        let diag = Diagnostic::new(Severity::Error, "bad statements".to_string())
+            .with_primary(span, "here");
+        assert_eq!(FormatDiagnostic::new(&registry, &diag).to_string(),
+"error: bad statements
+ --> main.xn:2:1
+  |
+2 |     a;
+  | ^^^^^^
+3 |     b;
+  | ^^^^^^ here
+");
+    }
+
+    #[test]
+    fn test_format_multiple_files() {
+        let main_source = "use lib.foo;\n";
+        let lib_source = "fn foo() {}\n";
+        let registry = SpanRegistry::new();
+        registry.set_file_source("main.xn", main_source);
+        registry.set_file_source("lib.xn", lib_source);
+        let use_span = add_span(&registry, "main.xn", main_source, "foo");
+        let def_span = add_span(&registry, "lib.xn", lib_source, "foo");
+
+        let diag = Diagnostic::new(Severity::Error, "private function".to_string())
+            .with_primary(use_span, "used here")
+            .with_secondary(def_span, "declared here");
+        assert_eq!(FormatDiagnostic::new(&registry, &diag).to_string(),
+"error: private function
+ --> main.xn:1:9
+  |
+1 | use lib.foo;
+  |         ^^^ used here
+ ::: lib.xn:1:4
+  |
+1 | fn foo() {}
+  |    --- declared here
+");
+    }
+
+    #[test]
+    fn test_format_without_source() {
+        let registry = SpanRegistry::new();
+        let span = add_span(&registry, "missing.xn", "abc", "b");
+
+        // Without a source, only the location and messages of labels are shown
+        let diag = Diagnostic::new(Severity::Error, "no source".to_string())
+            .with_primary(span, "here")
+            .with_primary(SpanId::INVALID, "ignored");
+        assert_eq!(FormatDiagnostic::new(&registry, &diag).to_string(),
+"error: no source
+ --> missing.xn:1:2
+  = here
+");
+    }
+
+    #[test]
+    fn test_format_json() {
+        let source = "let a = \"b\";\n";
+        let registry = SpanRegistry::new();
+        registry.set_file_source("dir\\main.xn", source);
+        let span = add_span(&registry, "dir\\main.xn", source, "\"b\"");
+
+        let mut diag = Diagnostic::new(Severity::Error, "bad \"string\"\n\tliteral\u{1}".to_string())
+            .with_primary(span, "here");
+        diag.code = Some(7);
+        assert_eq!(FormatDiagnosticJson::new(&registry, &diag).to_string(), concat!(
+            r#"{"severity":"error","code":"E0007","message":"bad \"string\"\n\tliteral\u0001","#,
+            r#""file":"dir\\main.xn","byte_start":8,"byte_end":11,"line_start":1,"column_start":9,"line_end":1,"column_end":12,"#,
+            r#""spans":[{"file":"dir\\main.xn","byte_start":8,"byte_end":11,"line_start":1,"column_start":9,"line_end":1,"column_end":12,"is_primary":true,"label":"here"}],"#,
+            r#""notes":[],"help":[]}"#,
+        ));
+
+        let diag = Diagnostic::new(Severity::Warning, "no location".to_string())
+            .with_note("a note");
+        assert_eq!(FormatDiagnosticJson::new(&registry, &diag).to_string(), concat!(
+            r#"{"severity":"warning","code":null,"message":"no location","#,
+            r#""file":null,"byte_start":null,"byte_end":null,"line_start":null,"column_start":null,"line_end":null,"column_end":null,"#,
+            r#""spans":[],"notes":["a note"],"help":[]}"#,
+        ));
+
+        // Every line is valid json
+        let value: serde_json::Value = serde_json::from_str(&FormatDiagnosticJson::new(&registry, &diag).to_string()).unwrap();
+        assert_eq!(value["notes"][0], "a note");
+    }
+}
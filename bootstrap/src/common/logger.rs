@@ -1,10 +1,38 @@
 #![allow(unused)]
 
 use std::{
-    io::Write,
+    io::{self, Write},
     fmt,
+    sync::atomic::{AtomicBool, Ordering},
 };
 
+static OUTPUT_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Write the compiler's output, e.g. banners, timings and dumps, to stderr instead of stdout, so stdout only contains machine-readable diagnostics
+pub fn set_output_to_stderr(to_stderr: bool) {
+    OUTPUT_TO_STDERR.store(to_stderr, Ordering::Relaxed);
+}
+
+/// Write the compiler's output to stdout, or to stderr when stdout is reserved for machine-readable diagnostics
+pub fn write_output(args: fmt::Arguments) {
+    if OUTPUT_TO_STDERR.load(Ordering::Relaxed) {
+        _ = io::stderr().lock().write_fmt(args);
+    } else {
+        _ = io::stdout().lock().write_fmt(args);
+    }
+}
+
+/// `println!` for the compiler's output, see `write_output`
+#[macro_export]
+macro_rules! outputln {
+    () => {
+        $crate::common::write_output(format_args!("\n"))
+    };
+    ($($arg:tt)*) => {
+        $crate::common::write_output(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
+
 pub struct Logger {
     
 }
@@ -15,8 +43,7 @@ impl Logger {
     }
 
     pub fn log_fmt(&self, args: fmt::Arguments) {
-        let stdout = std::io::stdout();
-        _ = stdout.lock().write_fmt(args);
+        write_output(args);
     }
 
     pub fn log(&self, s: &str) {
@@ -0,0 +1,74 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+static CURRENT:    AtomicUsize = AtomicUsize::new(0);
+static PEAK:       AtomicUsize = AtomicUsize::new(0);
+static PHASE_PEAK: AtomicUsize = AtomicUsize::new(0);
+
+/// System allocator that keeps track of the number of allocated bytes.
+///
+/// Memory usage is only tracked when this is registered as the `#[global_allocator]`, otherwise all memory functions return 0.
+pub struct TrackingAllocator;
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            add_allocated(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            add_allocated(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            if new_size > layout.size() {
+                add_allocated(new_size - layout.size());
+            } else {
+                CURRENT.fetch_sub(layout.size() - new_size, Ordering::Relaxed);
+            }
+        }
+        new_ptr
+    }
+}
+
+fn add_allocated(size: usize) {
+    let current = CURRENT.fetch_add(size, Ordering::Relaxed) + size;
+    PEAK.fetch_max(current, Ordering::Relaxed);
+    PHASE_PEAK.fetch_max(current, Ordering::Relaxed);
+}
+
+/// Get the number of bytes that are currently allocated
+pub fn current_memory() -> usize {
+    CURRENT.load(Ordering::Relaxed)
+}
+
+/// Get the highest number of bytes that were allocated at any point
+pub fn peak_memory() -> usize {
+    PEAK.load(Ordering::Relaxed)
+}
+
+/// Start measuring the peak memory of a new phase
+pub fn reset_phase_peak_memory() {
+    PHASE_PEAK.store(current_memory(), Ordering::Relaxed);
+}
+
+/// Get the highest number of bytes that were allocated since the last call to `reset_phase_peak_memory`
+pub fn phase_peak_memory() -> usize {
+    PHASE_PEAK.load(Ordering::Relaxed)
+}
@@ -1,12 +1,22 @@
 
 use std::{fmt, hash::Hash};
 
+use serde::{Deserialize, Serialize};
+
+mod concurrent;
+pub use concurrent::*;
+
 mod logger;
 pub use logger::*;
 
 mod stats;
 pub use stats::*;
 
+pub mod memory;
+
+mod trace;
+pub use trace::*;
+
 mod dag;
 pub use dag::Dag;
 
@@ -31,14 +41,24 @@ pub use paths::*;
 mod span;
 pub use span::*;
 
+mod diagnostics;
+pub use diagnostics::*;
+
 mod traits;
 pub use traits::*;
 
 mod expr_utils;
 pub use expr_utils::*;
 
+mod pass_manager;
+pub use pass_manager::*;
+
+/// Identity of the build of the compiler, computed by `build.rs` from its sources.
+/// Data that is stored on disk can only be read by a compiler with the same build id.
+pub const BUILD_ID: &str = env!("XENON_BUILD_ID");
+
 
-#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+#[derive(Clone, PartialEq, Eq, Debug, Hash, Serialize, Deserialize)]
 pub struct LibraryPath {
     pub group:  Option<String>,
     pub package: String,
@@ -85,7 +105,7 @@ impl fmt::Display for Abi {
 
 // =============================================================
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum Visibility {
     Public,
     Package {
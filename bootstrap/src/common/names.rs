@@ -1,6 +1,8 @@
 use std::{fmt, ops::Index};
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+use super::Interner;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct NameId(u32);
 
 impl NameId {
@@ -13,42 +15,30 @@ impl fmt::Display for NameId {
     }
 }
 
+/// Interned names, names can be added from multiple threads through a shared reference
 pub struct NameTable {
-    names: Vec<String>,
+    names: Interner<String>,
 }
 
 impl NameTable {
     pub fn new() -> Self {
         Self {
-            names: Vec::new(),
+            names: Interner::new(),
         }
     }
 
-    pub fn add(&mut self, name: &str) -> NameId {
-        // Naive implementation
-        let it = self.names.iter().enumerate().find(|(_, val)| *val == name);
-        match it {
-            Some((idx, _)) => NameId(idx as u32),
-            None => {
-                let idx = self.names.len() as u32;
-                self.names.push(name.to_string());
-                NameId(idx)
-            },
-        }
+    pub fn add(&self, name: &str) -> NameId {
+        NameId(self.names.add(name))
     }
 
     // When no name with the given path exists, `NameId::INVALID` is returned, which will never match any parsed name
     pub fn get_id_for_str(&self, name: &str) -> NameId {
-        self.names.iter()
-            .enumerate()
-            .find(|(_, val)| *val == name)
-            .map_or(NameId::INVALID, |(id, _)| NameId(id as u32))
+        self.names.find(name).map_or(NameId::INVALID, NameId)
     }
 
     pub fn get_name(&self, index: NameId) -> &str {
-        &self.names[index.0 as usize]
+        self.names.get(index.0).expect("invalid name id")
     }
-    
 }
 
 impl Index<NameId> for NameTable {
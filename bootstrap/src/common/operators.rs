@@ -1,11 +1,13 @@
 use std::{collections::HashMap, fmt};
 
+use serde::{Deserialize, Serialize};
+
 use crate::lexer::{Punctuation, PuncutationTable};
 
 use super::{LibraryPath, Logger, RootSymbolTable, RootUseTable, Scope, Symbol, SymbolRef};
 
 // TODO: Assign is infix, but special, so make sure we also look at it when looking for infix ops
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum OpType {
     Prefix,
     Postfix,
@@ -107,7 +109,7 @@ impl OperatorTable {
                     let (op_set, ops) = syms.get_direct_op_set_and_ops(&use_path.lib, name).unwrap();
                     process(op_set, ops);
                 },
-                None => for (_, (op_set, ops)) in syms.get_op_set_and_ops_for_lib(&use_path.lib).unwrap() {
+                None => for (_, (op_set, ops)) in syms.get_op_set_and_ops_for_lib(&use_path.lib).into_iter().flatten() {
                     process(op_set, ops);
                 },
             }
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+
+use super::{Diagnostic, Severity};
+
+/// Name of a pass and the passes it depends on, which need to run before it
+#[derive(Clone, Copy, Debug)]
+pub struct PassInfo {
+    pub name:         &'static str,
+    pub dependencies: &'static [&'static str],
+}
+
+/// Why a pass doesn't run
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PassSkip {
+    /// The pass was explicitly skipped
+    Skipped,
+    /// The pass depends on a pass that doesn't run
+    Dependency(&'static str),
+    /// Processing stops before the pass
+    Stopped,
+}
+
+/// Decides which passes run, based on the passes that should be skipped or after which processing should stop.
+///
+/// Passes are selected by their name, ignoring case, whitespace and punctuation, so `precedence-connecting` selects the 'Precedence Connecting' pass.
+pub struct PassManager {
+    passes:     Vec<PassInfo>,
+    indices:    HashMap<&'static str, usize>,
+    skipped:    Vec<Option<PassSkip>>,
+    dump_after: Vec<bool>,
+}
+
+impl PassManager {
+    /// Create a pass manager for `passes`, which need to be in the order they run in
+    pub fn new(passes: Vec<PassInfo>, stop_after: Option<&str>, skip: &[String], dump_after: &[String]) -> Result<Self, Vec<Diagnostic>> {
+        let indices: HashMap<_, _> = passes.iter().enumerate().map(|(idx, pass)| (pass.name, idx)).collect();
+        for (idx, pass) in passes.iter().enumerate() {
+            for dep in pass.dependencies {
+                debug_assert!(indices.get(dep).is_some_and(|dep_idx| *dep_idx < idx), "pass '{}' depends on '{dep}', which doesn't run before it", pass.name);
+            }
+        }
+
+        let mut errors = Vec::new();
+        let mut find = |name: &str, option: &str| {
+            let idx = passes.iter().position(|pass| normalize_name(pass.name) == normalize_name(name));
+            if idx.is_none() {
+                let names: Vec<_> = passes.iter().map(|pass| option_name(pass.name)).collect();
+                errors.push(Diagnostic::new(Severity::Error, format!("Unknown pass '{name}' passed to `{option}`, available passes are: {}", names.join(", "))));
+            }
+            idx
+        };
+
+        let stop_idx = stop_after.and_then(|name| find(name, "--stop-after"));
+        let skip_idxs: Vec<_> = skip.iter().filter_map(|name| find(name, "--skip")).collect();
+        let dump_idxs: Vec<_> = dump_after.iter().filter_map(|name| find(name, "--dump-hir-after")).collect();
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let mut skipped = vec![None; passes.len()];
+        for (idx, pass) in passes.iter().enumerate() {
+            skipped[idx] = if stop_idx.is_some_and(|stop_idx| idx > stop_idx) {
+                Some(PassSkip::Stopped)
+            } else if skip_idxs.contains(&idx) {
+                Some(PassSkip::Skipped)
+            } else {
+                pass.dependencies.iter()
+                    .find(|dep| skipped[indices[*dep]].is_some())
+                    .map(|dep| PassSkip::Dependency(dep))
+            };
+        }
+
+        let mut dump_flags = vec![false; passes.len()];
+        for idx in dump_idxs {
+            dump_flags[idx] = true;
+        }
+
+        Ok(Self {
+            passes,
+            indices,
+            skipped,
+            dump_after: dump_flags,
+        })
+    }
+
+    /// Create a pass manager that runs all passes
+    pub fn all(passes: Vec<PassInfo>) -> Self {
+        match Self::new(passes, None, &[], &[]) {
+            Ok(manager) => manager,
+            Err(_)      => unreachable!("no passes are selected by name"),
+        }
+    }
+
+    pub fn should_run(&self, name: &str) -> bool {
+        self.skip_reason(name).is_none()
+    }
+
+    /// Get the reason why a pass doesn't run, or `None` if it does
+    pub fn skip_reason(&self, name: &str) -> Option<PassSkip> {
+        self.skipped[self.index(name)]
+    }
+
+    pub fn dump_after(&self, name: &str) -> bool {
+        self.dump_after[self.index(name)]
+    }
+
+    /// Check if `name` runs before `other`
+    pub fn runs_before(&self, name: &str, other: &str) -> bool {
+        self.index(name) < self.index(other)
+    }
+
+    /// Check if every pass runs
+    pub fn runs_all(&self) -> bool {
+        self.skipped.iter().all(Option::is_none)
+    }
+
+    /// Get the passes that don't run because they are skipped, directly or through a dependency
+    pub fn skipped_passes(&self) -> impl Iterator<Item = (&'static str, PassSkip)> + '_ {
+        self.passes.iter().zip(&self.skipped)
+            .filter_map(|(pass, skip)| match skip {
+                Some(PassSkip::Stopped) | None => None,
+                Some(skip)                     => Some((pass.name, *skip)),
+            })
+    }
+
+    /// Get the passes after which the HIR is dumped
+    pub fn dumped_passes(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.passes.iter().zip(&self.dump_after)
+            .filter_map(|(pass, dump)| dump.then_some(pass.name))
+    }
+
+    fn index(&self, name: &str) -> usize {
+        *self.indices.get(name).unwrap_or_else(|| panic!("pass '{name}' is not registered with the pass manager"))
+    }
+}
+
+/// Get the name of a pass as it would be passed on the command line, e.g. `op-set-trait-association` for 'Op Set <-> Trait Association'
+fn option_name(name: &str) -> String {
+    let mut option = String::new();
+    for ch in name.chars() {
+        if ch.is_alphanumeric() {
+            option.extend(ch.to_lowercase());
+        } else if !option.is_empty() && !option.ends_with('-') {
+            option.push('-');
+        }
+    }
+    option.trim_end_matches('-').to_string()
+}
+
+fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|ch| ch.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PassInfo, PassManager, PassSkip};
+
+    // A -> B -> C, D doesn't depend on anything and E depends on A and D
+    const PASSES: &[PassInfo] = &[
+        PassInfo { name: "Pass A",     dependencies: &[] },
+        PassInfo { name: "Pass B",     dependencies: &["Pass A"] },
+        PassInfo { name: "Pass C",     dependencies: &["Pass B"] },
+        PassInfo { name: "Pass D",     dependencies: &[] },
+        PassInfo { name: "Pass A + D", dependencies: &["Pass A", "Pass D"] },
+    ];
+
+    fn manager(stop_after: Option<&str>, skip: &[&str]) -> Result<PassManager, Vec<super::Diagnostic>> {
+        let skip: Vec<String> = skip.iter().map(|name| name.to_string()).collect();
+        PassManager::new(PASSES.to_vec(), stop_after, &skip, &[])
+    }
+
+    #[test]
+    fn test_unknown_pass() {
+        let errors = manager(Some("pass-x"), &["pass-a", "pass-y"]).err().expect("unknown passes are rejected");
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].message.contains("'pass-x'") && errors[0].message.contains("`--stop-after`"), "{}", errors[0].message);
+        assert!(errors[1].message.contains("'pass-y'") && errors[1].message.contains("`--skip`"), "{}", errors[1].message);
+        // The available passes are listed with their option names
+        assert!(errors[0].message.contains("pass-a, pass-b, pass-c, pass-d, pass-a-d"), "{}", errors[0].message);
+    }
+
+    #[test]
+    fn test_skipped_dependency_cascades() {
+        let passes = manager(None, &["pass-a"]).unwrap();
+        assert_eq!(passes.skip_reason("Pass A"), Some(PassSkip::Skipped));
+        assert_eq!(passes.skip_reason("Pass B"), Some(PassSkip::Dependency("Pass A")));
+        // Dependencies of dependencies are skipped as well
+        assert_eq!(passes.skip_reason("Pass C"), Some(PassSkip::Dependency("Pass B")));
+        assert_eq!(passes.skip_reason("Pass D"), None);
+        assert_eq!(passes.skip_reason("Pass A + D"), Some(PassSkip::Dependency("Pass A")));
+        assert!(!passes.runs_all());
+
+        let skipped: Vec<_> = passes.skipped_passes().map(|(name, _)| name).collect();
+        assert_eq!(skipped, ["Pass A", "Pass B", "Pass C", "Pass A + D"]);
+    }
+
+    #[test]
+    fn test_stop_after_with_skip() {
+        let passes = manager(Some("PASS C"), &["pass d"]).unwrap();
+        assert!(passes.should_run("Pass A"));
+        assert!(passes.should_run("Pass B"));
+        assert!(passes.should_run("Pass C"));
+        // Passes after the stop are stopped, even when they are skipped or their dependencies are
+        assert_eq!(passes.skip_reason("Pass D"), Some(PassSkip::Stopped));
+        assert_eq!(passes.skip_reason("Pass A + D"), Some(PassSkip::Stopped));
+        // Stopped passes aren't reported as skipped
+        assert_eq!(passes.skipped_passes().count(), 0);
+
+        // Skipping a pass before the stop still cascades to the passes up to the stop
+        let passes = manager(Some("pass-c"), &["pass-b"]).unwrap();
+        assert!(passes.should_run("Pass A"));
+        assert_eq!(passes.skip_reason("Pass B"), Some(PassSkip::Skipped));
+        assert_eq!(passes.skip_reason("Pass C"), Some(PassSkip::Dependency("Pass B")));
+        assert_eq!(passes.skip_reason("Pass D"), Some(PassSkip::Stopped));
+    }
+}
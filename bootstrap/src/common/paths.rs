@@ -2,6 +2,8 @@
 
 use core::fmt;
 
+use serde::{ser, Deserialize, Deserializer, Serialize, Serializer};
+
 use crate::type_system::TypeHandle;
 
 use super::LibraryPath;
@@ -193,6 +195,27 @@ impl fmt::Display for Scope {
     }
 }
 
+// Scopes are serialized as the name and parameters of each segment, generic arguments refer to types in the type registry, so can't be serialized
+impl Serialize for Scope {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.idens.iter().any(|iden| !iden.gen_args.is_empty()) {
+            return Err(ser::Error::custom(format!("scope `{self}` contains generic arguments, which can't be serialized")));
+        }
+        serializer.collect_seq(self.idens.iter().map(|iden| (&iden.name, &iden.params)))
+    }
+}
+
+impl<'de> Deserialize<'de> for Scope {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let segments = Vec::<(String, Vec<String>)>::deserialize(deserializer)?;
+        let mut scope = Scope::new();
+        for (name, params) in segments {
+            scope.push_with_params(name, params);
+        }
+        Ok(scope)
+    }
+}
+
 
 //==============================================================================================================================
 
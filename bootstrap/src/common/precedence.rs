@@ -1,8 +1,10 @@
 use std::fmt;
 
+use serde::{Deserialize, Serialize};
+
 use super::{dag::Dag, Logger, RootSymbolTable, RootUseTable, Symbol};
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum PrecedenceAssocKind {
     None,
     Left,
@@ -72,7 +74,8 @@ impl PrecedenceDAG {
                     }
                 },
                 None => {
-                    let precedences = syms.get_precedences_for_lib(&path.lib).unwrap();
+                    // The implicit use of the current library doesn't require it to have any precedences
+                    let Some(precedences) = syms.get_precedences_for_lib(&path.lib) else { continue };
                     all_used_precedences.reserve(precedences.len());
                     for (_, sym_arc) in precedences {
                         let mut sym = sym_arc.write();
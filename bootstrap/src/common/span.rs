@@ -1,6 +1,8 @@
-use std::{cmp, fmt, ops::Index};
+use std::{cmp, fmt, ops::Index, sync::Arc};
 
+use serde::{Deserialize, Serialize};
 
+use super::{Interner, OnceVec};
 
 
 
@@ -8,7 +10,9 @@ use std::{cmp, fmt, ops::Index};
 
 
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub struct Span {
     // Moved in front for easier debugging
     pub file_id:     u32,
@@ -26,11 +30,11 @@ pub struct Span {
 // Order via row/column
 impl PartialOrd for Span {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        let column_cmp = self.column.cmp(&self.row);
-        if column_cmp != cmp::Ordering::Equal {
-            Some(column_cmp)
+        let row_cmp = self.row.cmp(&other.row);
+        if row_cmp != cmp::Ordering::Equal {
+            Some(row_cmp)
         } else {
-            Some(self.row.cmp(&other.row))
+            Some(self.column.cmp(&other.column))
         }
     }
 }
@@ -42,7 +46,7 @@ impl Ord for Span {
 }
 
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct SpanId(usize);
 
 impl SpanId {
@@ -55,41 +59,64 @@ impl fmt::Display for SpanId {
     }
 }
 
+/// Registry of all spans, spans can be added from multiple threads through a shared reference
 pub struct SpanRegistry {
-    pub files: Vec<String>,
-    pub spans: Vec<Span>,
+    files:   Interner<String>,
+    spans:   OnceVec<Span>,
+    // Source text of each file, used to render diagnostics
+    sources: OnceVec<SharedSource>,
 }
 
+/// Source text that is shared with the front-end, so the source of a file is only stored once
+pub type SharedSource = Arc<dyn AsRef<str> + Send + Sync>;
+
 #[allow(unused)]
 impl SpanRegistry {
     pub fn new() -> Self {
         Self {
-            files: Vec::new(),
-            spans: Vec::new(),
+            files: Interner::new(),
+            spans: OnceVec::new(),
+            sources: OnceVec::new(),
         }
     }
 
-    pub fn add_span(&mut self, file: &str, mut span: Span) -> SpanId {
-        let file_id = match self.files.iter().enumerate().find(|(_, path)| *path == file).map(|(id, _)| id) {
-            Some(file_id) => file_id as u32,
-            None => {
-                let file_id = self.files.len() as u32;
-                self.files.push(file.to_string());
-                file_id
-            },
-        };
+    fn get_or_add_file(&self, file: &str) -> u32 {
+        self.files.add(file)
+    }
 
-        span.file_id = file_id;
+    pub fn add_span(&self, file: &str, mut span: Span) -> SpanId {
+        span.file_id = self.get_or_add_file(file);
         self.add_span_(span)
     }
 
-    fn add_span_(&mut self, span: Span) -> SpanId {
-        let id = SpanId(self.spans.len() as usize);
-        self.spans.push(span);
-        id
+    /// Store the source of a file, so diagnostics can show snippets of it.
+    /// The source of a file can only be set once, later sources of the same file are ignored.
+    pub fn set_file_source(&self, file: &str, source: &str) {
+        let file_id = self.get_or_add_file(file);
+        if self.sources.get(file_id as usize).is_none() {
+            _ = self.sources.set(file_id as usize, Arc::new(source.to_string()));
+        }
+    }
+
+    /// Store the source of a file like `set_file_source`, without copying it
+    pub fn share_file_source(&self, file: &str, source: SharedSource) {
+        let file_id = self.get_or_add_file(file);
+        _ = self.sources.set(file_id as usize, source);
+    }
+
+    pub fn get_file_source(&self, file_id: u32) -> Option<&str> {
+        self.sources.get(file_id as usize).map(|source| (**source).as_ref())
+    }
+
+    pub fn get(&self, span: SpanId) -> Option<&Span> {
+        self.spans.get(span.0)
     }
 
-    pub fn combine_spans(&mut self, begin: SpanId, end: SpanId) -> SpanId {
+    fn add_span_(&self, span: Span) -> SpanId {
+        SpanId(self.spans.push(span))
+    }
+
+    pub fn combine_spans(&self, begin: SpanId, end: SpanId) -> SpanId {
         if begin == end {
             return begin;
         }
@@ -97,8 +124,8 @@ impl SpanRegistry {
             return SpanId::INVALID;
         }
 
-        let begin = self.spans[begin.0];
-        let end = self.spans[end.0];
+        let begin = self[begin];
+        let end = self[end];
 
         if begin.file_id != end.file_id {
             return SpanId::INVALID;
@@ -114,16 +141,12 @@ impl SpanRegistry {
     }
 
     pub fn get_file(&self, file_id: u32) -> &str {
-        &self.files[file_id as usize]
+        self.files.get(file_id).expect("invalid file id")
     }
 
     pub fn get_file_from_span_id(&self, span: SpanId) -> Option<&str> {
-        if span.0 >= self.spans.len() {
-            None
-        } else {
-            let file_id = self.spans[span.0].file_id;
-            Some(&self.files[file_id as usize])
-        }
+        let file_id = self.get(span)?.file_id;
+        Some(self.get_file(file_id))
     }
 }
 
@@ -131,10 +154,11 @@ impl Index<SpanId> for SpanRegistry {
     type Output = Span;
 
     fn index(&self, index: SpanId) -> &Self::Output {
-        &self.spans[index.0]
+        self.get(index).expect("invalid span id")
     }
 }
 
+#[allow(unused)]
 pub struct FormatSpanLoc<'a> {
     pub registry: &'a SpanRegistry,
     pub span:     SpanId
@@ -142,8 +166,8 @@ pub struct FormatSpanLoc<'a> {
 
 impl fmt::Display for FormatSpanLoc<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let span = &self.registry.spans[self.span.0];
-        let file = &self.registry.files[span.file_id as usize];
+        let span = &self.registry[self.span];
+        let file = self.registry.get_file(span.file_id);
         write!(f, "{file}:{}:{}", span.row, span.column)
     }
 }
@@ -162,8 +186,8 @@ impl<'a> FormatSpan<'a> {
 
 impl fmt::Display for FormatSpan<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let span = &self.registry.spans[self.span.0];
-        let file = &self.registry.files[span.file_id as usize];
+        let span = &self.registry[self.span];
+        let file = self.registry.get_file(span.file_id);
         write!(f, "{file}, {}:{}->{}:{} (chars: {}-{}, bytes: {}-{})",
             span.row,
             span.column,
@@ -176,3 +200,36 @@ impl fmt::Display for FormatSpan<'_> {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span_at(row: u32, column: u32) -> Span {
+        Span {
+            file_id: 0,
+            row,
+            column,
+            char_offset: 0,
+            byte_offset: 0,
+            char_len: 0,
+            byte_len: 0,
+            row_end: row,
+            column_end: column,
+        }
+    }
+
+    #[test]
+    fn test_span_order() {
+        // Rows take priority over columns
+        assert!(span_at(1, 20) < span_at(2, 1));
+        assert!(span_at(2, 1) > span_at(1, 20));
+        assert!(span_at(3, 4) < span_at(3, 5));
+        assert_eq!(span_at(3, 4).cmp(&span_at(3, 4)), cmp::Ordering::Equal);
+
+        let mut spans = vec![span_at(2, 1), span_at(1, 9), span_at(2, 0), span_at(1, 3)];
+        spans.sort();
+        let positions: Vec<_> = spans.iter().map(|span| (span.row, span.column)).collect();
+        assert_eq!(positions, [(1, 3), (1, 9), (2, 0), (2, 1)]);
+    }
+}
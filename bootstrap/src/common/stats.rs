@@ -1,12 +1,18 @@
 use core::fmt;
 use std::time;
 
-use crate::{common::Logger, hir::Hir};
+use serde::{Serialize, Serializer};
 
+use crate::{common::{memory, Logger, TimingTrace}, hir::Hir};
 
+
+/// Durations are serialized as milliseconds
+#[derive(Serialize)]
 pub struct CompilerStats {
     // Lexer
     pub file_count:                           u64,
+    pub cached_file_count:                    u64,
+    #[serde(rename = "lex_time_ms", serialize_with = "serialize_ms")]
     pub lex_time:                             time::Duration,
     pub bytes_parsed:                         u64,
     pub chars_parsed:                         u64,
@@ -14,10 +20,12 @@ pub struct CompilerStats {
     pub tokens_generated:                     u64,
 
     // Parser
+    #[serde(rename = "parse_time_ms", serialize_with = "serialize_ms")]
     pub parse_time:                           time::Duration,
     pub ast_nodes_generated:                  u64,
 
     // Ast
+    #[serde(rename = "ast_pass_time_ms", serialize_with = "serialize_ms")]
     pub ast_pass_time:                        time::Duration,
 
     // AST -> HIR lower
@@ -56,15 +64,39 @@ pub struct CompilerStats {
 
     // HIR
     pub num_hir_passes:                       u64,
+    #[serde(rename = "hir_pass_time_ms", serialize_with = "serialize_ms")]
     pub hir_pass_time:                        time::Duration,
     pub num_types_registered:                 u32,
-    
+
+    // Memory
+    pub phases:                               Vec<PhaseStats>,
+    /// Highest number of bytes allocated by the compiler, up until the end of this compilation
+    pub peak_memory:                          usize,
+
+    /// Time spans of each file in each phase and of each pass
+    #[serde(skip)]
+    pub trace:                                TimingTrace,
+}
+
+/// Time and memory usage of a phase of the compilation of a library
+#[derive(Serialize)]
+pub struct PhaseStats {
+    pub name:        &'static str,
+    #[serde(rename = "time_ms", serialize_with = "serialize_ms")]
+    pub time:        time::Duration,
+    /// Highest number of bytes allocated during the phase
+    pub peak_memory: usize,
+}
+
+fn serialize_ms<S: Serializer>(dur: &time::Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(dur.as_secs_f64() * 1000.0)
 }
 
 impl CompilerStats {
     pub fn new() -> Self {
         Self {
             file_count:                           0,
+            cached_file_count:                    0,
             lex_time:                             time::Duration::default(),
             bytes_parsed:                         0,
             chars_parsed:                         0,
@@ -112,6 +144,11 @@ impl CompilerStats {
             num_hir_passes:                       0,
             hir_pass_time:                        time::Duration::default(),
             num_types_registered:                 0,
+
+            phases:                               Vec::new(),
+            peak_memory:                          0,
+
+            trace:                                TimingTrace::new(),
         }
     }
     
@@ -119,6 +156,11 @@ impl CompilerStats {
         self.file_count += 1;
     }
 
+    /// Files loaded from the incremental cache are not counted in the lexer, parser and AST pass stats
+    pub fn add_cached_file(&mut self) {
+        self.cached_file_count += 1;
+    }
+
     pub fn add_lex(&mut self, time: time::Duration, num_bytes: u64, num_chars: u64, num_lines: u64, num_tokens: u64) {
         self.lex_time += time;
         self.bytes_parsed += num_bytes;
@@ -176,10 +218,27 @@ impl CompilerStats {
         self.num_hir_passes += 1;
     }
 
+    /// Start a phase, the returned instant needs to be passed to `end_phase`
+    pub fn begin_phase(&self) -> time::Instant {
+        memory::reset_phase_peak_memory();
+        time::Instant::now()
+    }
+
+    pub fn end_phase(&mut self, name: &'static str, start: time::Instant) {
+        let peak_memory = memory::phase_peak_memory();
+        self.phases.push(PhaseStats { name, time: start.elapsed(), peak_memory });
+        self.peak_memory = memory::peak_memory();
+
+        let mut args = serde_json::Map::new();
+        args.insert("peak_memory".to_string(), peak_memory.into());
+        self.trace.add_span(name, "phase", start, args);
+    }
+
 
     pub fn log(&self) {
         let logger = Logger::new();
         logger.log_fmt(format_args!("Files processed: {}\n", self.file_count));
+        logger.log_fmt(format_args!("Files loaded from the incremental cache: {}\n", self.cached_file_count));
 
         let lex_time = self.lex_time.as_secs_f32();
         logger.logln("- Lexer:");
@@ -254,6 +313,12 @@ impl CompilerStats {
         logger.log_fmt(format_args!("    Time:                 {:.2}ms\n", self.hir_pass_time.as_secs_f32() * 1000.0));
 
         logger.log_fmt(format_args!("    Num Types Registered: {}\n", self.num_types_registered));
+
+        logger.logln("- Phases:");
+        for phase in &self.phases {
+            logger.log_fmt(format_args!("    {:<20}  {:>9.2}ms  peak memory: {}\n", phase.name, phase.time.as_secs_f32() * 1000.0, MemoryFormatHelper { bytes: phase.peak_memory }));
+        }
+        logger.log_fmt(format_args!("    Peak memory: {}\n", MemoryFormatHelper { bytes: self.peak_memory }));
     }
 }
 
@@ -297,4 +362,24 @@ impl fmt::Display for NumFormatHelper {
         }
         Ok(())
     }
+}
+pub struct MemoryFormatHelper {
+    pub bytes: usize
+}
+
+impl fmt::Display for MemoryFormatHelper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const KIB: usize = 1024;
+        const MIB: usize = 1024 * KIB;
+        const GIB: usize = 1024 * MIB;
+        if self.bytes > GIB {
+            write!(f, "{:.2}GiB", self.bytes as f32 / GIB as f32)
+        } else if self.bytes > MIB {
+            write!(f, "{:.2}MiB", self.bytes as f32 / MIB as f32)
+        } else if self.bytes > KIB {
+            write!(f, "{:.1}KiB", self.bytes as f32 / KIB as f32)
+        } else {
+            write!(f, "{} bytes", self.bytes)
+        }
+    }
 }
\ No newline at end of file
@@ -7,6 +7,7 @@ use std::{
     sync::{Arc, Weak}
 };
 use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
 
 use crate::{common::UsePathKind, lexer::{Punctuation, PuncutationTable}, type_system::{Type, TypeHandle, TypeRef}};
 
@@ -89,6 +90,32 @@ impl Symbol {
         }
     }
 
+    pub fn path_mut(&mut self) -> &mut SymbolPath {
+        match self {
+            Symbol::Module(sym)       => &mut sym.path,
+            Symbol::Precedence(sym)   => &mut sym.path,
+            Symbol::Function(sym)     => &mut sym.path,
+            Symbol::TypeAlias(sym)    => &mut sym.path,
+            Symbol::DistinctType(sym) => &mut sym.path,
+            Symbol::OpaqueType(sym)   => &mut sym.path,
+            Symbol::Struct(sym)       => &mut sym.path,
+            Symbol::Union(sym)        => &mut sym.path,
+            Symbol::AdtEnum(sym)      => &mut sym.path,
+            Symbol::FlagEnum(sym)     => &mut sym.path,
+            Symbol::Bitfield(sym)     => &mut sym.path,
+            Symbol::Const(sym)        => &mut sym.path,
+            Symbol::Static(sym)       => &mut sym.path,
+            Symbol::Property(sym)     => &mut sym.path,
+            Symbol::Trait(sym)        => &mut sym.path,
+            Symbol::Impl(sym)         => &mut sym.path,
+            Symbol::TypeGeneric(sym)  => &mut sym.path,
+            Symbol::ValueGeneric(sym) => &mut sym.path,
+            Symbol::OpSet(sym)       => &mut sym.path,
+            Symbol::Operator(sym)     => &mut sym.path,
+            
+        }
+    }
+
     pub fn get_type(&self) -> Option<&TypeHandle> {
         match self {
             Symbol::Module(sym)       => None,
@@ -114,6 +141,74 @@ impl Symbol {
             
         }
     }
+
+    pub fn set_type(&mut self, ty: TypeHandle) {
+        match self {
+            Symbol::Function(sym)     => sym.ty = Some(ty),
+            Symbol::TypeAlias(sym)    => sym.ty = Some(ty),
+            Symbol::DistinctType(sym) => sym.ty = Some(ty),
+            Symbol::OpaqueType(sym)   => sym.ty = Some(ty),
+            Symbol::Struct(sym)       => sym.ty = Some(ty),
+            Symbol::Union(sym)        => sym.ty = Some(ty),
+            Symbol::AdtEnum(sym)      => sym.ty = Some(ty),
+            Symbol::FlagEnum(sym)     => sym.ty = Some(ty),
+            Symbol::Bitfield(sym)     => sym.ty = Some(ty),
+            Symbol::Const(sym)        => sym.ty = Some(ty),
+            Symbol::Static(sym)       => sym.ty = Some(ty),
+            Symbol::Property(sym)     => sym.ty = Some(ty),
+            Symbol::Trait(sym)        => sym.ty = Some(ty),
+            Symbol::TypeGeneric(sym)  => sym.ty = Some(ty),
+            Symbol::ValueGeneric(sym) => sym.ty = Some(ty),
+            _                         => (),
+        }
+    }
+
+    pub fn vis(&self) -> Option<&Visibility> {
+        match self {
+            Symbol::Module(_)         => None,
+            Symbol::Precedence(_)     => None,
+            Symbol::Function(sym)     => Some(&sym.vis),
+            Symbol::TypeAlias(sym)    => Some(&sym.vis),
+            Symbol::DistinctType(sym) => Some(&sym.vis),
+            Symbol::OpaqueType(sym)   => Some(&sym.vis),
+            Symbol::Struct(sym)       => Some(&sym.vis),
+            Symbol::Union(sym)        => Some(&sym.vis),
+            Symbol::AdtEnum(sym)      => Some(&sym.vis),
+            Symbol::FlagEnum(sym)     => Some(&sym.vis),
+            Symbol::Bitfield(sym)     => Some(&sym.vis),
+            Symbol::Const(sym)        => Some(&sym.vis),
+            Symbol::Static(sym)       => Some(&sym.vis),
+            Symbol::Property(sym)     => Some(&sym.vis),
+            Symbol::Trait(sym)        => Some(&sym.vis),
+            Symbol::Impl(sym)         => Some(&sym.vis),
+            Symbol::TypeGeneric(sym)  => Some(&sym.vis),
+            Symbol::ValueGeneric(sym) => Some(&sym.vis),
+            Symbol::OpSet(_)          => None,
+            Symbol::Operator(_)       => None,
+        }
+    }
+
+    pub fn set_vis(&mut self, vis: Visibility) {
+        match self {
+            Symbol::Function(sym)     => sym.vis = vis,
+            Symbol::TypeAlias(sym)    => sym.vis = vis,
+            Symbol::DistinctType(sym) => sym.vis = vis,
+            Symbol::OpaqueType(sym)   => sym.vis = vis,
+            Symbol::Struct(sym)       => sym.vis = vis,
+            Symbol::Union(sym)        => sym.vis = vis,
+            Symbol::AdtEnum(sym)      => sym.vis = vis,
+            Symbol::FlagEnum(sym)     => sym.vis = vis,
+            Symbol::Bitfield(sym)     => sym.vis = vis,
+            Symbol::Const(sym)        => sym.vis = vis,
+            Symbol::Static(sym)       => sym.vis = vis,
+            Symbol::Property(sym)     => sym.vis = vis,
+            Symbol::Trait(sym)        => sym.vis = vis,
+            Symbol::Impl(sym)         => sym.vis = vis,
+            Symbol::TypeGeneric(sym)  => sym.vis = vis,
+            Symbol::ValueGeneric(sym) => sym.vis = vis,
+            _                         => (),
+        }
+    }
 }
 
 //----------------------------------------------
@@ -125,7 +220,7 @@ pub struct ModuleSymbol {
 
 //----------------------------------------------
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum PrecedenceOrderKind {
     User,
     Lowest,
@@ -185,7 +280,7 @@ pub struct OpaqueTypeSymbol {
 
 //----------------------------------------------
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum StructKind {
     Normal,
     Tuple,
@@ -254,7 +349,7 @@ pub struct ConstSymbol {
 
 //----------------------------------------------
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum StaticKind {
     Normal,
     Extern,
@@ -289,7 +384,7 @@ pub struct PropertySymbol {
 
 //----------------------------------------------
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum TraitItemKind {
     Function,
     Method,
@@ -303,7 +398,7 @@ pub enum TraitItemKind {
     },
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct TraitItemRecord {
     pub name:        String,
     pub kind:        TraitItemKind,
@@ -539,6 +634,27 @@ impl SymbolTable {
         sym_entry.sub_table.get_sub_table(&idens[1..])
     }
 
+    fn collect_symbols<F>(&self, filter: &mut F, syms: &mut Vec<SymbolRef>) where
+        F: FnMut(&Symbol) -> bool
+    {
+        let mut entries: Vec<_> = self.entries.iter()
+            .flat_map(|(name, entry)| entry.sym_entry.iter().map(move |sym_entry| (name, &[][..], sym_entry))
+                .chain(entry.with_params.iter().map(move |(params, sym_entry)| (name, params.as_slice(), sym_entry)))
+            )
+            .collect();
+        entries.sort_by(|(name0, params0, _), (name1, params1, _)| (name0, params0).cmp(&(name1, params1)));
+
+        for (_, _, sym_entry) in entries {
+            if let Some(sym) = &sym_entry.symbol {
+                if !filter(&sym.read()) {
+                    continue;
+                }
+                syms.push(sym.clone());
+            }
+            sym_entry.sub_table.collect_symbols(filter, syms);
+        }
+    }
+
     fn get_or_add_entry(&mut self, name: String) -> &mut TableEntry {
         let entry = self.entries.entry(name);
         entry.or_insert(TableEntry::new())
@@ -822,9 +938,34 @@ impl RootSymbolTable {
     }
 
     fn add_symbol(&mut self, scope: &Scope, iden: PathIden, sym: Symbol) -> SymbolRef {
-        // SAFETY: We always add the table for `self.cur_lib`, so we know it exists
-        let cur_table = self.tables.get_mut(&self.cur_lib).unwrap();
-        cur_table.add_symbol(scope, iden, sym)
+        // Symbols of other libraries are added when their metadata is imported
+        let table = self.tables.entry(sym.path().lib().clone()).or_insert_with(SymbolTable::new);
+        table.add_symbol(scope, iden, sym)
+    }
+
+    pub fn has_lib(&self, lib: &LibraryPath) -> bool {
+        self.tables.contains_key(lib)
+    }
+
+    /// Get all symbols of a library, sorted by path.
+    /// `filter` is called for each symbol, symbols nested in a symbol for which it returns `false` are skipped
+    pub fn get_symbols_for_lib<F>(&self, lib: &LibraryPath, mut filter: F) -> Vec<SymbolRef> where
+        F: FnMut(&Symbol) -> bool
+    {
+        let mut syms = Vec::new();
+        if let Some(table) = self.tables.get(lib) {
+            table.collect_symbols(&mut filter, &mut syms);
+        }
+        syms
+    }
+
+    pub fn get_symbol_from_path(&self, path: &SymbolPath) -> Option<SymbolRef> {
+        let table = self.tables.get(path.lib())?;
+        table.get_direct_symbol(path.to_full_scope().to_lookup().idens())
+    }
+
+    pub fn type_impls(&self) -> impl Iterator<Item = (&TypeHandle, &Vec<SymbolRef>)> {
+        self.ty_table.iter()
     }
 
 
@@ -0,0 +1,106 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time,
+};
+
+use parking_lot::Mutex;
+use serde_json::{json, Map, Value};
+
+use super::CompilerStats;
+
+/// Time span of a part of the compilation, e.g. a phase for a single file, or a HIR pass
+pub struct TraceSpan {
+    pub name:     String,
+    pub category: &'static str,
+    pub start:    time::Instant,
+    pub duration: time::Duration,
+    /// Id of the thread the span was recorded on, ids are assigned in the order threads record their first span
+    pub thread:   u64,
+    pub args:     Map<String, Value>,
+}
+
+/// Collection of time spans, which can be recorded from multiple threads
+#[derive(Default)]
+pub struct TimingTrace {
+    spans: Mutex<Vec<TraceSpan>>,
+}
+
+impl TimingTrace {
+    pub fn new() -> Self {
+        Self {
+            spans: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Add a span from `start` until now
+    pub fn add_span(&self, name: impl Into<String>, category: &'static str, start: time::Instant, args: Map<String, Value>) {
+        let span = TraceSpan {
+            name: name.into(),
+            category,
+            start,
+            duration: start.elapsed(),
+            thread: thread_id(),
+            args,
+        };
+        self.spans.lock().push(span);
+    }
+
+    /// Remove all spans, ordered by their start time
+    pub fn take_spans(&self) -> Vec<TraceSpan> {
+        let mut spans = std::mem::take(&mut *self.spans.lock());
+        spans.sort_by_key(|span| span.start);
+        spans
+    }
+}
+
+fn thread_id() -> u64 {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+    thread_local! {
+        static ID: u64 = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    }
+    ID.with(|id| *id)
+}
+
+/// Create a trace in the Chrome trace event format, which can be opened in `chrome://tracing` or Perfetto.
+///
+/// Each compiled library is shown as a separate process, the stats of each library are stored under `stats`, which trace viewers ignore.
+/// Timestamps are relative to `origin`.
+pub fn chrome_trace(origin: time::Instant, libraries: &[(String, CompilerStats)]) -> Value {
+    let micros = |dur: time::Duration| dur.as_secs_f64() * 1_000_000.0;
+
+    let mut events = Vec::new();
+    let mut stats = Vec::new();
+    for (pid, (library, lib_stats)) in libraries.iter().enumerate() {
+        events.push(json!({
+            "name": "process_name",
+            "ph":   "M",
+            "pid":  pid,
+            "args": { "name": library },
+        }));
+
+        for span in lib_stats.trace.take_spans() {
+            events.push(json!({
+                "name": span.name,
+                "cat":  span.category,
+                "ph":   "X",
+                "ts":   micros(span.start.saturating_duration_since(origin)),
+                "dur":  micros(span.duration),
+                "pid":  pid,
+                "tid":  span.thread,
+                "args": span.args,
+            }));
+        }
+
+        let mut lib_stats = serde_json::to_value(lib_stats).unwrap_or_default();
+        if let Value::Object(lib_stats) = &mut lib_stats {
+            lib_stats.insert("library".to_string(), json!(library));
+        }
+        stats.push(lib_stats);
+    }
+
+    json!({
+        "traceEvents":     events,
+        "displayTimeUnit": "ms",
+        "stats":           stats,
+    })
+}
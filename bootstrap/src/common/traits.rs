@@ -31,6 +31,11 @@ impl TraitDag {
         self.dag.get_data(idx)
     }
 
+    /// Iterate over all traits, in order of their index
+    pub fn iter(&self) -> impl Iterator<Item = &TraitDagData> {
+        self.dag.iter()
+    }
+
     #[allow(unused)]
     pub fn get_base_ids(&self, idx: u32) -> &[u32] {
         self.dag.get_precomputed_predecessor_idxs(idx)
@@ -233,7 +233,8 @@ impl RootUseTable {
                 Some(name) => if sym_table.get_direct_precedence(&use_path.lib, name).is_none() {
                     invalid_precedences.push(use_path.clone());
                 },
-                None => if !sym_table.has_precedence_for_lib(&use_path.lib) {
+                // The implicit wildcard for the current library is valid, even if it doesn't declare any precedences
+                None => if use_path.lib != self.lib_path && !sym_table.has_precedence_for_lib(&use_path.lib) {
                     invalid_precedences.push(use_path.clone());
                 },
             }
@@ -271,7 +272,7 @@ impl RootUseTable {
                     let entry = collected_paths.entry(name.clone()).or_default();
                     entry.push(path.clone());
                 },
-                None => for (name, _) in sym_table.get_precedences_for_lib(&path.lib).unwrap() {
+                None => for (name, _) in sym_table.get_precedences_for_lib(&path.lib).into_iter().flatten() {
                     let entry = collected_paths.entry(name.clone()).or_default();
                     entry.push(path.clone());
                 },
@@ -300,7 +301,7 @@ impl RootUseTable {
                 Some(op_set) => if sym_table.get_direct_op_set(&op_use.lib, op_set).is_none() {
                     invalid_op_sets.push(op_use.clone());
                 },
-                None =>  if !sym_table.has_op_set_for_lib(&op_use.lib) {
+                None =>  if op_use.lib != self.lib_path && !sym_table.has_op_set_for_lib(&op_use.lib) {
                     invalid_op_sets.push(op_use.clone());
                 },
             }
@@ -339,7 +340,7 @@ impl RootUseTable {
                     let entry = collected_paths.entry(name.clone()).or_default();
                     entry.push(path.clone());
                 },
-                None => for (name, _) in sym_table.get_precedences_for_lib(&path.lib).unwrap() {
+                None => for (name, _) in sym_table.get_op_set_and_ops_for_lib(&path.lib).into_iter().flatten() {
                     let entry = collected_paths.entry(name.clone()).or_default();
                     entry.push(path.clone());
                 },
@@ -384,7 +385,7 @@ impl RootUseTable {
                     }
                 },
                 None => {
-                    for (_, (_, ops)) in sym_table.get_op_set_and_ops_for_lib(&path.lib).unwrap() {
+                    for (_, (_, ops)) in sym_table.get_op_set_and_ops_for_lib(&path.lib).into_iter().flatten() {
                         for (op_name, op) in ops {
                             let op = op.read();
                             let Symbol::Operator(op) = &*op else { unreachable!() };
@@ -0,0 +1,528 @@
+use std::{fs, path::Path};
+
+use crate::lexer::MetaElem;
+
+/// Info with a name, used for inferred parameters, named returns, and specific errors
+#[derive(Clone, Debug)]
+pub struct DocNamed {
+    pub name: String,
+    pub info: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct DocParam {
+    pub name:    String,
+    pub info:    String,
+    pub default: Option<String>,
+}
+
+/// General note or exhortation, e.g. `\todo(author) info`
+#[derive(Clone, Debug)]
+pub struct DocNote {
+    /// Kind of note, i.e. `bug`, `experimental`, `note`, `remark`, `todo`, or `perf`
+    pub kind:    String,
+    pub authors: Vec<String>,
+    pub info:    String,
+}
+
+#[derive(Clone, Debug)]
+pub struct DocFavicon {
+    pub path:  String,
+    pub sizes: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct DocLogo {
+    pub path:   String,
+    pub width:  Option<String>,
+    pub height: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub enum DocPlayground {
+    Embedded,
+    Url(String),
+}
+
+/// Meta of a `doc` attribute, with all values converted to text
+#[derive(Clone, Debug)]
+pub enum DocAttrMeta {
+    /// `@doc(name)`
+    Flag(String),
+    /// `@doc("text")`
+    Text(String),
+    /// `@doc(name = "text")`
+    Assign(String, String),
+    /// `@doc(name(...))`
+    Nested(String, Vec<DocAttrMeta>),
+}
+
+/// Parsed documentation of an item, following the doc comment format from the design.
+#[derive(Clone, Debug, Default)]
+pub struct DocComment {
+    pub short:              String,
+    /// Long description in markdown, which can contain item links
+    pub long:               String,
+    pub receiver:           Option<String>,
+    pub infer_params:       Vec<DocNamed>,
+    pub params:             Vec<DocParam>,
+    pub returns:            Option<String>,
+    pub named_returns:      Vec<DocNamed>,
+    pub since:              Option<String>,
+    pub pre:                Vec<String>,
+    pub post:               Vec<String>,
+    pub invar:              Vec<String>,
+    pub complexity:         Option<String>,
+    pub important:          Vec<String>,
+    pub warning:            Vec<String>,
+    pub attention:          Vec<String>,
+    pub errors:             Option<String>,
+    pub error:              Vec<DocNamed>,
+    pub issues:             Vec<String>,
+    pub notes:              Vec<DocNote>,
+    pub aliases:            Vec<String>,
+    pub no_source:          bool,
+    pub hidden:             bool,
+    /// `Some(true)` for `@doc(inline)`, `Some(false)` for `@doc(no_inline)`
+    pub inline:             Option<bool>,
+
+    // Only allowed in the documentation of the library root
+    pub favicons:           Vec<DocFavicon>,
+    pub logo:               Option<DocLogo>,
+    pub playground:         Option<DocPlayground>,
+    pub issue_tracker_base: Option<String>,
+}
+
+const NOTE_KINDS: [&str; 7] = ["bug", "experimental", "note", "remark", "remarks", "todo", "perf"];
+
+impl DocComment {
+    pub fn is_empty(&self) -> bool {
+        self.short.is_empty() && self.long.is_empty()
+    }
+
+    /// Get all markdown text in the documentation, i.e. the descriptions and the info of all elements
+    pub fn texts_mut(&mut self) -> Vec<&mut String> {
+        let mut texts = vec![&mut self.short, &mut self.long];
+        texts.extend(self.receiver.as_mut());
+        texts.extend(self.infer_params.iter_mut().map(|named| &mut named.info));
+        for param in &mut self.params {
+            texts.push(&mut param.info);
+            texts.extend(param.default.as_mut());
+        }
+        texts.extend(self.returns.as_mut());
+        texts.extend(self.named_returns.iter_mut().map(|named| &mut named.info));
+        texts.extend(self.pre.iter_mut().chain(&mut self.post).chain(&mut self.invar));
+        texts.extend(self.complexity.as_mut());
+        texts.extend(self.important.iter_mut().chain(&mut self.warning).chain(&mut self.attention));
+        texts.extend(self.errors.as_mut());
+        texts.extend(self.error.iter_mut().map(|named| &mut named.info));
+        texts.extend(self.notes.iter_mut().map(|note| &mut note.info));
+        texts
+    }
+
+    /// Get the text lines of doc comments, with the indentation of the comment removed.
+    /// Only the comments for which `is_top_level` matches are used, so item and module documentation can be split.
+    pub fn lines_from_meta<'a>(elems: impl IntoIterator<Item = &'a MetaElem>, is_top_level: bool, warnings: &mut Vec<String>) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut line_comments = Vec::new();
+        for elem in elems {
+            match elem {
+                MetaElem::LineDocComment(text) if !is_top_level => line_comments.push(text.as_str()),
+                MetaElem::LineTopDocComment(text) if is_top_level => line_comments.push(text.as_str()),
+                MetaElem::BlockDocComment(text) if !is_top_level => {
+                    lines.extend(Self::dedent(&line_comments, warnings));
+                    line_comments.clear();
+                    lines.extend(Self::dedent_block(text, warnings));
+                },
+                MetaElem::BlockTopDocComment(text) if is_top_level => {
+                    lines.extend(Self::dedent(&line_comments, warnings));
+                    line_comments.clear();
+                    lines.extend(Self::dedent_block(text, warnings));
+                },
+                _ => {},
+            }
+        }
+        lines.extend(Self::dedent(&line_comments, warnings));
+        lines
+    }
+
+    /// Remove the indentation of the first line with text from all lines
+    fn dedent(lines: &[&str], warnings: &mut Vec<String>) -> Vec<String> {
+        let indent_of = |line: &str| line.len() - line.trim_start().len();
+        let Some(indent) = lines.iter().find(|line| !line.trim().is_empty()).map(|line| indent_of(line)) else {
+            return Vec::new();
+        };
+
+        let mut reported = false;
+        lines.iter().map(|line| {
+            let line = line.trim_end();
+            if line.is_empty() {
+                String::new()
+            } else if indent_of(line) < indent {
+                if !reported {
+                    warnings.push(format!("Doc comment line `{}` starts before the first line of the comment", line.trim()));
+                    reported = true;
+                }
+                line.trim_start().to_string()
+            } else {
+                line[indent..].to_string()
+            }
+        }).collect()
+    }
+
+    /// Block comments can start their text on the same line as the comment starts, so the indentation is taken from the next line with text in that case
+    fn dedent_block(text: &str, warnings: &mut Vec<String>) -> Vec<String> {
+        let lines: Vec<&str> = text.lines().collect();
+        match lines.split_first() {
+            Some((first, rest)) if !first.trim().is_empty() => {
+                let mut out = vec![first.trim().to_string()];
+                out.extend(Self::dedent(rest, warnings));
+                out
+            },
+            _ => Self::dedent(&lines, warnings),
+        }
+    }
+
+    /// Replace all `\{include_str("path")}` elements with the contents of the file, relative to `base_dir`
+    pub fn expand_includes(line: &str, base_dir: &Path, warnings: &mut Vec<String>) -> String {
+        const START: &str = "\\{include_str(\"";
+        const END: &str = "\")}";
+
+        let mut out = String::new();
+        let mut rest = line;
+        while let Some(start) = rest.find(START) {
+            let Some(len) = rest[start + START.len()..].find(END) else { break };
+            let path = &rest[start + START.len()..start + START.len() + len];
+
+            out.push_str(&rest[..start]);
+            match fs::read_to_string(base_dir.join(path)) {
+                Ok(text) => out.push_str(text.trim_end()),
+                Err(err) => warnings.push(format!("Failed to include '{path}' in documentation: {err}")),
+            }
+            rest = &rest[start + START.len() + len + END.len()..];
+        }
+        out.push_str(rest);
+        out
+    }
+
+    /// Parse the text lines of doc comments and `doc` attributes
+    pub fn parse_lines(&mut self, lines: &[String], warnings: &mut Vec<String>) {
+        let mut short_lines = Vec::new();
+        let mut long_lines = Vec::new();
+        let mut short_done = !self.short.is_empty();
+        let mut in_code_block = false;
+
+        let mut idx = 0;
+        while idx < lines.len() {
+            let line = &lines[idx];
+            idx += 1;
+
+            if line.trim_start().starts_with("```") {
+                in_code_block = !in_code_block;
+                short_done = true;
+                long_lines.push(line.as_str());
+                continue;
+            }
+
+            if !in_code_block {
+                if let Some(elem) = line.strip_prefix('\\').filter(|elem| elem.starts_with(|ch: char| ch.is_ascii_alphabetic())) {
+                    // Indented lines after an element continue its info
+                    let mut text = elem.trim_end().to_string();
+                    while idx < lines.len() && lines[idx].starts_with(char::is_whitespace) && !lines[idx].trim().is_empty() {
+                        text.push(' ');
+                        text.push_str(lines[idx].trim());
+                        idx += 1;
+                    }
+
+                    let name_len = text.find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '_')).unwrap_or(text.len());
+                    let (name, mut rest) = text.split_at(name_len);
+                    let mut authors = Vec::new();
+                    if let Some(args) = rest.strip_prefix('(') {
+                        if let Some(end) = args.find(')') {
+                            authors = Self::split_list(&args[..end]);
+                            rest = &args[end + 1..];
+                        }
+                    }
+                    self.add_element(name, authors, rest.trim(), warnings);
+                    short_done = true;
+                    continue;
+                }
+            }
+
+            if short_done {
+                long_lines.push(line.as_str());
+            } else if line.trim().is_empty() {
+                short_done = !short_lines.is_empty();
+            } else {
+                short_lines.push(line.trim());
+            }
+        }
+
+        if !short_lines.is_empty() {
+            self.short = short_lines.join(" ");
+        }
+
+        let first = long_lines.iter().position(|line| !line.trim().is_empty());
+        let last = long_lines.iter().rposition(|line| !line.trim().is_empty());
+        if let (Some(first), Some(last)) = (first, last) {
+            if !self.long.is_empty() {
+                self.long.push_str("\n\n");
+            }
+            self.long.push_str(&long_lines[first..=last].join("\n"));
+        }
+    }
+
+    fn add_element(&mut self, name: &str, authors: Vec<String>, info: &str, warnings: &mut Vec<String>) {
+        let info = info.to_string();
+        let split_name = |info: &str| -> (String, String) {
+            match info.split_once(char::is_whitespace) {
+                Some((name, info)) => (name.to_string(), info.trim().to_string()),
+                None => (info.to_string(), String::new()),
+            }
+        };
+
+        match name {
+            "receiver"           => self.receiver = Some(info),
+            "infer_param"        => {
+                let (name, info) = split_name(&info);
+                self.infer_params.push(DocNamed { name, info });
+            },
+            "param"              => {
+                let (name, info) = split_name(&info);
+                self.params.push(DocParam { name, info, default: None });
+            },
+            "default"            => match self.params.last_mut() {
+                Some(param) => param.default = Some(info),
+                None        => warnings.push("`\\default` needs to follow a `\\param` element".to_string()),
+            },
+            "return"             => self.returns = Some(info),
+            "named_return"       => {
+                let (name, info) = split_name(&info);
+                self.named_returns.push(DocNamed { name, info });
+            },
+            "since"              => self.since = Some(info),
+            "pre"                => self.pre.push(info),
+            "post"               => self.post.push(info),
+            "invar"              => self.invar.push(info),
+            "complexity"         => self.complexity = Some(info),
+            "important"          => self.important.push(info),
+            "warning"            => self.warning.push(info),
+            "attention"          => self.attention.push(info),
+            "errors"             => self.errors = Some(info),
+            "error"              => {
+                let (name, info) = split_name(&info);
+                self.error.push(DocNamed { name, info });
+            },
+            "issue"              => self.issues.push(info),
+            "alias"              => self.aliases.push(info),
+            "no_source"          => self.no_source = true,
+            "issue_tracker_base" => self.issue_tracker_base = Some(info),
+            "favicon"            => {
+                let (path, args) = Self::parse_args(&info);
+                self.favicons.push(DocFavicon {
+                    path: path.unwrap_or_default(),
+                    sizes: Self::get_arg(&args, "sizes"),
+                });
+            },
+            "logo"               => {
+                if self.logo.is_some() {
+                    warnings.push("Only a single logo can be specified".to_string());
+                }
+                let (path, args) = Self::parse_args(&info);
+                self.logo = Some(DocLogo {
+                    path: path.unwrap_or_default(),
+                    width: Self::get_arg(&args, "width"),
+                    height: Self::get_arg(&args, "height"),
+                });
+            },
+            "playground"         => {
+                let (kind, args) = Self::parse_args(&info);
+                match (kind.as_deref(), Self::get_arg(&args, "url")) {
+                    (Some("embedded"), _) => self.playground = Some(DocPlayground::Embedded),
+                    (_, Some(url))        => self.playground = Some(DocPlayground::Url(url)),
+                    _                     => warnings.push(format!("Unknown playground `{info}`, expected `embedded` or `url=...`")),
+                }
+            },
+            "hidden" | "inline" | "no_inline" => warnings.push(format!("`{name}` can only be used in a `doc` attribute, i.e. `@doc({name})`")),
+            _ if NOTE_KINDS.contains(&name) => self.notes.push(DocNote { kind: name.to_string(), authors, info }),
+            _ => warnings.push(format!("Unknown doc element `\\{name}`")),
+        }
+    }
+
+    /// Apply a meta of a `doc` attribute, text metas are expected to be passed to `parse_lines` instead
+    pub fn apply_attr_meta(&mut self, meta: &DocAttrMeta, warnings: &mut Vec<String>) {
+        match meta {
+            DocAttrMeta::Flag(name) => match name.as_str() {
+                "inline"       => self.inline = Some(true),
+                "no_inline"    => self.inline = Some(false),
+                "hidden"       => self.hidden = true,
+                "no_source"    => self.no_source = true,
+                "experimental" => self.notes.push(DocNote { kind: name.clone(), authors: Vec::new(), info: String::new() }),
+                _              => warnings.push(format!("Unknown `doc` attribute flag `{name}`")),
+            },
+            DocAttrMeta::Text(text) => self.parse_lines(&text.lines().map(str::to_string).collect::<Vec<_>>(), warnings),
+            DocAttrMeta::Assign(name, value) => match name.as_str() {
+                "short" => self.short = value.clone(),
+                "long"  => {
+                    if !self.long.is_empty() {
+                        self.long.push_str("\n\n");
+                    }
+                    self.long.push_str(value.trim());
+                },
+                _       => self.add_element(name, Vec::new(), value, warnings),
+            },
+            DocAttrMeta::Nested(name, metas) => {
+                let mut positional = None;
+                let mut flags = Vec::new();
+                let mut args = Vec::new();
+                for meta in metas {
+                    match meta {
+                        DocAttrMeta::Flag(flag)          => flags.push(flag.as_str()),
+                        DocAttrMeta::Text(text)          => positional = Some(text.clone()),
+                        DocAttrMeta::Assign(name, value) => args.push((name.clone(), value.clone())),
+                        DocAttrMeta::Nested(name, _)     => warnings.push(format!("Unexpected nested `{name}` in `doc` attribute")),
+                    }
+                }
+                let arg = |name: &str| Self::get_arg(&args, name);
+                let named = || DocNamed { name: arg("name").unwrap_or_default(), info: arg("info").unwrap_or_default() };
+
+                match name.as_str() {
+                    "param"        => self.params.push(DocParam {
+                        name: arg("name").unwrap_or_default(),
+                        info: arg("info").unwrap_or_default(),
+                        default: arg("default"),
+                    }),
+                    "infer_param"  => self.infer_params.push(named()),
+                    "named_return" => self.named_returns.push(named()),
+                    "error"        => self.error.push(named()),
+                    "favicon"      => self.favicons.push(DocFavicon {
+                        path: arg("path").or(positional).unwrap_or_default(),
+                        sizes: arg("sizes"),
+                    }),
+                    "logo"         => self.logo = Some(DocLogo {
+                        path: arg("path").or(positional).unwrap_or_default(),
+                        width: arg("width"),
+                        height: arg("height"),
+                    }),
+                    "playground"   => match arg("url") {
+                        Some(url)                             => self.playground = Some(DocPlayground::Url(url)),
+                        None if flags.contains(&"embedded") => self.playground = Some(DocPlayground::Embedded),
+                        None                                  => warnings.push("Unknown playground, expected `embedded` or `url=...`".to_string()),
+                    },
+                    _ if NOTE_KINDS.contains(&name.as_str()) => self.notes.push(DocNote {
+                        kind: name.clone(),
+                        authors: arg("authors").map_or_else(Vec::new, |authors| Self::split_list(&authors)),
+                        info: arg("info").or(positional).unwrap_or_default(),
+                    }),
+                    _ => warnings.push(format!("Unknown `doc` attribute `{name}(..)`")),
+                }
+            },
+        }
+    }
+
+    /// Parse arguments in the form of `value, name=value, name="value"`
+    fn parse_args(text: &str) -> (Option<String>, Vec<(String, String)>) {
+        let mut positional = None;
+        let mut args = Vec::new();
+        for arg in Self::split_list(text) {
+            match arg.split_once('=') {
+                Some((name, value)) => args.push((name.trim().to_string(), value.trim().trim_matches('"').to_string())),
+                None                => positional = Some(arg.trim_matches('"').to_string()),
+            }
+        }
+        (positional, args)
+    }
+
+    fn get_arg(args: &[(String, String)], name: &str) -> Option<String> {
+        args.iter().find(|(arg, _)| arg == name).map(|(_, value)| value.clone())
+    }
+
+    fn split_list(text: &str) -> Vec<String> {
+        text.split(',')
+            .map(|elem| elem.trim().to_string())
+            .filter(|elem| !elem.is_empty())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lexer::MetaElem;
+
+    use super::DocComment;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn test_lines_from_meta() {
+        let elems = [
+            MetaElem::LineDocComment(" Short".to_string()),
+            MetaElem::Whitespace("\n".to_string()),
+            MetaElem::LineDocComment("".to_string()),
+            MetaElem::LineDocComment("   indented".to_string()),
+            MetaElem::LineComment(" not documentation".to_string()),
+            MetaElem::LineTopDocComment(" top".to_string()),
+            MetaElem::BlockDocComment(" First\n  second\n    third\n ".to_string()),
+        ];
+
+        let mut warnings = Vec::new();
+        let item_lines = DocComment::lines_from_meta(&elems, false, &mut warnings);
+        assert_eq!(item_lines, lines(&["Short", "", "  indented", "First", "second", "  third", ""]));
+
+        let top_lines = DocComment::lines_from_meta(&elems, true, &mut warnings);
+        assert_eq!(top_lines, lines(&["top"]));
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_lines_from_meta_bad_indent() {
+        let elems = [
+            MetaElem::LineDocComment("    first".to_string()),
+            MetaElem::LineDocComment("  second".to_string()),
+        ];
+
+        let mut warnings = Vec::new();
+        let doc_lines = DocComment::lines_from_meta(&elems, false, &mut warnings);
+        assert_eq!(doc_lines, lines(&["first", "second"]));
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_lines() {
+        let doc_lines = lines(&[
+            "Short description",
+            "that continues.",
+            "",
+            "Long description.",
+            "\\param a First parameter",
+            "  continued on the next line",
+            "\\default 1",
+            "\\todo(alice, bob) Something to do",
+            "```",
+            "\\param not_an_element",
+            "```",
+            "\\unknown",
+        ]);
+
+        let mut doc = DocComment::default();
+        let mut warnings = Vec::new();
+        doc.parse_lines(&doc_lines, &mut warnings);
+
+        assert_eq!(doc.short, "Short description that continues.");
+        assert_eq!(doc.long, "Long description.\n```\n\\param not_an_element\n```");
+
+        assert_eq!(doc.params.len(), 1);
+        assert_eq!(doc.params[0].name, "a");
+        assert_eq!(doc.params[0].info, "First parameter continued on the next line");
+        assert_eq!(doc.params[0].default.as_deref(), Some("1"));
+
+        assert_eq!(doc.notes.len(), 1);
+        assert_eq!(doc.notes[0].kind, "todo");
+        assert_eq!(doc.notes[0].authors, ["alice", "bob"]);
+        assert_eq!(doc.notes[0].info, "Something to do");
+
+        assert_eq!(warnings, ["Unknown doc element `\\unknown`"]);
+    }
+}
@@ -0,0 +1,417 @@
+use super::ITEM_LINK_SCHEME;
+
+/// Result of resolving an item link
+pub enum LinkTarget {
+    /// Link to an item, using `ITEM_LINK_SCHEME`
+    Item(String),
+    /// Builtin type, which doesn't have any documentation to link to
+    Builtin,
+    Unresolved,
+}
+
+/// Call `f` for each link in markdown text, outside of code, which returns the text to replace the link with, or `None` to keep it.
+/// The url is `None` for shortcut links, i.e. ``[`path`]``.
+pub fn replace_links(text: &str, f: &mut dyn FnMut(&str, Option<&str>) -> Option<String>) -> String {
+    let mut out = String::new();
+    let mut in_code_block = false;
+    for (idx, line) in text.split('\n').enumerate() {
+        if idx != 0 {
+            out.push('\n');
+        }
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+        }
+        if in_code_block || line.trim_start().starts_with("```") {
+            out.push_str(line);
+        } else {
+            replace_links_in_line(line, f, &mut out);
+        }
+    }
+    out
+}
+
+fn replace_links_in_line(line: &str, f: &mut dyn FnMut(&str, Option<&str>) -> Option<String>, out: &mut String) {
+    let mut idx = 0;
+    while idx < line.len() {
+        let rest = &line[idx..];
+        if rest.starts_with('`') {
+            let len = code_span_len(rest);
+            out.push_str(&rest[..len]);
+            idx += len;
+            continue;
+        }
+
+        if rest.starts_with('[') {
+            if let Some(close) = link_label_end(rest) {
+                let label = &rest[1..close];
+                let after = &rest[close + 1..];
+
+                if let Some(url_end) = after.strip_prefix('(').and_then(|url| url.find(')')) {
+                    let url = &after[1..url_end + 1];
+                    let len = close + 1 + url_end + 2;
+                    match f(label, Some(url)) {
+                        Some(replacement) => out.push_str(&replacement),
+                        None              => out.push_str(&rest[..len]),
+                    }
+                    idx += len;
+                    continue;
+                }
+
+                // Reference links are kept as is
+                if !after.starts_with('[') && !after.starts_with(':') {
+                    match f(label, None) {
+                        Some(replacement) => out.push_str(&replacement),
+                        None              => out.push_str(&rest[..close + 1]),
+                    }
+                    idx += close + 1;
+                    continue;
+                }
+            }
+        }
+
+        let ch = rest.chars().next().unwrap_or_default();
+        out.push(ch);
+        idx += ch.len_utf8();
+    }
+}
+
+/// Length of a code span at the start of the text, or only the backticks if the span isn't closed
+fn code_span_len(text: &str) -> usize {
+    let ticks = text.len() - text.trim_start_matches('`').len();
+    match text[ticks..].find(&text[..ticks]) {
+        Some(end) => ticks + end + ticks,
+        None      => ticks,
+    }
+}
+
+/// Find the closing bracket of a link label, skipping any code spans in it
+fn link_label_end(text: &str) -> Option<usize> {
+    let mut idx = 1;
+    while idx < text.len() {
+        let rest = &text[idx..];
+        if rest.starts_with('`') {
+            idx += code_span_len(rest);
+            continue;
+        }
+        let ch = rest.chars().next()?;
+        match ch {
+            ']' => return Some(idx),
+            '[' => return None,
+            _   => idx += ch.len_utf8(),
+        }
+    }
+    None
+}
+
+/// Whether the text of a link could be a path to an item, instead of a url
+fn is_item_path(text: &str) -> bool {
+    text.starts_with(|ch: char| ch.is_alphabetic() || ch == '_') &&
+    !text.contains("://") &&
+    text.chars().all(|ch| ch.is_alphanumeric() || "_.:()#-".contains(ch))
+}
+
+/// Rewrite all item links in markdown text to links using `ITEM_LINK_SCHEME`, unresolved links are reported in `warnings` and are replaced by their text
+pub fn rewrite_item_links(text: &str, mut resolve: impl FnMut(&str) -> LinkTarget, warnings: &mut Vec<String>) -> String {
+    replace_links(text, &mut |label, url| {
+        match url {
+            Some(url) => {
+                if !is_item_path(url) {
+                    return None;
+                }
+                match resolve(url) {
+                    LinkTarget::Item(target) => Some(format!("[{label}]({target})")),
+                    LinkTarget::Builtin      => Some(label.to_string()),
+                    LinkTarget::Unresolved   => {
+                        warnings.push(format!("Unresolved item link `{url}`"));
+                        Some(label.to_string())
+                    },
+                }
+            },
+            None => {
+                // Only code links are expected to be item links, other brackets are only linked when they can be resolved
+                let path = label.strip_prefix('`').and_then(|label| label.strip_suffix('`'));
+                let is_code = path.is_some();
+                let path = path.unwrap_or(label);
+                if !is_item_path(path) {
+                    return None;
+                }
+                match resolve(path) {
+                    LinkTarget::Item(target)          => Some(format!("[{label}]({target})")),
+                    LinkTarget::Unresolved if is_code => {
+                        warnings.push(format!("Unresolved item link `{path}`"));
+                        Some(label.to_string())
+                    },
+                    LinkTarget::Builtin if is_code    => Some(label.to_string()),
+                    _                                 => None,
+                }
+            },
+        }
+    })
+}
+
+/// Split a link using `ITEM_LINK_SCHEME` into the index of the item and the optional fragment
+pub fn parse_item_link(url: &str) -> Option<(usize, Option<&str>)> {
+    let link = url.strip_prefix(ITEM_LINK_SCHEME)?;
+    match link.split_once('#') {
+        Some((idx, fragment)) => Some((idx.parse().ok()?, Some(fragment))),
+        None                  => Some((link.parse().ok()?, None)),
+    }
+}
+
+// =============================================================================================================================
+
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '<'  => out.push_str("&lt;"),
+            '>'  => out.push_str("&gt;"),
+            '&'  => out.push_str("&amp;"),
+            '"'  => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _    => out.push(ch),
+        }
+    }
+    out
+}
+
+/// Convert markdown to HTML.
+///
+/// Only the commonly used subset of markdown is supported: paragraphs, headings, code blocks, lists, block quotes, code spans, emphasis, and links.
+/// Links are passed to `link`, which returns the url to use, or `None` if only the text of the link should be shown.
+pub fn markdown_to_html(text: &str, link: &dyn Fn(&str) -> Option<String>) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut out = String::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+
+    let flush = |paragraph: &mut Vec<&str>, out: &mut String| {
+        if !paragraph.is_empty() {
+            out.push_str(&format!("<p>{}</p>\n", inline_to_html(&paragraph.join("\n"), link)));
+            paragraph.clear();
+        }
+    };
+
+    let mut idx = 0;
+    while idx < lines.len() {
+        let line = lines[idx];
+        let trimmed = line.trim_start();
+        idx += 1;
+
+        if let Some(lang) = trimmed.strip_prefix("```") {
+            flush(&mut paragraph, &mut out);
+            let indent = line.len() - trimmed.len();
+            let mut code = String::new();
+            while idx < lines.len() && !lines[idx].trim_start().starts_with("```") {
+                let code_line = lines[idx];
+                let strip = indent.min(code_line.len() - code_line.trim_start().len());
+                code.push_str(&code_line[strip..]);
+                code.push('\n');
+                idx += 1;
+            }
+            idx += 1;
+
+            let lang = lang.trim();
+            let lang = if lang.is_empty() { "xenon" } else { lang };
+            out.push_str(&format!("<pre><code class=\"language-{}\">{}</code></pre>\n", escape_html(lang), escape_html(&code)));
+            continue;
+        }
+
+        if trimmed.is_empty() {
+            flush(&mut paragraph, &mut out);
+            continue;
+        }
+
+        let level = trimmed.len() - trimmed.trim_start_matches('#').len();
+        if (1..=6).contains(&level) && trimmed[level..].starts_with(' ') {
+            flush(&mut paragraph, &mut out);
+            // Headings in documentation are nested in the headings of the page
+            let html_level = (level + 3).min(6);
+            out.push_str(&format!("<h{html_level}>{}</h{html_level}>\n", inline_to_html(trimmed[level..].trim(), link)));
+            continue;
+        }
+
+        if let Some(quote_line) = trimmed.strip_prefix('>') {
+            flush(&mut paragraph, &mut out);
+            let mut quote = vec![quote_line.strip_prefix(' ').unwrap_or(quote_line)];
+            while idx < lines.len() && lines[idx].trim_start().starts_with('>') {
+                let quote_line = &lines[idx].trim_start()[1..];
+                quote.push(quote_line.strip_prefix(' ').unwrap_or(quote_line));
+                idx += 1;
+            }
+            out.push_str(&format!("<blockquote>\n{}</blockquote>\n", markdown_to_html(&quote.join("\n"), link)));
+            continue;
+        }
+
+        if let Some((ordered, item)) = list_item(trimmed) {
+            flush(&mut paragraph, &mut out);
+            let mut items = vec![item.to_string()];
+            while idx < lines.len() {
+                let next = lines[idx];
+                match list_item(next.trim_start()) {
+                    Some((next_ordered, item)) if next_ordered == ordered => items.push(item.to_string()),
+                    Some(_) => break,
+                    None if next.starts_with(char::is_whitespace) && !next.trim().is_empty() => {
+                        if let Some(last) = items.last_mut() {
+                            last.push('\n');
+                            last.push_str(next.trim());
+                        }
+                    },
+                    None => break,
+                }
+                idx += 1;
+            }
+
+            let tag = if ordered { "ol" } else { "ul" };
+            out.push_str(&format!("<{tag}>\n"));
+            for item in items {
+                out.push_str(&format!("<li>{}</li>\n", inline_to_html(&item, link)));
+            }
+            out.push_str(&format!("</{tag}>\n"));
+            continue;
+        }
+
+        paragraph.push(trimmed);
+    }
+    flush(&mut paragraph, &mut out);
+    out
+}
+
+/// Get the text of a list item, and whether it is part of an ordered list
+fn list_item(line: &str) -> Option<(bool, &str)> {
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(item) = line.strip_prefix(bullet) {
+            return Some((false, item));
+        }
+    }
+    let digits = line.len() - line.trim_start_matches(|ch: char| ch.is_ascii_digit()).len();
+    if digits != 0 {
+        if let Some(item) = line[digits..].strip_prefix(". ") {
+            return Some((true, item));
+        }
+    }
+    None
+}
+
+/// Convert the inline elements of markdown to HTML
+pub fn inline_to_html(text: &str, link: &dyn Fn(&str) -> Option<String>) -> String {
+    let mut out = String::new();
+    let mut in_strong = false;
+    let mut in_em = false;
+
+    let mut idx = 0;
+    while idx < text.len() {
+        let rest = &text[idx..];
+
+        if rest.starts_with('`') {
+            let len = code_span_len(rest);
+            let ticks = rest.len() - rest.trim_start_matches('`').len();
+            if len > ticks {
+                out.push_str(&format!("<code>{}</code>", escape_html(rest[ticks..len - ticks].trim())));
+            } else {
+                out.push_str(&rest[..len]);
+            }
+            idx += len;
+            continue;
+        }
+
+        if rest.starts_with('[') {
+            if let Some(close) = link_label_end(rest) {
+                if let Some(url_end) = rest[close + 1..].strip_prefix('(').and_then(|url| url.find(')')) {
+                    let label = &rest[1..close];
+                    let url = &rest[close + 2..close + 2 + url_end];
+                    match link(url) {
+                        Some(url) => out.push_str(&format!("<a href=\"{}\">{}</a>", escape_html(&url), inline_to_html(label, link))),
+                        None      => out.push_str(&inline_to_html(label, link)),
+                    }
+                    idx += close + 2 + url_end + 1;
+                    continue;
+                }
+            }
+        }
+
+        if let Some(escaped) = rest.strip_prefix('\\').and_then(|rest| rest.chars().next()).filter(char::is_ascii_punctuation) {
+            out.push_str(&escape_html(&escaped.to_string()));
+            idx += 2;
+            continue;
+        }
+
+        if rest.starts_with("**") {
+            out.push_str(if in_strong { "</strong>" } else { "<strong>" });
+            in_strong = !in_strong;
+            idx += 2;
+            continue;
+        }
+        if rest.starts_with('*') {
+            out.push_str(if in_em { "</em>" } else { "<em>" });
+            in_em = !in_em;
+            idx += 1;
+            continue;
+        }
+
+        let ch = rest.chars().next().unwrap_or_default();
+        out.push_str(&escape_html(&ch.to_string()));
+        idx += ch.len_utf8();
+    }
+
+    if in_em {
+        out.push_str("</em>");
+    }
+    if in_strong {
+        out.push_str("</strong>");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_item_link, rewrite_item_links, LinkTarget};
+
+    fn resolve(path: &str) -> LinkTarget {
+        match path {
+            "Point"        => LinkTarget::Item("xn-item:1".to_string()),
+            "Point.new(x)" => LinkTarget::Item("xn-item:2".to_string()),
+            "i32"          => LinkTarget::Builtin,
+            _              => LinkTarget::Unresolved,
+        }
+    }
+
+    fn rewrite(text: &str) -> (String, Vec<String>) {
+        let mut warnings = Vec::new();
+        let text = rewrite_item_links(text, resolve, &mut warnings);
+        (text, warnings)
+    }
+
+    #[test]
+    fn test_rewrite_item_links() {
+        assert_eq!(rewrite("See [`Point`]."), ("See [`Point`](xn-item:1).".to_string(), vec![]));
+        assert_eq!(rewrite("See [a point](Point)."), ("See [a point](xn-item:1).".to_string(), vec![]));
+        assert_eq!(rewrite("See [`Point.new(x)`]."), ("See [`Point.new(x)`](xn-item:2).".to_string(), vec![]));
+
+        // Builtin types don't have a page to link to
+        assert_eq!(rewrite("See [`i32`]."), ("See `i32`.".to_string(), vec![]));
+    }
+
+    #[test]
+    fn test_rewrite_unresolved_links() {
+        assert_eq!(rewrite("See [`Missing`]."), ("See `Missing`.".to_string(), vec!["Unresolved item link `Missing`".to_string()]));
+        assert_eq!(rewrite("See [text](Missing)."), ("See text.".to_string(), vec!["Unresolved item link `Missing`".to_string()]));
+
+        // Plain brackets are only links when they resolve, and urls are never item links
+        assert_eq!(rewrite("An [array] index"), ("An [array] index".to_string(), vec![]));
+        assert_eq!(rewrite("[site](https://example.com)"), ("[site](https://example.com)".to_string(), vec![]));
+    }
+
+    #[test]
+    fn test_rewrite_skips_code() {
+        assert_eq!(rewrite("`[Point]` and [`Point`]"), ("`[Point]` and [`Point`](xn-item:1)".to_string(), vec![]));
+        let block = "```\n[`Point`]\n```";
+        assert_eq!(rewrite(block), (block.to_string(), vec![]));
+    }
+
+    #[test]
+    fn test_parse_item_link() {
+        assert_eq!(parse_item_link("xn-item:3"), Some((3, None)));
+        assert_eq!(parse_item_link("xn-item:3#field.x"), Some((3, Some("field.x"))));
+        assert_eq!(parse_item_link("https://example.com"), None);
+    }
+}
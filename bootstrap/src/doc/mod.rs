@@ -0,0 +1,1217 @@
+// Documentation generation
+//
+// Doc comments and `doc` attributes are collected for all items of a library that went through the front-end,
+// attached to their symbols in the symbol table, and rendered as a static HTML or Markdown site.
+use std::{
+    collections::HashMap,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    ast::{self, AstNodeRef, AttribMeta, LiteralValue, UsePath, Visitor as _},
+    common::{Diagnostic, LibraryPath, NameId, NameTable, PathIden, Scope, Severity, SpanId, SymbolRef},
+    frontend::{FrontEnd, FrontEndFile},
+    hir::{self, Visitor as _},
+    lexer::{MetaElem, OpenCloseSymbol, Punctuation, StrongKeyword, Token},
+    literals::{Literal, LiteralTable},
+};
+
+mod comment;
+pub use comment::*;
+
+mod markdown;
+pub use markdown::*;
+
+mod render;
+pub use render::*;
+
+/// Scheme used for resolved item links in documentation text, followed by the index of the item and an optional fragment
+pub const ITEM_LINK_SCHEME: &str = "xn-item:";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DocItemKind {
+    Module,
+    Trait,
+    Struct,
+    Union,
+    Enum,
+    Bitfield,
+    TypeAlias,
+    Const,
+    Static,
+    Property,
+    Function,
+    OpSet,
+    Operator,
+    Precedence,
+    Impl,
+}
+
+impl DocItemKind {
+    /// All kinds, in the order they are shown on a module's page
+    pub const ALL: [DocItemKind; 15] = [
+        Self::Module,
+        Self::Trait,
+        Self::Struct,
+        Self::Union,
+        Self::Enum,
+        Self::Bitfield,
+        Self::TypeAlias,
+        Self::Const,
+        Self::Static,
+        Self::Property,
+        Self::Function,
+        Self::OpSet,
+        Self::Operator,
+        Self::Precedence,
+        Self::Impl,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Module     => "module",
+            Self::Trait      => "trait",
+            Self::Struct     => "struct",
+            Self::Union      => "union",
+            Self::Enum       => "enum",
+            Self::Bitfield   => "bitfield",
+            Self::TypeAlias  => "type alias",
+            Self::Const      => "const",
+            Self::Static     => "static",
+            Self::Property   => "property",
+            Self::Function   => "function",
+            Self::OpSet      => "operator set",
+            Self::Operator   => "operator",
+            Self::Precedence => "precedence",
+            Self::Impl       => "impl",
+        }
+    }
+
+    pub fn section_title(self) -> &'static str {
+        match self {
+            Self::Module     => "Modules",
+            Self::Trait      => "Traits",
+            Self::Struct     => "Structs",
+            Self::Union      => "Unions",
+            Self::Enum       => "Enums",
+            Self::Bitfield   => "Bitfields",
+            Self::TypeAlias  => "Type aliases",
+            Self::Const      => "Constants",
+            Self::Static     => "Statics",
+            Self::Property   => "Properties",
+            Self::Function   => "Functions",
+            Self::OpSet      => "Operator sets",
+            Self::Operator   => "Operators",
+            Self::Precedence => "Precedences",
+            Self::Impl       => "Implementations",
+        }
+    }
+
+    /// Prefix of the anchor of an item on its module's page
+    pub fn anchor_prefix(self) -> &'static str {
+        match self {
+            Self::Module     => "mod",
+            Self::Trait      => "trait",
+            Self::Struct     => "struct",
+            Self::Union      => "union",
+            Self::Enum       => "enum",
+            Self::Bitfield   => "bitfield",
+            Self::TypeAlias  => "type",
+            Self::Const      => "const",
+            Self::Static     => "static",
+            Self::Property   => "prop",
+            Self::Function   => "fn",
+            Self::OpSet      => "opset",
+            Self::Operator   => "op",
+            Self::Precedence => "precedence",
+            Self::Impl       => "impl",
+        }
+    }
+
+    /// Whether the whole declaration is shown as the signature, instead of only the part in front of the body
+    fn shows_body(self) -> bool {
+        matches!(self, Self::Struct | Self::Union | Self::Enum | Self::Bitfield | Self::TypeAlias | Self::Const | Self::Static)
+    }
+}
+
+/// Documented item, attached to the symbol it defines
+pub struct DocItem {
+    pub kind:       DocItemKind,
+    pub name:       String,
+    /// Parameter labels, for functions
+    pub params:     Vec<String>,
+    /// Scope the item is defined in
+    pub scope:      Scope,
+    pub sym:        Option<SymbolRef>,
+    pub signature:  String,
+    pub doc:        DocComment,
+    /// Index of the source file in `Documentation::sources`
+    pub file:       Option<usize>,
+    pub line:       u32,
+    pub span:       SpanId,
+    /// Module the item is located in, the library root module is its own module
+    pub module:     usize,
+    /// Trait, impl, or op set the item is a member of
+    pub parent:     Option<usize>,
+    pub members:    Vec<usize>,
+    /// Whether the item gets documented in its own module, items in private modules can still be shown through re-exports
+    pub documented: bool,
+}
+
+impl DocItem {
+    /// Path of the item within the library, including its own name
+    pub fn full_path(&self) -> Scope {
+        let mut path = self.scope.clone();
+        if self.kind != DocItemKind::Module || !self.name.is_empty() {
+            path.push_iden(PathIden::new(self.name.clone(), self.params.clone(), Vec::new()));
+        }
+        path
+    }
+
+    /// Name as shown in the documentation, including the parameter labels of functions
+    pub fn display_name(&self) -> String {
+        if self.kind == DocItemKind::Impl {
+            return self.signature.clone();
+        }
+        if self.params.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{}({})", self.name, self.params.iter().map(|param| format!("{param}:")).collect::<String>())
+        }
+    }
+
+    /// Anchor of the item on the page it is shown on
+    pub fn anchor(&self) -> String {
+        let mut anchor = format!("{}.{}", self.kind.anchor_prefix(), self.name);
+        for param in &self.params {
+            anchor.push('.');
+            anchor.push_str(param);
+        }
+        anchor
+    }
+}
+
+/// `pub use` in a documented module
+pub struct DocReexport {
+    pub module: usize,
+    /// Text of the path as it appears in the documentation, e.g. `shapes.Circle as Round`
+    pub text:   String,
+    pub target: Option<usize>,
+    /// Whether the documentation of the target is shown in the module, instead of a link to it
+    pub inline: bool,
+}
+
+/// Source file that can be shown in the documentation
+pub struct DocSource {
+    /// Path relative to the directory of the library root
+    pub rel_path: String,
+    pub text:     String,
+}
+
+pub struct DocConfig {
+    /// Document items that are not `pub`
+    pub document_private: bool,
+    /// Document items marked with `@doc(hidden)`
+    pub document_hidden:  bool,
+}
+
+/// Documentation of a single library
+pub struct Documentation {
+    pub lib_path:    LibraryPath,
+    /// Directory containing the library root, paths of logos and favicons are relative to this
+    pub root_dir:    PathBuf,
+    /// All items, the first is the root module of the library
+    pub items:       Vec<DocItem>,
+    pub reexports:   Vec<DocReexport>,
+    pub sources:     Vec<DocSource>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Documentation {
+    pub fn collect(front_end: &mut FrontEnd, config: &DocConfig) -> Self {
+        let mut hir_collector = HirDocCollector { items: Vec::new() };
+        hir_collector.visit(&mut front_end.hir, hir::VisitFlags::all());
+
+        let mut collector = DocCollector::new(front_end, config);
+        collector.collect_ast();
+        collector.collect_modules();
+        collector.collect_items(hir_collector.items);
+        collector.collect_reexports();
+        collector.resolve_links();
+
+        let DocCollector { root_dir, items, reexports, diagnostics, .. } = collector;
+        let sources = front_end.files.iter().map(|file| DocSource {
+            rel_path: file.path.strip_prefix(&root_dir).unwrap_or(&file.path).to_string_lossy().replace('\\', "/"),
+            text: file.source.to_string(),
+        }).collect();
+
+        Self {
+            lib_path: front_end.lib_path.clone(),
+            root_dir,
+            items,
+            reexports,
+            sources,
+            diagnostics,
+        }
+    }
+
+    pub fn root(&self) -> &DocItem {
+        &self.items[0]
+    }
+
+    /// Items that are shown on the page of a module, i.e. its own items and the items it inlines through re-exports
+    pub fn module_items(&self, module: usize) -> Vec<usize> {
+        let mut items: Vec<usize> = (1..self.items.len())
+            .filter(|&idx| {
+                let item = &self.items[idx];
+                item.module == module && item.documented && item.parent.is_none()
+            })
+            .collect();
+        let inlined: Vec<usize> = self.reexports.iter()
+            .filter(|reexport| reexport.module == module && reexport.inline)
+            .filter_map(|reexport| reexport.target)
+            .filter(|&target| self.items[target].kind != DocItemKind::Module && !items.contains(&target))
+            .collect();
+        items.extend(inlined);
+        items
+    }
+
+    /// Get the module whose page shows an item, `None` if the item isn't shown anywhere
+    pub fn page_of(&self, item: usize) -> Option<usize> {
+        let doc_item = &self.items[item];
+        if let Some(parent) = doc_item.parent {
+            return self.page_of(parent);
+        }
+        if doc_item.kind == DocItemKind::Module {
+            return doc_item.documented.then_some(item);
+        }
+        if doc_item.documented {
+            return Some(doc_item.module);
+        }
+        self.reexports.iter()
+            .find(|reexport| reexport.inline && reexport.target == Some(item) && self.items[reexport.module].documented)
+            .map(|reexport| reexport.module)
+    }
+}
+
+// =============================================================================================================================
+
+/// Converted `doc` attribute
+struct DocAttr {
+    is_mod: bool,
+    metas:  Vec<DocAttrMeta>,
+}
+
+struct AstModule {
+    file:        usize,
+    node_id:     ast::NodeId,
+    attr_ids:    Vec<usize>,
+    scope:       Scope,
+    is_pub:      bool,
+    /// Index of the opening brace of an inline module
+    block_start: Option<u32>,
+}
+
+struct AstUse {
+    file:     usize,
+    attr_ids: Vec<usize>,
+    scope:    Scope,
+    external: bool,
+    /// Path segments, alias, and whether the path is a wildcard
+    paths:    Vec<(Vec<String>, Option<String>, bool)>,
+}
+
+/// Collect modules, `pub use`s, and `doc` attributes from the AST of a file
+struct AstDocCollector<'a> {
+    file:      usize,
+    ast:       &'a ast::Ast,
+    names:     &'a NameTable,
+    lits:      &'a LiteralTable,
+    scope:     Scope,
+    modules:   Vec<AstModule>,
+    uses:      Vec<AstUse>,
+    doc_attrs: HashMap<(usize, usize), DocAttr>,
+    /// Module attributes, i.e. `@!doc(..)`, together with the scope of the module they apply to
+    mod_attrs: Vec<(Scope, usize)>,
+}
+
+impl AstDocCollector<'_> {
+    fn convert_meta(&self, meta: &AttribMeta) -> Option<DocAttrMeta> {
+        let path_str = |path: &AstNodeRef<ast::SimplePath>| {
+            path.names.iter().map(|(name, _)| self.names[*name].to_string()).collect::<Vec<_>>().join(".")
+        };
+        match meta {
+            AttribMeta::Simple { path }          => Some(DocAttrMeta::Flag(path_str(path))),
+            AttribMeta::Expr { expr }            => self.expr_text(expr).map(DocAttrMeta::Text),
+            AttribMeta::Assign { path, expr, .. } => self.expr_text(expr).map(|text| DocAttrMeta::Assign(path_str(path), text)),
+            AttribMeta::Meta { path, metas, .. } => Some(DocAttrMeta::Nested(path_str(path), metas.iter().filter_map(|meta| self.convert_meta(meta)).collect())),
+        }
+    }
+
+    fn expr_text(&self, expr: &ast::Expr) -> Option<String> {
+        match expr {
+            ast::Expr::Literal(lit) => match lit.literal {
+                LiteralValue::Lit(lit_id) => match &self.lits[lit_id] {
+                    Literal::String(text) => Some(text.clone()),
+                    lit                   => Some(lit.to_string()),
+                },
+                LiteralValue::Bool(value) => Some(value.to_string()),
+            },
+            _ => None,
+        }
+    }
+
+    fn collect_use_paths(&self, path: &UsePath, prefix: &[String], out: &mut Vec<(Vec<String>, Option<String>, bool)>) {
+        let with_segments = |segments: &[NameId]| {
+            let mut full = prefix.to_vec();
+            full.extend(segments.iter().map(|name| self.names[*name].to_string()));
+            full
+        };
+        match path {
+            UsePath::SelfPath { alias, .. }            => out.push((prefix.to_vec(), alias.map(|name| self.names[name].to_string()), false)),
+            UsePath::SubPaths { segments, sub_paths, .. } => {
+                let full = with_segments(segments);
+                for sub_path in sub_paths {
+                    self.collect_use_paths(sub_path, &full, out);
+                }
+            },
+            UsePath::Alias { segments, alias, .. }     => out.push((with_segments(segments), alias.map(|name| self.names[name].to_string()), false)),
+            UsePath::Wildcard { segments, .. }         => out.push((with_segments(segments), None, true)),
+        }
+    }
+}
+
+impl ast::Visitor for AstDocCollector<'_> {
+    fn visit_module(&mut self, node: &AstNodeRef<ast::ModuleItem>) where Self: Sized {
+        // Module attributes in front of the module apply to the surrounding module, so they are visited first
+        for attr in &node.attrs {
+            self.visit_attribute(attr);
+        }
+
+        let mut scope = self.scope.clone();
+        scope.push(self.names[node.name].to_string());
+        self.modules.push(AstModule {
+            file: self.file,
+            node_id: node.node_id,
+            attr_ids: node.attrs.iter().filter(|attr| !attr.is_mod).map(|attr| attr.node_id.index()).collect(),
+            scope: scope.clone(),
+            is_pub: is_pub_vis(&node.vis),
+            block_start: node.block.as_ref().and_then(|block| self.ast.meta.get(block.node_id.index())).map(|meta| meta.first_tok),
+        });
+
+        if let Some(block) = &node.block {
+            let outer = std::mem::replace(&mut self.scope, scope);
+            self.visit_block(block);
+            self.scope = outer;
+        }
+    }
+
+    fn visit_use(&mut self, node: &AstNodeRef<ast::UseItem>) where Self: Sized {
+        for attr in &node.attrs {
+            self.visit_attribute(attr);
+        }
+        if !is_pub_vis(&node.vis) {
+            return;
+        }
+
+        let mut paths = Vec::new();
+        self.collect_use_paths(&node.path, &[], &mut paths);
+        self.uses.push(AstUse {
+            file: self.file,
+            attr_ids: node.attrs.iter().map(|attr| attr.node_id.index()).collect(),
+            scope: self.scope.clone(),
+            external: node.group.is_some() || node.package.is_some() || node.library.is_some(),
+            paths,
+        });
+    }
+
+    fn visit_attribute(&mut self, node: &AstNodeRef<ast::Attribute>) where Self: Sized {
+        let is_doc = node.path.names.len() == 1 && &self.names[node.path.names[0].0] == "doc";
+        if !is_doc {
+            return;
+        }
+
+        let metas = node.metas.iter().filter_map(|meta| self.convert_meta(meta)).collect();
+        if node.is_mod {
+            self.mod_attrs.push((self.scope.clone(), node.node_id.index()));
+        }
+        self.doc_attrs.insert((self.file, node.node_id.index()), DocAttr { is_mod: node.is_mod, metas });
+    }
+}
+
+fn is_pub_vis(vis: &Option<AstNodeRef<ast::Visibility>>) -> bool {
+    vis.as_ref().is_some_and(|vis| matches!(**vis, ast::Visibility::Pub { .. }))
+}
+
+// =============================================================================================================================
+
+struct HirItem {
+    kind:     DocItemKind,
+    span:     SpanId,
+    node_id:  ast::NodeId,
+    attr_ids: Vec<usize>,
+    /// `None` for items that have the visibility of their parent, e.g. trait members
+    is_pub:   Option<bool>,
+    sym:      Option<SymbolRef>,
+    parent:   Option<Option<SymbolRef>>,
+}
+
+/// Collect all items in the HIR that define a symbol
+struct HirDocCollector {
+    items: Vec<HirItem>,
+}
+
+impl HirDocCollector {
+    fn add(&mut self, kind: DocItemKind, span: SpanId, node_id: ast::NodeId, attrs: &[Box<hir::Attribute>], is_pub: Option<bool>, sym: &Option<SymbolRef>) -> &mut HirItem {
+        self.items.push(HirItem {
+            kind,
+            span,
+            node_id,
+            attr_ids: attrs.iter().map(|attr| attr.node_id.index()).collect(),
+            is_pub,
+            sym: sym.clone(),
+            parent: None,
+        });
+        self.items.last_mut().unwrap()
+    }
+}
+
+fn is_pub(vis: &hir::Visibility) -> Option<bool> {
+    Some(matches!(vis, hir::Visibility::Pub { .. }))
+}
+
+/// Members of trait impls have the visibility of the trait
+fn impl_member_is_pub(impl_ref: &hir::Ref<hir::Impl>, vis: &hir::Visibility) -> Option<bool> {
+    if impl_ref.read().impl_trait.is_some() {
+        None
+    } else {
+        is_pub(vis)
+    }
+}
+
+impl hir::Visitor for HirDocCollector {
+    fn visit_function(&mut self, node: &mut hir::Function, ctx: &mut hir::FunctionContext) {
+        self.add(DocItemKind::Function, node.span, node.node_id, &node.attrs, is_pub(&node.vis), &ctx.sym);
+    }
+
+    fn visit_extern_function_no_body(&mut self, node: &mut hir::ExternFunctionNoBody, ctx: &mut hir::FunctionContext) {
+        self.add(DocItemKind::Function, node.span, node.node_id, &node.attrs, is_pub(&node.vis), &ctx.sym);
+    }
+
+    fn visit_type_alias(&mut self, node: &mut hir::TypeAlias, ctx: &mut hir::TypeAliasContext) {
+        self.add(DocItemKind::TypeAlias, node.span, node.node_id, &node.attrs, is_pub(&node.vis), &ctx.sym);
+    }
+
+    fn visit_distinct_type(&mut self, node: &mut hir::DistinctType, ctx: &mut hir::TypeAliasContext) {
+        self.add(DocItemKind::TypeAlias, node.span, node.node_id, &node.attrs, is_pub(&node.vis), &ctx.sym);
+    }
+
+    fn visit_opaque_type(&mut self, node: &mut hir::OpaqueType, ctx: &mut hir::TypeAliasContext) {
+        self.add(DocItemKind::TypeAlias, node.span, node.node_id, &node.attrs, is_pub(&node.vis), &ctx.sym);
+    }
+
+    fn visit_struct(&mut self, node: &mut hir::Struct, ctx: &mut hir::StructContext) {
+        self.add(DocItemKind::Struct, node.span, node.node_id, &node.attrs, is_pub(&node.vis), &ctx.sym);
+    }
+
+    fn visit_tuple_struct(&mut self, node: &mut hir::TupleStruct, ctx: &mut hir::StructContext) {
+        self.add(DocItemKind::Struct, node.span, node.node_id, &node.attrs, is_pub(&node.vis), &ctx.sym);
+    }
+
+    fn visit_unit_struct(&mut self, node: &mut hir::UnitStruct, ctx: &mut hir::StructContext) {
+        self.add(DocItemKind::Struct, node.span, node.node_id, &node.attrs, is_pub(&node.vis), &ctx.sym);
+    }
+
+    fn visit_union(&mut self, node: &mut hir::Union, ctx: &mut hir::UnionContext) {
+        self.add(DocItemKind::Union, node.span, node.node_id, &node.attrs, is_pub(&node.vis), &ctx.sym);
+    }
+
+    fn visit_adt_enum(&mut self, node: &mut hir::AdtEnum, ctx: &mut hir::AdtEnumContext) {
+        self.add(DocItemKind::Enum, node.span, node.node_id, &node.attrs, is_pub(&node.vis), &ctx.sym);
+    }
+
+    fn visit_flag_enum(&mut self, node: &mut hir::FlagEnum, ctx: &mut hir::FlagEnumContext) {
+        self.add(DocItemKind::Enum, node.span, node.node_id, &node.attrs, is_pub(&node.vis), &ctx.sym);
+    }
+
+    fn visit_bitfield(&mut self, node: &mut hir::Bitfield, ctx: &mut hir::BitfieldContext) {
+        self.add(DocItemKind::Bitfield, node.span, node.node_id, &node.attrs, is_pub(&node.vis), &ctx.sym);
+    }
+
+    fn visit_const(&mut self, node: &mut hir::Const, ctx: &mut hir::ConstContext) {
+        self.add(DocItemKind::Const, node.span, node.node_id, &node.attrs, is_pub(&node.vis), &ctx.sym);
+    }
+
+    fn visit_static(&mut self, node: &mut hir::Static, ctx: &mut hir::StaticContext) {
+        self.add(DocItemKind::Static, node.span, node.node_id, &node.attrs, is_pub(&node.vis), &ctx.sym);
+    }
+
+    fn visit_tls_static(&mut self, node: &mut hir::TlsStatic, ctx: &mut hir::StaticContext) {
+        self.add(DocItemKind::Static, node.span, node.node_id, &node.attrs, is_pub(&node.vis), &ctx.sym);
+    }
+
+    fn visit_extern_static(&mut self, node: &mut hir::ExternStatic, ctx: &mut hir::StaticContext) {
+        self.add(DocItemKind::Static, node.span, node.node_id, &node.attrs, is_pub(&node.vis), &ctx.sym);
+    }
+
+    fn visit_trait(&mut self, node: &mut hir::Trait, ctx: &mut hir::TraitContext) {
+        self.add(DocItemKind::Trait, node.span, node.node_id, &node.attrs, is_pub(&node.vis), &ctx.sym);
+    }
+
+    fn visit_trait_function(&mut self, _trait_ref: hir::Ref<hir::Trait>, trait_ctx: hir::Ref<hir::TraitContext>, node: &mut hir::TraitFunction, ctx: &mut hir::FunctionContext) {
+        self.add(DocItemKind::Function, node.span, node.node_id, &node.attrs, None, &ctx.sym).parent = Some(trait_ctx.read().sym.clone());
+    }
+
+    fn visit_trait_method(&mut self, _trait_ref: hir::Ref<hir::Trait>, trait_ctx: hir::Ref<hir::TraitContext>, node: &mut hir::TraitMethod, ctx: &mut hir::FunctionContext) {
+        self.add(DocItemKind::Function, node.span, node.node_id, &node.attrs, None, &ctx.sym).parent = Some(trait_ctx.read().sym.clone());
+    }
+
+    fn visit_trait_type_alias(&mut self, _trait_ref: hir::Ref<hir::Trait>, trait_ctx: hir::Ref<hir::TraitContext>, node: &mut hir::TraitTypeAlias, ctx: &mut hir::TypeAliasContext) {
+        self.add(DocItemKind::TypeAlias, node.span, node.node_id, &node.attrs, None, &ctx.sym).parent = Some(trait_ctx.read().sym.clone());
+    }
+
+    fn visit_trait_const(&mut self, _trait_ref: hir::Ref<hir::Trait>, trait_ctx: hir::Ref<hir::TraitContext>, node: &mut hir::TraitConst, ctx: &mut hir::ConstContext) {
+        self.add(DocItemKind::Const, node.span, node.node_id, &node.attrs, None, &ctx.sym).parent = Some(trait_ctx.read().sym.clone());
+    }
+
+    fn visit_trait_property(&mut self, _trait_ref: hir::Ref<hir::Trait>, trait_ctx: hir::Ref<hir::TraitContext>, node: &mut hir::TraitProperty, ctx: &mut hir::PropertyContext) {
+        self.add(DocItemKind::Property, node.span, node.node_id, &node.attrs, None, &ctx.sym).parent = Some(trait_ctx.read().sym.clone());
+    }
+
+    fn visit_impl(&mut self, node: &mut hir::Impl, ctx: &mut hir::ImplContext) {
+        self.add(DocItemKind::Impl, node.span, node.node_id, &node.attrs, None, &ctx.sym);
+    }
+
+    fn visit_impl_function(&mut self, impl_ref: hir::Ref<hir::Impl>, impl_ctx: hir::Ref<hir::ImplContext>, node: &mut hir::Function, ctx: &mut hir::FunctionContext) {
+        self.add(DocItemKind::Function, node.span, node.node_id, &node.attrs, impl_member_is_pub(&impl_ref, &node.vis), &ctx.sym).parent = Some(impl_ctx.read().sym.clone());
+    }
+
+    fn visit_method(&mut self, impl_ref: hir::Ref<hir::Impl>, impl_ctx: hir::Ref<hir::ImplContext>, node: &mut hir::Method, ctx: &mut hir::FunctionContext) {
+        self.add(DocItemKind::Function, node.span, node.node_id, &node.attrs, impl_member_is_pub(&impl_ref, &node.vis), &ctx.sym).parent = Some(impl_ctx.read().sym.clone());
+    }
+
+    fn visit_impl_type_alias(&mut self, impl_ref: hir::Ref<hir::Impl>, impl_ctx: hir::Ref<hir::ImplContext>, node: &mut hir::TypeAlias, ctx: &mut hir::TypeAliasContext) {
+        self.add(DocItemKind::TypeAlias, node.span, node.node_id, &node.attrs, impl_member_is_pub(&impl_ref, &node.vis), &ctx.sym).parent = Some(impl_ctx.read().sym.clone());
+    }
+
+    fn visit_impl_const(&mut self, impl_ref: hir::Ref<hir::Impl>, impl_ctx: hir::Ref<hir::ImplContext>, node: &mut hir::Const, ctx: &mut hir::ConstContext) {
+        self.add(DocItemKind::Const, node.span, node.node_id, &node.attrs, impl_member_is_pub(&impl_ref, &node.vis), &ctx.sym).parent = Some(impl_ctx.read().sym.clone());
+    }
+
+    fn visit_impl_static(&mut self, impl_ref: hir::Ref<hir::Impl>, impl_ctx: hir::Ref<hir::ImplContext>, node: &mut hir::Static, ctx: &mut hir::StaticContext) {
+        self.add(DocItemKind::Static, node.span, node.node_id, &node.attrs, impl_member_is_pub(&impl_ref, &node.vis), &ctx.sym).parent = Some(impl_ctx.read().sym.clone());
+    }
+
+    fn visit_impl_tls_static(&mut self, impl_ref: hir::Ref<hir::Impl>, impl_ctx: hir::Ref<hir::ImplContext>, node: &mut hir::TlsStatic, ctx: &mut hir::StaticContext) {
+        self.add(DocItemKind::Static, node.span, node.node_id, &node.attrs, impl_member_is_pub(&impl_ref, &node.vis), &ctx.sym).parent = Some(impl_ctx.read().sym.clone());
+    }
+
+    fn visit_property(&mut self, impl_ref: hir::Ref<hir::Impl>, impl_ctx: hir::Ref<hir::ImplContext>, node: &mut hir::Property, ctx: &mut hir::PropertyContext) {
+        self.add(DocItemKind::Property, node.span, node.node_id, &node.attrs, impl_member_is_pub(&impl_ref, &node.vis), &ctx.sym).parent = Some(impl_ctx.read().sym.clone());
+    }
+
+    fn visit_op_set(&mut self, node: &mut hir::OpSet, ctx: &mut hir::OpSetContext) {
+        self.add(DocItemKind::OpSet, node.span, node.node_id, &node.attrs, is_pub(&node.vis), &ctx.sym);
+    }
+
+    fn visit_operator(&mut self, _op_set_ref: hir::Ref<hir::OpSet>, op_set_ctx: hir::Ref<hir::OpSetContext>, node: &mut hir::Operator, ctx: &mut hir::OperatorContext) {
+        self.add(DocItemKind::Operator, node.span, node.node_id, &[], None, &ctx.sym).parent = Some(op_set_ctx.read().sym.clone());
+    }
+
+    fn visit_precedence(&mut self, node: &mut hir::Precedence, ctx: hir::Ref<hir::PrecedenceContext>) {
+        self.add(DocItemKind::Precedence, node.span, node.node_id, &node.attrs, is_pub(&node.vis), &ctx.read().sym);
+    }
+}
+
+// =============================================================================================================================
+
+struct DocCollector<'a> {
+    front_end:       &'a FrontEnd,
+    config:          &'a DocConfig,
+    root_dir:        PathBuf,
+
+    doc_attrs:       HashMap<(usize, usize), DocAttr>,
+    /// Module attributes, with the scope of the module they apply to, and the file and node they are located at
+    mod_attrs:       Vec<(Scope, (usize, usize))>,
+    ast_modules:     Vec<AstModule>,
+    ast_uses:        Vec<AstUse>,
+
+    items:           Vec<DocItem>,
+    module_by_scope: HashMap<Scope, usize>,
+    reexports:       Vec<DocReexport>,
+    diagnostics:     Vec<Diagnostic>,
+}
+
+impl<'a> DocCollector<'a> {
+    fn new(front_end: &'a FrontEnd, config: &'a DocConfig) -> Self {
+        let root_dir = front_end.files.first()
+            .and_then(|file| file.path.parent())
+            .map_or_else(PathBuf::new, Path::to_path_buf);
+
+        Self {
+            front_end,
+            config,
+            root_dir,
+            doc_attrs: HashMap::new(),
+            mod_attrs: Vec::new(),
+            ast_modules: Vec::new(),
+            ast_uses: Vec::new(),
+            items: Vec::new(),
+            module_by_scope: HashMap::new(),
+            reexports: Vec::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn collect_ast(&mut self) {
+        let names = self.front_end.ctx.names.read();
+        let lits = self.front_end.ctx.lits.read();
+        for (idx, file) in self.front_end.files.iter().enumerate() {
+            let mut collector = AstDocCollector {
+                file: idx,
+                ast: &file.ast,
+                names: &names,
+                lits: &lits,
+                scope: file.scope.clone(),
+                modules: Vec::new(),
+                uses: Vec::new(),
+                doc_attrs: HashMap::new(),
+                mod_attrs: Vec::new(),
+            };
+            collector.visit(&file.ast);
+
+            self.ast_modules.extend(collector.modules);
+            self.ast_uses.extend(collector.uses);
+            self.doc_attrs.extend(collector.doc_attrs);
+            self.mod_attrs.extend(collector.mod_attrs.into_iter().map(|(scope, node_id)| (scope, (idx, node_id))));
+        }
+    }
+
+    fn collect_modules(&mut self) {
+        let front_end = self.front_end;
+        let files = &front_end.files;
+
+        let mut warnings = Vec::new();
+        let lines = match files.first() {
+            Some(file) => Self::top_level_lines(file, None, &mut warnings),
+            None       => Vec::new(),
+        };
+        let doc = self.build_doc(0, &lines, &self.module_attr_keys(&Scope::new(), &[]), &mut warnings);
+        if let Some(DocPlayground::Embedded) = doc.playground {
+            warnings.push("The embedded playground is not supported yet, code blocks won't get a button to run them".to_string());
+        }
+        self.add_warnings(warnings, SpanId::INVALID);
+        self.items.push(DocItem {
+            kind: DocItemKind::Module,
+            name: String::new(),
+            params: Vec::new(),
+            scope: Scope::new(),
+            sym: None,
+            signature: String::new(),
+            doc,
+            file: (!files.is_empty()).then_some(0),
+            line: 1,
+            span: SpanId::INVALID,
+            module: 0,
+            parent: None,
+            members: Vec::new(),
+            documented: true,
+        });
+        self.module_by_scope.insert(Scope::new(), 0);
+
+        // Parents need to be added before their sub-modules
+        let mut modules = std::mem::take(&mut self.ast_modules);
+        modules.sort_by_key(|module| module.scope.len());
+        for module in &modules {
+            let parent_scope = module.scope.parent();
+            let Some(&parent) = self.module_by_scope.get(&parent_scope) else {
+                continue;
+            };
+            let file = &files[module.file];
+
+            let mut warnings = Vec::new();
+            let (mut lines, decl) = Self::leading_lines(file, module.node_id.index(), &module.attr_ids, &mut warnings);
+            let mod_file = match module.block_start {
+                Some(_) => Some(file),
+                None    => files.iter().find(|file| file.scope == module.scope),
+            };
+            if let Some(mod_file) = mod_file {
+                lines.extend(Self::top_level_lines(mod_file, module.block_start, &mut warnings));
+            }
+
+            let keys = self.module_attr_keys(&module.scope, &module.attr_ids.iter().map(|&id| (module.file, id)).collect::<Vec<_>>());
+            let doc = self.build_doc(module.file, &lines, &keys, &mut warnings);
+            Self::check_root_only(&doc, &mut warnings);
+            let span = Self::token_span(file, decl);
+            self.add_warnings(warnings, span);
+            if doc.hidden && !self.config.document_hidden {
+                continue;
+            }
+
+            let name = module.scope.last().map_or_else(String::new, |iden| iden.name.clone());
+            let sym = self.front_end.ctx.syms.read().get_symbol(None, &parent_scope, &name);
+            let idx = self.items.len();
+            self.items.push(DocItem {
+                kind: DocItemKind::Module,
+                signature: format!("mod {name}"),
+                name,
+                params: Vec::new(),
+                scope: parent_scope,
+                sym,
+                doc,
+                file: Some(module.file),
+                line: self.token_line(file, decl),
+                span,
+                module: parent,
+                parent: None,
+                members: Vec::new(),
+                documented: (module.is_pub || self.config.document_private) && self.items[parent].documented,
+            });
+            self.module_by_scope.insert(module.scope.clone(), idx);
+        }
+    }
+
+    fn collect_items(&mut self, hir_items: Vec<HirItem>) {
+        let front_end = self.front_end;
+        let files = &front_end.files;
+
+        // Sort the items in source order, this also makes sure traits and impls come before their members
+        let mut hir_items: Vec<(usize, u32, HirItem)> = hir_items.into_iter()
+            .filter_map(|item| {
+                let (path, _, _) = self.front_end.span_location(item.span)?;
+                let file = files.iter().position(|file| file.path == path)?;
+                let first_tok = files[file].ast.meta.get(item.node_id.index())?.first_tok;
+                Some((file, first_tok, item))
+            })
+            .collect();
+        hir_items.sort_by_key(|(file, first_tok, _)| (*file, *first_tok));
+
+        for (file_idx, _, item) in hir_items {
+            let Some(sym) = &item.sym else { continue };
+            let file = &files[file_idx];
+
+            let parent = match &item.parent {
+                Some(Some(parent_sym)) => match self.items.iter().position(|item| item.sym.as_ref().is_some_and(|sym| Arc::ptr_eq(sym, parent_sym))) {
+                    Some(parent) => Some(parent),
+                    None         => continue,
+                },
+                Some(None) => continue,
+                None       => None,
+            };
+            if !item.is_pub.unwrap_or(true) && !self.config.document_private {
+                continue;
+            }
+
+            let mut warnings = Vec::new();
+            let (lines, decl) = Self::leading_lines(file, item.node_id.index(), &item.attr_ids, &mut warnings);
+            let keys: Vec<_> = item.attr_ids.iter()
+                .map(|&id| (file_idx, id))
+                .filter(|key| self.doc_attrs.get(key).is_some_and(|attr| !attr.is_mod))
+                .collect();
+            let doc = self.build_doc(file_idx, &lines, &keys, &mut warnings);
+            Self::check_root_only(&doc, &mut warnings);
+            let span = Self::token_span(file, decl);
+            self.add_warnings(warnings, span);
+            if doc.hidden && !self.config.document_hidden {
+                continue;
+            }
+
+            let (name, params, scope) = {
+                let sym = sym.read();
+                let path = sym.path();
+                (path.iden().name.clone(), path.iden().params.clone(), path.scope().clone())
+            };
+
+            // Items are located in the innermost module containing them, e.g. impl members are in the module of the impl
+            let mut mod_scope = scope.clone();
+            let module = loop {
+                if let Some(&module) = self.module_by_scope.get(&mod_scope) {
+                    break Some(module);
+                }
+                if mod_scope.pop().is_none() {
+                    break None;
+                }
+            };
+            let Some(module) = module else { continue };
+
+            let last_tok = file.ast.meta[item.node_id.index()].last_tok as usize;
+            let documented = match parent {
+                Some(parent) => self.items[parent].documented,
+                None         => self.items[module].documented,
+            };
+
+            let idx = self.items.len();
+            self.items.push(DocItem {
+                kind: item.kind,
+                name,
+                params,
+                scope,
+                sym: Some(sym.clone()),
+                signature: self.signature(file, decl, last_tok, item.kind),
+                doc,
+                file: Some(file_idx),
+                line: self.token_line(file, decl),
+                span,
+                module,
+                parent,
+                members: Vec::new(),
+                documented,
+            });
+            if let Some(parent) = parent {
+                self.items[parent].members.push(idx);
+            }
+        }
+    }
+
+    fn collect_reexports(&mut self) {
+        let uses = std::mem::take(&mut self.ast_uses);
+        for use_item in uses {
+            let Some(&module) = self.module_by_scope.get(&use_item.scope) else { continue };
+            if !self.items[module].documented {
+                continue;
+            }
+
+            let mut warnings = Vec::new();
+            let mut doc = DocComment::default();
+            for &attr_id in &use_item.attr_ids {
+                if let Some(attr) = self.doc_attrs.get(&(use_item.file, attr_id)) {
+                    for meta in &attr.metas {
+                        doc.apply_attr_meta(meta, &mut warnings);
+                    }
+                }
+            }
+            self.add_warnings(warnings, SpanId::INVALID);
+
+            let mod_path = self.items[module].full_path();
+            for (segments, alias, is_wildcard) in use_item.paths {
+                let mut text = segments.join(".");
+                if is_wildcard {
+                    text.push_str(".*");
+                }
+                if let Some(alias) = alias {
+                    text.push_str(" as ");
+                    text.push_str(&alias);
+                }
+
+                let target = if use_item.external || segments.is_empty() {
+                    None
+                } else {
+                    self.find_item(&mod_path, &segments, None)
+                };
+                // Items that are only reachable through the re-export are inlined by default
+                let inline = !is_wildcard && match doc.inline {
+                    Some(inline) => inline,
+                    None         => target.is_some_and(|target| !self.items[target].documented),
+                };
+                self.reexports.push(DocReexport { module, text, target, inline });
+            }
+        }
+    }
+
+    fn resolve_links(&mut self) {
+        // Lookups can panic on symbols that were only partially processed, which is handled by falling back to the collected items
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(|_| {}));
+
+        for idx in 0..self.items.len() {
+            let mut doc = std::mem::take(&mut self.items[idx].doc);
+            let mut warnings = Vec::new();
+            for text in doc.texts_mut() {
+                *text = rewrite_item_links(text, |link| self.resolve_link(idx, link), &mut warnings);
+            }
+            self.items[idx].doc = doc;
+            self.add_warnings(warnings, self.items[idx].span);
+        }
+
+        panic::set_hook(hook);
+    }
+
+    fn resolve_link(&self, item_idx: usize, link: &str) -> LinkTarget {
+        let (path, fragment) = match link.split_once('#') {
+            Some((path, fragment)) => (path.trim(), Some(fragment)),
+            None                   => (link.trim(), None),
+        };
+        let path = path.replace("::", ".");
+
+        let (path, params) = match path.find('(') {
+            Some(open) => {
+                let params = path[open + 1..].trim_end_matches(')')
+                    .split(':')
+                    .map(str::trim)
+                    .filter(|param| !param.is_empty())
+                    .map(str::to_string)
+                    .collect::<Vec<_>>();
+                (path[..open].to_string(), Some(params))
+            },
+            None => (path, None),
+        };
+        let segments: Vec<String> = path.split('.').map(|segment| segment.trim().to_string()).collect();
+        if segments.iter().any(String::is_empty) {
+            return LinkTarget::Unresolved;
+        }
+        if segments.len() == 1 && params.is_none() && StrongKeyword::PRIM_TY_NAMES.contains(&segments[0].as_str()) {
+            return LinkTarget::Builtin;
+        }
+
+        let item = &self.items[item_idx];
+        let module = if item.kind == DocItemKind::Module { item_idx } else { item.module };
+
+        let mut rest = segments.as_slice();
+        let base = match rest[0].as_str() {
+            "self"            => {
+                rest = &rest[1..];
+                Some(self.items[module].full_path())
+            },
+            "super"           => {
+                let mut base = self.items[module].full_path();
+                while rest.first().is_some_and(|segment| segment == "super") {
+                    base.pop();
+                    rest = &rest[1..];
+                }
+                Some(base)
+            },
+            "lib" | "package" => {
+                rest = &rest[1..];
+                Some(Scope::new())
+            },
+            "Self"            => {
+                rest = &rest[1..];
+                Some(self.items[item.parent.unwrap_or(item_idx)].full_path())
+            },
+            _                 => None,
+        };
+
+        let target = match base {
+            Some(base) if rest.is_empty() => self.module_by_scope.get(&base).copied()
+                .or_else(|| self.items.iter().position(|item| item.full_path() == base)),
+            Some(base) => self.find_exact(&base, rest, params.as_deref()),
+            None => self.lookup_symbol(&item.scope, rest, params.as_deref())
+                .or_else(|| self.find_item(&item.full_path(), rest, params.as_deref())),
+        };
+
+        match target {
+            Some(target) => LinkTarget::Item(match fragment {
+                Some(fragment) => format!("{ITEM_LINK_SCHEME}{target}#{fragment}"),
+                None           => format!("{ITEM_LINK_SCHEME}{target}"),
+            }),
+            None => LinkTarget::Unresolved,
+        }
+    }
+
+    /// Look up a path using the symbol and use tables, this is only possible when the library has no errors
+    fn lookup_symbol(&self, scope: &Scope, segments: &[String], params: Option<&[String]>) -> Option<usize> {
+        if self.front_end.has_errors {
+            return None;
+        }
+
+        let (last, segments) = segments.split_last()?;
+        let mut sym_path = Scope::new();
+        for segment in segments {
+            sym_path.push(segment.clone());
+        }
+        match params {
+            Some(params) => sym_path.push_with_params(last.clone(), params.to_vec()),
+            None         => sym_path.push(last.clone()),
+        }
+
+        let syms = self.front_end.ctx.syms.read();
+        let uses = self.front_end.ctx.uses.read();
+        let found = panic::catch_unwind(AssertUnwindSafe(|| syms.get_symbol_with_uses(&uses, scope, None, &sym_path)));
+        let Ok(Ok(sym)) = found else { return None };
+        self.items.iter().position(|item| item.sym.as_ref().is_some_and(|item_sym| Arc::ptr_eq(item_sym, &sym)))
+    }
+
+    /// Find an item relative to `base` or any of its parent scopes
+    fn find_item(&self, base: &Scope, segments: &[String], params: Option<&[String]>) -> Option<usize> {
+        let mut base = base.clone();
+        loop {
+            if let Some(item) = self.find_exact(&base, segments, params) {
+                return Some(item);
+            }
+            base.pop()?;
+        }
+    }
+
+    fn find_exact(&self, base: &Scope, segments: &[String], params: Option<&[String]>) -> Option<usize> {
+        let mut path: Vec<&str> = base.idens().iter().map(|iden| iden.name.as_str()).collect();
+        path.extend(segments.iter().map(String::as_str));
+
+        self.items.iter().position(|item| {
+            let full_path = item.full_path();
+            full_path.len() == path.len() &&
+            full_path.idens().iter().zip(&path).all(|(iden, name)| iden.name == *name) &&
+            params.is_none_or(|params| params == item.params)
+        })
+    }
+
+    fn build_doc(&self, file: usize, lines: &[String], attr_keys: &[(usize, usize)], warnings: &mut Vec<String>) -> DocComment {
+        let base_dir = self.front_end.files.get(file)
+            .and_then(|file| file.path.parent())
+            .unwrap_or(&self.root_dir);
+        // Empty lines need to be kept, as they separate paragraphs
+        let expand = |text: &str, warnings: &mut Vec<String>| -> Vec<String> {
+            text.split('\n')
+                .flat_map(|line| DocComment::expand_includes(line.trim_end_matches('\r'), base_dir, warnings).trim_end().split('\n').map(str::to_string).collect::<Vec<_>>())
+                .collect()
+        };
+
+        let mut doc = DocComment::default();
+        let lines: Vec<String> = lines.iter().flat_map(|line| expand(line, warnings)).collect();
+        doc.parse_lines(&lines, warnings);
+
+        for key in attr_keys {
+            let Some(attr) = self.doc_attrs.get(key) else { continue };
+            for meta in &attr.metas {
+                match meta {
+                    DocAttrMeta::Text(text) => doc.parse_lines(&expand(text, warnings), warnings),
+                    _                       => doc.apply_attr_meta(meta, warnings),
+                }
+            }
+        }
+        doc
+    }
+
+    fn check_root_only(doc: &DocComment, warnings: &mut Vec<String>) {
+        if !doc.favicons.is_empty() || doc.logo.is_some() || doc.playground.is_some() || doc.issue_tracker_base.is_some() {
+            warnings.push("`favicon`, `logo`, `playground`, and `issue_tracker_base` are only allowed in the documentation of the library root".to_string());
+        }
+    }
+
+    /// Keys of all doc attributes of a module, i.e. the attributes on the module item and all module attributes inside it
+    fn module_attr_keys(&self, scope: &Scope, item_keys: &[(usize, usize)]) -> Vec<(usize, usize)> {
+        let mut keys = item_keys.to_vec();
+        keys.extend(self.mod_attrs.iter().filter(|(attr_scope, _)| attr_scope == scope).map(|(_, key)| *key));
+        keys
+    }
+
+    /// Get the doc comment lines in front of an item and its attributes, together with the index of the first token of the item after its attributes
+    fn leading_lines(file: &FrontEndFile, node_id: usize, attr_ids: &[usize], warnings: &mut Vec<String>) -> (Vec<String>, usize) {
+        let Some(meta) = file.ast.meta.get(node_id) else {
+            return (Vec::new(), 0);
+        };
+        let first = meta.first_tok as usize;
+        let decl = attr_ids.iter()
+            .filter_map(|&attr_id| file.ast.meta.get(attr_id))
+            .map(|meta| meta.last_tok as usize)
+            .fold(first, usize::max);
+
+        let metadata = &file.ast.tokens.metadata;
+        let end = (decl + 1).min(metadata.len());
+        let elems = metadata[first.min(end)..end].iter().flat_map(|meta| &meta.meta_elems);
+        (DocComment::lines_from_meta(elems, false, warnings), decl)
+    }
+
+    /// Get the top-level doc comment lines of a file, or of an inline module starting at the brace at `block_start`
+    fn top_level_lines(file: &FrontEndFile, block_start: Option<u32>, warnings: &mut Vec<String>) -> Vec<String> {
+        let tokens = &file.ast.tokens;
+        let idx = block_start.map_or(0, |start| start as usize + 1);
+        let elems: &[MetaElem] = match tokens.metadata.get(idx) {
+            Some(meta) => &meta.meta_elems,
+            None       => &tokens.tail_meta_elems,
+        };
+        DocComment::lines_from_meta(elems, true, warnings)
+    }
+
+    fn token_span(file: &FrontEndFile, idx: usize) -> SpanId {
+        file.ast.tokens.metadata.get(idx).map_or(SpanId::INVALID, |meta| meta.span_id)
+    }
+
+    fn token_line(&self, file: &FrontEndFile, idx: usize) -> u32 {
+        let spans = self.front_end.ctx.spans.read();
+        spans.get(Self::token_span(file, idx)).map_or(1, |span| span.row)
+    }
+
+    /// Get the source text of the declaration of an item, without its body unless the body is part of what the item declares, e.g. the fields of a struct
+    fn signature(&self, file: &FrontEndFile, start: usize, end: usize, kind: DocItemKind) -> String {
+        let tokens = &file.ast.tokens;
+        let end = end.min(tokens.tokens.len());
+
+        let mut depth = 0usize;
+        let mut last = start;
+        for idx in start..end {
+            match tokens.tokens[idx] {
+                Token::OpenSymbol(OpenCloseSymbol::Brace) if depth == 0 && !kind.shows_body() => break,
+                Token::Punctuation(Punctuation::Semicolon) if depth == 0 => break,
+                Token::OpenSymbol(_)  => depth += 1,
+                Token::CloseSymbol(_) => depth = depth.saturating_sub(1),
+                _ => {},
+            }
+            last = idx + 1;
+        }
+        if last == start {
+            return String::new();
+        }
+
+        let spans = self.front_end.ctx.spans.read();
+        let begin = spans.get(tokens.metadata[start].span_id);
+        let finish = spans.get(tokens.metadata[last - 1].span_id);
+        match (begin, finish) {
+            (Some(begin), Some(finish)) => file.source
+                .get(begin.byte_offset as usize..(finish.byte_offset + finish.byte_len) as usize)
+                .unwrap_or_default()
+                .to_string(),
+            _ => String::new(),
+        }
+    }
+
+    fn add_warnings(&mut self, warnings: Vec<String>, span: SpanId) {
+        for warning in warnings {
+            self.diagnostics.push(Diagnostic::new(Severity::Warning, warning).with_primary(span, "in the documentation of this item"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs};
+
+    use crate::frontend::FrontEnd;
+
+    use super::{DocConfig, Documentation, ITEM_LINK_SCHEME};
+
+    #[test]
+    fn test_resolve_links() {
+        let dir = std::env::temp_dir().join(format!("xenon-doc-test-{}", std::process::id())).join("links");
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src/lib.xn"), r#"//! Library containing a [`shapes.Circle`]
+
+pub mod shapes;
+
+/// A point, created by [`origin`]
+pub struct Point {
+    x: i32,
+}
+
+/// Get the origin, which is a [`Point`] with a [`i32`] coordinate, see [`Missing`]
+pub fn origin() {}
+"#).unwrap();
+        fs::write(dir.join("src/shapes.xn"), r#"/// A circle around a [`super.Point`], see [`lib.origin`]
+pub struct Circle {
+    r: i32,
+}
+"#).unwrap();
+
+        let root = fs::canonicalize(dir.join("src/lib.xn")).unwrap();
+        let mut front_end = FrontEnd::for_root(&root, &HashMap::new());
+        let docs = Documentation::collect(&mut front_end, &DocConfig { document_private: false, document_hidden: false });
+
+        let item = |name: &str| docs.items.iter().position(|item| item.name == name).unwrap_or_else(|| panic!("no item `{name}`"));
+        let link = |name: &str| format!("{ITEM_LINK_SCHEME}{}", item(name));
+
+        assert!(docs.root().doc.short.contains(&format!("[`shapes.Circle`]({})", link("Circle"))), "{}", docs.root().doc.short);
+        assert!(docs.items[item("Point")].doc.short.contains(&format!("[`origin`]({})", link("origin"))));
+        assert!(docs.items[item("Circle")].doc.short.contains(&format!("[`super.Point`]({})", link("Point"))));
+        assert!(docs.items[item("Circle")].doc.short.contains(&format!("[`lib.origin`]({})", link("origin"))));
+
+        // Builtin types are not linked, and unresolved links are reported
+        let origin_doc = &docs.items[item("origin")].doc.short;
+        assert!(origin_doc.contains(&format!("[`Point`]({})", link("Point"))));
+        assert!(origin_doc.contains("a `i32` coordinate, see `Missing`"), "{origin_doc}");
+        let warnings: Vec<&str> = docs.diagnostics.iter().map(|diag| diag.message.as_str()).collect();
+        assert_eq!(warnings, ["Unresolved item link `Missing`"]);
+
+        _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,737 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use super::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DocFormat {
+    Html,
+    Markdown,
+}
+
+impl DocFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            DocFormat::Html     => "html",
+            DocFormat::Markdown => "md",
+        }
+    }
+}
+
+const STYLE_CSS: &str = r#"body { margin: 0; display: flex; font-family: sans-serif; line-height: 1.5; color: #222; }
+nav.sidebar { width: 260px; min-height: 100vh; padding: 16px; background: #f5f5f5; box-sizing: border-box; flex-shrink: 0; }
+nav.sidebar .lib { display: block; font-size: 1.4em; font-weight: bold; margin-bottom: 12px; }
+nav.sidebar img.logo { display: block; max-width: 100%; margin-bottom: 8px; }
+nav.sidebar ul { list-style: none; padding-left: 0; }
+#search { width: 100%; box-sizing: border-box; padding: 4px; }
+#search-results li { margin: 4px 0; }
+main { padding: 16px 32px; max-width: 960px; flex-grow: 1; }
+a { color: #3060c0; text-decoration: none; }
+a:hover { text-decoration: underline; }
+code, pre { font-family: monospace; background: #f0f0f0; }
+pre { padding: 8px; overflow-x: auto; }
+pre.source { background: none; }
+pre.source .line-no { display: inline-block; width: 4em; color: #999; text-align: right; margin-right: 1em; }
+pre.source :target { background: #fff3b0; }
+.kind { color: #777; font-weight: normal; }
+.src { float: right; font-size: 0.8em; font-weight: normal; }
+.since { color: #777; font-size: 0.9em; }
+.member { margin-left: 24px; }
+.callout { padding: 8px; margin: 8px 0; border-left: 4px solid #999; background: #fafafa; }
+.callout.important { border-color: #3060c0; }
+.callout.warning { border-color: #d08000; }
+.callout.attention { border-color: #c03030; }
+.run { float: right; font-size: 0.8em; }
+table.summary td { padding: 2px 12px 2px 0; vertical-align: top; }
+"#;
+
+const SEARCH_JS: &str = r#"(function() {
+    const input = document.getElementById("search");
+    const results = document.getElementById("search-results");
+    if (!input || !results || !window.SEARCH_INDEX) {
+        return;
+    }
+    const root = document.body.dataset.root;
+    input.addEventListener("input", function() {
+        const query = input.value.trim().toLowerCase();
+        results.innerHTML = "";
+        if (query.length === 0) {
+            return;
+        }
+        const matches = window.SEARCH_INDEX.filter(function(item) {
+            return item.path.toLowerCase().includes(query) || item.aliases.some(function(alias) { return alias.toLowerCase().includes(query); });
+        });
+        for (const item of matches.slice(0, 50)) {
+            const li = document.createElement("li");
+            const a = document.createElement("a");
+            a.href = root + item.url;
+            a.textContent = item.path;
+            li.appendChild(a);
+            li.appendChild(document.createTextNode(" (" + item.kind + ")"));
+            results.appendChild(li);
+        }
+    });
+})();
+"#;
+
+impl Documentation {
+    /// Write the documentation into a directory named after the library in `out_dir`, and get the path of its index page
+    pub fn write(&self, out_dir: &Path, format: DocFormat) -> io::Result<PathBuf> {
+        let lib_dir = out_dir.join(&self.lib_path.library);
+        let renderer = Renderer { doc: self, format, static_files: Vec::new() };
+        renderer.write(&lib_dir)?;
+        Ok(lib_dir.join(format!("index.{}", format.extension())))
+    }
+}
+
+struct Renderer<'a> {
+    doc:          &'a Documentation,
+    format:       DocFormat,
+    /// Local files that are copied into the documentation, with the path they are copied to
+    static_files: Vec<(PathBuf, String)>,
+}
+
+impl Renderer<'_> {
+    fn write(mut self, lib_dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(lib_dir)?;
+        self.collect_static_files();
+
+        for (idx, item) in self.doc.items.iter().enumerate() {
+            if item.kind != DocItemKind::Module || !item.documented {
+                continue;
+            }
+            let path = lib_dir.join(self.page_path(idx));
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let page = match self.format {
+                DocFormat::Html     => self.html_module_page(idx),
+                DocFormat::Markdown => self.md_module_page(idx),
+            };
+            fs::write(path, page)?;
+        }
+
+        for (idx, source) in self.doc.sources.iter().enumerate() {
+            if !self.shows_source(idx) {
+                continue;
+            }
+            let path = lib_dir.join(self.source_path(idx));
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let page = match self.format {
+                DocFormat::Html     => self.html_source_page(idx),
+                DocFormat::Markdown => format!("# Source of `{}`\n\n```xenon\n{}\n```\n", source.rel_path, source.text.trim_end()),
+            };
+            fs::write(path, page)?;
+        }
+
+        let index = serde_json::to_string(&self.search_index()).map_err(io::Error::other)?;
+        fs::write(lib_dir.join("search-index.json"), &index)?;
+        if self.format == DocFormat::Html {
+            fs::write(lib_dir.join("search-index.js"), format!("window.SEARCH_INDEX = {index};\n"))?;
+            fs::write(lib_dir.join("search.js"), SEARCH_JS)?;
+            fs::write(lib_dir.join("style.css"), STYLE_CSS)?;
+        }
+
+        for (from, to) in &self.static_files {
+            let to = lib_dir.join(to);
+            if let Some(dir) = to.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::copy(from, to).map_err(|err| io::Error::new(err.kind(), format!("Failed to copy {}: {err}", from.display())))?;
+        }
+        Ok(())
+    }
+
+    /// Logos and favicons with a local path are copied into the `static` directory
+    fn collect_static_files(&mut self) {
+        let root_doc = &self.doc.root().doc;
+        let paths = root_doc.favicons.iter().map(|favicon| &favicon.path).chain(root_doc.logo.as_ref().map(|logo| &logo.path));
+        for path in paths {
+            if path.contains("://") || path.is_empty() {
+                continue;
+            }
+            let from = self.doc.root_dir.join(path);
+            let name = from.file_name().map_or_else(|| path.clone(), |name| name.to_string_lossy().to_string());
+            self.static_files.push((from, format!("static/{name}")));
+        }
+    }
+
+    fn static_url(&self, path: &str, prefix: &str) -> String {
+        match self.static_files.iter().position(|(from, _)| *from == self.doc.root_dir.join(path)) {
+            Some(idx) => format!("{prefix}{}", self.static_files[idx].1),
+            None      => path.to_string(),
+        }
+    }
+
+    // =========================================================================================================================
+
+    fn page_path(&self, module: usize) -> String {
+        let mut path = String::new();
+        for iden in self.doc.items[module].full_path().idens() {
+            path.push_str(&iden.name);
+            path.push('/');
+        }
+        path.push_str("index.");
+        path.push_str(self.format.extension());
+        path
+    }
+
+    fn source_path(&self, source: usize) -> String {
+        format!("src/{}.{}", self.doc.sources[source].rel_path, self.format.extension())
+    }
+
+    /// Prefix to get from the page of a module to the root of the documentation
+    fn rel_prefix(&self, module: usize) -> String {
+        "../".repeat(self.doc.items[module].full_path().len())
+    }
+
+    fn shows_source(&self, source: usize) -> bool {
+        self.doc.items.iter().enumerate().any(|(idx, item)| item.file == Some(source) && !item.doc.no_source && self.doc.page_of(idx).is_some())
+    }
+
+    /// Anchor of an item, including the anchors of the items it's a member of
+    fn full_anchor(&self, item: usize) -> String {
+        let doc_item = &self.doc.items[item];
+        match doc_item.parent {
+            Some(parent) => format!("{}.{}", self.full_anchor(parent), doc_item.anchor()),
+            None         => doc_item.anchor(),
+        }
+    }
+
+    /// Get the url of an item from the page of `from`, `None` if the item isn't shown anywhere
+    fn item_url(&self, from: usize, item: usize, fragment: Option<&str>) -> Option<String> {
+        let page = self.doc.page_of(item)?;
+        let mut url = format!("{}{}", self.rel_prefix(from), self.page_path(page));
+        if page == from {
+            url.clear();
+        }
+        match fragment {
+            Some(fragment) => url.push_str(&format!("#{fragment}")),
+            None if page != item => url.push_str(&format!("#{}", self.full_anchor(item))),
+            None if url.is_empty() => url.push('#'),
+            None => {},
+        }
+        Some(url)
+    }
+
+    fn source_url(&self, from: usize, item: usize) -> Option<String> {
+        let doc_item = &self.doc.items[item];
+        let source = doc_item.file?;
+        if doc_item.doc.no_source || self.doc.root().doc.no_source {
+            return None;
+        }
+        let mut url = format!("{}{}", self.rel_prefix(from), self.source_path(source));
+        if self.format == DocFormat::Html {
+            url.push_str(&format!("#L{}", doc_item.line));
+        }
+        Some(url)
+    }
+
+    /// Map a link in documentation text to the url to use on the page of `from`
+    fn map_link(&self, from: usize, url: &str) -> Option<String> {
+        match parse_item_link(url) {
+            Some((item, fragment)) => self.item_url(from, item, fragment),
+            None if url.starts_with(ITEM_LINK_SCHEME) => None,
+            None => Some(url.to_string()),
+        }
+    }
+
+    fn issue_url(&self, issue: &str) -> String {
+        let issue = issue.trim().trim_start_matches('#');
+        match &self.doc.root().doc.issue_tracker_base {
+            Some(base) if issue.chars().all(|ch| ch.is_ascii_digit()) => format!("{}/{issue}", base.trim_end_matches('/')),
+            _ => issue.to_string(),
+        }
+    }
+
+    fn search_index(&self) -> Vec<serde_json::Value> {
+        let mut index = Vec::new();
+        for (idx, item) in self.doc.items.iter().enumerate().skip(1) {
+            let Some(url) = self.item_url(0, idx, None) else { continue };
+            let mut path = item.full_path().idens().iter().map(|iden| iden.name.clone()).collect::<Vec<_>>();
+            if item.kind == DocItemKind::Impl {
+                path.pop();
+                path.push(item.display_name());
+            }
+            index.push(serde_json::json!({
+                "name":    item.display_name(),
+                "path":    path.join("."),
+                "kind":    item.kind.as_str(),
+                "url":     url,
+                "aliases": item.doc.aliases,
+                "short":   item.doc.short,
+            }));
+        }
+        index
+    }
+
+    /// Items shown on a module's page, grouped by kind
+    fn module_sections(&self, module: usize) -> Vec<(DocItemKind, Vec<usize>)> {
+        let items = self.doc.module_items(module);
+
+        DocItemKind::ALL.iter()
+            .map(|&kind| (kind, items.iter().copied().filter(|&idx| self.doc.items[idx].kind == kind).collect::<Vec<_>>()))
+            .filter(|(_, items)| !items.is_empty())
+            .collect()
+    }
+
+    fn module_title(&self, module: usize) -> String {
+        if module == 0 {
+            self.doc.lib_path.library.clone()
+        } else {
+            format!("{}.{}", self.doc.lib_path.library, self.doc.items[module].full_path())
+        }
+    }
+
+    // =========================================================================================================================
+
+    fn html_module_page(&self, module: usize) -> String {
+        let doc_item = &self.doc.items[module];
+        let mut content = String::new();
+
+        let kind = if module == 0 { "Library" } else { "Module" };
+        content.push_str(&format!("<h1><span class=\"kind\">{kind}</span> {}", escape_html(&self.module_title(module))));
+        if let Some(url) = self.source_url(module, module) {
+            content.push_str(&format!(" <a class=\"src\" href=\"{}\">source</a>", escape_html(&url)));
+        }
+        content.push_str("</h1>\n");
+        content.push_str(&self.html_doc(module, module));
+
+        let reexports: Vec<&DocReexport> = self.doc.reexports.iter().filter(|reexport| reexport.module == module && !reexport.inline).collect();
+        if !reexports.is_empty() {
+            content.push_str("<h2 id=\"reexports\">Re-exports</h2>\n<ul>\n");
+            for reexport in reexports {
+                let text = escape_html(&reexport.text);
+                match reexport.target.and_then(|target| self.item_url(module, target, None)) {
+                    Some(url) => content.push_str(&format!("<li><code>pub use <a href=\"{}\">{text}</a></code></li>\n", escape_html(&url))),
+                    None      => content.push_str(&format!("<li><code>pub use {text}</code></li>\n")),
+                }
+            }
+            content.push_str("</ul>\n");
+        }
+
+        for (kind, items) in self.module_sections(module) {
+            content.push_str(&format!("<h2 id=\"{}\">{}</h2>\n<table class=\"summary\">\n", kind.anchor_prefix(), kind.section_title()));
+            for &item in &items {
+                let url = self.item_url(module, item, None).unwrap_or_default();
+                content.push_str(&format!(
+                    "<tr><td><a href=\"{}\">{}</a></td><td>{}</td></tr>\n",
+                    escape_html(&url),
+                    escape_html(&self.doc.items[item].display_name()),
+                    inline_to_html(&self.doc.items[item].doc.short, &|url| self.map_link(module, url)),
+                ));
+            }
+            content.push_str("</table>\n");
+
+            if kind != DocItemKind::Module {
+                for &item in &items {
+                    content.push_str(&self.html_item(module, item));
+                }
+            }
+        }
+
+        let title = match module {
+            0 => format!("{} - Documentation", self.doc.lib_path.library),
+            _ => format!("{} - {}", doc_item.name, self.module_title(module)),
+        };
+        self.html_page(&self.rel_prefix(module), &title, &self.html_sidebar(module), &content)
+    }
+
+    fn html_sidebar(&self, module: usize) -> String {
+        let mut sidebar = String::new();
+        for (kind, items) in self.module_sections(module) {
+            sidebar.push_str(&format!("<h3>{}</h3>\n<ul>\n", kind.section_title()));
+            for item in items {
+                let url = self.item_url(module, item, None).unwrap_or_default();
+                sidebar.push_str(&format!("<li><a href=\"{}\">{}</a></li>\n", escape_html(&url), escape_html(&self.doc.items[item].display_name())));
+            }
+            sidebar.push_str("</ul>\n");
+        }
+        sidebar
+    }
+
+    fn html_page(&self, prefix: &str, title: &str, sidebar: &str, content: &str) -> String {
+        let root_doc = &self.doc.root().doc;
+
+        let mut head = String::new();
+        for favicon in &root_doc.favicons {
+            let sizes = favicon.sizes.as_ref().map_or_else(String::new, |sizes| format!(" sizes=\"{}\"", escape_html(sizes)));
+            head.push_str(&format!("<link rel=\"icon\" href=\"{}\"{sizes}>\n", escape_html(&self.static_url(&favicon.path, prefix))));
+        }
+
+        let mut logo = String::new();
+        if let Some(doc_logo) = &root_doc.logo {
+            let mut attrs = String::new();
+            if let Some(width) = &doc_logo.width {
+                attrs.push_str(&format!(" width=\"{}\"", escape_html(width)));
+            }
+            if let Some(height) = &doc_logo.height {
+                attrs.push_str(&format!(" height=\"{}\"", escape_html(height)));
+            }
+            logo = format!("<img class=\"logo\" src=\"{}\" alt=\"logo\"{attrs}>\n", escape_html(&self.static_url(&doc_logo.path, prefix)));
+        }
+
+        format!(
+r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title}</title>
+<link rel="stylesheet" href="{prefix}style.css">
+{head}<script src="{prefix}search-index.js" defer></script>
+<script src="{prefix}search.js" defer></script>
+</head>
+<body data-root="{prefix}">
+<nav class="sidebar">
+{logo}<a class="lib" href="{prefix}index.html">{lib}</a>
+<input id="search" type="search" placeholder="Search..." autocomplete="off">
+<ul id="search-results"></ul>
+{sidebar}</nav>
+<main>
+{content}</main>
+</body>
+</html>
+"#,
+            title = escape_html(title),
+            lib = escape_html(&self.doc.lib_path.library),
+        )
+    }
+
+    fn html_item(&self, page: usize, item: usize) -> String {
+        let doc_item = &self.doc.items[item];
+        let anchor = escape_html(&self.full_anchor(item));
+        let class = if doc_item.parent.is_some() { "item member" } else { "item" };
+
+        let mut out = format!("<section class=\"{class}\" id=\"{anchor}\">\n");
+        let heading = if doc_item.parent.is_some() { "h4" } else { "h3" };
+        out.push_str(&format!(
+            "<{heading}><a href=\"#{anchor}\"><span class=\"kind\">{}</span> {}</a>",
+            doc_item.kind.as_str(),
+            escape_html(&doc_item.display_name()),
+        ));
+        if let Some(url) = self.source_url(page, item) {
+            out.push_str(&format!(" <a class=\"src\" href=\"{}\">source</a>", escape_html(&url)));
+        }
+        out.push_str(&format!("</{heading}>\n"));
+
+        if !doc_item.signature.is_empty() {
+            out.push_str(&format!("<pre class=\"signature\"><code>{}</code></pre>\n", escape_html(&doc_item.signature)));
+        }
+        out.push_str(&self.html_doc(page, item));
+
+        for &member in &doc_item.members {
+            out.push_str(&self.html_item(page, member));
+        }
+        out.push_str("</section>\n");
+        out
+    }
+
+    fn html_doc(&self, page: usize, item: usize) -> String {
+        let doc = &self.doc.items[item].doc;
+        let link = |url: &str| self.map_link(page, url);
+        let inline = |text: &str| inline_to_html(text, &link);
+        let mut out = String::new();
+
+        if let Some(since) = &doc.since {
+            out.push_str(&format!("<div class=\"since\">Since {}</div>\n", escape_html(since)));
+        }
+        for note in doc.notes.iter().filter(|note| note.kind == "experimental") {
+            out.push_str(&format!("<div class=\"callout attention\"><strong>Experimental</strong> {}</div>\n", inline(&note.info)));
+        }
+        if !doc.short.is_empty() {
+            out.push_str(&format!("<p class=\"short\">{}</p>\n", inline(&doc.short)));
+        }
+        if !doc.long.is_empty() {
+            out.push_str(&self.add_run_links(markdown_to_html(&doc.long, &link)));
+        }
+
+        for (class, title, texts) in [("important", "Important", &doc.important), ("warning", "Warning", &doc.warning), ("attention", "Attention", &doc.attention)] {
+            for text in texts {
+                out.push_str(&format!("<div class=\"callout {class}\"><strong>{title}:</strong> {}</div>\n", inline(text)));
+            }
+        }
+
+        let mut sections = Vec::new();
+        if let Some(receiver) = &doc.receiver {
+            sections.push(("Receiver", vec![inline(receiver)]));
+        }
+        if !doc.infer_params.is_empty() {
+            sections.push(("Inferred parameters", doc.infer_params.iter().map(|param| format!("<code>{}</code>: {}", escape_html(&param.name), inline(&param.info))).collect()));
+        }
+        if !doc.params.is_empty() {
+            sections.push(("Parameters", doc.params.iter().map(|param| {
+                let mut text = format!("<code>{}</code>: {}", escape_html(&param.name), inline(&param.info));
+                if let Some(default) = &param.default {
+                    text.push_str(&format!(" (default: {})", inline(default)));
+                }
+                text
+            }).collect()));
+        }
+        if let Some(returns) = &doc.returns {
+            sections.push(("Returns", vec![inline(returns)]));
+        }
+        if !doc.named_returns.is_empty() {
+            sections.push(("Named returns", doc.named_returns.iter().map(|ret| format!("<code>{}</code>: {}", escape_html(&ret.name), inline(&ret.info))).collect()));
+        }
+        if doc.errors.is_some() || !doc.error.is_empty() {
+            let mut errors: Vec<String> = doc.errors.iter().map(|errors| inline(errors)).collect();
+            errors.extend(doc.error.iter().map(|err| format!("<code>{}</code>: {}", escape_html(&err.name), inline(&err.info))));
+            sections.push(("Errors", errors));
+        }
+        for (title, texts) in [("Pre-conditions", &doc.pre), ("Post-conditions", &doc.post), ("Invariants", &doc.invar)] {
+            if !texts.is_empty() {
+                sections.push((title, texts.iter().map(|text| inline(text)).collect()));
+            }
+        }
+        if let Some(complexity) = &doc.complexity {
+            sections.push(("Complexity", vec![inline(complexity)]));
+        }
+        if !doc.issues.is_empty() {
+            sections.push(("Issues", doc.issues.iter().map(|issue| {
+                let url = self.issue_url(issue);
+                format!("<a href=\"{}\">{}</a>", escape_html(&url), escape_html(issue))
+            }).collect()));
+        }
+        for (title, entries) in sections {
+            out.push_str(&format!("<h5>{title}</h5>\n<ul>\n"));
+            for entry in entries {
+                out.push_str(&format!("<li>{entry}</li>\n"));
+            }
+            out.push_str("</ul>\n");
+        }
+
+        for note in doc.notes.iter().filter(|note| note.kind != "experimental") {
+            let authors = if note.authors.is_empty() { String::new() } else { format!(" ({})", escape_html(&note.authors.join(", "))) };
+            out.push_str(&format!("<div class=\"callout note\"><strong>{}</strong>{authors}: {}</div>\n", note_title(&note.kind), inline(&note.info)));
+        }
+        out
+    }
+
+    /// Add links to run the code blocks in documentation, when a playground url is set
+    fn add_run_links(&self, html: String) -> String {
+        const START: &str = "<pre><code class=\"language-xenon\">";
+        const END: &str = "</code></pre>";
+
+        let Some(DocPlayground::Url(base)) = &self.doc.root().doc.playground else {
+            return html;
+        };
+
+        let mut out = String::new();
+        let mut rest = html.as_str();
+        while let Some(start) = rest.find(START) {
+            let Some(len) = rest[start..].find(END) else { break };
+            let code = unescape_html(&rest[start + START.len()..start + len]);
+            let separator = if base.contains('?') { '&' } else { '?' };
+            out.push_str(&rest[..start]);
+            out.push_str(&format!("<a class=\"run\" href=\"{}{separator}code={}\">Run</a>", escape_html(base), percent_encode(&code)));
+            out.push_str(&rest[start..start + len + END.len()]);
+            rest = &rest[start + len + END.len()..];
+        }
+        out.push_str(rest);
+        out
+    }
+
+    fn html_source_page(&self, source: usize) -> String {
+        let doc_source = &self.doc.sources[source];
+        let mut content = format!("<h1><span class=\"kind\">Source</span> {}</h1>\n<pre class=\"source\"><code>", escape_html(&doc_source.rel_path));
+        for (idx, line) in doc_source.text.lines().enumerate() {
+            let line_no = idx + 1;
+            content.push_str(&format!("<span id=\"L{line_no}\"><a class=\"line-no\" href=\"#L{line_no}\">{line_no}</a>{}</span>\n", escape_html(line)));
+        }
+        content.push_str("</code></pre>\n");
+
+        // Source pages are nested as deep as their path in the `src` directory
+        let prefix = "../".repeat(self.source_path(source).matches('/').count());
+        self.html_page(&prefix, &doc_source.rel_path, "", &content)
+    }
+
+    // =========================================================================================================================
+
+    fn md_module_page(&self, module: usize) -> String {
+        let mut out = String::new();
+        let root_doc = &self.doc.root().doc;
+        if let Some(logo) = &root_doc.logo {
+            out.push_str(&format!("![logo]({})\n\n", self.static_url(&logo.path, &self.rel_prefix(module))));
+        }
+
+        let kind = if module == 0 { "Library" } else { "Module" };
+        out.push_str(&format!("# {kind} `{}`\n\n", self.module_title(module)));
+        if let Some(url) = self.source_url(module, module) {
+            out.push_str(&format!("[source]({url})\n\n"));
+        }
+        out.push_str(&self.md_doc(module, module));
+
+        let reexports: Vec<&DocReexport> = self.doc.reexports.iter().filter(|reexport| reexport.module == module && !reexport.inline).collect();
+        if !reexports.is_empty() {
+            out.push_str("## Re-exports\n\n");
+            for reexport in reexports {
+                match reexport.target.and_then(|target| self.item_url(module, target, None)) {
+                    Some(url) => out.push_str(&format!("- [`pub use {}`]({url})\n", reexport.text)),
+                    None      => out.push_str(&format!("- `pub use {}`\n", reexport.text)),
+                }
+            }
+            out.push('\n');
+        }
+
+        for (kind, items) in self.module_sections(module) {
+            out.push_str(&format!("## {}\n\n| Name | Description |\n| --- | --- |\n", kind.section_title()));
+            for &item in &items {
+                let url = self.item_url(module, item, None).unwrap_or_default();
+                let short = self.md_text(module, &self.doc.items[item].doc.short).replace('|', "\\|");
+                out.push_str(&format!("| [`{}`]({url}) | {short} |\n", self.doc.items[item].display_name()));
+            }
+            out.push('\n');
+
+            if kind != DocItemKind::Module {
+                for &item in &items {
+                    out.push_str(&self.md_item(module, item, 3));
+                }
+            }
+        }
+        out
+    }
+
+    fn md_item(&self, page: usize, item: usize, level: usize) -> String {
+        let doc_item = &self.doc.items[item];
+        let mut out = format!("<a id=\"{}\"></a>\n\n", self.full_anchor(item));
+        out.push_str(&format!("{} {} `{}`", "#".repeat(level), doc_item.kind.as_str(), doc_item.display_name()));
+        if let Some(url) = self.source_url(page, item) {
+            out.push_str(&format!(" ([source]({url}), line {})", doc_item.line));
+        }
+        out.push_str("\n\n");
+
+        if !doc_item.signature.is_empty() {
+            out.push_str(&format!("```xenon\n{}\n```\n\n", doc_item.signature));
+        }
+        out.push_str(&self.md_doc(page, item));
+
+        for &member in &doc_item.members {
+            out.push_str(&self.md_item(page, member, (level + 1).min(6)));
+        }
+        out
+    }
+
+    /// Rewrite item links in markdown text to the urls of the items
+    fn md_text(&self, page: usize, text: &str) -> String {
+        replace_links(text, &mut |label, url| {
+            let url = url?;
+            if !url.starts_with(ITEM_LINK_SCHEME) {
+                return None;
+            }
+            match self.map_link(page, url) {
+                Some(url) => Some(format!("[{label}]({url})")),
+                None      => Some(label.to_string()),
+            }
+        })
+    }
+
+    fn md_doc(&self, page: usize, item: usize) -> String {
+        let doc = &self.doc.items[item].doc;
+        let text = |text: &str| self.md_text(page, text);
+        let mut out = String::new();
+
+        if let Some(since) = &doc.since {
+            out.push_str(&format!("_Since {since}_\n\n"));
+        }
+        for note in doc.notes.iter().filter(|note| note.kind == "experimental") {
+            out.push_str(&format!("> **Experimental** {}\n\n", text(&note.info)));
+        }
+        if !doc.short.is_empty() {
+            out.push_str(&format!("{}\n\n", text(&doc.short)));
+        }
+        if !doc.long.is_empty() {
+            out.push_str(&format!("{}\n\n", text(&doc.long)));
+        }
+        for (title, texts) in [("Important", &doc.important), ("Warning", &doc.warning), ("Attention", &doc.attention)] {
+            for info in texts {
+                out.push_str(&format!("> **{title}:** {}\n\n", text(info)));
+            }
+        }
+
+        let mut sections = Vec::new();
+        if let Some(receiver) = &doc.receiver {
+            sections.push(("Receiver", vec![text(receiver)]));
+        }
+        if !doc.infer_params.is_empty() {
+            sections.push(("Inferred parameters", doc.infer_params.iter().map(|param| format!("`{}`: {}", param.name, text(&param.info))).collect()));
+        }
+        if !doc.params.is_empty() {
+            sections.push(("Parameters", doc.params.iter().map(|param| {
+                let mut entry = format!("`{}`: {}", param.name, text(&param.info));
+                if let Some(default) = &param.default {
+                    entry.push_str(&format!(" (default: {})", text(default)));
+                }
+                entry
+            }).collect()));
+        }
+        if let Some(returns) = &doc.returns {
+            sections.push(("Returns", vec![text(returns)]));
+        }
+        if !doc.named_returns.is_empty() {
+            sections.push(("Named returns", doc.named_returns.iter().map(|ret| format!("`{}`: {}", ret.name, text(&ret.info))).collect()));
+        }
+        if doc.errors.is_some() || !doc.error.is_empty() {
+            let mut errors: Vec<String> = doc.errors.iter().map(|errors| text(errors)).collect();
+            errors.extend(doc.error.iter().map(|err| format!("`{}`: {}", err.name, text(&err.info))));
+            sections.push(("Errors", errors));
+        }
+        for (title, texts) in [("Pre-conditions", &doc.pre), ("Post-conditions", &doc.post), ("Invariants", &doc.invar)] {
+            if !texts.is_empty() {
+                sections.push((title, texts.iter().map(|info| text(info)).collect()));
+            }
+        }
+        if let Some(complexity) = &doc.complexity {
+            sections.push(("Complexity", vec![text(complexity)]));
+        }
+        if !doc.issues.is_empty() {
+            sections.push(("Issues", doc.issues.iter().map(|issue| format!("[{issue}]({})", self.issue_url(issue))).collect()));
+        }
+        for (title, entries) in sections {
+            out.push_str(&format!("**{title}**\n\n"));
+            for entry in entries {
+                out.push_str(&format!("- {entry}\n"));
+            }
+            out.push('\n');
+        }
+
+        for note in doc.notes.iter().filter(|note| note.kind != "experimental") {
+            let authors = if note.authors.is_empty() { String::new() } else { format!(" ({})", note.authors.join(", ")) };
+            out.push_str(&format!("> **{}**{authors}: {}\n\n", note_title(&note.kind), text(&note.info)));
+        }
+        out
+    }
+}
+
+fn note_title(kind: &str) -> &'static str {
+    match kind {
+        "bug"                => "Bug",
+        "remark" | "remarks" => "Remark",
+        "todo"               => "Todo",
+        "perf"               => "Performance",
+        _                    => "Note",
+    }
+}
+
+fn unescape_html(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+fn percent_encode(text: &str) -> String {
+    let mut out = String::new();
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => out.push(byte as char),
+            _ => out.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    out
+}
@@ -1,6 +1,6 @@
-use core::fmt::Display;
+use core::fmt::{self, Display};
 
-use crate::{common::{SymbolLookupError, SymbolPath, UseTableError}, lexer::{OpenCloseSymbol, Token}};
+use crate::{common::{SpanId, SymbolLookupError, SymbolPath, UseTableError}, lexer::{OpenCloseSymbol, Token}};
 
 
 // TODO: Split into distinct error subsets
@@ -16,6 +16,24 @@ use crate::{common::{SymbolLookupError, SymbolPath, UseTableError}, lexer::{Open
 // E8000-E8999: Reserved
 // E9000-E9999: Reserved
 
+/// Common interface for all error code enums
+pub trait ErrorCode {
+    /// Get the numeric error code, e.g. `1003` for `E1003`
+    fn code(&self) -> u16;
+
+    /// Write the error message, without the `E####: ` prefix
+    fn fmt_message(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+}
+
+/// Display adapter that only writes the message of an error code
+pub struct ErrorMessage<'a, T: ErrorCode>(pub &'a T);
+
+impl<T: ErrorCode> Display for ErrorMessage<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt_message(f)
+    }
+}
+
 //==============================================================================================================================
 
 
@@ -74,16 +92,36 @@ pub enum LexErrorCode {
 
     InvalidCharInOp{ ch: char },
     InvalidOpSequence { name: String },
+
+    // Invalid byte sequence in source for its encoding
+    InvalidEncoding{ encoding: &'static str, bytes: Vec<u8> },
+    // Too many invalid sequences to report them all
+    TooManyInvalidEncoding{ encoding: &'static str, count: usize },
+
+    // Text is not in Unicode Normalization Form C (NFC), only emitted as a warning
+    NotNormalized{ normalized: String },
+
+    // Name or punctuation is confusable with another one, only emitted as a warning
+    Confusable{ kind: &'static str, found: String, other: String },
 }
 
 impl Display for LexErrorCode {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let code: u16 = 1000 + unsafe { *((self as *const Self).cast::<u16>()) };
-        write!(f, "E{code:04}: ")?;
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "E{:04}: ", self.code())?;
+        self.fmt_message(f)
+    }
+}
+
+impl ErrorCode for LexErrorCode {
+    fn code(&self) -> u16 {
+        1000 + unsafe { *((self as *const Self).cast::<u16>()) }
+    }
+
+    fn fmt_message(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::InternalError(err)                     => write!(f, "Internal compiler error: {err}"),
             // Lexer
-            Self::InvalidBOM(bom)                        => write!(f, "Found unsupported Byte Order Marker (BOM): {bom}, expected either no BOM or a utf-8 BOM."),
+            Self::InvalidBOM(bom)                        => write!(f, "Found unsupported Byte Order Marker (BOM): {bom}, expected either no BOM or a utf-8, utf-16 or utf-32 BOM."),
             Self::InvalidBinInLit                        => write!(f, "Found invalid character in binary literal"),
             Self::InvalidOctInLit                        => write!(f, "Found invalid character in octal literal"),
             Self::InvalidHexInLit                        => write!(f, "Found invalid character in hexadecimal integer literal"),
@@ -104,6 +142,16 @@ impl Display for LexErrorCode {
             Self::MismatchCloseSym { found, expected }   => write!(f, "Mismatch when closing block, found '{}', expected '{}'", found.as_close_display_str(), expected.as_close_display_str()),
             Self::InvalidCharInOp { ch }                 => write!(f, "Unsupported character in operator: '{ch}'"),
             Self::InvalidOpSequence { name }             => write!(f, "Unsupported character sequence in operator: {name}"),
+            Self::InvalidEncoding { encoding, bytes }    => {
+                write!(f, "Invalid {encoding} byte sequence:")?;
+                for byte in bytes {
+                    write!(f, " 0x{byte:02X}")?;
+                }
+                Ok(())
+            },
+            Self::TooManyInvalidEncoding { encoding, count } => write!(f, "Found {count} more invalid {encoding} byte sequences, the file might not be a text file"),
+            Self::NotNormalized { .. }                   => write!(f, "Found text that is not NFC normalized"),
+            Self::Confusable { kind, found, other }      => write!(f, "Found {kind} '{found}' that is confusable with '{other}'"),
         }
     }
 }
@@ -162,9 +210,18 @@ pub enum ParseErrorCode {
 }
 
 impl Display for ParseErrorCode {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let code: u16 = 2000 + unsafe { *((self as *const Self).cast::<u16>()) };
-        write!(f, "E{code:04}: ")?;
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "E{:04}: ", self.code())?;
+        self.fmt_message(f)
+    }
+}
+
+impl ErrorCode for ParseErrorCode {
+    fn code(&self) -> u16 {
+        2000 + unsafe { *((self as *const Self).cast::<u16>()) }
+    }
+
+    fn fmt_message(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::InternalError(err)                                  => write!(f, "Internal compiler error: {err}"),
 
@@ -228,12 +285,23 @@ pub enum AstErrorCode {
     TraitPropNotAllDefOrNone,
 
     ExternFuncHasGenerics,
+
+    ImplTraitNotAllowed,
 }
 
 impl Display for AstErrorCode {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let code: u16 = 3000 + unsafe { *((self as *const Self).cast::<u16>()) };
-        write!(f, "E{code:04}: ")?;
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "E{:04}: ", self.code())?;
+        self.fmt_message(f)
+    }
+}
+
+impl ErrorCode for AstErrorCode {
+    fn code(&self) -> u16 {
+        3000 + unsafe { *((self as *const Self).cast::<u16>()) }
+    }
+
+    fn fmt_message(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::InternalError(err)               => write!(f, "Internal compiler error: {err}"),
             Self::InvalidAttribute { info }        => write!(f, "Invalid attribute: {info}"),
@@ -268,6 +336,8 @@ impl Display for AstErrorCode {
 
             Self::ExternFuncHasGenerics          => write!(f, "External functions are not allowed to have generics"),
 
+            Self::ImplTraitNotAllowed            => write!(f, "`impl Trait` types are only allowed as the type of a function parameter, or as a function's return type"),
+
             #[allow(unreachable_patterns)]
             _                                     => write!(f, "Unknown AST error"),
         }
@@ -295,8 +365,9 @@ pub enum HirErrorCode {
     OperatorNoPrecedence { op: String },
     OperatorNoOrder { op0: String, op1: String },
 
-    CycleInTraitDag { cycle: String },
-    CycleInPrecedenceDag { cycle: String },
+    // Cycles store the name and span of each element in the cycle
+    CycleInTraitDag { cycle: Vec<(String, SpanId)> },
+    CycleInPrecedenceDag { cycle: Vec<(String, SpanId)> },
 
     UnknownSymbol{ err: SymbolLookupError },
     UnknownSymbolOrVar { name: String, err: SymbolLookupError },
@@ -316,13 +387,23 @@ pub enum HirErrorCode {
     UseTable { err: UseTableError },
 
     NotSupportedYet { info: &'static str },
+
+    InvariantViolation { pass: &'static str, info: String },
 }
 
 impl Display for HirErrorCode {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let code: u16 = 4000 + unsafe { *((self as *const Self).cast::<u16>()) };
-        write!(f, "E{code:04}: ")?;
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "E{:04}: ", self.code())?;
+        self.fmt_message(f)
+    }
+}
+
+impl ErrorCode for HirErrorCode {
+    fn code(&self) -> u16 {
+        4000 + unsafe { *((self as *const Self).cast::<u16>()) }
+    }
 
+    fn fmt_message(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::InternalError(err)                      => write!(f, "Internal compiler error: {err}"),
 
@@ -356,8 +437,14 @@ impl Display for HirErrorCode {
             Self::OperatorNoPrecedence { op }             => write!(f, "Operator does not have any precedence: {op}, this expression should be wrapped by parentheses to ensure a correct order"),
             Self::OperatorNoOrder { op0, op1 }            => write!(f, "Operators {op0} and {op1} do not have ordered precedences"),
 
-            Self::CycleInTraitDag { cycle }               => write!(f, "Cycle in trait DAG: {cycle}"),
-            Self::CycleInPrecedenceDag { cycle }          => write!(f, "Cycle in precedence DAG: {cycle}"),
+            Self::CycleInTraitDag { cycle }               => {
+                write!(f, "Cycle in trait DAG: ")?;
+                write_cycle(f, cycle)
+            },
+            Self::CycleInPrecedenceDag { cycle }          => {
+                write!(f, "Cycle in precedence DAG: ")?;
+                write_cycle(f, cycle)
+            },
 
             Self::UnknownSymbol { err }                   => write!(f, "Failed to find symbol: {err}"),
             Self::UnknownSymbolOrVar { name, err }        => write!(f, "Cannot find any variable names {name} in the current scope, or any symbol: {err}"),
@@ -372,8 +459,20 @@ impl Display for HirErrorCode {
 
             Self::NotSupportedYet { info }                => write!(f, "{info} is currently not supported yet"),
 
+            Self::InvariantViolation { pass, info }       => write!(f, "Internal compiler error: HIR invariant broken after pass '{pass}': {info}"),
+
             #[allow(unreachable_patterns)]
             _                                             => write!(f, "Unknown HIR error"),
         }
     }
+}
+
+fn write_cycle(f: &mut fmt::Formatter<'_>, cycle: &[(String, SpanId)]) -> fmt::Result {
+    for (name, _) in cycle {
+        write!(f, "{name} -> ")?;
+    }
+    if let Some((name, _)) = cycle.first() {
+        write!(f, "{name}")?;
+    }
+    Ok(())
 }
\ No newline at end of file
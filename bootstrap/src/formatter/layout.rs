@@ -0,0 +1,523 @@
+use std::ops::Range;
+
+use crate::{
+    common::NameTable,
+    lexer::{MetaElem, OpenCloseSymbol, Punctuation, PuncutationTable, Token, TokenStore, WeakKeyword},
+};
+
+use super::{AlignKind, FormatConfig};
+
+/// Text of a single token or comment on a line
+pub(super) struct Piece {
+    pub text:          String,
+    pub token:         Option<usize>,
+    // Number of spaces in front of the piece, ignored for the first piece on a line
+    pub spaces:        usize,
+    // Width of the whitespace in front of the piece in the source
+    pub source_spaces: usize,
+}
+
+pub(super) struct Line {
+    pub indent:       usize,
+    pub pieces:       Vec<Piece>,
+    pub blank_lines:  usize,
+    // The line was aligned by hand, i.e. it contains multiple spaces between 2 tokens
+    pub hand_aligned: bool,
+}
+
+impl Line {
+    fn first_token(&self) -> Option<usize> {
+        self.pieces.first().and_then(|piece| piece.token)
+    }
+
+    fn is_single_line(&self) -> bool {
+        self.pieces.iter().all(|piece| !piece.text.contains('\n'))
+    }
+}
+
+pub(super) struct Layout<'a> {
+    tokens:    &'a TokenStore,
+    names:     &'a NameTable,
+    puncts:    &'a PuncutationTable,
+    config:    &'a FormatConfig,
+
+    prefix:    String,
+    lines:     Vec<Line>,
+    ends_with_newline: bool,
+
+    // Indent of the lines the currently open symbols are on
+    open_stack:    Vec<usize>,
+    newlines:      usize,
+    ws_width:      Option<usize>,
+    force_newline: bool,
+    prev_token:    Option<usize>,
+}
+
+impl<'a> Layout<'a> {
+    pub fn new(tokens: &'a TokenStore, names: &'a NameTable, puncts: &'a PuncutationTable, config: &'a FormatConfig) -> Self {
+        Self {
+            tokens,
+            names,
+            puncts,
+            config,
+            prefix: String::new(),
+            lines: Vec::new(),
+            ends_with_newline: true,
+            open_stack: Vec::new(),
+            newlines: 0,
+            ws_width: None,
+            force_newline: false,
+            prev_token: None,
+        }
+    }
+
+    /// Split the tokens and comments into lines, keeping the line breaks of the source
+    pub fn build(&mut self) {
+        if self.tokens.has_bom {
+            self.prefix.push('\u{FEFF}');
+        }
+        if let Some(shebang) = &self.tokens.shebang {
+            self.lines.push(Line {
+                indent: 0,
+                pieces: vec![Piece { text: format!("#!{shebang}"), token: None, spaces: 0, source_spaces: 0 }],
+                blank_lines: 0,
+                hand_aligned: false,
+            });
+            self.force_newline = true;
+        }
+
+        for (idx, meta) in self.tokens.metadata.iter().enumerate() {
+            for elem in &meta.meta_elems {
+                self.add_trivia(elem);
+            }
+            self.add_token(idx);
+        }
+        for elem in &self.tokens.tail_meta_elems {
+            self.add_trivia(elem);
+        }
+        // A missing line break at the end of the file is not added
+        self.ends_with_newline = self.newlines > 0;
+
+        // Lines that are aligned by hand keep the spacing of the source
+        for line in &mut self.lines {
+            line.hand_aligned = line.pieces.iter().skip(1).any(|piece| piece.token.is_some() && piece.source_spaces > 1);
+            if line.hand_aligned {
+                for piece in &mut line.pieces {
+                    piece.spaces = piece.source_spaces;
+                }
+            }
+        }
+    }
+
+    fn add_trivia(&mut self, elem: &MetaElem) {
+        let mut text = String::new();
+        elem.write_source(&mut text);
+
+        let is_line_comment = match elem {
+            MetaElem::Whitespace(ws) => {
+                self.newlines += ws.matches(['\n', '\u{0085}']).count();
+                self.ws_width = Some(ws.chars().count());
+                return;
+            },
+            MetaElem::LineComment(_) |
+            MetaElem::LineDocComment(_) |
+            MetaElem::LineTopDocComment(_) => true,
+            _ => false,
+        };
+
+        let text = text.trim_end().to_string();
+        if self.newlines > 0 || self.force_newline || self.lines.is_empty() {
+            let indent = self.open_stack.last().map_or(0, |indent| indent + 1);
+            self.push_line(indent, Piece { text, token: None, spaces: 0, source_spaces: 0 }, false);
+        } else {
+            // Comments at the end of a line keep their distance to the code in front of them
+            let source_spaces = self.ws_width.unwrap_or_default();
+            let spaces = source_spaces.max(is_line_comment as usize);
+            self.lines.last_mut().unwrap().pieces.push(Piece { text, token: None, spaces, source_spaces });
+        }
+
+        self.newlines = 0;
+        self.ws_width = None;
+        self.force_newline = is_line_comment;
+    }
+
+    fn add_token(&mut self, idx: usize) {
+        let token = self.tokens.tokens[idx];
+        let text = self.tokens.token_text(idx, self.names, self.puncts).to_string();
+
+        let close_indent = match token {
+            Token::CloseSymbol(_) => self.open_stack.pop(),
+            _ => None,
+        };
+
+        if self.newlines > 0 || self.force_newline || self.lines.is_empty() {
+            let indent = match close_indent {
+                Some(indent) => indent,
+                None => self.open_stack.last().map_or(0, |indent| indent + 1) + self.is_continuation(token) as usize,
+            };
+            let is_close = close_indent.is_some();
+            self.push_line(indent, Piece { text, token: Some(idx), spaces: 0, source_spaces: 0 }, is_close);
+        } else {
+            let line = self.lines.last_mut().unwrap();
+            let had_ws = self.ws_width.is_some();
+            let spaces = match line.pieces.last().and_then(|piece| piece.token) {
+                Some(prev) => space_between(self.tokens.tokens[prev], token, had_ws),
+                None       => had_ws,
+            } as usize;
+            line.pieces.push(Piece { text, token: Some(idx), spaces, source_spaces: self.ws_width.unwrap_or_default() });
+        }
+
+        if let Token::OpenSymbol(_) = token {
+            let indent = self.lines.last().unwrap().indent;
+            self.open_stack.push(indent);
+        }
+
+        self.newlines = 0;
+        self.ws_width = None;
+        self.force_newline = false;
+        self.prev_token = Some(idx);
+    }
+
+    fn push_line(&mut self, indent: usize, piece: Piece, is_close: bool) {
+        // Blank lines are kept, but never directly follow an opening symbol or precede a closing symbol
+        let after_open = matches!(self.prev_token.map(|idx| self.tokens.tokens[idx]), Some(Token::OpenSymbol(_))) &&
+            self.lines.last().is_some_and(|line| line.pieces.last().is_some_and(|piece| piece.token.is_some()));
+        let blank_lines = if after_open || is_close {
+            0
+        } else if self.lines.is_empty() {
+            // Blank lines at the start of the file
+            self.newlines
+        } else {
+            self.newlines.saturating_sub(1)
+        };
+
+        self.lines.push(Line {
+            indent,
+            pieces: vec![piece],
+            blank_lines,
+            hand_aligned: false,
+        });
+    }
+
+    /// Check if a line starting with `token` continues the expression on the line before it
+    fn is_continuation(&self, token: Token) -> bool {
+        let prev_continues = match self.prev_token.map(|idx| self.tokens.tokens[idx]) {
+            Some(Token::Punctuation(punct)) => !matches!(punct,
+                Punctuation::Comma | Punctuation::Semicolon | Punctuation::Exclaim | Punctuation::Question | Punctuation::At
+            ),
+            _ => false,
+        };
+        // A leading `.` can also start an inferred path, so this only continues an unfinished statement
+        let prev_finished = matches!(self.prev_token.map(|idx| self.tokens.tokens[idx]),
+            None | Some(Token::Punctuation(Punctuation::Semicolon | Punctuation::Comma) | Token::OpenSymbol(_) | Token::CloseSymbol(OpenCloseSymbol::Brace))
+        );
+        let continues_prev = !prev_finished && matches!(token, Token::Punctuation(
+            Punctuation::Dot | Punctuation::QuestionDot | Punctuation::SingleArrowR | Punctuation::DoubleArrow | Punctuation::Equals
+        ));
+        prev_continues || continues_prev
+    }
+
+    fn indent_width(&self, indent: usize) -> usize {
+        indent * self.config.indent_width
+    }
+
+    fn line_width(&self, line: &Line) -> usize {
+        let mut width = self.indent_width(line.indent);
+        for (idx, piece) in line.pieces.iter().enumerate() {
+            if idx != 0 {
+                width += piece.spaces;
+            }
+            width += piece.text.chars().count();
+        }
+        width
+    }
+
+    /// Split lines that are too long, by putting each element of the outermost list on the line on its own line
+    pub fn split_long_lines(&mut self) {
+        let mut idx = 0;
+        while idx < self.lines.len() {
+            let line = &self.lines[idx];
+            if self.line_width(line) <= self.config.max_width || !line.is_single_line() || line.pieces.iter().any(|piece| piece.token.is_none()) {
+                idx += 1;
+                continue;
+            }
+
+            match self.split_line(idx) {
+                // Check the new lines again, as they might still be too long
+                Some(new_lines) => { self.lines.splice(idx..=idx, new_lines); },
+                None            => idx += 1,
+            }
+        }
+    }
+
+    fn split_line(&self, line_idx: usize) -> Option<Vec<Line>> {
+        let line = &self.lines[line_idx];
+        let tokens = &self.tokens.tokens;
+        let token_of = |piece: &Piece| tokens[piece.token.unwrap()];
+
+        // Find the first list that is opened and closed on this line, and contains at least 1 comma
+        let mut depth = 0;
+        let mut group = None;
+        for (idx, piece) in line.pieces.iter().enumerate() {
+            match token_of(piece) {
+                Token::OpenSymbol(_) => {
+                    if depth == 0 {
+                        group = Some((idx, Vec::new()));
+                    }
+                    depth += 1;
+                },
+                Token::CloseSymbol(_) => {
+                    depth -= 1;
+                    if depth == 0 {
+                        match group.take() {
+                            Some((open, commas)) if !commas.is_empty() && idx > open + 1 => return Some(self.split_group(line, open, &commas, idx)),
+                            _ => {},
+                        }
+                    }
+                },
+                Token::Punctuation(Punctuation::Comma) if depth == 1 => {
+                    if let Some((_, commas)) = &mut group {
+                        commas.push(idx);
+                    }
+                },
+                _ => {},
+            }
+        }
+        None
+    }
+
+    fn split_group(&self, line: &Line, open: usize, commas: &[usize], close: usize) -> Vec<Line> {
+        let copy = |range: Range<usize>| -> Vec<Piece> {
+            line.pieces[range].iter()
+                .enumerate()
+                .map(|(idx, piece)| Piece {
+                    text: piece.text.clone(),
+                    token: piece.token,
+                    spaces: if idx == 0 { 0 } else { piece.spaces },
+                    source_spaces: piece.source_spaces,
+                })
+                .collect()
+        };
+        let new_line = |indent: usize, pieces: Vec<Piece>, blank_lines: usize| Line {
+            indent,
+            pieces,
+            blank_lines,
+            hand_aligned: line.hand_aligned,
+        };
+
+        let mut lines = vec![new_line(line.indent, copy(0..open + 1), line.blank_lines)];
+        let mut start = open + 1;
+        for &comma in commas.iter().chain(std::iter::once(&close)) {
+            let end = if comma == close { close } else { comma + 1 };
+            if end > start {
+                lines.push(new_line(line.indent + 1, copy(start..end), 0));
+            }
+            start = end;
+        }
+        lines.push(new_line(line.indent, copy(close..line.pieces.len()), 0));
+        lines
+    }
+
+    /// Align the members of the block that is made up of the tokens in `range`
+    pub fn align(&mut self, kind: AlignKind, range: Range<usize>) {
+        let tokens = &self.tokens.tokens;
+        let Some(open) = range.clone().find(|&idx| matches!(tokens[idx], Token::OpenSymbol(OpenCloseSymbol::Brace))) else {
+            return;
+        };
+        let Some(open_line) = self.lines.iter().position(|line| line.pieces.iter().any(|piece| piece.token == Some(open))) else {
+            return;
+        };
+        let member_indent = self.lines[open_line].indent + 1;
+
+        let mut groups = vec![Vec::new()];
+        for line_idx in open_line + 1..self.lines.len() {
+            let line = &self.lines[line_idx];
+            let Some(first) = line.first_token() else {
+                // Comments split groups
+                groups.push(Vec::new());
+                continue;
+            };
+            if !range.contains(&first) || line.indent < member_indent {
+                break;
+            }
+            if line.blank_lines > 0 {
+                groups.push(Vec::new());
+            }
+            // Lines inside of a member, or closing one, are part of that member
+            if line.indent > member_indent || matches!(self.tokens.tokens[first], Token::CloseSymbol(_)) {
+                continue;
+            }
+
+            if line.is_single_line() && !self.anchors(kind, line).is_empty() {
+                groups.last_mut().unwrap().push(line_idx);
+            } else {
+                groups.push(Vec::new());
+            }
+        }
+
+        for group in groups {
+            self.align_group(kind, &group);
+        }
+    }
+
+    /// Align a group of member lines, only groups that were already aligned by hand are aligned, so that they stay aligned when their members change
+    fn align_group(&mut self, kind: AlignKind, group: &[usize]) {
+        if group.len() < 2 || !group.iter().any(|&line_idx| self.lines[line_idx].hand_aligned) {
+            return;
+        }
+
+        let num_anchors = match kind {
+            AlignKind::OpSet => 3,
+            AlignKind::Precedence |
+            AlignKind::Match => 1,
+        };
+
+        for anchor_idx in 0..num_anchors {
+            let mut columns = Vec::new();
+            for &line_idx in group {
+                let line = &self.lines[line_idx];
+                if let Some(&(_, piece_idx)) = self.anchors(kind, line).iter().find(|(idx, _)| *idx == anchor_idx) {
+                    columns.push((line_idx, piece_idx, self.column_of(line, piece_idx)));
+                }
+            }
+
+            let Some(target) = columns.iter().map(|(_, _, column)| *column).max() else {
+                continue;
+            };
+            for (line_idx, piece_idx, column) in columns {
+                self.lines[line_idx].pieces[piece_idx].spaces += target - column;
+            }
+        }
+    }
+
+    fn column_of(&self, line: &Line, piece_idx: usize) -> usize {
+        let mut column = 0;
+        for (idx, piece) in line.pieces[..=piece_idx].iter().enumerate() {
+            if idx != 0 {
+                column += piece.spaces;
+            }
+            if idx != piece_idx {
+                column += piece.text.chars().count();
+            }
+        }
+        column
+    }
+
+    /// Get the pieces on a member line that need to be aligned, as pairs of the anchor index and the piece index
+    fn anchors(&self, kind: AlignKind, line: &Line) -> Vec<(usize, usize)> {
+        let tokens = &self.tokens.tokens;
+
+        // Only look at tokens that are not nested in the line
+        let mut depth = 0;
+        let mut line_tokens = Vec::new();
+        for (idx, piece) in line.pieces.iter().enumerate() {
+            let Some(token_idx) = piece.token else { continue };
+            let token = tokens[token_idx];
+            if let Token::CloseSymbol(_) = token {
+                depth -= 1;
+            }
+            if depth == 0 {
+                line_tokens.push((idx, token));
+            }
+            if let Token::OpenSymbol(_) = token {
+                depth += 1;
+            }
+        }
+
+        let mut anchors = Vec::new();
+        match kind {
+            AlignKind::OpSet => {
+                if !matches!(line_tokens.first(), Some((_, Token::WeakKw(WeakKeyword::Infix | WeakKeyword::Prefix | WeakKeyword::Postfix | WeakKeyword::Assign)))) {
+                    return anchors;
+                }
+
+                let mut next_anchor = 0;
+                for &(idx, token) in &line_tokens {
+                    let anchor = match token {
+                        Token::Punctuation(Punctuation::Colon)        => 0,
+                        Token::Punctuation(Punctuation::SingleArrowR) => 1,
+                        Token::Punctuation(Punctuation::Equals)       => 2,
+                        _ => continue,
+                    };
+                    if anchor >= next_anchor {
+                        anchors.push((anchor, idx));
+                        next_anchor = anchor + 1;
+                    }
+                }
+            },
+            AlignKind::Precedence => {
+                if let [(_, Token::Name(_) | Token::WeakKw(_)), (_, Token::Punctuation(Punctuation::Colon)), (value, _), ..] = line_tokens[..] {
+                    anchors.push((0, value));
+                }
+            },
+            AlignKind::Match => {
+                if let Some(&(idx, _)) = line_tokens.iter().find(|(_, token)| *token == Token::Punctuation(Punctuation::DoubleArrow)) {
+                    anchors.push((0, idx));
+                }
+            },
+        }
+        anchors
+    }
+
+    pub fn render(&self, crlf: bool) -> String {
+        let newline = if crlf { "\r\n" } else { "\n" };
+        let indent_unit = if self.config.hard_tabs { "\t".to_string() } else { " ".repeat(self.config.indent_width) };
+
+        let mut out = self.prefix.clone();
+        for (line_idx, line) in self.lines.iter().enumerate() {
+            for _ in 0..line.blank_lines {
+                out.push_str(newline);
+            }
+
+            let mut text = indent_unit.repeat(line.indent);
+            for (idx, piece) in line.pieces.iter().enumerate() {
+                if idx != 0 {
+                    text.extend(std::iter::repeat_n(' ', piece.spaces));
+                }
+                text.push_str(&piece.text);
+            }
+            out.push_str(text.trim_end());
+            if line_idx + 1 != self.lines.len() || self.ends_with_newline {
+                out.push_str(newline);
+            }
+        }
+        out
+    }
+}
+
+fn is_word(token: Token) -> bool {
+    matches!(token, Token::StrongKw(_) | Token::WeakKw(_) | Token::Name(_) | Token::Literal(_) | Token::Underscore)
+}
+
+/// Check if 2 tokens on the same line need to be separated by a space.
+/// When this is ambiguous, e.g. for operators which could either be unary or binary, whether the source had whitespace is kept.
+fn space_between(prev: Token, next: Token, had_ws: bool) -> bool {
+    use Punctuation as P;
+
+    let is_list_open = |token| matches!(token, Token::OpenSymbol(sym) if sym != OpenCloseSymbol::Brace);
+    let is_list_close = |token| matches!(token, Token::CloseSymbol(sym) if sym != OpenCloseSymbol::Brace);
+    // Tokens that a following `.`, `(` or `[` is attached to, e.g. for field accesses, calls and indexing
+    let is_attachable = |token| matches!(token, Token::WeakKw(_) | Token::Name(_) | Token::Literal(_) | Token::Underscore | Token::CloseSymbol(_));
+
+    match (prev, next) {
+        (_, Token::Punctuation(P::Comma | P::Semicolon))                                => false,
+        (prev, _) if is_list_open(prev)                                                 => false,
+        (_, next) if is_list_close(next)                                                => false,
+        (Token::OpenSymbol(OpenCloseSymbol::Brace), Token::CloseSymbol(OpenCloseSymbol::Brace)) => false,
+        (Token::OpenSymbol(OpenCloseSymbol::Brace), _) |
+        (_, Token::CloseSymbol(OpenCloseSymbol::Brace))                                 => had_ws,
+        (Token::Punctuation(P::Comma | P::Semicolon), _)                                => true,
+        (Token::Punctuation(P::Dot | P::QuestionDot), _)                                => false,
+        (prev, Token::Punctuation(P::Dot | P::QuestionDot))                             => had_ws && !is_attachable(prev),
+        (Token::Punctuation(P::At), _)                                                  => false,
+        // Colons are used for labels, paths and literal suffixes, which are all written without spaces
+        (Token::Punctuation(P::Colon), _) |
+        (_, Token::Punctuation(P::Colon))                                               => had_ws,
+        (Token::Punctuation(P::Equals | P::SingleArrowR | P::DoubleArrow | P::ColonEquals | P::AndAnd), _) |
+        (_, Token::Punctuation(P::Equals | P::SingleArrowR | P::DoubleArrow | P::ColonEquals | P::AndAnd)) => true,
+        (prev, next) if is_list_open(next)                                              => had_ws && !is_attachable(prev),
+        (_, Token::OpenSymbol(OpenCloseSymbol::Brace))                                  => true,
+        (prev, next) if is_word(prev) && is_word(next)                                  => true,
+        _                                                                               => had_ws,
+    }
+}
@@ -0,0 +1,239 @@
+use std::ops::Range;
+
+use crate::{
+    ast::{Ast, AstNode, AstNodeRef, MatchExpr, OpSet, Parser, Precedence, Visitor},
+    common::{Diagnostic, NameTable, Severity, SpanRegistry},
+    lexer::{Lexer, MetaElem, PuncutationTable, TokenStore},
+    literals::LiteralTable,
+};
+
+mod layout;
+use layout::*;
+
+pub struct FormatConfig {
+    /// Number of spaces per indentation level, ignored when using hard tabs
+    pub indent_width: usize,
+    pub hard_tabs:    bool,
+    /// Maximum width of a line, before lists within it are split over multiple lines
+    pub max_width:    usize,
+    /// Keep the members of `op trait` and `precedence` blocks, and the arms of `match` expressions aligned, when they are already aligned by hand
+    pub align:        bool,
+}
+
+impl Default for FormatConfig {
+    fn default() -> Self {
+        Self {
+            indent_width: 4,
+            hard_tabs: false,
+            max_width: 120,
+            align: true,
+        }
+    }
+}
+
+/// Canonical source formatter.
+///
+/// Formatting is done on the tokens of a file, using the AST to find blocks that need to be aligned.
+/// All comments, blank lines and the line structure of the source are kept, apart from long lists being split.
+/// Lines that are aligned by hand, i.e. that contain multiple spaces between tokens, keep the spacing of the source.
+pub struct Formatter<'a> {
+    config: &'a FormatConfig,
+}
+
+impl<'a> Formatter<'a> {
+    pub fn new(config: &'a FormatConfig) -> Self {
+        Self { config }
+    }
+
+    /// Format a file, files with lexer or parser errors are not formatted, and their errors are returned instead
+    pub fn format(&self, file: &str, source: &str, spans: &SpanRegistry) -> Result<String, Vec<Diagnostic>> {
+        let literals = LiteralTable::new();
+        let names = NameTable::new();
+        let puncts = PuncutationTable::new();
+
+        let (tokens, errors) = Self::lex(file, source, &literals, &names, &puncts, spans);
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let mut parser = Parser::new(&tokens, &names, spans);
+        parser.parse();
+        let Parser { mut ast, errors, .. } = parser;
+        if !errors.is_empty() {
+            return Err(errors.iter().map(|err| err.to_diagnostic(&tokens)).collect());
+        }
+        ast.tokens = tokens;
+
+        let mut regions = AlignRegionCollector { ast: &ast, regions: Vec::new() };
+        if self.config.align {
+            regions.visit(&ast);
+        }
+
+        let mut layout = Layout::new(&ast.tokens, &names, &puncts, self.config);
+        layout.build();
+        layout.split_long_lines();
+        for (kind, range) in &regions.regions {
+            layout.align(*kind, range.clone());
+        }
+        let output = layout.render(source.contains("\r\n"));
+
+        // Make sure that formatting never changes the meaning of a file, the output is lexed as a separate file, so the original source stays available
+        let formatted_file = format!("{file} (formatted)");
+        let (new_tokens, errors) = Self::lex(&formatted_file, &output, &literals, &names, &puncts, spans);
+        if !errors.is_empty() || !Self::tokens_match(&ast.tokens, &new_tokens, &names, &puncts) {
+            return Err(vec![Diagnostic::new(Severity::Error, format!("Formatting {file} would change its meaning, the file was left untouched"))]);
+        }
+
+        Ok(output)
+    }
+
+    fn lex(file: &str, source: &str, literals: &LiteralTable, names: &NameTable, puncts: &PuncutationTable, spans: &SpanRegistry) -> (TokenStore, Vec<Diagnostic>) {
+        let mut lexer = Lexer::new(file, source, literals, names, puncts, spans);
+        // The formatter keeps names exactly as they were written
+        lexer.skip_normalization = true;
+        lexer.lex();
+        let Lexer { tokens, errors, .. } = lexer;
+        (tokens, errors.iter().map(|err| err.to_diagnostic()).collect())
+    }
+
+    fn tokens_match(old: &TokenStore, new: &TokenStore, names: &NameTable, puncts: &PuncutationTable) -> bool {
+        let comments = |tokens: &TokenStore| -> Vec<String> {
+            tokens.metadata.iter()
+                .flat_map(|meta| &meta.meta_elems)
+                .chain(&tokens.tail_meta_elems)
+                .filter(|elem| !matches!(elem, MetaElem::Whitespace(_)))
+                .map(|elem| {
+                    let mut text = String::new();
+                    elem.write_source(&mut text);
+                    text.trim_end().to_string()
+                })
+                .collect()
+        };
+
+        old.tokens.len() == new.tokens.len() &&
+        (0..old.tokens.len()).all(|idx| old.token_text(idx, names, puncts) == new.token_text(idx, names, puncts)) &&
+        comments(old) == comments(new)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum AlignKind {
+    OpSet,
+    Precedence,
+    Match,
+}
+
+/// Collect the token ranges of all blocks that need their members aligned
+struct AlignRegionCollector<'a> {
+    ast:     &'a Ast,
+    regions: Vec<(AlignKind, Range<usize>)>,
+}
+
+impl AlignRegionCollector<'_> {
+    fn add(&mut self, kind: AlignKind, node: &dyn AstNode) {
+        let meta = &self.ast.meta[node.node_id().index()];
+        self.regions.push((kind, meta.first_tok as usize..meta.last_tok as usize));
+    }
+}
+
+impl Visitor for AlignRegionCollector<'_> {
+    fn visit_op_set(&mut self, node: &AstNodeRef<OpSet>) {
+        self.add(AlignKind::OpSet, &**node);
+    }
+
+    fn visit_precedence(&mut self, node: &AstNodeRef<Precedence>) {
+        self.add(AlignKind::Precedence, &**node);
+    }
+
+    fn visit_match_expr(&mut self, node: &AstNodeRef<MatchExpr>) {
+        self.add(AlignKind::Match, &**node);
+        crate::ast::helpers::visit_match_expr(self, node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::{Path, PathBuf}};
+
+    use crate::common::SpanRegistry;
+
+    use super::{FormatConfig, Formatter};
+
+    fn root_dir() -> &'static Path {
+        Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap()
+    }
+
+    fn files_with_extension(dir: &Path, ext: &str) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = fs::read_dir(dir).unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|path_ext| path_ext == ext))
+            .collect();
+        files.sort();
+        assert!(!files.is_empty(), "no .{ext} files in {}", dir.display());
+        files
+    }
+
+    fn format(path: &Path, source: &str) -> String {
+        let config = FormatConfig::default();
+        let spans = SpanRegistry::new();
+        match Formatter::new(&config).format(&path.to_string_lossy(), source, &spans) {
+            Ok(formatted) => formatted,
+            Err(errors) => panic!("failed to format {}: {:?}", path.display(), errors.iter().map(|diag| &diag.message).collect::<Vec<_>>()),
+        }
+    }
+
+    /// Every `<name>.xn` in `tests/fmt` is formatted and compared against `<name>.fmt`
+    #[test]
+    fn test_golden_files() {
+        for path in files_with_extension(&root_dir().join("tests/fmt"), "xn") {
+            let source = fs::read_to_string(&path).unwrap();
+            let expected = fs::read_to_string(path.with_extension("fmt")).unwrap();
+
+            let formatted = format(&path, &source);
+            assert!(formatted == expected, "{} is not formatted as expected, got:\n{formatted}", path.display());
+
+            let reformatted = format(&path, &formatted);
+            assert!(reformatted == formatted, "formatting {} is not idempotent, got:\n{reformatted}", path.display());
+        }
+    }
+
+    /// Formatting the output of the formatter again should not change it
+    #[test]
+    fn test_idempotent() {
+        fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    collect_files(&path, files);
+                } else if path.extension().is_some_and(|ext| ext == "xn") {
+                    files.push(path);
+                }
+            }
+        }
+
+        let mut files = Vec::new();
+        collect_files(&root_dir().join("tests"), &mut files);
+
+        let config = FormatConfig::default();
+        let formatter = Formatter::new(&config);
+        for path in files {
+            let Ok(source) = fs::read_to_string(&path) else { continue };
+            // Tests with lexer or parser errors can't be formatted
+            let spans = SpanRegistry::new();
+            let Ok(formatted) = formatter.format(&path.to_string_lossy(), &source, &spans) else { continue };
+
+            let reformatted = format(&path, &formatted);
+            assert!(reformatted == formatted, "formatting {} is not idempotent, got:\n{reformatted}", path.display());
+        }
+    }
+
+    /// The core library is formatted by hand, which needs to be kept as is
+    #[test]
+    fn test_core_unchanged() {
+        for path in files_with_extension(&root_dir().join("core/src"), "xn") {
+            let source = fs::read_to_string(&path).unwrap();
+            let formatted = format(&path, &source);
+            assert!(formatted == source, "formatting changes {}, got:\n{formatted}", path.display());
+        }
+    }
+}
@@ -0,0 +1,691 @@
+use std::{
+    collections::HashMap,
+    fs, io, panic,
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+    time,
+};
+use parking_lot::{Condvar, Mutex};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ast::{self, passes::AstPass as _, Parser, Visitor as _},
+    common::{CompilerStats, Diagnostic, LibraryPath, PassInfo, PassManager, Scope, Severity, SpanId, SpanRegistry, TimingTrace},
+    hir::{self, passes::PassContext},
+    incremental::{CacheKey, CacheTables, IncrementalCache},
+    lexer::{ConfusablesLint, Lexer, SourceFile, SourceText, TokenStore},
+    manifest::Manifest,
+    metadata::LibraryMetadata,
+    prelude::{self, PreludeMode},
+    target::{self, BuildTarget},
+};
+
+/// File that went through the front-end
+pub struct FrontEndFile {
+    pub path:   PathBuf,
+    pub source: Arc<SourceText>,
+    pub scope:  Scope,
+    pub ast:    ast::Ast,
+}
+
+/// Result of running all front-end passes over a single library, without reporting anything.
+///
+/// This is used by tools that need to look at the state of a library, instead of compiling it.
+pub struct FrontEnd {
+    pub lib_path:    LibraryPath,
+    pub files:       Vec<FrontEndFile>,
+    pub diagnostics: Vec<Diagnostic>,
+    pub hir:         hir::Hir,
+    pub ctx:         PassContext,
+    /// Whether importing a library or any HIR pass failed, symbols can only be looked up through the use table when none did
+    pub has_errors:  bool,
+}
+
+impl FrontEnd {
+    /// Run all passes on the library with `root` as its root file, like it would be when it is compiled, including its dependencies and the core library.
+    /// Dependencies with out of date metadata are built first, but their metadata isn't written.
+    ///
+    /// The sources in `overlay` are used instead of the files on disk, it needs to use canonical paths.
+    pub fn for_root(root: &Path, overlay: &HashMap<PathBuf, String>) -> Self {
+        let root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+        let (dependencies, mut target, mut diagnostics) = match target::targets_for_root(&root, None, false) {
+            Ok((dependencies, target)) => (dependencies, target, Vec::new()),
+            Err(errors)                => (Vec::new(), target::file_target(&root), errors),
+        };
+        // Paths of files are derived from the root, so they are canonical as well
+        target.root = root.to_string_lossy().to_string();
+
+        let (built, errors) = Self::build_dependencies(&dependencies);
+        diagnostics.extend(errors);
+
+        let mut front_end = Self::run(&target, overlay, &built);
+        diagnostics.append(&mut front_end.diagnostics);
+        front_end.diagnostics = diagnostics;
+        front_end
+    }
+
+    /// Build the libraries in `targets` that have out of date metadata, without writing the metadata, so tools don't change the output of builds.
+    /// Returns the metadata of the libraries that were built, by the path it would be written to, and the errors of the libraries that failed to build
+    pub fn build_dependencies(targets: &[BuildTarget]) -> (HashMap<PathBuf, LibraryMetadata>, Vec<Diagnostic>) {
+        let mut built = HashMap::new();
+        let mut errors = Vec::new();
+        for target in targets {
+            let Some(metadata_out) = &target.metadata_out else { continue };
+            if target.is_up_to_date() {
+                continue;
+            }
+
+            // The diagnostics of the library refer to its own spans, so they can't be reported with those of the library that depends on it
+            let front_end = Self::run(target, &HashMap::new(), &built);
+            if front_end.has_errors || front_end.diagnostics.iter().any(|diag| diag.severity == Severity::Error) {
+                errors.push(Diagnostic::new(Severity::Error, format!("Failed to build `{}` from '{}'", target.lib_path, target.root)));
+                continue;
+            }
+            let metadata = LibraryMetadata::export(&target.lib_path, &front_end.ctx.syms.read(), &front_end.ctx.trait_dag.read(), &front_end.ctx.puncts.read());
+            built.insert(metadata_out.clone(), metadata);
+        }
+        (built, errors)
+    }
+
+    /// Run all passes on `target`, the sources in `overlay` are used instead of the files on disk, and the metadata in `built` instead of the metadata on disk
+    pub fn run(target: &BuildTarget, overlay: &HashMap<PathBuf, String>, built: &HashMap<PathBuf, LibraryMetadata>) -> Self {
+        let ctx = PassContext::new(target.lib_path.clone());
+        let passes = PassManager::all(pass_infos());
+        let options = FrontEndOptions { jobs: default_jobs(), ..FrontEndOptions::default() };
+        let mut stats = CompilerStats::new();
+
+        let processed = process_files(target, &options, &passes, &ctx, overlay, &stats.trace);
+
+        let mut diagnostics = Vec::new();
+        let mut files = Vec::new();
+        let mut ast_ctxs = Vec::new();
+        let mut confusables = ConfusablesLint::new();
+        for file in processed {
+            diagnostics.extend(file.diagnostics().cloned());
+            let (Some(ast), Some(ast_ctx)) = (file.ast, file.ast_ctx) else { continue };
+            confusables.collect(&ast.tokens, &ctx.names.read(), &ctx.puncts.read());
+            files.push(FrontEndFile { path: ast.file.clone(), source: file.source, scope: file.scope, ast });
+            ast_ctxs.push(ast_ctx);
+        }
+        diagnostics.extend(confusables.check());
+
+        let import_errors = import_libraries(target, files.first().map(|file| &file.ast), &ctx, built);
+        let import_failed = !import_errors.is_empty();
+        diagnostics.extend(import_errors);
+
+        let mut hir = hir::Hir::new();
+        for (file, ast_ctx) in files.iter().zip(&mut ast_ctxs) {
+            lower_file(&ctx, &mut hir, &file.ast, ast_ctx);
+        }
+        for (file, ast_ctx) in files.iter().zip(&ast_ctxs) {
+            diagnostics.extend(ast_ctx.errors.lock().iter().map(|err| err.to_diagnostic(&file.ast)));
+        }
+
+        let hir_failed = hir::process_hir(&mut hir, false, false, &mut stats, &ctx, options.jobs, &passes);
+        diagnostics.extend(ctx.errors.read().iter().map(|err| err.to_diagnostic()));
+
+        Self {
+            lib_path: target.lib_path.clone(),
+            files,
+            diagnostics,
+            hir,
+            ctx,
+            has_errors: import_failed || hir_failed,
+        }
+    }
+
+    /// Get the library path for a root file, using the package manifest when the file is the root of one of its artifacts.
+    /// Otherwise the name of the package directory it is in is used, as libraries are expected to be laid out as `<package>/src/lib.xn`.
+    pub fn lib_path_for_root(root: &Path) -> LibraryPath {
+        if let Some(manifest) = Manifest::find(root).and_then(|path| Manifest::load(&path).ok()) {
+            if let Some(artifact) = manifest.artifact_for_root(root) {
+                return manifest.lib_path(artifact);
+            }
+        }
+
+        // Relative paths like `src/lib.xn` don't contain the package directory
+        let root = fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+        let dir = root.parent();
+        let dir = match dir.and_then(|dir| dir.file_name()) {
+            Some(name) if name == "src" => dir.and_then(|dir| dir.parent()),
+            _ => dir,
+        };
+        let package = dir.and_then(|dir| dir.file_name())
+            .or(root.file_stem())
+            .map_or_else(|| "main".to_string(), |name| name.to_string_lossy().to_string());
+
+        LibraryPath {
+            group: None,
+            package: package.clone(),
+            library: package,
+        }
+    }
+
+    /// Read and decode a file, a file that is valid utf-8 is kept as is, so a memory-mapped file isn't copied
+    fn read_source(file: &str, spans: &SpanRegistry) -> Result<SourceText, Vec<Diagnostic>> {
+        let source_file = SourceFile::open(file)
+            .map_err(|err| vec![Diagnostic::new(Severity::Error, format!("Failed to read {file}: {err}"))])?;
+        source_file.into_text(file, spans)
+            .map_err(|errors| errors.iter().map(|err| err.to_diagnostic()).collect())
+    }
+
+    pub fn file(&self, path: &Path) -> Option<&FrontEndFile> {
+        self.files.iter().find(|file| file.path == path)
+    }
+
+    /// Get the file and byte range of a span
+    pub fn span_location(&self, span: SpanId) -> Option<(PathBuf, usize, usize)> {
+        let spans = self.ctx.spans.read();
+        let span = spans.get(span)?;
+        let path = PathBuf::from(spans.get_file(span.file_id));
+        Some((path, span.byte_offset as usize, (span.byte_offset + span.byte_len) as usize))
+    }
+}
+
+// =============================================================================================================================
+
+/// Options of the per-file stages of the front-end
+#[derive(Default)]
+pub struct FrontEndOptions {
+    /// Number of files that are processed in parallel
+    pub jobs:               usize,
+    pub skip_normalization: bool,
+    /// Use the incremental cache, only complete front-end output is cached
+    pub incremental:        bool,
+    /// Stop after lexing
+    pub lex_only:           bool,
+    /// Stop after parsing
+    pub parse_only:         bool,
+}
+
+/// Diagnostics and duration of a front-end stage for a single file
+pub struct Stage {
+    pub diagnostics: Vec<Diagnostic>,
+    pub duration:    time::Duration,
+}
+
+/// Front-end output for a single file, files are processed in parallel and only reported once all files are processed, so the output doesn't depend on the order files are processed in
+pub struct ProcessedFile {
+    pub input_file:   String,
+    pub source:       Arc<SourceText>,
+    /// Scope of the module in the file
+    pub scope:        Scope,
+    pub read_errors:  Vec<Diagnostic>,
+    pub lex:          Option<Stage>,
+    /// Number of bytes, chars and lines that were lexed
+    pub lex_counts:   (u64, u64, u64),
+    /// Tokens, when the file isn't parsed, otherwise they are stored in the AST
+    pub tokens:       Option<TokenStore>,
+    pub parse:        Option<Stage>,
+    pub ast:          Option<ast::Ast>,
+    pub ast_passes:   Option<Stage>,
+    pub pass_timings: Vec<(&'static str, time::Duration)>,
+    pub ast_ctx:      Option<ast::passes::Context>,
+    pub sub_modules:  Vec<String>,
+    /// Time it took to load the file from the incremental cache, if it was loaded from it
+    pub cache_load:   Option<time::Duration>,
+    pub cache_errors: Vec<Diagnostic>,
+}
+
+/// Front-end output of a file that is stored in the incremental cache
+#[derive(Serialize)]
+struct CachedFileRef<'a> {
+    lex_counts:    (u64, u64, u64),
+    lex_diags:     &'a [Diagnostic],
+    parse_diags:   &'a [Diagnostic],
+    ast:           &'a ast::Ast,
+    ast_diags:     &'a [Diagnostic],
+    ctx_nodes:     &'a [ast::passes::ContextNode],
+    sub_modules:   &'a [(PathBuf, Scope)],
+    module_probes: &'a [(PathBuf, bool)],
+}
+
+#[derive(Deserialize)]
+struct CachedFile {
+    lex_counts:    (u64, u64, u64),
+    lex_diags:     Vec<Diagnostic>,
+    parse_diags:   Vec<Diagnostic>,
+    ast:           ast::Ast,
+    ast_diags:     Vec<Diagnostic>,
+    ctx_nodes:     Vec<ast::passes::ContextNode>,
+    /// Paths and scopes of the sub-modules in other files
+    sub_modules:   Vec<(PathBuf, Scope)>,
+    /// Paths that were checked for module files, and whether they were files
+    module_probes: Vec<(PathBuf, bool)>,
+}
+
+impl CachedFile {
+    /// The per-file passes look for the files of sub-modules, so their output is only valid if those files still exist, and the ones that didn't still don't
+    fn is_up_to_date(&self) -> bool {
+        self.module_probes.iter().all(|(path, is_file)| path.is_file() == *is_file)
+    }
+}
+
+impl ProcessedFile {
+    fn new(input_file: String, scope: Scope) -> Self {
+        Self {
+            input_file,
+            source: Arc::new(SourceText::Decoded(String::new())),
+            scope,
+            read_errors: Vec::new(),
+            lex: None,
+            lex_counts: (0, 0, 0),
+            tokens: None,
+            parse: None,
+            ast: None,
+            ast_passes: None,
+            pass_timings: Vec::new(),
+            ast_ctx: None,
+            sub_modules: Vec::new(),
+            cache_load: None,
+            cache_errors: Vec::new(),
+        }
+    }
+
+    /// Get all diagnostics of the file, in the order of the stages that reported them
+    pub fn diagnostics(&self) -> impl Iterator<Item = &Diagnostic> {
+        let stages = [&self.lex, &self.parse, &self.ast_passes];
+        self.read_errors.iter()
+            .chain(stages.into_iter().flatten().flat_map(|stage| &stage.diagnostics))
+            .chain(&self.cache_errors)
+    }
+
+    /// Fill in the front-end output from a file loaded from the incremental cache
+    fn load_cached(&mut self, cached: CachedFile, ast_ctx: ast::passes::Context, load_duration: time::Duration) {
+        let stage = |diagnostics| Some(Stage { diagnostics, duration: time::Duration::ZERO });
+        self.lex = stage(cached.lex_diags);
+        self.lex_counts = cached.lex_counts;
+        self.parse = stage(cached.parse_diags);
+        self.ast = Some(cached.ast);
+        self.ast_passes = stage(cached.ast_diags);
+        self.ast_ctx = Some(ast_ctx);
+        self.sub_modules = cached.sub_modules.iter().map(|(path, _)| path.to_str().unwrap().to_string()).collect();
+        self.cache_load = Some(load_duration);
+    }
+
+    /// Store the front-end output in the incremental cache, this can only be done after all per-file passes have run
+    fn store_cached(&self, cache: &IncrementalCache, key: CacheKey, tables: CacheTables, sub_modules: &[(PathBuf, Scope)], module_probes: &[(PathBuf, bool)]) -> io::Result<()> {
+        let (Some(lex), Some(parse), Some(ast), Some(ast_passes), Some(ast_ctx)) = (&self.lex, &self.parse, &self.ast, &self.ast_passes, &self.ast_ctx) else {
+            return Ok(());
+        };
+        let cached = CachedFileRef {
+            lex_counts: self.lex_counts,
+            lex_diags: &lex.diagnostics,
+            parse_diags: &parse.diagnostics,
+            ast,
+            ast_diags: &ast_passes.diagnostics,
+            ctx_nodes: ast_ctx.nodes(),
+            sub_modules,
+            module_probes,
+        };
+        cache.store(key, &cached, tables)
+    }
+
+    fn do_ast_pass<F>(&mut self, passes: &PassManager, trace: &TimingTrace, pass_name: &'static str, f: F) where
+        F: FnOnce()
+    {
+        if !passes.should_run(pass_name) {
+            return;
+        }
+        let start = time::Instant::now();
+        f();
+        self.pass_timings.push((pass_name, time::Instant::now() - start));
+        trace.add_span(pass_name, "ast pass", start, file_trace_args(&self.input_file));
+    }
+}
+
+/// Arguments of a trace span for a single file
+pub fn file_trace_args(input_file: &str) -> serde_json::Map<String, serde_json::Value> {
+    let mut args = serde_json::Map::new();
+    args.insert("file".to_string(), input_file.into());
+    args
+}
+
+/// Lex, parse and run the per-file AST passes on all files of the module tree of `target`, or load their output from the incremental cache.
+/// The sources in `overlay` are used instead of the files on disk, it is looked up using canonical paths.
+///
+/// Nothing is reported, the files are returned in module tree order, starting with the root file
+pub fn process_files(target: &BuildTarget, options: &FrontEndOptions, passes: &PassManager, ctx: &PassContext, overlay: &HashMap<PathBuf, String>, trace: &TimingTrace) -> Vec<ProcessedFile> {
+    let library_path = &target.lib_path;
+
+    // Only complete front-end output is cached
+    let all_ast_passes = ast::passes::AST_PASSES.iter().all(|pass| passes.should_run(pass.name));
+    let use_cache = options.incremental && !options.lex_only && !options.parse_only && all_ast_passes;
+    let cache = use_cache.then(|| IncrementalCache::new(target.cache_dir.clone()));
+
+    // The tables only need to be read-locked to add to them, so files can be processed in parallel
+    process_module_tree(PathBuf::from(&target.root), options.jobs, |path, base_scope| {
+        let input_file = path.to_str().unwrap().to_string();
+        let mut file = ProcessedFile::new(input_file.clone(), base_scope.clone());
+
+        let overlay_source = match overlay.is_empty() {
+            true  => None,
+            false => overlay.get(&fs::canonicalize(&path).unwrap_or_else(|_| path.clone())),
+        };
+        file.source = match overlay_source {
+            Some(source) => Arc::new(SourceText::Decoded(source.clone())),
+            None => match FrontEnd::read_source(&input_file, &ctx.spans.read()) {
+                Ok(source) => Arc::new(source),
+                Err(errors) => {
+                    file.read_errors = errors;
+                    return (file, Vec::new());
+                },
+            },
+        };
+        let file_content = &**file.source;
+
+        let spans = ctx.spans.read();
+        // The lexer only stores the source of the file when it isn't stored yet, so this shares it instead of copying it
+        spans.share_file_source(&input_file, file.source.clone());
+        let names = ctx.names.read();
+        let puncts = ctx.puncts.read();
+        let lits = ctx.lits.read();
+
+        let tables = CacheTables { names: &names, puncts: &puncts, lits: &lits, spans: &spans };
+        let cache_key = CacheKey::new(&input_file, file_content, &base_scope, &[options.skip_normalization]);
+        let load_start = time::Instant::now();
+        let cached = cache.as_ref()
+            .and_then(|cache| cache.load::<CachedFile>(cache_key, tables))
+            .filter(CachedFile::is_up_to_date);
+        if let Some(mut cached) = cached {
+            // Redo the changes the per-file passes made outside of the file
+            let sub_paths = std::mem::take(&mut cached.sub_modules);
+            {
+                let mut syms = ctx.syms.write();
+                for (path, scope) in &sub_paths {
+                    syms.add_module(None, &scope.parent(), &scope.last().unwrap().name, path.clone());
+                }
+            }
+
+            let ctx_nodes = std::mem::take(&mut cached.ctx_nodes);
+            let ast_ctx = ast::passes::Context::with_nodes(library_path.clone(), ctx.syms.clone(), base_scope, ctx_nodes, ctx.precedence_dag.clone());
+            cached.sub_modules = sub_paths.clone();
+            file.load_cached(cached, ast_ctx, time::Instant::now() - load_start);
+            trace.add_span("Incremental cache load", "cache", load_start, file_trace_args(&input_file));
+            return (file, sub_paths);
+        }
+
+        let lex_start = time::Instant::now();
+        let mut lexer = Lexer::new(&input_file, file_content, &lits, &names, &puncts, &spans);
+        lexer.skip_normalization = options.skip_normalization;
+        lexer.lex();
+        file.lex_counts = lexer.stats();
+
+        let Lexer { tokens, errors, warnings, .. } = lexer;
+        file.lex = Some(Stage {
+            diagnostics: errors.iter().chain(&warnings).map(|err| err.to_diagnostic()).collect(),
+            duration: time::Instant::now() - lex_start,
+        });
+        trace.add_span("Lex", "lex", lex_start, file_trace_args(&input_file));
+
+        if options.lex_only {
+            file.tokens = Some(tokens);
+            return (file, Vec::new());
+        }
+
+        let parse_start = time::Instant::now();
+        let mut parser = Parser::new(&tokens, &names, &spans);
+        parser.parse();
+
+        let Parser { mut ast, errors, .. } = parser;
+        file.parse = Some(Stage {
+            diagnostics: errors.iter().map(|err| err.to_diagnostic(&tokens)).collect(),
+            duration: time::Instant::now() - parse_start,
+        });
+        trace.add_span("Parse", "parse", parse_start, file_trace_args(&input_file));
+        ast.tokens = tokens;
+        ast.file = input_file.clone().into();
+
+        if options.parse_only {
+            file.ast = Some(ast);
+            return (file, Vec::new());
+        }
+
+        let ast_start = time::Instant::now();
+
+        let mut ast_ctx = ast::passes::Context::new(
+            library_path.clone(),
+            ctx.syms.clone(),
+            base_scope.clone(),
+            &ast,
+            ctx.precedence_dag.clone(),
+        );
+
+        file.do_ast_pass(passes, trace, ast::passes::ContextSetup::NAME, || {
+            let mut pass = ast::passes::ContextSetup::new(&mut ast_ctx);
+            pass.visit(&ast);
+        });
+
+        file.do_ast_pass(passes, trace, ast::passes::ModuleScopePass::NAME, || {
+            let mut pass = ast::passes::ModuleScopePass::new(&mut ast_ctx, base_scope.clone(), &names);
+            pass.visit(&ast);
+        });
+
+        file.do_ast_pass(passes, trace, ast::passes::ModuleAttributeResolver::NAME, || {
+            let mut pass = ast::passes::ModuleAttributeResolver::new(&mut ast_ctx, &names, &lits);
+            pass.visit(&ast);
+        });
+
+        let mut sub_paths = Vec::new();
+        let mut module_probes = Vec::new();
+        file.do_ast_pass(passes, trace, ast::passes::ModulePathResolution::NAME, || {
+            let mut input_path = path.clone();
+            input_path.pop();
+            let mut pass = ast::passes::ModulePathResolution::new(&mut ast_ctx, &names, input_path);
+            pass.visit(&ast);
+
+            sub_paths = pass.collected_paths;
+            module_probes = pass.probed_paths;
+        });
+
+        file.ast_passes = Some(Stage {
+            diagnostics: ast_ctx.errors.lock().drain(..).map(|err| err.to_diagnostic(&ast)).collect(),
+            duration: time::Instant::now() - ast_start,
+        });
+        trace.add_span("AST passes", "ast", ast_start, file_trace_args(&input_file));
+        file.sub_modules = sub_paths.iter().map(|(path, _)| path.to_str().unwrap().to_string()).collect();
+        file.ast = Some(ast);
+        file.ast_ctx = Some(ast_ctx);
+
+        if let Some(cache) = &cache {
+            if let Err(err) = file.store_cached(cache, cache_key, tables, &sub_paths, &module_probes) {
+                file.cache_errors.push(Diagnostic::new(Severity::Warning, format!("Failed to store {input_file} in the incremental cache: {err}")));
+            }
+        }
+        (file, sub_paths)
+    })
+}
+
+/// Import the metadata of the libraries `target` depends on and of the core library, and add the uses of the prelude.
+/// This needs to happen before lowering, so uses of their precedences and operators can be resolved.
+///
+/// The root module, `root_ast`, can opt out of the core library or its prelude.
+/// Metadata in `built` is used instead of the metadata on disk, for libraries that were built without writing their metadata.
+/// Returns the errors of the libraries that couldn't be imported
+pub fn import_libraries(target: &BuildTarget, root_ast: Option<&ast::Ast>, ctx: &PassContext, built: &HashMap<PathBuf, LibraryMetadata>) -> Vec<Diagnostic> {
+    let import = |path: &Path| {
+        let loaded;
+        let metadata = match built.get(path) {
+            Some(metadata) => metadata,
+            None => {
+                loaded = LibraryMetadata::load(path)?;
+                &loaded
+            },
+        };
+        metadata.import(&mut ctx.syms.write(), &mut ctx.type_reg.write(), &mut ctx.trait_dag.write(), &ctx.puncts.read())
+    };
+
+    let mut errors = Vec::new();
+    for path in &target.dependencies {
+        if let Err(err) = import(path) {
+            errors.push(err);
+        }
+    }
+
+    let prelude_mode = root_ast.map_or(PreludeMode::Full, |ast| PreludeMode::from_attributes(ast, &ctx.names.read()));
+    let mut core = target.core.as_ref().filter(|_| prelude_mode != PreludeMode::NoCore);
+    if let Some(core_lib) = core {
+        // The core library might already be imported as a dependency
+        let is_imported = ctx.syms.read().has_lib(&core_lib.lib_path);
+        if !is_imported {
+            if let Err(err) = import(&core_lib.metadata) {
+                errors.push(err);
+                core = None;
+            }
+        }
+    }
+
+    if let (Some(core), PreludeMode::Full) = (core, prelude_mode) {
+        prelude::add_prelude_uses(&mut ctx.uses.write(), &core.lib_path);
+    }
+    errors
+}
+
+/// Lower the AST of a file to HIR, errors are added to the context of the file
+pub fn lower_file(ctx: &PassContext, hir: &mut hir::Hir, ast: &ast::Ast, ast_ctx: &mut ast::passes::Context) {
+    let spans = ctx.spans.read();
+    let mut names = ctx.names.write();
+    let lits = ctx.lits.read();
+    let mut uses = ctx.uses.write();
+    let mut pass = ast::passes::AstToHirLowering::new(ast_ctx, &mut names, &lits, &spans, hir, &mut uses, ctx.lib_path.clone());
+    pass.visit(ast);
+}
+
+
+/// Get the names and dependencies of all AST and HIR passes, in the order they run
+pub fn pass_infos() -> Vec<PassInfo> {
+    ast::passes::AST_PASSES.iter().copied().chain(hir::hir_pass_infos()).collect()
+}
+
+/// Number of threads to use when it isn't specified
+pub fn default_jobs() -> usize {
+    thread::available_parallelism().map_or(1, |jobs| jobs.get())
+}
+
+/// File waiting to be processed, `key` is the position of the file in the module tree
+struct QueuedModule {
+    key:   Vec<u32>,
+    path:  PathBuf,
+    scope: Scope,
+}
+
+struct ModuleQueue {
+    pending:   Vec<QueuedModule>,
+    in_flight: usize,
+}
+
+/// Marks a file as done when it is dropped, even when processing it panicked, so the other workers don't wait for it forever
+struct InFlightGuard<'a> {
+    queue:   &'a Mutex<ModuleQueue>,
+    cond:    &'a Condvar,
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.queue.lock().in_flight -= 1;
+        self.cond.notify_all();
+    }
+}
+
+/// Process the files of a module tree on `jobs` worker threads, starting at the root file of a library.
+///
+/// `process` is called for each file with the scope of its module, and returns its result together with the files of the sub-modules it declares, which are then queued.
+/// Results are returned in a deterministic order, independent of the order the files were processed in: each file is followed by its sub-modules, in the order they are declared.
+/// When processing a file panics, the remaining files are still processed, after which the panic is resumed on the calling thread.
+pub fn process_module_tree<R, F>(root: PathBuf, jobs: usize, process: F) -> Vec<R> where
+    R: Send,
+    F: Fn(PathBuf, Scope) -> (R, Vec<(PathBuf, Scope)>) + Sync,
+{
+    let queue = Mutex::new(ModuleQueue {
+        pending: vec![QueuedModule { key: Vec::new(), path: root, scope: Scope::new() }],
+        in_flight: 0,
+    });
+    let cond = Condvar::new();
+    let results = Mutex::new(Vec::new());
+
+    let worker = || loop {
+        let module = {
+            let mut queue = queue.lock();
+            loop {
+                if let Some(module) = queue.pending.pop() {
+                    queue.in_flight += 1;
+                    break Some(module);
+                }
+                if queue.in_flight == 0 {
+                    break None;
+                }
+                cond.wait(&mut queue);
+            }
+        };
+        let Some(QueuedModule { key, path, scope }) = module else {
+            cond.notify_all();
+            return;
+        };
+
+        let guard = InFlightGuard { queue: &queue, cond: &cond };
+        let (result, sub_modules) = process(path, scope);
+
+        {
+            let mut queue = queue.lock();
+            for (idx, (path, scope)) in sub_modules.into_iter().enumerate() {
+                let mut key = key.clone();
+                key.push(idx as u32);
+                queue.pending.push(QueuedModule { key, path, scope });
+            }
+        }
+        results.lock().push((key, result));
+        drop(guard);
+    };
+
+    thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs.max(1)).map(|_| {
+            thread::Builder::new()
+                .name("front-end".to_string())
+                .spawn_scoped(scope, worker)
+                .expect("failed to spawn front-end worker")
+        }).collect();
+
+        for worker in workers {
+            if let Err(payload) = worker.join() {
+                panic::resume_unwind(payload);
+            }
+        }
+    });
+
+    let mut results = results.into_inner();
+    results.sort_by(|(a, _), (b, _)| a.cmp(b));
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::{Component, PathBuf}};
+
+    use super::FrontEnd;
+
+    #[test]
+    fn test_lib_path_for_relative_root() {
+        let pkg_dir = env::temp_dir().join(format!("xenon-frontend-test-{}", std::process::id())).join("my_pkg");
+        fs::create_dir_all(pkg_dir.join("src")).unwrap();
+        fs::write(pkg_dir.join("src/lib.xn"), "").unwrap();
+
+        // Same path relative to the current directory
+        let cwd = env::current_dir().unwrap();
+        let mut relative = PathBuf::new();
+        for _ in cwd.components().skip(1) {
+            relative.push("..");
+        }
+        relative.extend(pkg_dir.components().filter(|comp| matches!(comp, Component::Normal(_))));
+
+        for dir in [pkg_dir.clone(), relative] {
+            // Neither the parent of the root nor its parent have the name of the package
+            let root = dir.join("src/../src/lib.xn");
+            let lib_path = FrontEnd::lib_path_for_root(&root);
+            assert_eq!(lib_path.package, "my_pkg", "wrong package for {}", root.display());
+            assert_eq!(lib_path.library, "my_pkg");
+        }
+
+        _ = fs::remove_dir_all(pkg_dir.parent().unwrap());
+    }
+}
@@ -1609,8 +1609,26 @@ impl Visitor for CodePrinter<'_> {
     }
 
     fn visit_closure_expr(&mut self, node: &mut ClosureExpr) {
-        // TODO
-        self.logger.log("<closure>");
+        if node.is_moved {
+            self.logger.log("move ");
+        }
+        if node.params.is_empty() {
+            self.logger.log("||");
+        } else {
+            self.logger.logln("|");
+            self.logger.push_indent();
+            for param in &mut node.params {
+                self.log_fn_param(param);
+            }
+            self.logger.pop_indent();
+            self.logger.prefixed_log("|");
+        }
+        if let Some(ret) = &mut node.ret {
+            self.logger.log(" -> ");
+            self.visit_type(ret);
+        }
+        self.logger.log(" ");
+        self.visit_expr(&mut node.body);
     }
 
     fn visit_loop_expr(&mut self, node: &mut LoopExpr) {
@@ -1982,6 +2000,26 @@ impl Visitor for CodePrinter<'_> {
         }
     }
 
+    fn visit_trait_object_type(&mut self, node: &mut TraitObjectType) {
+        self.logger.log("dyn ");
+        for (idx, bound) in node.bounds.bounds.iter_mut().enumerate() {
+            if idx != 0 {
+                self.logger.log(" & ");
+            }
+            self.visit_path(bound);
+        }
+    }
+
+    fn visit_impl_trait_type(&mut self, node: &mut ImplTraitType) {
+        self.logger.log("impl ");
+        for (idx, bound) in node.bounds.bounds.iter_mut().enumerate() {
+            if idx != 0 {
+                self.logger.log(" & ");
+            }
+            self.visit_path(bound);
+        }
+    }
+
     fn visit_gen_params(&mut self, node: &mut GenericParams) {
         self.logger.log("[");
 
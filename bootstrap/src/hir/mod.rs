@@ -7,12 +7,15 @@ use std::{fmt, sync::Arc};
 use parking_lot::RwLock;
 
 use crate::{
-    ast, common::{Abi, FormatSpan, NameId, OpType, PrecedenceAssocKind, Scope, SpanId, SpanRegistry, SymbolRef, TraitItemRecord, VarInfoId, VarScopeId, VariableInfo}, error_warning::{HirErrorCode, LexErrorCode}, lexer::Punctuation, literals::LiteralId, type_system
+    ast, common::{Abi, Diagnostic, NameId, OpType, PrecedenceAssocKind, Scope, SpanId, SpanRegistry, SymbolRef, TraitItemRecord, VarInfoId, VarScopeId, VariableInfo}, error_warning::{HirErrorCode, LexErrorCode}, lexer::Punctuation, literals::LiteralId, type_system
 };
 
 mod visitor;
 pub use visitor::*;
 
+mod parallel;
+pub use parallel::*;
+
 pub mod utils;
 
 mod node_logger;
@@ -22,7 +25,13 @@ mod code_printer;
 pub use code_printer::*;
 
 pub mod passes;
-pub use passes::Pass;
+pub use passes::{Pass, ParallelPass};
+
+mod process;
+pub use process::*;
+
+mod verify;
+pub use verify::*;
 
 // =============================================================================================================================
 
@@ -42,23 +51,25 @@ impl fmt::Display for HirError {
     }
 }
 
-pub struct FormatHirError<'a> {
-    spans: &'a SpanRegistry,
-    error: HirError
-}
-
-impl<'a> FormatHirError<'a> {
-    pub fn new(spans: &'a SpanRegistry, error: HirError) -> Self {
-        Self { spans, error }
-    }
-}
-
-impl fmt::Display for FormatHirError<'_> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if self.error.span == SpanId::INVALID {
-            write!(f, "{}", self.error.err)
-        } else {
-            write!(f, "{}: {}", FormatSpan::new(self.spans, self.error.span), self.error.err)
+impl HirError {
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let diag = Diagnostic::error(&self.err);
+        match &self.err {
+            HirErrorCode::CycleInTraitDag { cycle } |
+            HirErrorCode::CycleInPrecedenceDag { cycle } => {
+                let mut diag = diag;
+                for (idx, (name, span)) in cycle.iter().enumerate() {
+                    let next = &cycle[(idx + 1) % cycle.len()].0;
+                    let message = format!("`{name}` depends on `{next}`");
+                    diag = if idx == 0 {
+                        diag.with_primary(*span, message)
+                    } else {
+                        diag.with_secondary(*span, message)
+                    };
+                }
+                diag
+            },
+            _ => diag.with_primary(self.span, ""),
         }
     }
 }
@@ -1085,7 +1096,111 @@ pub struct FieldAccessExpr {
     pub is_propagating: bool,
 }
 
-// TODO: This is not correct yet
+/// How a captured place is stored in a closure, ordered from the weakest to the strongest mode
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum CaptureMode {
+    ImmutableBorrow,
+    /// Immutable borrow which has to be unique, used when assigning through a dereference of a captured place
+    UniqueImmutableBorrow,
+    MutableBorrow,
+    Move,
+}
+
+impl fmt::Display for CaptureMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptureMode::ImmutableBorrow       => write!(f, "immutable borrow"),
+            CaptureMode::UniqueImmutableBorrow => write!(f, "unique immutable borrow"),
+            CaptureMode::MutableBorrow         => write!(f, "mutable borrow"),
+            CaptureMode::Move                  => write!(f, "move"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CaptureProjection {
+    Field(NameId),
+    TupleIndex(usize),
+    Deref,
+}
+
+/// Capture rules that depend on the type of the captured place, which can't be applied yet, as the types of expressions aren't known during capture analysis.
+/// A capture with pending rules is provisional: its mode and place can still change
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct PendingCaptureRules {
+    /// The place is read by value, which copies it when its type is `Copy`, otherwise it is moved.
+    /// Until then, the place is assumed to be copied, so it only needs an immutable borrow
+    pub by_value:    bool,
+    /// The place is the receiver of a method call, which is borrowed mutably or read by value depending on the `self` of the method.
+    /// Until then, it is assumed to be borrowed immutably
+    pub receiver:    bool,
+    /// The place has projections, which are truncated at the rightmost dereference of a shared reference when it is borrowed immutably,
+    /// and before the first dereference of a pointer, union field, or field of a packed struct or bitfield.
+    /// Field accesses that implicitly dereference a reference don't have a dereference in the place yet
+    pub projections: bool,
+}
+
+impl PendingCaptureRules {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl fmt::Display for PendingCaptureRules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rules = [(self.by_value, "by value"), (self.receiver, "receiver"), (self.projections, "projections")];
+        let names: Vec<_> = rules.iter().filter(|(is_pending, _)| *is_pending).map(|(_, name)| *name).collect();
+        write!(f, "{}", names.join(", "))
+    }
+}
+
+/// Place from the surrounding environment that is captured by a closure, e.g. `foo.bar.1`
+#[derive(Clone)]
+pub struct Capture {
+    /// Span of the first use of the place within the closure
+    pub span:        SpanId,
+    pub var:         NameId,
+    /// Scope the captured variable is declared in
+    pub var_scope:   VarScopeId,
+    pub projections: Vec<CaptureProjection>,
+    pub mode:        CaptureMode,
+    pub pending:     PendingCaptureRules,
+}
+
+impl Capture {
+    /// Check if the place of this capture is the same as, or an ancestor of, the place of `other`
+    pub fn is_prefix_of(&self, other: &Capture) -> bool {
+        self.var == other.var &&
+        self.var_scope == other.var_scope &&
+        other.projections.starts_with(&self.projections)
+    }
+}
+
+#[derive(Clone)]
+pub struct ClosureContext {
+    pub var_scope: VarScopeId,
+    pub captures:  Vec<Capture>,
+}
+
+impl ClosureContext {
+    pub fn new() -> Self {
+        Self {
+            var_scope: VarScopeId::INVALID,
+            captures:  Vec::new(),
+        }
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        !self.captures.is_empty()
+    }
+}
+
+impl Default for ClosureContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Clone)]
 pub struct ClosureExpr {
     pub span:     SpanId,
@@ -1094,6 +1209,7 @@ pub struct ClosureExpr {
     pub params:   Vec<FnParam>,
     pub ret:      Option<Box<Type>>,
     pub body:     Box<Expr>,
+    pub ctx:      ClosureContext,
 }
 
 #[derive(Clone)]
@@ -1409,6 +1525,8 @@ pub enum Type {
     Reference(ReferenceType),
     Optional(OptionalType),
     Fn(FnType),
+    TraitObject(TraitObjectType),
+    ImplTrait(ImplTraitType),
 }
 
 impl Type {
@@ -1426,6 +1544,8 @@ impl Type {
             Type::Reference(ty) => &ty.ctx,
             Type::Optional(ty) => &ty.ctx,
             Type::Fn(ty) => &ty.ctx,
+            Type::TraitObject(ty) => &ty.ctx,
+            Type::ImplTrait(ty) => &ty.ctx,
         }
     }
 }
@@ -1575,6 +1695,23 @@ pub struct FnType {
     pub ctx:       TypeContext,
 }
 
+#[derive(Clone)]
+pub struct TraitObjectType {
+    pub span:    SpanId,
+    pub node_id: ast::NodeId,
+    pub bounds:  Box<TraitBounds>,
+    pub ctx:     TypeContext,
+}
+
+/// Abstract return type, argument-position `impl Trait` types are lowered to anonymous generic type parameters
+#[derive(Clone)]
+pub struct ImplTraitType {
+    pub span:    SpanId,
+    pub node_id: ast::NodeId,
+    pub bounds:  Box<TraitBounds>,
+    pub ctx:     TypeContext,
+}
+
 // =============================================================================================================================
 
 #[derive(Clone)]
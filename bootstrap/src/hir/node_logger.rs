@@ -125,7 +125,7 @@ impl NodeLogger<'_> {
     pub fn log_fn_param(&mut self, param: &mut FnParam) {
         match param {
             FnParam::Param { span, attrs, label, pattern, ty } => {
-                self.logger.prefixed_log_fmt(format_args!("Param: {}", label.map_or("", |label| &self.names[label])));
+                self.logger.prefixed_log_fmt(format_args!("Param: {}\n", label.map_or("", |label| &self.names[label])));
                 self.logger.push_indent();
                 self.log_slice_indented("Attributes", attrs, |this, attr| {
                     this.visit_attribute(attr);
@@ -135,7 +135,7 @@ impl NodeLogger<'_> {
                 self.logger.pop_indent();
             },
             FnParam::Opt { span, attrs, label, pattern, ty, def } => {
-                self.logger.prefixed_log_fmt(format_args!("Optional Param: {}", label.map_or("", |label| &self.names[label])));
+                self.logger.prefixed_log_fmt(format_args!("Optional Param: {}\n", label.map_or("", |label| &self.names[label])));
                 self.logger.push_indent();
                 self.log_slice_indented("Attributes", attrs, |this, attr| {
                     this.visit_attribute(attr);
@@ -146,7 +146,7 @@ impl NodeLogger<'_> {
                 self.logger.pop_indent();
             },
             FnParam::Variadic { span, attrs, name, ty } => {
-                self.logger.prefixed_log_fmt(format_args!("Varaidic param: {}", &self.names[*name]));
+                self.logger.prefixed_log_fmt(format_args!("Varaidic param: {}\n", &self.names[*name]));
                 self.logger.push_indent();
                 self.log_slice_indented("Attributes", attrs, |this, attr| {
                     this.visit_attribute(attr);
@@ -157,6 +157,21 @@ impl NodeLogger<'_> {
         }
     }
 
+    pub fn log_capture(&mut self, capture: &mut Capture) {
+        let mut place = self.names[capture.var].to_string();
+        for proj in &capture.projections {
+            match proj {
+                CaptureProjection::Field(name)     => { place.push('.'); place.push_str(&self.names[*name]); },
+                CaptureProjection::TupleIndex(idx) => place.push_str(&format!(".{idx}")),
+                CaptureProjection::Deref           => place.push('^'),
+            }
+        }
+        match capture.pending.is_empty() {
+            true  => self.logger.prefixed_log_fmt(format_args!("Capture: {place} ({})\n", capture.mode)),
+            false => self.logger.prefixed_log_fmt(format_args!("Capture: {place} ({}, pending: {})\n", capture.mode, capture.pending)),
+        }
+    }
+
     pub fn log_attr_meta(&mut self, attr_meta: &mut AttrMeta) {
         match attr_meta {
             AttrMeta::Simple { path } => self.log_indented("Simple Attribute Meta", |this| this.visit_simple_path(path)),
@@ -1379,7 +1394,11 @@ impl Visitor for NodeLogger<'_> {
     fn visit_closure_expr(&mut self, node: &mut ClosureExpr) {
         self.log_node("Closure expression", node.node_id, |this| {
             this.logger.prefixed_log_fmt(format_args!("Is moved: {}\n", node.is_moved));
-            // TODO
+            this.log_slice_indented("Params", &mut node.params, |this, param| this.log_fn_param(param));
+            this.log_opt_indented("Return Type", &mut node.ret, |this, ty| this.visit_type(ty));
+            this.log_slice_indented("Captures", &mut node.ctx.captures, |this, capture| this.log_capture(capture));
+            this.logger.set_last_at_indent();
+            this.log_single_indented("Body", |this| this.visit_expr(&mut node.body));
         });
     }
 
@@ -1492,9 +1511,9 @@ impl Visitor for NodeLogger<'_> {
 
     fn visit_iden_pattern(&mut self, node: &mut IdenPattern) {
         self.log_node("Identifier pattern", node.node_id, |this| {
-            this.logger.log_fmt(format_args!("Is ref: {}\n", node.is_ref));
-            this.logger.log_fmt(format_args!("Is mut: {}\n", node.is_mut));
-            this.logger.log_fmt(format_args!("Name: {}\n", &this.names[node.name]));
+            this.logger.prefixed_log_fmt(format_args!("Is ref: {}\n", node.is_ref));
+            this.logger.prefixed_log_fmt(format_args!("Is mut: {}\n", node.is_mut));
+            this.logger.prefixed_log_fmt(format_args!("Name: {}\n", &this.names[node.name]));
             this.logger.set_last_at_indent();
             this.log_opt_indented("Bound", &mut node.bound, |this, bound| this.visit_pattern(bound));
         });
@@ -1714,6 +1733,20 @@ impl Visitor for NodeLogger<'_> {
         });
     }
 
+    fn visit_trait_object_type(&mut self, node: &mut TraitObjectType) {
+        self.log_node("Trait object type", node.node_id, |this| {
+            this.logger.set_last_at_indent();
+            this.log_single_indented("Bounds", |this| this.visit_trait_bounds(&mut node.bounds));
+        });
+    }
+
+    fn visit_impl_trait_type(&mut self, node: &mut ImplTraitType) {
+        self.log_node("Impl trait type", node.node_id, |this| {
+            this.logger.set_last_at_indent();
+            this.log_single_indented("Bounds", |this| this.visit_trait_bounds(&mut node.bounds));
+        });
+    }
+
     // =============================================================
 
     fn visit_gen_params(&mut self, node: &mut GenericParams) {
@@ -0,0 +1,215 @@
+use std::{mem, panic, thread};
+
+use super::{Hir, VisitFlags, Visitor};
+
+/// Item kinds in the order they are visited by `Visitor::visit`.
+/// The members of traits, impls and op sets are kept together with the item they belong to.
+const ITEM_GROUPS: [VisitFlags; 20] = [
+    VisitFlags::Function,
+    VisitFlags::ExternFunctionNoBody,
+    VisitFlags::TypeAlias,
+    VisitFlags::DistinctType,
+    VisitFlags::OpaqueType,
+    VisitFlags::Struct,
+    VisitFlags::TupleStruct,
+    VisitFlags::UnitStruct,
+    VisitFlags::Union,
+    VisitFlags::AdtEnum,
+    VisitFlags::FlagEnum,
+    VisitFlags::Bitfield,
+    VisitFlags::Const,
+    VisitFlags::Static,
+    VisitFlags::TlsStatic,
+    VisitFlags::ExternStatic,
+    VisitFlags::AnyTrait,
+    VisitFlags::AnyImpl,
+    VisitFlags::AnyOp,
+    VisitFlags::Precedence,
+];
+
+/// Visit the items in the HIR on up to `jobs` threads.
+///
+/// Item kinds are visited one at a time, in the same order as `Visitor::visit`. The items of a kind are split into contiguous ranges,
+/// which are each moved into a separate `Hir` and visited by a worker, using its own visitor created by `new_visitor` from the index of the worker.
+/// `after_kind` is called after each kind is visited, when no worker is running, so per-worker state can be merged in the order of the items.
+pub fn visit_parallel<T, F, A>(hir: &mut Hir, flags: VisitFlags, jobs: usize, new_visitor: F, mut after_kind: A) where
+    T: Visitor,
+    F: Fn(usize) -> T + Sync,
+    A: FnMut(),
+{
+    for group in ITEM_GROUPS {
+        let flags = flags & group;
+        if flags.is_none() {
+            continue;
+        }
+
+        let mut len = GroupLen { hir, len: None };
+        group_fields(group, &mut len);
+        let len = len.len.unwrap_or_default();
+        if len == 0 {
+            continue;
+        }
+
+        let num_parts = jobs.clamp(1, len);
+        let chunk_len = len.div_ceil(num_parts);
+        let starts: Vec<usize> = (0..len).step_by(chunk_len).collect();
+        let mut parts: Vec<Hir> = starts.iter().map(|_| Hir::new()).collect();
+
+        group_fields(group, &mut SplitGroup { hir, parts: &mut parts, starts: &starts });
+
+        if parts.len() == 1 {
+            new_visitor(0).visit(&mut parts[0], flags);
+        } else {
+            thread::scope(|scope| {
+                let new_visitor = &new_visitor;
+                let workers: Vec<_> = parts.iter_mut().enumerate().map(|(idx, part)| {
+                    thread::Builder::new()
+                        .name("hir-pass".to_string())
+                        .spawn_scoped(scope, move || new_visitor(idx).visit(part, flags))
+                        .expect("failed to spawn HIR pass worker")
+                }).collect();
+
+                for worker in workers {
+                    if let Err(payload) = worker.join() {
+                        panic::resume_unwind(payload);
+                    }
+                }
+            });
+        }
+
+        group_fields(group, &mut MergeGroup { hir, parts: &mut parts, starts: &starts });
+        after_kind();
+    }
+}
+
+/// Called for each vector in the HIR that stores items of a group, `members` is used for items that store the index of the item they belong to
+trait GroupFields {
+    fn items<T>(&mut self, field: fn(&mut Hir) -> &mut Vec<T>);
+    fn members<N, C>(&mut self, field: fn(&mut Hir) -> &mut Vec<(usize, N, C)>);
+}
+
+fn group_fields(group: VisitFlags, f: &mut impl GroupFields) {
+    if group == VisitFlags::Function {
+        f.items(|hir| &mut hir.functions);
+    } else if group == VisitFlags::ExternFunctionNoBody {
+        f.items(|hir| &mut hir.extern_functions_no_body);
+    } else if group == VisitFlags::TypeAlias {
+        f.items(|hir| &mut hir.type_aliases);
+    } else if group == VisitFlags::DistinctType {
+        f.items(|hir| &mut hir.distinct_types);
+    } else if group == VisitFlags::OpaqueType {
+        f.items(|hir| &mut hir.opaque_types);
+    } else if group == VisitFlags::Struct {
+        f.items(|hir| &mut hir.structs);
+    } else if group == VisitFlags::TupleStruct {
+        f.items(|hir| &mut hir.tuple_structs);
+    } else if group == VisitFlags::UnitStruct {
+        f.items(|hir| &mut hir.unit_structs);
+    } else if group == VisitFlags::Union {
+        f.items(|hir| &mut hir.unions);
+    } else if group == VisitFlags::AdtEnum {
+        f.items(|hir| &mut hir.adt_enums);
+    } else if group == VisitFlags::FlagEnum {
+        f.items(|hir| &mut hir.flag_enums);
+    } else if group == VisitFlags::Bitfield {
+        f.items(|hir| &mut hir.bitfields);
+    } else if group == VisitFlags::Const {
+        f.items(|hir| &mut hir.consts);
+    } else if group == VisitFlags::Static {
+        f.items(|hir| &mut hir.statics);
+    } else if group == VisitFlags::TlsStatic {
+        f.items(|hir| &mut hir.tls_statics);
+    } else if group == VisitFlags::ExternStatic {
+        f.items(|hir| &mut hir.extern_statics);
+    } else if group == VisitFlags::AnyTrait {
+        f.items(|hir| &mut hir.traits);
+        f.members(|hir| &mut hir.trait_functions);
+        f.members(|hir| &mut hir.trait_methods);
+        f.members(|hir| &mut hir.trait_type_alias);
+        f.members(|hir| &mut hir.trait_consts);
+        f.members(|hir| &mut hir.trait_properties);
+    } else if group == VisitFlags::AnyImpl {
+        f.items(|hir| &mut hir.impls);
+        f.members(|hir| &mut hir.impl_functions);
+        f.members(|hir| &mut hir.methods);
+        f.members(|hir| &mut hir.impl_type_aliases);
+        f.members(|hir| &mut hir.impl_consts);
+        f.members(|hir| &mut hir.impl_statics);
+        f.members(|hir| &mut hir.impl_tls_statics);
+        f.members(|hir| &mut hir.properties);
+    } else if group == VisitFlags::AnyOp {
+        f.items(|hir| &mut hir.op_sets);
+        f.members(|hir| &mut hir.operators);
+        f.members(|hir| &mut hir.op_contracts);
+    } else if group == VisitFlags::Precedence {
+        f.items(|hir| &mut hir.precedences);
+    }
+}
+
+/// Number of items in a group, members are counted as part of the item they belong to
+struct GroupLen<'a> {
+    hir: &'a mut Hir,
+    len: Option<usize>,
+}
+
+impl GroupFields for GroupLen<'_> {
+    fn items<T>(&mut self, field: fn(&mut Hir) -> &mut Vec<T>) {
+        self.len.get_or_insert(field(self.hir).len());
+    }
+
+    fn members<N, C>(&mut self, _field: fn(&mut Hir) -> &mut Vec<(usize, N, C)>) {}
+}
+
+/// Move the items of a group into the parts, part `n` gets the items starting at `starts[n]`
+struct SplitGroup<'a> {
+    hir:    &'a mut Hir,
+    parts:  &'a mut [Hir],
+    starts: &'a [usize],
+}
+
+impl GroupFields for SplitGroup<'_> {
+    fn items<T>(&mut self, field: fn(&mut Hir) -> &mut Vec<T>) {
+        let mut items = mem::take(field(self.hir));
+        for (part, start) in self.parts.iter_mut().zip(self.starts).rev() {
+            *field(part) = items.split_off(*start);
+        }
+    }
+
+    fn members<N, C>(&mut self, field: fn(&mut Hir) -> &mut Vec<(usize, N, C)>) {
+        let mut members = mem::take(field(self.hir));
+        for (part, start) in self.parts.iter_mut().zip(self.starts).rev() {
+            // Members are sorted by the index of the item they belong to, which is made relative to the items in the part
+            let offset = members.partition_point(|(idx, ..)| idx < start);
+            let mut part_members = members.split_off(offset);
+            for (idx, ..) in &mut part_members {
+                *idx -= start;
+            }
+            *field(part) = part_members;
+        }
+    }
+}
+
+/// Move the items of a group back from the parts, in order
+struct MergeGroup<'a> {
+    hir:    &'a mut Hir,
+    parts:  &'a mut [Hir],
+    starts: &'a [usize],
+}
+
+impl GroupFields for MergeGroup<'_> {
+    fn items<T>(&mut self, field: fn(&mut Hir) -> &mut Vec<T>) {
+        for part in self.parts.iter_mut() {
+            field(self.hir).append(field(part));
+        }
+    }
+
+    fn members<N, C>(&mut self, field: fn(&mut Hir) -> &mut Vec<(usize, N, C)>) {
+        for (part, start) in self.parts.iter_mut().zip(self.starts) {
+            let members = field(part);
+            for (idx, ..) in members.iter_mut() {
+                *idx += start;
+            }
+            field(self.hir).append(members);
+        }
+    }
+}
@@ -1,8 +1,8 @@
-use std::mem;
+use std::{collections::HashMap, mem};
 
 use utils::PatternIdenCollection;
 
-use crate::{common::{NameTable, VarInfoHandle, VarScopeId, VariableInfoScopeBuilder}, hir::*};
+use crate::{common::{NameId, NameTable, OpType, SpanId, VarInfoHandle, VarScopeId, VariableInfo, VariableInfoScopeBuilder}, hir::*, lexer::Punctuation};
 
 use super::{Pass, PassContext};
 
@@ -105,6 +105,16 @@ impl Visitor for VariableScopeCollection<'_> {
         self.scope_stack.pop();
     }
 
+    fn visit_closure_expr(&mut self, node: &mut ClosureExpr) {
+        let scope_id = self.var_info.add_scope(node.span, self.scope_stack.last().copied());
+        node.ctx.var_scope = scope_id;
+        self.scope_stack.push(scope_id);
+
+        helpers::visit_closure_expr(self, node);
+
+        self.scope_stack.pop();
+    }
+
     fn visit_path(&mut self, path: &mut Path) {
         if let Some(var_scope) = self.scope_stack.last() {
             path.ctx.var_scope = *var_scope;
@@ -114,6 +124,7 @@ impl Visitor for VariableScopeCollection<'_> {
 
 impl Pass for VariableScopeCollection<'_> {
     const NAME: &'static str = "Variable Scope Collection";
+    const DEPENDENCIES: &'static [&'static str] = &[passes::PathGen::NAME];
 }
 
 //==============================================================================================================================
@@ -284,7 +295,21 @@ impl Visitor for VariableCollection<'_> {
 
         helpers::visit_block(self, node);
 
-        self.cur_scope = self.scope_stack.pop().unwrap();
+        self.scope_stack.pop();
+        self.cur_scope = self.scope_stack.last().copied().unwrap_or(VarScopeId::INVALID);
+    }
+
+    fn visit_closure_expr(&mut self, node: &mut ClosureExpr) {
+        self.scope_stack.push(node.ctx.var_scope);
+        self.cur_scope = node.ctx.var_scope;
+
+        if let Some(info) = &self.info {
+            self.add_params(info, &mut node.params);
+        }
+        helpers::visit_closure_expr(self, node);
+
+        self.scope_stack.pop();
+        self.cur_scope = self.scope_stack.last().copied().unwrap_or(VarScopeId::INVALID);
     }
 
     fn visit_var_decl(&mut self, node: &mut VarDecl) {
@@ -294,9 +319,435 @@ impl Visitor for VariableCollection<'_> {
             let debug_name = names[node.name].to_string();
             info.add_var(self.cur_scope, node.name, debug_name, node.span, node.is_mut, false);
         }
+        helpers::visit_var_decl(self, node);
+    }
+
+    fn visit_uninit_var_decl(&mut self, node: &mut UninitVarDecl) {
+        {
+            let mut info = self.info.as_ref().unwrap().write();
+            let names = self.ctx.names.read();
+            let debug_name = names[node.name].to_string();
+            info.add_var(self.cur_scope, node.name, debug_name, node.span, node.is_mut, false);
+        }
+        helpers::visit_uninit_var_decl(self, node);
     }
 }
 
 impl Pass for VariableCollection<'_> {
     const NAME: &'static str = "Variable Collection";
+    const DEPENDENCIES: &'static [&'static str] = &[VariableScopeCollection::NAME];
+}
+//==============================================================================================================================
+
+struct ClosureCaptures {
+    var_scope: VarScopeId,
+    is_moved:  bool,
+    captures:  Vec<Capture>,
+}
+
+/// Collect the places from the surrounding environment that each closure captures, and how they are captured.
+///
+/// The capture mode of a place depends on how it is used:
+/// - `&place` borrows it immutably
+/// - assigning to it borrows it mutably, or uniquely immutably when assigning through a dereference
+/// - reading it by value, e.g. `y := a.0`, borrows it immutably when its type is `Copy`, and moves it otherwise
+/// - a method call on it borrows it immutably or mutably, or reads it by value, depending on the `self` of the method
+/// - destructuring it, using `let` or `match`, only captures the parts that the patterns bind, each according to the binding mode
+///
+/// Within a `move` closure, every place is moved, truncated before its first dereference.
+/// A place used in a nested closure is also captured by the closures around it.
+///
+/// The types of expressions aren't known yet, so captures that depend on the type of the place are provisional, and record the rules that are still pending:
+/// - by value reads and method receivers are captured by immutable borrow, as if they are `Copy` and take `&self`
+/// - places are not yet truncated at the rightmost dereference of a shared reference, or before a pointer dereference, union field, or field of a packed struct or bitfield
+/// - field accesses that implicitly dereference a reference are not yet part of the place
+pub struct ClosureCaptureAnalysis<'a> {
+    ctx:         &'a PassContext,
+    self_name:   NameId,
+    info:        Option<VarInfoHandle>,
+    scope_stack: Vec<VarScopeId>,
+    closures:    Vec<ClosureCaptures>,
+    /// Compiler generated variables a place is bound to when it is destructured, which refer to that place
+    aliases:     HashMap<NameId, (NameId, SpanId, Vec<CaptureProjection>)>,
+}
+
+impl<'a> ClosureCaptureAnalysis<'a> {
+    pub fn new(ctx: &'a PassContext) -> Self {
+        let self_name = ctx.names.write().add("self");
+        Self {
+            ctx,
+            self_name,
+            info: None,
+            scope_stack: Vec::new(),
+            closures: Vec::new(),
+            aliases: HashMap::new(),
+        }
+    }
+}
+
+impl ClosureCaptureAnalysis<'_> {
+    fn visit_fn_body<F: FnOnce(&mut Self)>(&mut self, ctx: &FunctionContext, f: F) {
+        self.info = Some(self.ctx.var_infos.read().get(ctx.var_info));
+        self.scope_stack.push(VarScopeId::PROCESS_INITIAL);
+
+        f(self);
+
+        self.scope_stack.pop();
+        self.info = None;
+        self.aliases.clear();
+    }
+
+    /// Get the variable and projections of the place `expr` refers to.
+    ///
+    /// Arrays and slices are never partially captured, so the place of an index expression is the place being indexed.
+    fn get_place(&self, expr: &Expr) -> Option<(NameId, SpanId, Vec<CaptureProjection>)> {
+        let mut projections = Vec::new();
+        let mut expr = expr;
+        loop {
+            match expr {
+                Expr::Path(PathExpr::Named { span, start: PathStart::None, iden: Identifier { name: IdenName::Name { name, .. }, gen_args: None, .. }, .. }) => {
+                    projections.reverse();
+                    return Some(match self.aliases.get(name) {
+                        Some((var, span, base)) => (*var, *span, base.iter().cloned().chain(projections).collect()),
+                        None                    => (*name, *span, projections),
+                    });
+                },
+                Expr::Path(PathExpr::SelfPath { span, .. }) => {
+                    projections.reverse();
+                    return Some((self.self_name, *span, projections));
+                },
+                Expr::FieldAccess(FieldAccessExpr { expr: inner, field: Identifier { name: IdenName::Name { name, .. }, gen_args: None, .. }, is_propagating: false, .. }) => {
+                    projections.push(CaptureProjection::Field(*name));
+                    expr = inner;
+                },
+                Expr::TupleIndex(TupleIndexExpr { expr: inner, index, .. }) => {
+                    projections.push(CaptureProjection::TupleIndex(*index));
+                    expr = inner;
+                },
+                Expr::Prefix(PrefixExpr { op: Punctuation::Caret, expr: inner, .. }) |
+                Expr::Postfix(PostfixExpr { op: Punctuation::Caret, expr: inner, .. }) => {
+                    projections.push(CaptureProjection::Deref);
+                    expr = inner;
+                },
+                Expr::Index(IndexExpr { is_opt: false, expr: inner, .. }) => {
+                    projections.clear();
+                    expr = inner;
+                },
+                _ => return None,
+            }
+        }
+    }
+
+    /// Visit the indices used within a place, which aren't part of the place itself
+    fn visit_place_indices(&mut self, expr: &mut Expr) {
+        match expr {
+            Expr::FieldAccess(FieldAccessExpr { expr, .. }) |
+            Expr::TupleIndex(TupleIndexExpr { expr, .. }) |
+            Expr::Prefix(PrefixExpr { expr, .. }) |
+            Expr::Postfix(PostfixExpr { expr, .. }) => self.visit_place_indices(expr),
+            Expr::Index(IndexExpr { expr, index, .. }) => {
+                self.visit_place_indices(expr);
+                self.visit_expr(index);
+            },
+            _ => (),
+        }
+    }
+
+    fn visit_operand(&mut self, expr: &mut Expr, mode: CaptureMode, pending: PendingCaptureRules) {
+        match self.get_place(expr) {
+            Some((var, span, projections)) => {
+                self.capture_place(var, span, projections, mode, pending);
+                self.visit_place_indices(expr);
+            },
+            None => helpers::visit_expr(self, expr),
+        }
+    }
+
+    /// Capture the parts of a place that are bound by a pattern it is matched against
+    fn capture_pattern(&mut self, var: NameId, span: SpanId, projections: &[CaptureProjection], pattern: &mut Pattern) {
+        let projected = |projection: CaptureProjection| {
+            let mut projections = projections.to_vec();
+            projections.push(projection);
+            projections
+        };
+
+        match pattern {
+            Pattern::Wildcard(_) |
+            Pattern::Rest(_) => (),
+            Pattern::Iden(IdenPattern { is_ref, is_mut, bound: None, .. }) => {
+                let (mode, pending) = binding_capture_mode(*is_ref, *is_mut);
+                self.capture_place(var, span, projections.to_vec(), mode, pending);
+            },
+            Pattern::Tuple(TuplePattern { patterns, .. }) |
+            Pattern::TupleStruct(TupleStructPattern { patterns, .. }) => {
+                // Elements after a rest pattern can only be indexed once the number of elements is known, so the whole place is captured when they bind anything
+                let rest_idx = patterns.iter().position(|pattern| matches!(**pattern, Pattern::Rest(_))).unwrap_or(patterns.len());
+                if patterns[rest_idx..].iter().any(|pattern| !matches!(**pattern, Pattern::Wildcard(_) | Pattern::Rest(_))) {
+                    self.capture_whole_pattern(var, span, projections, pattern);
+                    return;
+                }
+                for (idx, pattern) in patterns[..rest_idx].iter_mut().enumerate() {
+                    self.capture_pattern(var, span, &projected(CaptureProjection::TupleIndex(idx)), pattern);
+                }
+            },
+            Pattern::Struct(StructPattern { fields, .. }) => for field in fields {
+                match field {
+                    StructPatternField::Named { name, pattern, .. } => self.capture_pattern(var, span, &projected(CaptureProjection::Field(*name)), pattern),
+                    StructPatternField::TupleIndex { index, pattern, .. } => self.capture_pattern(var, span, &projected(CaptureProjection::TupleIndex(*index)), pattern),
+                    StructPatternField::Iden { is_ref, is_mut, iden, .. } => {
+                        let (mode, pending) = binding_capture_mode(*is_ref, *is_mut);
+                        self.capture_place(var, span, projected(CaptureProjection::Field(*iden)), mode, pending);
+                    },
+                    StructPatternField::Rest => (),
+                }
+            },
+            Pattern::Reference(ReferencePattern { pattern, .. }) => self.capture_pattern(var, span, &projected(CaptureProjection::Deref), pattern),
+            Pattern::Alternative(AlternativePattern { patterns, .. }) => for pattern in patterns {
+                self.capture_pattern(var, span, projections, pattern);
+            },
+            _ => self.capture_whole_pattern(var, span, projections, pattern),
+        }
+    }
+
+    /// Capture the whole place a pattern is matched against, as the pattern reads more than just the parts it binds, e.g. a literal or the elements of a slice
+    fn capture_whole_pattern(&mut self, var: NameId, span: SpanId, projections: &[CaptureProjection], pattern: &mut Pattern) {
+        let mut iden_collect = PatternIdenCollection::new();
+        iden_collect.visit_pattern(pattern);
+        let (mut mode, mut pending) = (CaptureMode::ImmutableBorrow, PendingCaptureRules::default());
+        for iden in iden_collect.is_mut_and_names {
+            let (iden_mode, iden_pending) = binding_capture_mode(iden.is_ref, iden.is_mut);
+            mode = mode.max(iden_mode);
+            pending.by_value |= iden_pending.by_value;
+        }
+        self.capture_place(var, span, projections.to_vec(), mode, pending);
+    }
+
+    fn capture_place(&mut self, var: NameId, span: SpanId, projections: Vec<CaptureProjection>, mode: CaptureMode, pending: PendingCaptureRules) {
+        if self.closures.is_empty() {
+            return;
+        }
+        let Some(info) = &self.info else { return };
+        let info = info.read();
+
+        let var_scope = {
+            let spans = self.ctx.spans.read();
+            let cur_scope = *self.scope_stack.last().unwrap();
+            match info.get_var(cur_scope, var, span, &spans) {
+                Some(entry) => entry.scope,
+                None        => return,
+            }
+        };
+
+        let mut capture = Capture {
+            span,
+            var,
+            var_scope,
+            projections,
+            mode,
+            pending,
+        };
+        // Assigning through a dereference only needs the reference to be unique
+        if capture.mode == CaptureMode::MutableBorrow && truncate_at_deref(&mut capture) {
+            capture.mode = CaptureMode::UniqueImmutableBorrow;
+        }
+
+        for closure in self.closures.iter_mut().rev() {
+            if is_scope_within(&info, var_scope, closure.var_scope) {
+                break;
+            }
+            // Everything used within a move closure is moved into it, independent of its type
+            if closure.is_moved {
+                truncate_at_deref(&mut capture);
+                capture.mode = CaptureMode::Move;
+                capture.pending.by_value = false;
+                capture.pending.receiver = false;
+            }
+            capture.pending.projections = !capture.projections.is_empty();
+            add_capture(&mut closure.captures, capture.clone());
+        }
+    }
+}
+
+/// Truncate the place of a capture right before its first dereference, returns whether the place was truncated
+fn truncate_at_deref(capture: &mut Capture) -> bool {
+    match capture.projections.iter().position(|proj| *proj == CaptureProjection::Deref) {
+        Some(idx) => {
+            capture.projections.truncate(idx);
+            true
+        },
+        None => false,
+    }
+}
+
+/// Get the mode a place bound by a pattern is captured with
+fn binding_capture_mode(is_ref: bool, is_mut: bool) -> (CaptureMode, PendingCaptureRules) {
+    match (is_ref, is_mut) {
+        (true, false) => (CaptureMode::ImmutableBorrow, PendingCaptureRules::default()),
+        (true, true)  => (CaptureMode::MutableBorrow, PendingCaptureRules::default()),
+        (false, _)    => (CaptureMode::ImmutableBorrow, PendingCaptureRules { by_value: true, ..PendingCaptureRules::default() }),
+    }
+}
+
+fn is_scope_within(info: &VariableInfo, scope: VarScopeId, ancestor: VarScopeId) -> bool {
+    let mut scope = Some(scope);
+    while let Some(id) = scope {
+        if id == ancestor {
+            return true;
+        }
+        scope = info.get_scope(id).parent;
+    }
+    false
+}
+
+/// Add a capture, when both a place and one of its ancestors are captured, only the ancestor is kept, using the strongest mode of the two.
+/// Rules that are pending for how the descendant is used are kept pending for the ancestor
+fn add_capture(captures: &mut Vec<Capture>, mut capture: Capture) {
+    if let Some(ancestor) = captures.iter_mut().find(|ancestor| ancestor.is_prefix_of(&capture)) {
+        ancestor.mode = ancestor.mode.max(capture.mode);
+        ancestor.pending.by_value |= capture.pending.by_value;
+        ancestor.pending.receiver |= capture.pending.receiver;
+        return;
+    }
+
+    // Keep the position and span of the first use of any of the descendants
+    let mut pos = None;
+    let mut idx = 0;
+    captures.retain(|descendant| {
+        let is_descendant = capture.is_prefix_of(descendant);
+        if is_descendant {
+            capture.mode = capture.mode.max(descendant.mode);
+            capture.pending.by_value |= descendant.pending.by_value;
+            capture.pending.receiver |= descendant.pending.receiver;
+            if pos.is_none() {
+                pos = Some(idx);
+                capture.span = descendant.span;
+            }
+        } else {
+            idx += 1;
+        }
+        !is_descendant
+    });
+    captures.insert(pos.unwrap_or(captures.len()), capture);
+}
+
+impl Visitor for ClosureCaptureAnalysis<'_> {
+    fn visit_function(&mut self, node: &mut Function, ctx: &mut FunctionContext) {
+        self.visit_fn_body(ctx, |this| helpers::visit_function(this, node));
+    }
+
+    fn visit_trait_function(&mut self, trait_ref: Ref<Trait>, trait_ctx: Ref<TraitContext>, node: &mut TraitFunction, ctx: &mut FunctionContext) {
+        self.visit_fn_body(ctx, |this| helpers::visit_trait_function(this, node));
+    }
+
+    fn visit_trait_method(&mut self, trait_ref: Ref<Trait>, trait_ctx: Ref<TraitContext>, node: &mut TraitMethod, ctx: &mut FunctionContext) {
+        self.visit_fn_body(ctx, |this| helpers::visit_trait_method(this, node));
+    }
+
+    fn visit_impl_function(&mut self, impl_ref: Ref<Impl>, impl_ctx: Ref<ImplContext>, node: &mut Function, ctx: &mut FunctionContext) {
+        self.visit_fn_body(ctx, |this| helpers::visit_function(this, node));
+    }
+
+    fn visit_method(&mut self, impl_ref: Ref<Impl>, impl_ctx: Ref<ImplContext>, node: &mut Method, ctx: &mut FunctionContext) {
+        self.visit_fn_body(ctx, |this| helpers::visit_method(this, node));
+    }
+
+    //--------------------------------------------------------------
+
+    fn visit_block(&mut self, node: &mut Block) {
+        self.scope_stack.push(node.ctx.var_scope);
+        helpers::visit_block(self, node);
+        self.scope_stack.pop();
+    }
+
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        self.visit_operand(expr, CaptureMode::ImmutableBorrow, PendingCaptureRules { by_value: true, ..PendingCaptureRules::default() });
+    }
+
+    fn visit_var_decl(&mut self, node: &mut VarDecl) {
+        // Destructuring a place binds it to a compiler generated variable, which is then matched against the pattern,
+        // so uses of that variable are uses of the place
+        if node.allow_du && !self.closures.is_empty() {
+            if let Some(place) = self.get_place(&node.expr) {
+                self.visit_place_indices(&mut node.expr);
+                self.aliases.insert(node.name, place);
+                return;
+            }
+        }
+        helpers::visit_var_decl(self, node);
+    }
+
+    fn visit_prefix_expr(&mut self, node: &mut PrefixExpr) {
+        if node.op == Punctuation::Ampersand {
+            self.visit_operand(&mut node.expr, CaptureMode::ImmutableBorrow, PendingCaptureRules::default());
+        } else {
+            helpers::visit_prefix_expr(self, node);
+        }
+    }
+
+    fn visit_infix_expr(&mut self, node: &mut InfixExpr) {
+        let is_assign = node.op == Punctuation::Equals ||
+            node.op == Punctuation::ColonEquals ||
+            self.ctx.op_table.read().get(OpType::Assign, node.op).is_some();
+        if !is_assign {
+            helpers::visit_infix_expr(self, node);
+            return;
+        }
+
+        // Binding a place to `_` doesn't read it
+        if matches!(*node.left, Expr::Underscore(_)) && self.get_place(&node.right).is_some() {
+            self.visit_place_indices(&mut node.right);
+        } else {
+            self.visit_operand(&mut node.left, CaptureMode::MutableBorrow, PendingCaptureRules::default());
+            self.visit_expr(&mut node.right);
+        }
+    }
+
+    fn visit_inplace_expr(&mut self, node: &mut InplaceExpr) {
+        self.visit_operand(&mut node.left, CaptureMode::MutableBorrow, PendingCaptureRules::default());
+        self.visit_expr(&mut node.right);
+    }
+
+    fn visit_method_call_expr(&mut self, node: &mut MethodCallExpr) {
+        self.visit_operand(&mut node.receiver, CaptureMode::ImmutableBorrow, PendingCaptureRules { receiver: true, ..PendingCaptureRules::default() });
+        helpers::visit_iden(self, &mut node.method);
+        for arg in &mut node.args {
+            self.visit_expr(&mut arg.expr);
+        }
+    }
+
+    fn visit_match_expr(&mut self, node: &mut MatchExpr) {
+        // Matching a place only reads the parts of it the patterns bind, so matching against only wildcards doesn't read it
+        let Some((var, span, projections)) = self.get_place(&node.scrutinee) else {
+            helpers::visit_match_expr(self, node);
+            return;
+        };
+
+        self.visit_place_indices(&mut node.scrutinee);
+        for branch in &mut node.branches {
+            self.capture_pattern(var, span, &projections, &mut branch.pattern);
+            if let Some(guard) = &mut branch.guard {
+                self.visit_expr(guard);
+            }
+            self.visit_expr(&mut branch.body);
+        }
+    }
+
+    fn visit_closure_expr(&mut self, node: &mut ClosureExpr) {
+        self.scope_stack.push(node.ctx.var_scope);
+        self.closures.push(ClosureCaptures {
+            var_scope: node.ctx.var_scope,
+            is_moved:  node.is_moved,
+            captures:  Vec::new(),
+        });
+
+        helpers::visit_closure_expr(self, node);
+
+        node.ctx.captures = self.closures.pop().unwrap().captures;
+        self.scope_stack.pop();
+    }
+}
+
+impl Pass for ClosureCaptureAnalysis<'_> {
+    const NAME: &'static str = "Closure Capture Analysis";
+    const DEPENDENCIES: &'static [&'static str] = &[VariableCollection::NAME];
 }
\ No newline at end of file
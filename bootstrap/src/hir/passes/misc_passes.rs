@@ -379,6 +379,7 @@ impl Visitor for VisibilityProcess {
 
 impl Pass for VisibilityProcess {
     const NAME: &'static str = "Visibility Processing";
+    const DEPENDENCIES: &'static [&'static str] = &[passes::SymbolGeneration::NAME];
 }
 
 //==============================================================================================================================
@@ -616,18 +617,21 @@ impl Visitor for SelfTyReplacePass<'_> {
     }
 }
 
+impl SelfTyReplacePass<'_> {
+    /// Items in which `Self` is replaced
+    pub const VISIT_FLAGS: VisitFlags = VisitFlags::Struct
+        .bitor(VisitFlags::TupleStruct)
+        .bitor(VisitFlags::Union)
+        .bitor(VisitFlags::AdtEnum)
+        .bitor(VisitFlags::AnyImpl)
+        .bitor(VisitFlags::AnyTrait);
+}
+
 impl Pass for SelfTyReplacePass<'_> {
     const NAME: &'static str = "Self Type Replacement";
+    const DEPENDENCIES: &'static [&'static str] = &[passes::SymbolGeneration::NAME];
 
     fn process(&mut self, hir: &mut Hir) {
-        let flags = VisitFlags::Struct
-                  | VisitFlags::TupleStruct
-                  | VisitFlags::Union
-                  | VisitFlags::AdtEnum
-                  | VisitFlags::Union
-                  | VisitFlags::AnyImpl
-                  | VisitFlags::AnyTrait;
-
-        self.visit(hir, flags);
+        self.visit(hir, Self::VISIT_FLAGS);
     }
 }
\ No newline at end of file
@@ -60,15 +60,59 @@ pub struct PassContext {
 }
 
 impl PassContext {
+    /// Create a context with empty tables for the library `lib_path`
+    pub fn new(lib_path: LibraryPath) -> Self {
+        Self {
+            names: Arc::new(RwLock::new(NameTable::new())),
+            puncts: Arc::new(RwLock::new(PuncutationTable::new())),
+            lits: Arc::new(RwLock::new(LiteralTable::new())),
+            spans: Arc::new(RwLock::new(SpanRegistry::new())),
+            syms: Arc::new(RwLock::new(RootSymbolTable::new(lib_path.clone()))),
+            uses: Arc::new(RwLock::new(RootUseTable::new(lib_path.clone()))),
+            type_reg: Arc::new(RwLock::new(TypeRegistry::new())),
+            trait_dag: Arc::new(RwLock::new(TraitDag::new())),
+            precedence_dag: Arc::new(RwLock::new(PrecedenceDAG::new())),
+            op_table: Arc::new(RwLock::new(OperatorTable::new())),
+            var_infos: Arc::new(RwLock::new(VarInfoMap::new())),
+            lib_path,
+            errors: Arc::new(RwLock::new(Vec::new())),
+        }
+    }
+
     pub fn add_error(&self, err: HirError) {
         self.errors.write().push(err);
     }
+
+    /// Create a context for a worker of a parallel pass, which shares all tables, but collects its errors separately
+    pub fn worker_context(&self) -> Self {
+        Self {
+            errors: Arc::new(RwLock::new(Vec::new())),
+            ..self.clone()
+        }
+    }
 }
 
+// Steps run in between passes, which finalize the tables filled in by the passes before them
+pub const FINALIZE_PRECEDENCE_USES: &str = "Finalizing use table precedences";
+pub const BUILD_PRECEDENCE_DAG:     &str = "Building and checking precedence dag";
+pub const FINALIZE_OPERATOR_USES:   &str = "Finalizing use table operators";
+pub const BUILD_OPERATOR_TABLE:     &str = "Building operator table";
+pub const FINALIZE_USES:            &str = "Finalizing use table";
+pub const CHECK_TRAIT_DAG:          &str = "Checking trait dag";
+
 pub trait Pass: Visitor {
     const NAME: &'static str;
+    /// Passes and steps that need to run before this pass
+    const DEPENDENCIES: &'static [&'static str];
 
     fn process(&mut self, hir: &mut Hir) {
         self.visit(hir, VisitFlags::all());
     }
+}
+
+/// Pass which processes each item independently of the other items of the same kind, so items can be visited on multiple threads.
+///
+/// Each worker gets its own pass, created with its own context, so errors can be collected in the order of the items.
+pub trait ParallelPass<'a>: Pass {
+    fn new_worker(ctx: &'a PassContext) -> Self;
 }
\ No newline at end of file
@@ -4,6 +4,7 @@ use std::{collections::{HashMap, VecDeque}, mem, sync::Weak};
 use passes::PassContext;
 
 use crate::{
+    ast::passes::{AstPass, AstToHirLowering},
     common::{LibraryPath, NameTable, OperatorInfo, OperatorTable, PathIden, PrecedenceDAG, PrecedenceOrder, RootSymbolTable, RootUseTable, Symbol, SymbolPath, SymbolTable, UseTable, WeakSymbolRef},
     hir::*, lexer::PuncutationTable
 };
@@ -38,6 +39,7 @@ impl Visitor for OperatorSymbolGen<'_> {
 
 impl Pass for OperatorSymbolGen<'_> {
     const NAME: &'static str = "Operator Symbol Generation";
+    const DEPENDENCIES: &'static [&'static str] = &[AstToHirLowering::NAME];
 
     fn process(&mut self, hir: &mut Hir) {
         self.visit(hir, VisitFlags::AnyOp);
@@ -61,6 +63,7 @@ impl Visitor for OperatorSetDependencyProcess<'_> {
 
 impl Pass for OperatorSetDependencyProcess<'_> {
     const NAME: &'static str = "Operator Set Dependency Processing";
+    const DEPENDENCIES: &'static [&'static str] = &[passes::SimplePathGen::NAME, passes::FINALIZE_OPERATOR_USES];
 
     fn process(&mut self, hir: &mut Hir) {
         let mut to_process: VecDeque<_> = (0..hir.op_sets.len()).collect();
@@ -193,6 +196,7 @@ impl Visitor for OpSetConnect<'_> {
 
 impl Pass for OpSetConnect<'_> {
     const NAME: &'static str = "Operator Set Connecting";
+    const DEPENDENCIES: &'static [&'static str] = &[OperatorSetDependencyProcess::NAME, passes::BUILD_PRECEDENCE_DAG];
 }
 
 //==============================================================================================================================
@@ -214,6 +218,7 @@ impl Visitor for OpTagging<'_> {
 
 impl Pass for OpTagging<'_> {
     const NAME: &'static str = "Operator Tagging";
+    const DEPENDENCIES: &'static [&'static str] = &[OpSetConnect::NAME];
 
     fn process(&mut self, hir: &mut Hir) {
         let names = self.ctx.names.read();
@@ -322,6 +327,7 @@ impl<'a> InfixReorder<'a> {
 
 impl Pass for InfixReorder<'_> {
     const NAME: &'static str = "Infix Reordering";
+    const DEPENDENCIES: &'static [&'static str] = &[passes::BUILD_PRECEDENCE_DAG, passes::BUILD_OPERATOR_TABLE];
 
     fn process(&mut self, hir: &mut Hir) {
         let flags = VisitFlags::Function | VisitFlags::TraitFunction | VisitFlags::Method |
@@ -450,6 +456,7 @@ impl<'a> OpTraitGen<'a> {
 
 impl Pass for OpTraitGen<'_> {
     const NAME: &'static str = "Operator Trait Gen";
+    const DEPENDENCIES: &'static [&'static str] = &[OpSetConnect::NAME];
 
     fn process(&mut self, hir: &mut Hir) {
         self.visit(hir, VisitFlags::OpSet | VisitFlags::Operator);
@@ -678,6 +685,7 @@ impl Visitor for OpSetTraitAssociation<'_> {
 
 impl Pass for OpSetTraitAssociation<'_> {
     const NAME: &'static str = "Op Set <-> Trait Association";
+    const DEPENDENCIES: &'static [&'static str] = &[OpTraitGen::NAME, passes::FINALIZE_USES];
 
     fn process(&mut self, hir: &mut Hir) {
         for (_, ctx) in &hir.op_sets {
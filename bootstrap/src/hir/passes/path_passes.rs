@@ -1,10 +1,10 @@
 use crate::{
-    ast::NodeId,
+    ast::{passes::{AstPass, AstToHirLowering}, NodeId},
     common::{self, LibraryPath, PathGeneric, PathIden, Scope, Symbol},
     hir::*,
 };
 
-use super::{Pass, PassContext};
+use super::{ParallelPass, Pass, PassContext};
 
 pub struct SimplePathGen<'a> {
     ctx: &'a PassContext
@@ -36,6 +36,13 @@ impl Visitor for SimplePathGen<'_> {
 
 impl Pass for SimplePathGen<'_> {
     const NAME: &'static str = "Simple Path Generation";
+    const DEPENDENCIES: &'static [&'static str] = &[AstToHirLowering::NAME];
+}
+
+impl<'a> ParallelPass<'a> for SimplePathGen<'a> {
+    fn new_worker(ctx: &'a PassContext) -> Self {
+        Self::new(ctx)
+    }
 }
 
 //==============================================================================================================================
@@ -76,6 +83,7 @@ impl Visitor for ImplTraitPathGen<'_> {
 
 impl Pass for ImplTraitPathGen<'_> {
     const NAME: &'static str = "Implementation Trait Path Generation";
+    const DEPENDENCIES: &'static [&'static str] = &[passes::SymbolGeneration::NAME];
 
     fn process(&mut self, hir: &mut Hir) {
         self.visit(hir, VisitFlags::Impl);
@@ -144,7 +152,7 @@ impl Visitor for PathGen<'_> {
                             }
                             
                             // Not a local var, so needs to be a symbol accessible to the current scope (which does not use any generics)
-                            let syms = self.ctx.syms.write();
+                            let syms = self.ctx.syms.read();
                             let uses = self.ctx.uses.read();
                             let names = self.ctx.names.read();
 
@@ -191,4 +199,11 @@ impl Visitor for PathGen<'_> {
 
 impl Pass for PathGen<'_> {
     const NAME: &'static str = "Path Generation";
+    const DEPENDENCIES: &'static [&'static str] = &[passes::SelfTyReplacePass::NAME, passes::FINALIZE_USES];
+}
+
+impl<'a> ParallelPass<'a> for PathGen<'a> {
+    fn new_worker(ctx: &'a PassContext) -> Self {
+        Self::new(ctx)
+    }
 }
\ No newline at end of file
@@ -1,6 +1,7 @@
 use passes::PassContext;
 
 use crate::{
+    ast::passes::{AstPass, AstToHirLowering},
     common::{NameTable, PrecedenceDAG, PrecedenceOrderKind, Symbol},
     hir::*, literals::{Literal, LiteralTable},
 };
@@ -38,6 +39,7 @@ impl Visitor for PrecedenceSymGen<'_> {
 
 impl Pass for PrecedenceSymGen<'_> {
     const NAME: &'static str = "Precedence Symbol Generation";
+    const DEPENDENCIES: &'static [&'static str] = &[AstToHirLowering::NAME];
 
     fn process(&mut self, hir: &mut Hir) {
         self.visit(hir, VisitFlags::Precedence);
@@ -62,6 +64,7 @@ impl<'a> PrecedenceAttrib<'a> {
 
 impl Pass for PrecedenceAttrib<'_> {
     const NAME: &'static str = "Precedence Attribute Processing";
+    const DEPENDENCIES: &'static [&'static str] = &[PrecedenceSymGen::NAME];
 
     fn process(&mut self, hir: &mut Hir) {
         self.visit(hir, VisitFlags::Precedence);
@@ -213,4 +216,5 @@ impl Visitor for PrecedenceConnect<'_> {
 
 impl Pass for PrecedenceConnect<'_> {
     const NAME: &'static str = "Precedence Connecting";
+    const DEPENDENCIES: &'static [&'static str] = &[passes::SimplePathGen::NAME, passes::FINALIZE_PRECEDENCE_USES];
 }
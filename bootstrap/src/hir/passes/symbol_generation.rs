@@ -215,11 +215,16 @@ impl Visitor for SymbolGeneration<'_> {
     fn visit_tuple_struct(&mut self, node: &mut TupleStruct, ctx: &mut StructContext) {
         let iden = self.get_symbol_iden(node.name, None, node.generics.as_ref());
         let sym = self.ctx.syms.write().add_struct(None, &ctx.scope, iden.clone(), StructKind::Tuple);
-        ctx.sym = Some(sym);
-
+        
         if let Some(generics) = &mut node.generics {
+            let sym = sym.read();
+            self.generic_scope = sym.path().to_full_scope();
+            let mut  uses = self.ctx.uses.write();
+            uses.add_generic_use(self.generic_scope.clone());
             self.process_gen_params(generics, &iden);
         }
+
+        ctx.sym = Some(sym);
     }
 
     fn visit_unit_struct(&mut self, node: &mut UnitStruct, ctx: &mut StructContext) {
@@ -523,6 +528,13 @@ impl Visitor for SymbolGeneration<'_> {
 
 impl Pass for SymbolGeneration<'_> {
     const NAME: &'static str = "Symbol Generation";
+    const DEPENDENCIES: &'static [&'static str] = &[passes::SimplePathGen::NAME, passes::OpTraitGen::NAME];
+}
+
+impl<'a> ParallelPass<'a> for SymbolGeneration<'a> {
+    fn new_worker(ctx: &'a PassContext) -> Self {
+        Self::new(ctx)
+    }
 }
 
 
@@ -551,6 +563,7 @@ impl Visitor for TypeImplSymbolAssoc<'_> {
 
 impl Pass for TypeImplSymbolAssoc<'_> {
     const NAME: &'static str = "Type <-> Impl Symbol Association Pass";
+    const DEPENDENCIES: &'static [&'static str] = &[passes::ItemLevelTypeGen::NAME, passes::ImplTraitSymResolve::NAME];
 
     fn process(&mut self, hir: &mut Hir) {
         self.visit(hir, VisitFlags::Impl);
@@ -25,6 +25,7 @@ impl<'a> TraitDagGen<'a> {
 
 impl Pass for TraitDagGen<'_> {
     const NAME: &'static str = "Trait DAG Generation";
+    const DEPENDENCIES: &'static [&'static str] = &[passes::FINALIZE_USES];
 
     fn process(&mut self, hir: &mut Hir) {
         let mut dag = self.ctx.trait_dag.write();
@@ -109,6 +110,7 @@ impl Visitor for TraitItemProcess<'_> {
 
 impl Pass for TraitItemProcess<'_> {
     const NAME: &'static str = "Trait Item processing";
+    const DEPENDENCIES: &'static [&'static str] = &[passes::CHECK_TRAIT_DAG];
 
     fn process(&mut self, hir: &mut Hir) {
         self.visit(hir, VisitFlags::AnyTrait);
@@ -232,6 +234,7 @@ impl Visitor for ImplTraitSymResolve<'_> {
 
 impl Pass for ImplTraitSymResolve<'_> {
     const NAME: &'static str = "Impl Trait symbol resolve";
+    const DEPENDENCIES: &'static [&'static str] = &[passes::ImplTraitPathGen::NAME, passes::FINALIZE_USES];
 
     fn process(&mut self, hir: &mut Hir) {
         self.visit(hir, VisitFlags::Impl);
@@ -256,6 +259,7 @@ impl Visitor for ImplTraitItemCollection<'_> {}
 
 impl Pass for ImplTraitItemCollection<'_> {
     const NAME: &'static str = "Impl trait item collection";
+    const DEPENDENCIES: &'static [&'static str] = &[ImplTraitSymResolve::NAME];
 
     fn process(&mut self, hir: &mut Hir) {
         helpers::visit_impl_cond(self, hir, VisitFlags::Impl, |this, node, ctx| {
@@ -354,6 +358,7 @@ impl Visitor for TraitImpl<'_> {
 
 impl Pass for TraitImpl<'_> {
     const NAME: &'static str = "Trait Impl Check And Default Impl";
+    const DEPENDENCIES: &'static [&'static str] = &[ImplTraitItemCollection::NAME, TraitItemProcess::NAME];
 
     fn process(&mut self, hir: &mut Hir) {
         // Collect all implemented items, and then mark the ones that are implemented
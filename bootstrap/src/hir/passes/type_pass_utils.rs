@@ -172,6 +172,38 @@ impl Visitor for TypeGenUtils<'_> {
         node.ctx.ty = Some(ty);
     }
 
+    fn visit_trait_object_type(&mut self, node: &mut TraitObjectType) {
+        if node.ctx.ty.is_some() {
+            return;
+        }
+
+        helpers::visit_trait_object_type(self, node);
+
+        let traits = node.bounds.bounds.iter()
+            .map(|bound| SymbolPath::from_scope(LibraryPath::new(), bound.ctx.path.clone()).unwrap())
+            .collect();
+
+        let mut registry = self.ctx.type_reg.write();
+        let ty = registry.create_trait_object_type(traits);
+        node.ctx.ty = Some(ty);
+    }
+
+    fn visit_impl_trait_type(&mut self, node: &mut ImplTraitType) {
+        if node.ctx.ty.is_some() {
+            return;
+        }
+
+        helpers::visit_impl_trait_type(self, node);
+
+        let traits = node.bounds.bounds.iter()
+            .map(|bound| SymbolPath::from_scope(LibraryPath::new(), bound.ctx.path.clone()).unwrap())
+            .collect();
+
+        let mut registry = self.ctx.type_reg.write();
+        let ty = registry.create_impl_trait_type(traits);
+        node.ctx.ty = Some(ty);
+    }
+
     // =============================================================
  
     fn visit_path(&mut self, node: &mut Path) {
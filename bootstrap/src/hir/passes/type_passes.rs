@@ -435,4 +435,5 @@ impl Visitor for ItemLevelTypeGen<'_> {
 
 impl Pass for ItemLevelTypeGen<'_> {
     const NAME: &'static str = "Item Level Type Generation";
+    const DEPENDENCIES: &'static [&'static str] = &[passes::PathGen::NAME];
 }
\ No newline at end of file
@@ -0,0 +1,301 @@
+use std::time;
+
+use crate::{
+    common::{CompilerStats, PassInfo, PassManager, SpanId, Symbol},
+    error_warning::HirErrorCode,
+};
+
+use super::{passes, verify_hir, visit_parallel, CodePrinter, Hir, HirError, ParallelPass, Pass, VisitFlags, Visitor};
+
+type PassFn = fn(&mut Hir, &passes::PassContext, &[passes::PassContext]);
+/// Step that finalizes tables, returns `false` if processing can't continue because of errors
+type StepFn = fn(&Hir, &passes::PassContext) -> bool;
+
+enum HirPassKind {
+    Pass(PassFn),
+    Step(StepFn),
+}
+
+/// Pass over the HIR, or a step in between passes
+struct HirPass {
+    info: PassInfo,
+    kind: HirPassKind,
+}
+
+impl HirPass {
+    const fn pass<T: Pass>(run: PassFn) -> Self {
+        Self {
+            info: PassInfo { name: T::NAME, dependencies: T::DEPENDENCIES },
+            kind: HirPassKind::Pass(run),
+        }
+    }
+
+    const fn step(name: &'static str, dependencies: &'static [&'static str], run: StepFn) -> Self {
+        Self {
+            info: PassInfo { name, dependencies },
+            kind: HirPassKind::Step(run),
+        }
+    }
+}
+
+/// All HIR passes and the steps in between them, in the order they run
+const HIR_PASSES: &[HirPass] = {
+    use passes::*;
+    &[
+        // Base pass
+        HirPass::pass::<SimplePathGen>(|hir, ctx, workers| do_parallel_hir_pass::<SimplePathGen>(hir, ctx, workers)),
+
+        // Precedences
+        HirPass::pass::<PrecedenceSymGen>(|hir, ctx, _| PrecedenceSymGen::new(ctx).process(hir)),
+        HirPass::step(FINALIZE_PRECEDENCE_USES, &[PrecedenceSymGen::NAME], finalize_precedence_uses),
+        HirPass::pass::<PrecedenceAttrib>(|hir, ctx, _| PrecedenceAttrib::new(ctx).process(hir)),
+        HirPass::pass::<PrecedenceConnect>(|hir, ctx, _| PrecedenceConnect::new(ctx).process(hir)),
+        HirPass::step(BUILD_PRECEDENCE_DAG, &[PrecedenceAttrib::NAME, PrecedenceConnect::NAME], build_precedence_dag),
+
+        // Operators
+        HirPass::pass::<OperatorSymbolGen>(|hir, ctx, _| OperatorSymbolGen::new(ctx).process(hir)),
+        HirPass::step(FINALIZE_OPERATOR_USES, &[OperatorSymbolGen::NAME], finalize_operator_uses),
+        HirPass::pass::<OperatorSetDependencyProcess>(|hir, ctx, _| OperatorSetDependencyProcess::new(ctx).process(hir)),
+        HirPass::pass::<OpSetConnect>(|hir, ctx, _| OpSetConnect::new(ctx).process(hir)),
+        HirPass::pass::<OpTagging>(|hir, ctx, _| OpTagging::new(ctx).process(hir)),
+        HirPass::pass::<OpTraitGen>(|hir, ctx, _| OpTraitGen::new(ctx).process(hir)),
+        HirPass::step(BUILD_OPERATOR_TABLE, &[OpTagging::NAME, OpTraitGen::NAME], build_operator_table),
+        HirPass::pass::<InfixReorder>(|hir, ctx, _| InfixReorder::new(ctx).process(hir)),
+
+        // Symbol gen
+        HirPass::pass::<SymbolGeneration>(|hir, ctx, workers| do_parallel_hir_pass::<SymbolGeneration>(hir, ctx, workers)),
+        HirPass::step(FINALIZE_USES, &[SymbolGeneration::NAME], finalize_uses),
+        HirPass::pass::<OpSetTraitAssociation>(|hir, ctx, _| OpSetTraitAssociation::new(ctx).process(hir)),
+
+        // Trait
+        HirPass::pass::<TraitDagGen>(|hir, ctx, _| TraitDagGen::new(ctx).process(hir)),
+        HirPass::step(CHECK_TRAIT_DAG, &[TraitDagGen::NAME], check_trait_dag),
+        HirPass::pass::<TraitItemProcess>(|hir, ctx, _| TraitItemProcess::new(ctx).process(hir)),
+
+        // Impl trait processing
+        HirPass::pass::<ImplTraitPathGen>(|hir, ctx, _| ImplTraitPathGen::new(ctx).process(hir)),
+        HirPass::pass::<ImplTraitSymResolve>(|hir, ctx, _| ImplTraitSymResolve::new(ctx).process(hir)),
+        HirPass::pass::<ImplTraitItemCollection>(|hir, ctx, _| ImplTraitItemCollection::new(ctx).process(hir)),
+        HirPass::pass::<TraitImpl>(|hir, ctx, _| TraitImpl::new(ctx).process(hir)),
+
+        // Misc
+        HirPass::pass::<VisibilityProcess>(|hir, ctx, _| VisibilityProcess::new(ctx.lib_path.clone()).process(hir)),
+        HirPass::pass::<SelfTyReplacePass>(|hir, ctx, _| SelfTyReplacePass::new(ctx).process(hir)),
+        HirPass::pass::<PathGen>(|hir, ctx, workers| do_parallel_hir_pass::<PathGen>(hir, ctx, workers)),
+
+        // Variable collection
+        HirPass::pass::<VariableScopeCollection>(|hir, ctx, _| VariableScopeCollection::new(ctx).process(hir)),
+        HirPass::pass::<VariableCollection>(|hir, ctx, _| VariableCollection::new(ctx).process(hir)),
+        HirPass::pass::<ClosureCaptureAnalysis>(|hir, ctx, _| ClosureCaptureAnalysis::new(ctx).process(hir)),
+
+        // Types
+        HirPass::pass::<ItemLevelTypeGen>(|hir, ctx, _| ItemLevelTypeGen::new(ctx).process(hir)),
+        HirPass::pass::<TypeImplSymbolAssoc>(|hir, ctx, _| TypeImplSymbolAssoc::new(ctx).process(hir)),
+    ]
+};
+
+/// Get the names and dependencies of all HIR passes, in the order they run
+pub fn hir_pass_infos() -> impl Iterator<Item = PassInfo> {
+    HIR_PASSES.iter().map(|pass| pass.info)
+}
+
+/// Run all HIR passes that `passes` selects in order, returns `true` if any pass reported an error.
+///
+/// Passes that support it are run on up to `jobs` threads.
+/// When `verify` is set, the HIR is checked after each pass, and processing stops at the first pass that breaks an invariant.
+pub fn process_hir(hir: &mut Hir, pass_timings: bool, verify: bool, stats: &mut CompilerStats, ctx: &passes::PassContext, jobs: usize, passes: &PassManager) -> bool {
+    let worker_ctxs: Vec<_> = (0..jobs.max(1)).map(|_| ctx.worker_context()).collect();
+
+    for pass in HIR_PASSES {
+        let name = pass.info.name;
+        if !passes.should_run(name) {
+            continue;
+        }
+
+        let start = time::Instant::now();
+        match pass.kind {
+            HirPassKind::Pass(run) => {
+                run(hir, ctx, &worker_ctxs);
+                log_hir_pass_time(pass_timings, stats, start, name, true);
+
+                if verify && !verify_hir(hir, ctx, passes, name) {
+                    return true;
+                }
+            },
+            HirPassKind::Step(run) => {
+                if !run(hir, ctx) {
+                    return true;
+                }
+                log_hir_pass_time(pass_timings, stats, start, name, false);
+            },
+        }
+
+        if passes.dump_after(name) {
+            dump_hir(hir, ctx, name);
+        }
+    }
+
+    !ctx.errors.read().is_empty()
+}
+
+/// Print the HIR as pseudo-code
+pub fn dump_hir(hir: &mut Hir, ctx: &passes::PassContext, after: &str) {
+    let names = ctx.names.read();
+    let puncts = ctx.puncts.read();
+    let lits = ctx.lits.read();
+
+    crate::outputln!("--------------------------------");
+    crate::outputln!("HIR after '{after}':");
+    let mut hir_printer = CodePrinter::new(&names, &lits, &puncts);
+    hir_printer.visit(hir, VisitFlags::all());
+}
+
+//==============================================================================================================================
+
+fn finalize_precedence_uses(_hir: &Hir, ctx: &passes::PassContext) -> bool {
+    let mut uses = ctx.uses.write();
+    let syms = ctx.syms.read();
+    if let Err(err) = uses.finalize_precedences(&syms) {
+        ctx.add_error(HirError {
+            span: SpanId::INVALID,
+            err: HirErrorCode::UseTable { err },
+        });
+        return false;
+    }
+    true
+}
+
+fn build_precedence_dag(hir: &Hir, ctx: &passes::PassContext) -> bool {
+    let mut precedence_dag = ctx.precedence_dag.write();
+    let syms = ctx.syms.read();
+    let uses = ctx.uses.read();
+    precedence_dag.build_from_syms(&syms, &uses);
+
+    let cycles = precedence_dag.check_cycles();
+    if !cycles.is_empty() {
+        for cycle in cycles {
+            let cycle: Vec<_> = cycle.iter().map(|idx| {
+                let name = precedence_dag.get_name(*idx).unwrap().to_string();
+                let span = hir.precedences.iter()
+                    .find(|(_, ctx)| ctx.read().sym.as_ref().is_some_and(|sym| matches!(&*sym.read(), Symbol::Precedence(sym) if sym.id as u32 == *idx)))
+                    .map_or(SpanId::INVALID, |(item, _)| item.span);
+                (name, span)
+            }).collect();
+
+            ctx.add_error(HirError {
+                span: cycle[0].1,
+                err: HirErrorCode::CycleInPrecedenceDag { cycle },
+            })
+        }
+        
+        return false;
+    }
+    precedence_dag.calculate_order();
+    true
+}
+
+fn finalize_operator_uses(_hir: &Hir, ctx: &passes::PassContext) -> bool {
+    let mut uses = ctx.uses.write();
+    let syms = ctx.syms.read();
+    if let Err(err) = uses.finalize_operators(&syms) {
+        ctx.add_error(HirError {
+            span: SpanId::INVALID,
+            err: HirErrorCode::UseTable { err },
+        });
+        return false;
+    }
+    true
+}
+
+fn build_operator_table(_hir: &Hir, ctx: &passes::PassContext) -> bool {
+    let mut op_table = ctx.op_table.write();
+    let syms = ctx.syms.read();
+    let uses = ctx.uses.read();
+
+    op_table.build_from_symbols(&syms, &uses);
+    true
+}
+
+fn finalize_uses(_hir: &Hir, ctx: &passes::PassContext) -> bool {
+    let mut uses = ctx.uses.write();
+    let syms = ctx.syms.read();
+    if let Err(err) = uses.finalize(&syms) {
+        ctx.add_error(HirError {
+            span: SpanId::INVALID,
+            err: HirErrorCode::UseTable { err },
+        });
+        return false;
+    }
+    true
+}
+
+fn check_trait_dag(hir: &Hir, ctx: &passes::PassContext) -> bool {
+    let mut trait_dag = ctx.trait_dag.write();
+
+    let cycles = trait_dag.check_cycles();
+    if !cycles.is_empty() {
+        for cycle in cycles {
+            let cycle: Vec<_> = cycle.iter().map(|idx| {
+                let sym = trait_dag.get(*idx).unwrap().symbol.read();
+                let Symbol::Trait(sym) = &*sym else { unreachable!() };
+                let span = hir.traits.iter()
+                    .find(|(_, ctx)| ctx.read().dag_idx == *idx)
+                    .map_or(SpanId::INVALID, |(item, _)| item.read().span);
+                (sym.path.to_string(), span)
+            }).collect();
+
+            ctx.add_error(HirError {
+                span: cycle[0].1,
+                err: HirErrorCode::CycleInTraitDag { cycle },
+            })
+        }
+        
+        return false;
+    }
+    trait_dag.calculate_predecessors();
+    true
+}
+
+//==============================================================================================================================
+
+/// Run a pass with one worker per context in `worker_ctxs`.
+/// After each kind of item, the errors of the workers are moved to `ctx` in the order of the items, so they don't depend on the number of workers.
+fn do_parallel_hir_pass<'a, T: ParallelPass<'a>>(hir: &mut Hir, ctx: &'a passes::PassContext, worker_ctxs: &'a [passes::PassContext]) {
+    if worker_ctxs.len() <= 1 {
+        T::new_worker(ctx).process(hir);
+    } else {
+        visit_parallel(hir, VisitFlags::all(), worker_ctxs.len(), |idx| T::new_worker(&worker_ctxs[idx]), || {
+            let mut errors = ctx.errors.write();
+            for worker_ctx in worker_ctxs {
+                errors.append(&mut worker_ctx.errors.write());
+            }
+        });
+    }
+}
+
+fn log_hir_pass_time(pass_timings: bool, stats: &mut CompilerStats, start: time::Instant, name: &str, is_direct_pass: bool) {
+    let dur = time::Instant::now() - start;
+    let category = if is_direct_pass { "hir pass" } else { "hir step" };
+    stats.trace.add_span(name, category, start, serde_json::Map::new());
+    stats.add_hir_pass(dur);
+
+    if !pass_timings {
+        return;
+    }
+
+    const NAME_WIDTH: usize = 40;
+    let name = if is_direct_pass {
+        format!("HIR pass '{name:NAME_WIDTH$}' took ")
+    } else {
+        format!("{name:0$} took ", NAME_WIDTH + 11)
+    };
+    
+    let dur = dur.as_secs_f32() * 1000.0;
+    if dur < 10.0 {
+        crate::outputln!("{name}{:.2} ms", dur)
+    } else if dur < 1000.0 {
+        crate::outputln!("{name}{:.1} ms", dur)
+    } else {
+        crate::outputln!("{name}{:.2} s", dur / 1000.0);
+    }
+}
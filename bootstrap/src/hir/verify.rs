@@ -0,0 +1,280 @@
+use crate::{
+    common::{PassManager, SpanId, SymbolRef},
+    error_warning::HirErrorCode,
+    type_system,
+};
+
+use super::{passes::{self, PassContext}, helpers, Hir, HirError, Pass, Path, PathStart, Type, VisitFlags, Visitor};
+
+/// Check the invariants that later passes rely on, after the pass `after` has run.
+///
+/// Which invariants are checked depends on the passes that have run so far:
+/// - impl item vectors are always sorted by the index of their impl
+/// - every item context has its symbol set after 'Symbol Generation', or the pass that generates the symbols of the item kind
+/// - no `Self` or placeholder types remain after 'Self Type Replacement'
+/// - every path is resolved to a symbol path after 'Path Generation'
+///
+/// Returns `false` if any invariant is broken, the violations are reported as errors in `ctx`.
+pub fn verify_hir(hir: &mut Hir, ctx: &PassContext, passes: &PassManager, after: &'static str) -> bool {
+    let has_run = |name: &str| passes.should_run(name) && !passes.runs_before(after, name);
+
+    let mut verifier = HirVerifier {
+        ctx,
+        after,
+        check_self_ty: false,
+        check_paths:   false,
+        num_errors:    0,
+    };
+
+    verifier.verify_impl_item_order(hir);
+    if has_run(passes::PrecedenceSymGen::NAME) {
+        for (node, ctx) in &hir.precedences {
+            verifier.verify_sym(node.span, &ctx.read().sym, "precedence");
+        }
+    }
+    if has_run(passes::OperatorSymbolGen::NAME) {
+        for (node, ctx) in &hir.op_sets {
+            verifier.verify_sym(node.read().span, &ctx.read().sym, "operator set");
+        }
+        for (_, node, ctx) in &hir.operators {
+            verifier.verify_sym(node.span, &ctx.sym, "operator");
+        }
+    }
+    if has_run(passes::SymbolGeneration::NAME) {
+        verifier.verify_item_syms(hir);
+    }
+
+    if has_run(passes::SelfTyReplacePass::NAME) {
+        verifier.check_self_ty = true;
+        verifier.visit(hir, passes::SelfTyReplacePass::VISIT_FLAGS);
+        verifier.check_self_ty = false;
+    }
+    if has_run(passes::PathGen::NAME) {
+        verifier.check_paths = true;
+        verifier.visit(hir, VisitFlags::all());
+    }
+
+    verifier.num_errors == 0
+}
+
+struct HirVerifier<'a> {
+    ctx:           &'a PassContext,
+    after:         &'static str,
+    check_self_ty: bool,
+    check_paths:   bool,
+    num_errors:    usize,
+}
+
+impl HirVerifier<'_> {
+    fn add_violation(&mut self, span: SpanId, info: String) {
+        self.num_errors += 1;
+        self.ctx.add_error(HirError {
+            span,
+            err: HirErrorCode::InvariantViolation { pass: self.after, info },
+        });
+    }
+
+    fn verify_sym(&mut self, span: SpanId, sym: &Option<SymbolRef>, kind: &str) {
+        if sym.is_none() {
+            self.add_violation(span, format!("{kind} has no symbol"));
+        }
+    }
+
+    fn verify_item_syms(&mut self, hir: &Hir) {
+        for (node, ctx) in &hir.functions                { self.verify_sym(node.span, &ctx.sym, "function"); }
+        for (node, ctx) in &hir.extern_functions_no_body { self.verify_sym(node.span, &ctx.sym, "extern function"); }
+        for (node, ctx) in &hir.type_aliases             { self.verify_sym(node.span, &ctx.sym, "type alias"); }
+        for (node, ctx) in &hir.distinct_types           { self.verify_sym(node.span, &ctx.sym, "distinct type"); }
+        for (node, ctx) in &hir.opaque_types             { self.verify_sym(node.span, &ctx.sym, "opaque type"); }
+        for (node, ctx) in &hir.structs                  { self.verify_sym(node.span, &ctx.sym, "struct"); }
+        for (node, ctx) in &hir.tuple_structs            { self.verify_sym(node.span, &ctx.sym, "tuple struct"); }
+        for (node, ctx) in &hir.unit_structs             { self.verify_sym(node.span, &ctx.sym, "unit struct"); }
+        for (node, ctx) in &hir.unions                   { self.verify_sym(node.span, &ctx.sym, "union"); }
+        for (node, ctx) in &hir.adt_enums                { self.verify_sym(node.span, &ctx.sym, "enum"); }
+        for (node, ctx) in &hir.flag_enums               { self.verify_sym(node.span, &ctx.sym, "flag enum"); }
+        for (node, ctx) in &hir.bitfields                { self.verify_sym(node.span, &ctx.sym, "bitfield"); }
+        for (node, ctx) in &hir.consts                   { self.verify_sym(node.span, &ctx.sym, "const"); }
+        for (node, ctx) in &hir.statics                  { self.verify_sym(node.span, &ctx.sym, "static"); }
+        for (node, ctx) in &hir.tls_statics              { self.verify_sym(node.span, &ctx.sym, "tls static"); }
+        for (node, ctx) in &hir.extern_statics           { self.verify_sym(node.span, &ctx.sym, "extern static"); }
+
+        for (node, ctx) in &hir.traits                   { self.verify_sym(node.read().span, &ctx.read().sym, "trait"); }
+        for (_, node, ctx) in &hir.trait_functions       { self.verify_sym(node.span, &ctx.sym, "trait function"); }
+        for (_, node, ctx) in &hir.trait_methods         { self.verify_sym(node.span, &ctx.sym, "trait method"); }
+        for (_, node, ctx) in &hir.trait_type_alias      { self.verify_sym(node.span, &ctx.sym, "trait type alias"); }
+        for (_, node, ctx) in &hir.trait_consts          { self.verify_sym(node.span, &ctx.sym, "trait const"); }
+        for (_, node, ctx) in &hir.trait_properties      { self.verify_sym(node.span, &ctx.sym, "trait property"); }
+
+        for (node, ctx) in &hir.impls                    { self.verify_sym(node.read().span, &ctx.read().sym, "impl"); }
+        for (_, node, ctx) in &hir.impl_functions        { self.verify_sym(node.span, &ctx.sym, "impl function"); }
+        for (_, node, ctx) in &hir.methods               { self.verify_sym(node.span, &ctx.sym, "method"); }
+        for (_, node, ctx) in &hir.impl_type_aliases     { self.verify_sym(node.span, &ctx.sym, "impl type alias"); }
+        for (_, node, ctx) in &hir.impl_consts           { self.verify_sym(node.span, &ctx.sym, "impl const"); }
+        for (_, node, ctx) in &hir.impl_statics          { self.verify_sym(node.span, &ctx.sym, "impl static"); }
+        for (_, node, ctx) in &hir.impl_tls_statics      { self.verify_sym(node.span, &ctx.sym, "impl tls static"); }
+        for (_, node, ctx) in &hir.properties            { self.verify_sym(node.span, &ctx.sym, "property"); }
+    }
+
+    // Impl items are found using a binary search on the impl index, see `Hir::find_impl_def_insert_loc`
+    fn verify_impl_item_order(&mut self, hir: &Hir) {
+        let num_impls = hir.impls.len();
+        let mut verify = |kind: &str, idxs: Vec<usize>| {
+            if let Some(pos) = idxs.windows(2).position(|idxs| idxs[0] > idxs[1]) {
+                self.add_violation(SpanId::INVALID, format!("{kind} are not sorted by impl, item {} belongs to impl {}, but the next item to impl {}", pos, idxs[pos], idxs[pos + 1]));
+            }
+            if let Some(idx) = idxs.iter().find(|idx| **idx >= num_impls) {
+                self.add_violation(SpanId::INVALID, format!("{kind} refer to impl {idx}, but there are only {num_impls} impls"));
+            }
+        };
+
+        verify("impl functions", hir.impl_functions.iter().map(|(idx, ..)| *idx).collect());
+        verify("methods", hir.methods.iter().map(|(idx, ..)| *idx).collect());
+        verify("impl type aliases", hir.impl_type_aliases.iter().map(|(idx, ..)| *idx).collect());
+        verify("impl consts", hir.impl_consts.iter().map(|(idx, ..)| *idx).collect());
+        verify("impl statics", hir.impl_statics.iter().map(|(idx, ..)| *idx).collect());
+        verify("impl tls statics", hir.impl_tls_statics.iter().map(|(idx, ..)| *idx).collect());
+        verify("properties", hir.properties.iter().map(|(idx, ..)| *idx).collect());
+    }
+}
+
+impl Visitor for HirVerifier<'_> {
+    fn visit_path(&mut self, path: &mut Path) {
+        helpers::visit_path(self, path);
+
+        if self.check_self_ty && matches!(path.start, PathStart::SelfTy { .. }) {
+            self.add_violation(path.span, "path still starts with `Self`".to_string());
+        }
+
+        if self.check_paths && path.ctx.path.len() != path.idens.len() {
+            self.add_violation(path.span, "path is not resolved to a symbol path".to_string());
+        }
+    }
+
+    fn visit_type(&mut self, node: &mut Type) {
+        helpers::visit_type(self, node);
+
+        if self.check_self_ty {
+            if let Some(ty) = &node.ctx().ty {
+                if matches!(*ty.get(), type_system::Type::Placeholder) {
+                    self.add_violation(SpanId::INVALID, "type is still a placeholder".to_string());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, env, fs, process};
+
+    use crate::{
+        common::{CompilerStats, PassManager, Scope},
+        error_warning::HirErrorCode,
+        frontend::{self, FrontEndOptions},
+        hir::{self, passes::{self, Pass as _, PassContext}, Hir, Path, VisitFlags, Visitor},
+        target,
+    };
+
+    use super::verify_hir;
+
+    const SOURCE: &str = "
+struct A {
+    x: i32,
+}
+
+struct B {
+    x: i32,
+}
+
+impl A {
+    const C: i32 = 1;
+}
+
+impl B {
+    const C: i32 = 2;
+}
+
+fn make(a: A) -> A {
+    a
+}
+";
+
+    /// Clears the symbol path of the first path it visits
+    struct PathCorrupter {
+        done: bool,
+    }
+
+    impl Visitor for PathCorrupter {
+        fn visit_path(&mut self, path: &mut Path) {
+            if !self.done {
+                path.ctx.path = Scope::new();
+                self.done = true;
+            }
+        }
+    }
+
+    /// Run the passes up to and including 'Path Generation' on `SOURCE`
+    fn process(name: &str) -> (Hir, PassContext, PassManager) {
+        let dir = env::temp_dir().join(format!("xenon-verify-test-{}-{name}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let root = dir.join("lib.xn");
+        fs::write(&root, SOURCE).unwrap();
+
+        let target = target::file_target(&root);
+        let ctx = PassContext::new(target.lib_path.clone());
+        let passes = PassManager::new(frontend::pass_infos(), Some("path-generation"), &[], &[]).unwrap();
+        let options = FrontEndOptions { jobs: 1, ..FrontEndOptions::default() };
+        let mut stats = CompilerStats::new();
+
+        let processed = frontend::process_files(&target, &options, &passes, &ctx, &HashMap::new(), &stats.trace);
+        let mut hir = Hir::new();
+        for file in processed {
+            let (Some(ast), Some(mut ast_ctx)) = (file.ast, file.ast_ctx) else { panic!("{} failed to parse", file.input_file) };
+            frontend::lower_file(&ctx, &mut hir, &ast, &mut ast_ctx);
+        }
+        let failed = hir::process_hir(&mut hir, false, false, &mut stats, &ctx, 1, &passes);
+        assert!(!failed && ctx.errors.read().is_empty(), "processing the test source failed");
+
+        _ = fs::remove_dir_all(&dir);
+        (hir, ctx, passes)
+    }
+
+    fn violations(ctx: &PassContext) -> Vec<String> {
+        ctx.errors.read().iter()
+            .map(|err| match &err.err {
+                HirErrorCode::InvariantViolation { pass, info } => {
+                    assert_eq!(*pass, passes::PathGen::NAME);
+                    info.clone()
+                },
+                _ => panic!("unexpected error"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_hir() {
+        let (mut hir, ctx, passes) = process("valid");
+        assert!(verify_hir(&mut hir, &ctx, &passes, passes::PathGen::NAME));
+        assert!(violations(&ctx).is_empty());
+    }
+
+    #[test]
+    fn test_corrupted_hir() {
+        let (mut hir, ctx, passes) = process("corrupted");
+
+        // The consts of the second impl now come before those of the first
+        assert_eq!(hir.impl_consts.len(), 2);
+        assert_ne!(hir.impl_consts[0].0, hir.impl_consts[1].0);
+        hir.impl_consts.swap(0, 1);
+
+        let mut corrupter = PathCorrupter { done: false };
+        corrupter.visit(&mut hir, VisitFlags::all());
+        assert!(corrupter.done);
+
+        assert!(!verify_hir(&mut hir, &ctx, &passes, passes::PathGen::NAME));
+        let violations = violations(&ctx);
+        assert_eq!(violations.len(), 2, "{violations:?}");
+        assert!(violations[0].starts_with("impl consts are not sorted by impl"), "{}", violations[0]);
+        assert_eq!(violations[1], "path is not resolved to a symbol path");
+    }
+}
@@ -491,6 +491,14 @@ pub trait Visitor: Sized {
     fn visit_fn_type(&mut self, node: &mut FnType) {
         helpers::visit_fn_type(self, node);
     }
+
+    fn visit_trait_object_type(&mut self, node: &mut TraitObjectType) {
+        helpers::visit_trait_object_type(self, node);
+    }
+
+    fn visit_impl_trait_type(&mut self, node: &mut ImplTraitType) {
+        helpers::visit_impl_trait_type(self, node);
+    }
     
     // =============================================================
     
@@ -1625,6 +1633,8 @@ pub(crate) mod helpers {
             Type::Reference(ty)   => visitor.visit_reference_type(ty),
             Type::Optional(ty)    => visitor.visit_optional_type(ty),
             Type::Fn(ty)          => visitor.visit_fn_type(ty),
+            Type::TraitObject(ty) => visitor.visit_trait_object_type(ty),
+            Type::ImplTrait(ty)   => visitor.visit_impl_trait_type(ty),
         }
     }
 
@@ -1676,6 +1686,14 @@ pub(crate) mod helpers {
             visitor.visit_type(ty);
         }
     }
+
+    pub fn visit_trait_object_type<T: Visitor>(visitor: &mut T, node: &mut TraitObjectType) {
+        visitor.visit_trait_bounds(&mut node.bounds);
+    }
+
+    pub fn visit_impl_trait_type<T: Visitor>(visitor: &mut T, node: &mut ImplTraitType) {
+        visitor.visit_trait_bounds(&mut node.bounds);
+    }
     
     // =============================================================
 
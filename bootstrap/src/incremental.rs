@@ -0,0 +1,418 @@
+// Incremental compilation cache.
+//
+// Lexing, parsing and the per-file AST passes only depend on the content of a file, its path and the scope of its module,
+// so their output is stored on disk for each file, keyed by a hash of those and of the build id of the compiler.
+// Files with a stored entry for their key skip straight to HIR lowering.
+//
+// Names, punctuation, literals and spans are referred to by ids into tables that are shared by all files, which are filled in a different order on every run.
+// An entry therefore stores the values its ids refer to, and ids are written as an index into those values, and remapped into the current tables when the entry is loaded.
+// Values are only added to the tables once the whole entry is known to be valid, so a stale or corrupt entry leaves no trace.
+
+use std::{
+    any::Any,
+    cell::RefCell,
+    collections::HashMap,
+    fs,
+    hash::Hash,
+    io,
+    path::PathBuf,
+    process,
+    sync::Arc,
+};
+
+use serde::{de::{self, DeserializeOwned}, ser, Deserialize, Deserializer, Serialize, Serializer};
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::{
+    common::{NameId, NameTable, Scope, Span, SpanId, SpanRegistry, BUILD_ID},
+    lexer::{PunctuationId, PuncutationTable},
+    literals::{Literal, LiteralId, LiteralTable},
+};
+
+/// Version of the layout of cache entries, needs to be bumped when the layout of any cached data changes
+const FORMAT_VERSION: u32 = 2;
+
+/// Tables that ids in cached data refer to
+#[derive(Clone, Copy)]
+pub struct CacheTables<'a> {
+    pub names:  &'a NameTable,
+    pub puncts: &'a PuncutationTable,
+    pub lits:   &'a LiteralTable,
+    pub spans:  &'a SpanRegistry,
+}
+
+/// Key of the cache entry of a file
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CacheKey(u128);
+
+impl CacheKey {
+    /// Create the key for a file, `options` contains anything else that affects the output, like command line flags
+    pub fn new(path: &str, source: &str, module_scope: &Scope, options: &[bool]) -> Self {
+        let mut hasher = Xxh3::new();
+        let module_scope = module_scope.to_string();
+        for part in [BUILD_ID, path, &module_scope, source] {
+            // Prefix each part with its length, so different splits of the same bytes don't result in the same key
+            hasher.update(&(part.len() as u64).to_le_bytes());
+            hasher.update(part.as_bytes());
+        }
+        hasher.update(&FORMAT_VERSION.to_le_bytes());
+        for option in options {
+            hasher.update(&[*option as u8]);
+        }
+        Self(hasher.digest128())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    key:     u128,
+    names:   Vec<String>,
+    puncts:  Vec<String>,
+    lits:    Vec<Literal>,
+    /// Files the spans are in, the file id of a span is an index into this
+    files:   Vec<String>,
+    spans:   Vec<Span>,
+    payload: Vec<u8>,
+}
+
+/// On-disk cache containing an entry per file
+pub struct IncrementalCache {
+    dir: PathBuf,
+}
+
+impl IncrementalCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Load the entry for `key`, `None` is returned when there is no valid entry
+    pub fn load<T: DeserializeOwned>(&self, key: CacheKey, tables: CacheTables) -> Option<T> {
+        let bytes = fs::read(self.entry_path(key)).ok()?;
+        let entry: CacheEntry = bincode::deserialize(&bytes).ok()?;
+        if entry.key != key.0 || entry.spans.iter().any(|span| span.file_id as usize >= entry.files.len()) {
+            return None;
+        }
+
+        // Deserialize the payload against placeholder ids first, so nothing is added to the tables for an entry that can't be loaded
+        let mut map = IdMap::new();
+        map.names.ids = vec![NameId::INVALID; entry.names.len()];
+        map.puncts.ids = vec![PunctuationId::INVALID; entry.puncts.len()];
+        map.lits.ids = vec![LiteralId::INVALID; entry.lits.len()];
+        map.spans.ids = vec![SpanId::INVALID; entry.spans.len()];
+        with_id_map(map, || bincode::deserialize::<T>(&entry.payload)).0.ok()?;
+
+        let mut map = IdMap::new();
+        map.names.ids = entry.names.iter().map(|name| tables.names.add(name)).collect();
+        map.puncts.ids = entry.puncts.iter().map(|punct| tables.puncts.add(punct)).collect();
+        map.lits.ids = entry.lits.into_iter().map(|lit| tables.lits.add(lit)).collect();
+        map.spans.ids = entry.spans.iter().map(|span| tables.spans.add_span(&entry.files[span.file_id as usize], *span)).collect();
+
+        with_id_map(map, || bincode::deserialize(&entry.payload)).0.ok()
+    }
+
+    /// Store `value` as the entry for `key`, replacing any existing entry
+    pub fn store<T: Serialize>(&self, key: CacheKey, value: &T, tables: CacheTables) -> io::Result<()> {
+        let (payload, map) = with_id_map(IdMap::new(), || bincode::serialize(value));
+        let payload = payload.map_err(io::Error::other)?;
+
+        let mut files: Vec<String> = Vec::new();
+        let mut spans = Vec::with_capacity(map.spans.ids.len());
+        for span_id in &map.spans.ids {
+            let Some(span) = tables.spans.get(*span_id) else {
+                return Err(io::Error::other(format!("{span_id} is not registered")));
+            };
+            let file = tables.spans.get_file(span.file_id);
+            let file_idx = match files.iter().position(|path| path == file) {
+                Some(idx) => idx,
+                None => {
+                    files.push(file.to_string());
+                    files.len() - 1
+                },
+            };
+            spans.push(Span { file_id: file_idx as u32, ..*span });
+        }
+
+        let entry = CacheEntry {
+            key: key.0,
+            names: map.names.ids.iter().map(|name| tables.names[*name].to_string()).collect(),
+            puncts: map.puncts.ids.iter().map(|punct| tables.puncts[*punct].to_string()).collect(),
+            lits: map.lits.ids.iter().map(|lit| tables.lits[*lit].clone()).collect(),
+            files,
+            spans,
+            payload,
+        };
+        let bytes = bincode::serialize(&entry).map_err(io::Error::other)?;
+
+        // Write to a temporary file first, so other compiler instances never see a partially written entry
+        fs::create_dir_all(&self.dir)?;
+        let path = self.entry_path(key);
+        let tmp_path = path.with_extension(format!("tmp{}", process::id()));
+        fs::write(&tmp_path, bytes)?;
+        fs::rename(&tmp_path, &path)
+    }
+
+    fn entry_path(&self, key: CacheKey) -> PathBuf {
+        self.dir.join(format!("{:032x}.bin", key.0))
+    }
+}
+
+// =============================================================
+
+/// Mapping between ids in the current tables and indices in a cache entry
+struct IdTable<T> {
+    /// Indices of ids, only used while storing an entry
+    indices: HashMap<T, u32>,
+    ids:     Vec<T>,
+}
+
+impl<T: Copy + Eq + Hash> IdTable<T> {
+    fn new() -> Self {
+        Self {
+            indices: HashMap::new(),
+            ids: Vec::new(),
+        }
+    }
+
+    fn index_of(&mut self, id: T) -> u32 {
+        *self.indices.entry(id).or_insert_with(|| {
+            self.ids.push(id);
+            self.ids.len() as u32 - 1
+        })
+    }
+}
+
+struct IdMap {
+    names:        IdTable<NameId>,
+    puncts:       IdTable<PunctuationId>,
+    lits:         IdTable<LiteralId>,
+    spans:        IdTable<SpanId>,
+    /// Indices of AST nodes, by address, while storing an entry
+    node_indices: HashMap<*const (), u32>,
+    /// AST nodes that have been loaded so far
+    nodes:        Vec<Arc<dyn Any + Send + Sync>>,
+}
+
+impl IdMap {
+    fn new() -> Self {
+        Self {
+            names: IdTable::new(),
+            puncts: IdTable::new(),
+            lits: IdTable::new(),
+            spans: IdTable::new(),
+            node_indices: HashMap::new(),
+            nodes: Vec::new(),
+        }
+    }
+}
+
+thread_local! {
+    static ID_MAP: RefCell<Option<IdMap>> = const { RefCell::new(None) };
+}
+
+fn with_id_map<R>(map: IdMap, f: impl FnOnce() -> R) -> (R, IdMap) {
+    ID_MAP.with_borrow_mut(|cur| *cur = Some(map));
+    let res = f();
+    let map = ID_MAP.with_borrow_mut(Option::take).expect("id map was removed while in use");
+    (res, map)
+}
+
+fn access_id_map<R>(f: impl FnOnce(&mut IdMap) -> R) -> Option<R> {
+    ID_MAP.with_borrow_mut(|map| map.as_mut().map(f))
+}
+
+const NO_ID_MAP: &str = "ids can only be (de)serialized as part of an incremental cache entry";
+
+/// Id that refers into a table that is shared by all files
+trait TableId: Copy + Eq + Hash {
+    const INVALID: Self;
+
+    fn table(map: &mut IdMap) -> &mut IdTable<Self>;
+
+    fn serialize<S: Serializer>(self, serializer: S) -> Result<S::Ok, S::Error> {
+        let index = match self == Self::INVALID {
+            true  => None,
+            false => Some(access_id_map(|map| Self::table(map).index_of(self)).ok_or_else(|| ser::Error::custom(NO_ID_MAP))?),
+        };
+        index.serialize(serializer)
+    }
+
+    fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let Some(index) = Option::<u32>::deserialize(deserializer)? else {
+            return Ok(Self::INVALID);
+        };
+        access_id_map(|map| Self::table(map).ids.get(index as usize).copied())
+            .ok_or_else(|| de::Error::custom(NO_ID_MAP))?
+            .ok_or_else(|| de::Error::custom(format!("id index {index} is out of range")))
+    }
+}
+
+macro_rules! impl_table_id {
+    ($ty:ty, $table:ident) => {
+        impl TableId for $ty {
+            const INVALID: Self = <$ty>::INVALID;
+
+            fn table(map: &mut IdMap) -> &mut IdTable<Self> {
+                &mut map.$table
+            }
+        }
+
+        impl Serialize for $ty {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                TableId::serialize(*self, serializer)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $ty {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                <Self as TableId>::deserialize(deserializer)
+            }
+        }
+    };
+}
+
+impl_table_id!(NameId, names);
+impl_table_id!(PunctuationId, puncts);
+impl_table_id!(LiteralId, lits);
+impl_table_id!(SpanId, spans);
+
+// =============================================================
+
+/// Register the address of the AST node at `index`, so references to it can be stored as its index
+pub(crate) fn register_node_address(address: *const (), index: u32) -> Result<(), &'static str> {
+    access_id_map(|map| { map.node_indices.insert(address, index); }).ok_or(NO_ID_MAP)
+}
+
+pub(crate) fn node_index(address: *const ()) -> Result<u32, &'static str> {
+    access_id_map(|map| map.node_indices.get(&address).copied())
+        .ok_or(NO_ID_MAP)?
+        .ok_or("reference to a node that is not part of the AST")
+}
+
+/// Add a loaded AST node, nodes need to be added in order, so they can be referenced by their index
+pub(crate) fn add_loaded_node(node: Arc<dyn Any + Send + Sync>) -> Result<(), &'static str> {
+    access_id_map(|map| map.nodes.push(node)).ok_or(NO_ID_MAP)
+}
+
+pub(crate) fn loaded_node<T: Any + Send + Sync>(index: u32) -> Result<Arc<T>, &'static str> {
+    access_id_map(|map| map.nodes.get(index as usize).cloned())
+        .ok_or(NO_ID_MAP)?
+        .ok_or("reference to a node that has not been loaded yet")?
+        .downcast()
+        .map_err(|_| "reference to a node of the wrong kind")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(row: u32) -> Span {
+        Span { file_id: 0, row, column: 1, char_offset: 0, byte_offset: 0, char_len: 1, byte_len: 1, row_end: row, column_end: 2 }
+    }
+
+    struct Tables {
+        names:  NameTable,
+        puncts: PuncutationTable,
+        lits:   LiteralTable,
+        spans:  SpanRegistry,
+    }
+
+    impl Tables {
+        fn new() -> Self {
+            Self { names: NameTable::new(), puncts: PuncutationTable::new(), lits: LiteralTable::new(), spans: SpanRegistry::new() }
+        }
+
+        fn get(&self) -> CacheTables<'_> {
+            CacheTables { names: &self.names, puncts: &self.puncts, lits: &self.lits, spans: &self.spans }
+        }
+    }
+
+    fn cache_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("xenon-incremental-{name}-{}", process::id()));
+        _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dir = cache_dir("round-trip");
+        let cache = IncrementalCache::new(dir.clone());
+        let key = CacheKey::new("src/main.xn", "fn main() {}", &Scope::new(), &[false]);
+
+        let tables = Tables::new();
+        let value = (
+            vec![tables.names.add("main"), tables.names.add("other"), tables.names.add("main"), NameId::INVALID],
+            tables.puncts.add("+="),
+            tables.lits.add(Literal::Char('x')),
+            vec![tables.spans.add_span("src/main.xn", span(1)), tables.spans.add_span("src/other.xn", span(2))],
+        );
+        cache.store(key, &value, tables.get()).unwrap();
+
+        // Fill the tables of the loading compiler in a different order, so the ids don't line up
+        let loaded_tables = Tables::new();
+        loaded_tables.names.add("unrelated");
+        loaded_tables.spans.add_span("src/unrelated.xn", span(3));
+        let (names, punct, lit, spans) = cache.load::<(Vec<NameId>, PunctuationId, LiteralId, Vec<SpanId>)>(key, loaded_tables.get()).unwrap();
+
+        let names: Vec<&str> = names[..3].iter().map(|name| &loaded_tables.names[*name]).collect();
+        assert_eq!(names, ["main", "other", "main"]);
+        assert_eq!(&loaded_tables.puncts[punct], "+=");
+        assert_eq!(loaded_tables.lits[lit], Literal::Char('x'));
+        for (span_id, (file, row)) in spans.iter().zip([("src/main.xn", 1), ("src/other.xn", 2)]) {
+            assert_eq!(loaded_tables.spans.get_file_from_span_id(*span_id), Some(file));
+            assert_eq!(loaded_tables.spans[*span_id].row, row);
+        }
+
+        _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_key_invalidation() {
+        let scope = Scope::new();
+        let mut sub_scope = Scope::new();
+        sub_scope.push("sub".to_string());
+
+        let key = CacheKey::new("a.xn", "fn main() {}", &scope, &[false]);
+        assert_eq!(key, CacheKey::new("a.xn", "fn main() {}", &scope, &[false]));
+        assert_ne!(key, CacheKey::new("b.xn", "fn main() {}", &scope, &[false]));
+        assert_ne!(key, CacheKey::new("a.xn", "fn main() { }", &scope, &[false]));
+        assert_ne!(key, CacheKey::new("a.xn", "fn main() {}", &sub_scope, &[false]));
+        assert_ne!(key, CacheKey::new("a.xn", "fn main() {}", &scope, &[true]));
+        // Parts are length prefixed, so moving bytes between them changes the key
+        assert_ne!(CacheKey::new("ab", "c", &scope, &[]), CacheKey::new("a", "bc", &scope, &[]));
+    }
+
+    #[test]
+    fn test_invalid_entry_is_not_loaded() {
+        let dir = cache_dir("invalid");
+        let cache = IncrementalCache::new(dir.clone());
+        let key = CacheKey::new("a.xn", "fn main() {}", &Scope::new(), &[false]);
+        let other_key = CacheKey::new("a.xn", "fn other() {}", &Scope::new(), &[false]);
+
+        let tables = Tables::new();
+        let value = (tables.names.add("cached_name"), 1u8);
+        cache.store(key, &value, tables.get()).unwrap();
+
+        let loaded_tables = Tables::new();
+        assert!(cache.load::<(NameId, u8)>(other_key, loaded_tables.get()).is_none());
+
+        // An entry that is stored under the wrong key is not used
+        fs::copy(cache.entry_path(key), cache.entry_path(other_key)).unwrap();
+        assert!(cache.load::<(NameId, u8)>(other_key, loaded_tables.get()).is_none());
+
+        // An entry whose payload doesn't match the requested type is rejected, without adding its names to the tables
+        assert!(cache.load::<(NameId, u64)>(key, loaded_tables.get()).is_none());
+        assert_eq!(loaded_tables.names.get_id_for_str("cached_name"), NameId::INVALID);
+
+        // As is a truncated entry
+        let bytes = fs::read(cache.entry_path(key)).unwrap();
+        fs::write(cache.entry_path(key), &bytes[..bytes.len() - 1]).unwrap();
+        assert!(cache.load::<(NameId, u8)>(key, loaded_tables.get()).is_none());
+        assert_eq!(loaded_tables.names.get_id_for_str("cached_name"), NameId::INVALID);
+
+        fs::write(cache.entry_path(key), bytes).unwrap();
+        let (name, val) = cache.load::<(NameId, u8)>(key, loaded_tables.get()).unwrap();
+        assert_eq!((&loaded_tables.names[name], val), ("cached_name", 1));
+
+        _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::{HashMap, HashSet};
+
+use unicode_security::confusable_detection::skeleton;
+
+use crate::{
+    common::{Diagnostic, NameTable, SpanId},
+    error_warning::LexErrorCode,
+};
+
+use super::{PuncutationTable, Token, TokenStore};
+
+/// Lint for names and punctuation that are visually confusable with each other, using the skeleton algorithm from UAX #39.
+///
+/// Tokens are collected from every file in a library, each text is only kept at its first occurrence.
+pub struct ConfusablesLint {
+    names:  Vec<(String, SpanId)>,
+    puncts: Vec<(String, SpanId)>,
+    seen:   HashSet<(bool, String)>,
+}
+
+impl ConfusablesLint {
+    pub fn new() -> Self {
+        Self {
+            names: Vec::new(),
+            puncts: Vec::new(),
+            seen: HashSet::new(),
+        }
+    }
+
+    pub fn collect(&mut self, tokens: &TokenStore, names: &NameTable, puncts: &PuncutationTable) {
+        for (token, meta) in tokens.tokens.iter().zip(&tokens.metadata) {
+            let (is_name, text) = match token {
+                Token::Name(name_id)      => (true, &names[*name_id]),
+                Token::Punctuation(punct) => (false, punct.as_str(puncts)),
+                _                         => continue,
+            };
+
+            if self.seen.insert((is_name, text.to_string())) {
+                let list = if is_name { &mut self.names } else { &mut self.puncts };
+                list.push((text.to_string(), meta.span_id));
+            }
+        }
+    }
+
+    /// Check all collected text, returning a warning for every name or punctuation that is confusable with one that was used before it
+    pub fn check(&self) -> Vec<Diagnostic> {
+        let mut warnings = Vec::new();
+        Self::check_list(&self.names, "name", &mut warnings);
+        Self::check_list(&self.puncts, "punctuation", &mut warnings);
+        warnings
+    }
+
+    fn check_list(list: &[(String, SpanId)], kind: &'static str, warnings: &mut Vec<Diagnostic>) {
+        let skeletons: Vec<String> = list.iter().map(|(text, _)| skeleton(text).collect()).collect();
+        let mut groups: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (idx, skeleton) in skeletons.iter().enumerate() {
+            groups.entry(skeleton).or_default().push(idx);
+        }
+
+        for (idx, (text, span)) in list.iter().enumerate() {
+            let group = &groups[skeletons[idx].as_str()];
+
+            // Only compare against the first occurrence that is confusable, and skip purely ascii pairs, like 'l' and 'I'
+            let Some(&other_idx) = group.iter().find(|&&other_idx| other_idx < idx && !(text.is_ascii() && list[other_idx].0.is_ascii())) else {
+                continue;
+            };
+            let (other, other_span) = &list[other_idx];
+
+            let err = LexErrorCode::Confusable { kind, found: text.clone(), other: other.clone() };
+            warnings.push(Diagnostic::warning(&err)
+                .with_primary(*span, format!("looks like '{other}'"))
+                .with_secondary(*other_span, format!("'{other}' is used here"))
+                .with_note(format!("'{text}' is written as {}, while '{other}' is written as {}", Self::code_points(text), Self::code_points(other)))
+            );
+        }
+    }
+
+    fn code_points(s: &str) -> String {
+        s.chars().map(|ch| format!("U+{:04X}", ch as u32)).collect::<Vec<_>>().join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{common::{Diagnostic, NameTable, SpanRegistry}, lexer::{Lexer, PuncutationTable}, literals::LiteralTable};
+
+    use super::ConfusablesLint;
+
+    /// Lex each source as a separate file and run the lint over all of them
+    fn check(sources: &[&str]) -> Vec<Diagnostic> {
+        let literals = LiteralTable::new();
+        let names = NameTable::new();
+        let punctuation = PuncutationTable::new();
+        let spans = SpanRegistry::new();
+
+        let mut lint = ConfusablesLint::new();
+        for (idx, source) in sources.iter().enumerate() {
+            let file = format!("{idx}.xn");
+            let mut lexer = Lexer::new(&file, source, &literals, &names, &punctuation, &spans);
+            lexer.lex();
+            assert!(lexer.errors.is_empty());
+            lint.collect(&lexer.tokens, &names, &punctuation);
+        }
+        lint.check()
+    }
+
+    #[test]
+    fn test_confusable_names() {
+        // Cyrillic 'а' (U+0430) looks like latin 'a'
+        let warnings = check(&["let a = 1; let \u{0430} = 2;"]);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].message, "Found name '\u{0430}' that is confusable with 'a'");
+        assert_eq!(warnings[0].labels.len(), 2);
+        assert_eq!(warnings[0].notes, ["'\u{0430}' is written as U+0430, while 'a' is written as U+0061"]);
+
+        // Only part of a name needs to be confusable, e.g. a greek omicron in 'foo'
+        let warnings = check(&["let foo = 1; let f\u{03BF}o = 2;"]);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].message, "Found name 'f\u{03BF}o' that is confusable with 'foo'");
+    }
+
+    #[test]
+    fn test_confusable_across_files() {
+        // The first occurrence is the one that is pointed to, even when it is in another file
+        let warnings = check(&["let \u{0441}at = 1;", "let cat = 2;"]);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].message, "Found name 'cat' that is confusable with '\u{0441}at'");
+    }
+
+    #[test]
+    fn test_confusable_punctuation() {
+        // U+235F (APL circle star) looks like U+229B (circled asterisk operator)
+        let warnings = check(&["a \u{229B} b; a \u{235F} b;"]);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].message, "Found punctuation '\u{235F}' that is confusable with '\u{229B}'");
+    }
+
+    #[test]
+    fn test_not_confusable() {
+        // Distinct names, repeated names, and ascii-only pairs like 'l' and 'I' are not reported
+        assert!(check(&["let foo = 1; let bar = foo; let foo = bar;"]).is_empty());
+        assert!(check(&["let l = 1; let I = 2; let rn = 3; let m = 4;"]).is_empty());
+        assert!(check(&["let \u{0430} = 1;", "let \u{0430} = 2;"]).is_empty());
+
+        // Non-latin names are fine when nothing they look like is used
+        assert!(check(&["let \u{0434}\u{0430} = 1; let \u{03C0} = 3;"]).is_empty());
+
+        // '|' has the same skeleton as 'l', but names and punctuation are checked separately
+        assert!(check(&["let l = a | b;"]).is_empty());
+    }
+}
@@ -1,11 +1,17 @@
 
 mod tokens;
 mod tables;
+mod source;
+mod confusables;
 
 use std::{collections::HashMap, fmt::{Debug, Display}, mem};
 
+use unicode_normalization::{char::{compose, is_combining_mark}, is_nfc_quick, IsNormalized, UnicodeNormalization};
+
 pub use tokens::*;
 pub use tables::*;
+pub use source::*;
+pub use confusables::*;
 
 use crate::{
     error_warning::LexErrorCode,
@@ -14,27 +20,47 @@ use crate::{
 };
 
 #[derive(Debug)]
-#[allow(unused)]
 pub struct LexerErr {
-    file:        String,
-    err:         LexErrorCode,
-    byte_offset: u64,
-    char_offset: u64,
-    line:        u32,
-    columnn:     u32,
-    byte_len:    u32,
-    char_len:    u32,
+    pub err:        LexErrorCode,
+    pub span:       SpanId,
+    // Span of the opening symbol, when the error is caused by an unmatched closing symbol
+    pub open_span:  Option<SpanId>,
 }
 
 impl LexerErr {
-    pub fn set_path(&mut self, path: String) {
-        self.file = path;
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let diag = Diagnostic::error(&self.err);
+        match &self.err {
+            LexErrorCode::InvalidBOM(_) =>
+                diag.with_primary(self.span, "unsupported byte order marker")
+                    .with_help("re-encode the file as utf-8, utf-16 or utf-32"),
+            LexErrorCode::InvalidEncoding { encoding, .. } =>
+                diag.with_primary(self.span, format!("invalid {encoding}"))
+                    .with_help("source files need to be encoded as utf-8, or utf-16/utf-32 with a BOM"),
+            LexErrorCode::NotNormalized { normalized } =>
+                Diagnostic::warning(&self.err)
+                    .with_primary(self.span, format!("normalized to '{normalized}'"))
+                    .with_note("names and punctuation are compared in Unicode Normalization Form C (NFC)"),
+            LexErrorCode::UnclosedBlockComment =>
+                diag.with_primary(self.span, "comment starts here")
+                    .with_note("block comments can be nested, each '/*' needs a matching '*/'"),
+            LexErrorCode::NoOpeningSym { sym } =>
+                diag.with_primary(self.span, format!("unmatched '{}'", sym.as_close_display_str())),
+            LexErrorCode::MismatchCloseSym { found, expected } => {
+                let diag = diag.with_primary(self.span, format!("expected '{}'", expected.as_close_display_str()));
+                match self.open_span {
+                    Some(open_span) => diag.with_secondary(open_span, format!("'{}' opened here", expected.as_open_display_str())),
+                    None => diag,
+                }.with_help(format!("'{}' does not close '{}'", found.as_close_display_str(), expected.as_open_display_str()))
+            },
+            _ => diag.with_primary(self.span, ""),
+        }
     }
 }
 
 impl Display for LexerErr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}({}:{}): {}", &self.file, self.line, self.columnn, self.err)
+        write!(f, "{}: {}", self.span, self.err)
     }
 }
 
@@ -47,10 +73,10 @@ enum DigitLexMode {
 
 pub struct Lexer<'a> {
     pub tokens:   TokenStore,
-    literals:     &'a mut LiteralTable,
-    names:        &'a mut NameTable,
-    punctuation:  &'a mut PuncutationTable,
-    spans:        &'a mut SpanRegistry,
+    literals:     &'a LiteralTable,
+    names:        &'a NameTable,
+    punctuation:  &'a PuncutationTable,
+    spans:        &'a SpanRegistry,
 
     meta_elems:   Vec<MetaElem>,
 
@@ -67,12 +93,20 @@ pub struct Lexer<'a> {
     tab_width:    u32,
 
     op_seq_map:   HashMap<&'static str, char>,
+
+    // Skip NFC normalization of names and punctuation, when the source is known to be normalized
+    pub skip_normalization: bool,
+
+    pub errors:   Vec<LexerErr>,
+    pub warnings: Vec<LexerErr>,
 }
 
 
 
 impl<'a> Lexer<'a> {
-    pub fn new(file: &'a str, source: &'a str, literals: &'a mut LiteralTable, names: &'a mut NameTable, punctuation: &'a mut PuncutationTable, spans: &'a mut SpanRegistry) -> Self {
+    pub fn new(file: &'a str, source: &'a str, literals: &'a LiteralTable, names: &'a NameTable, punctuation: &'a PuncutationTable, spans: &'a SpanRegistry) -> Self {
+        spans.set_file_source(file, source);
+
         let mut op_seq_map = HashMap::with_capacity(Self::OP_SEQ_MAPPING.len());
         for (name, ch) in Self::OP_SEQ_MAPPING {
             op_seq_map.insert(name, ch);
@@ -94,6 +128,9 @@ impl<'a> Lexer<'a> {
             columnn: 1,
             tab_width: 4, // TODO: This is not guaranteed to be 4
             op_seq_map,
+            skip_normalization: false,
+            errors: Vec::new(),
+            warnings: Vec::new(),
         }
     }
 }
@@ -654,9 +691,14 @@ impl Lexer<'_> {
             column_end: self.columnn + char_len,
         });
 
+        // Only keep the text if it can't be rebuilt from the token, e.g. literals or names that weren't normalized
+        let text = &self.cursor[..byte_len as usize];
+        let raw_text = (token.source_text(self.names, self.punctuation) != Some(text)).then(|| text.to_string());
+
         let metadata = TokenMetadata {
             span_id: span,
             meta_elems,
+            raw_text,
         };
 
         self.tokens.push(token, metadata);
@@ -668,6 +710,10 @@ impl Lexer<'_> {
         }
     }
 
+    fn last_span(&self) -> SpanId {
+        self.tokens.metadata.last().map_or(SpanId::INVALID, |meta| meta.span_id)
+    }
+
     fn add_strong_keyword(&mut self, keyword: StrongKeyword) {
         let len = keyword.as_str().len() as u32;
         self.add_token(Token::StrongKw(keyword), len, len);
@@ -679,8 +725,13 @@ impl Lexer<'_> {
     }
 
     fn add_name(&mut self, name: &str) {
-        let name_id = self.names.add(name);
+        let normalized = self.normalize(name);
+        let name_id = self.names.add(normalized.as_deref().unwrap_or(name));
         self.add_token(Token::Name(name_id), name.chars().count() as u32, name.len() as u32);
+
+        if let Some(normalized) = normalized {
+            self.add_normalization_warning(normalized);
+        }
     }
 
     fn add_whitespace(&mut self, whitespace: &str) {
@@ -714,8 +765,39 @@ impl Lexer<'_> {
     }
 
     fn add_punctuation(&mut self, s: &str) {
-        let punct = Punctuation::from_str(s, &mut self.punctuation);
+        let normalized = self.normalize(s);
+        let punct = Punctuation::from_str(normalized.as_deref().unwrap_or(s), self.punctuation);
         self.add_token(Token::Punctuation(punct), s.chars().count() as u32, s.len() as u32);
+
+        if let Some(normalized) = normalized {
+            self.add_normalization_warning(normalized);
+        }
+    }
+
+    /// Normalize text to NFC before it gets interned, returns `None` if it already is normalized
+    fn normalize(&self, s: &str) -> Option<String> {
+        if self.skip_normalization || s.is_ascii() {
+            return None;
+        }
+
+        match is_nfc_quick(s.chars()) {
+            IsNormalized::Yes => None,
+            IsNormalized::Maybe |
+            IsNormalized::No => {
+                let normalized: String = s.nfc().collect();
+                (normalized != s).then_some(normalized)
+            },
+        }
+    }
+
+    /// Warn about the last token not being NFC normalized
+    fn add_normalization_warning(&mut self, normalized: String) {
+        let span = self.tokens.metadata.last().unwrap().span_id;
+        self.warnings.push(LexerErr {
+            err: LexErrorCode::NotNormalized { normalized },
+            span,
+            open_span: None,
+        });
     }
 
     fn add_literal(&mut self, lit: Literal, char_len: u32, byte_len: u32) {
@@ -723,40 +805,83 @@ impl Lexer<'_> {
         self.add_token(Token::Literal(id), char_len, byte_len);
     }
 
-    fn gen_err(&self, err: LexErrorCode, byte_len: u32, char_len: u32) -> LexerErr {
+    fn gen_err(&mut self, err: LexErrorCode, byte_len: u32, char_len: u32) -> LexerErr {
+        let span = self.spans.add_span(self.file, Span {
+            file_id: 0,
+            char_offset: self.char_offset,
+            byte_offset: self.byte_offset,
+            char_len: char_len as u64,
+            byte_len: byte_len as u64,
+            row: self.line,
+            row_end: self.line,
+            column: self.columnn,
+            column_end: self.columnn + char_len,
+        });
+
         LexerErr {
-            file: String::new(),
             err,
-            byte_offset: self.byte_offset,
-            char_offset: self.char_offset,
-            line: self.line,
-            columnn: self.columnn,
-            byte_len,
-            char_len,
+            span,
+            open_span: None,
         }
     }
-    
-    pub fn lex(&mut self) -> Result<(), LexerErr> {
-        if self.cursor.is_empty() {
-            return Ok(());
+
+    /// Record an error and skip the malformed text by adding an `Error` token covering `byte_len` bytes.
+    /// At least 1 character is always skipped, so the lexer can never get stuck.
+    fn add_error(&mut self, err: LexErrorCode, byte_len: u32) -> &mut LexerErr {
+        let mut byte_len = (byte_len as usize).clamp(Self::next_char_len(self.cursor), self.cursor.len());
+        while !self.cursor.is_char_boundary(byte_len) {
+            byte_len += 1;
         }
 
-        self.lex_bom().map_err(|(err, len)| LexerErr {
-            file: String::new(),
+        let char_offset = self.char_offset;
+        let byte_offset = self.byte_offset;
+        let row = self.line;
+        let column = self.columnn;
+
+        // The skipped text can span multiple lines, e.g. in unclosed strings
+        let text = &self.cursor[..byte_len];
+        self.consume_str(text);
+
+        let span = self.spans.add_span(self.file, Span {
+            file_id: 0,
+            char_offset,
+            byte_offset,
+            char_len: self.char_offset - char_offset,
+            byte_len: byte_len as u64,
+            row,
+            row_end: self.line,
+            column,
+            column_end: self.columnn,
+        });
+        let metadata = TokenMetadata {
+            span_id: span,
+            meta_elems: mem::take(&mut self.meta_elems),
+            raw_text: Some(text.to_string()),
+        };
+        self.tokens.push(Token::Error, metadata);
+
+        self.errors.push(LexerErr {
             err,
-            byte_offset: self.byte_offset,
-            char_offset: self.char_offset,
-            line: self.line,
-            columnn: self.columnn,
-            byte_len: len,
-            char_len: len,
-        })?;
+            span,
+            open_span: None,
+        });
+        self.errors.last_mut().unwrap()
+    }
+
+    /// Lex the source, any errors are collected in `errors`
+    pub fn lex(&mut self) {
+        if self.cursor.is_empty() {
+            return;
+        }
+
+        self.lex_bom();
         self.lex_shebang();
 
         let mut open_close_stack = Vec::new(); 
         while !self.cursor.is_empty() {
             let ch = self.cursor.chars().next().unwrap();
-            let non_alphanum_idx = self.cursor.find(|ch: char| !ch.is_alphanumeric() && ch != '_').unwrap_or(self.cursor.len());
+            // Combining marks are included, so names in a non-normalized form can be normalized as a whole
+            let non_alphanum_idx = self.cursor.find(|ch: char| !ch.is_alphanumeric() && ch != '_' && !is_combining_mark(ch)).unwrap_or(self.cursor.len());
             let sub_str = &self.cursor[..non_alphanum_idx];
  
             match (ch, non_alphanum_idx) {
@@ -1179,15 +1304,21 @@ impl Lexer<'_> {
                     self.add_name(sub_str);
                 },
                 ('0', _) => if sub_str.starts_with("0b") {
-                    self.lex_binary_lit(sub_str).map_err(|err| self.gen_err(err, sub_str.len() as u32, sub_str.chars().count() as u32))?;
+                    if let Err(err) = self.lex_binary_lit(sub_str) {
+                        self.add_error(err, sub_str.len() as u32);
+                    }
                 } else if sub_str.starts_with("0o") {
-                    self.lex_octal_lit(sub_str).map_err(|err| self.gen_err(err, sub_str.len() as u32, sub_str.chars().count() as u32))?;
+                    if let Err(err) = self.lex_octal_lit(sub_str) {
+                        self.add_error(err, sub_str.len() as u32);
+                    }
                 } else if sub_str.starts_with("0x") {
-                    self.lex_hex_lit(sub_str).map_err(|err| self.gen_err(err, sub_str.len() as u32, sub_str.chars().count() as u32))?;
+                    if let Err(err) = self.lex_hex_lit(sub_str) {
+                        self.add_error(err, sub_str.len() as u32);
+                    }
                 } else if sub_str.find(|ch: char| (ch < '0' || ch > '9') && ch != '_').is_some() {
                     self.add_name(sub_str);
-                } else {
-                    self.lex_decimal(sub_str).map_err(|err| self.gen_err(err, sub_str.len() as u32, sub_str.chars().count() as u32))?;
+                } else if let Err(err) = self.lex_decimal(sub_str) {
+                    self.add_error(err, sub_str.len() as u32);
                 },
                 (ch @ (' ' | '\u{000B}' | '\u{000C}' | '\u{2028}' | '\u{2029}'), _) => {
                     let end = self.cursor.find(|it: char| it != ch).unwrap_or(self.cursor.len());
@@ -1203,7 +1334,7 @@ impl Lexer<'_> {
                     self.add_whitespace(&self.cursor[..end]);
                 },
                 ('\n' | '\u{0085}', _) => {
-                    self.add_whitespace(&self.cursor[..1]);
+                    self.add_whitespace(&self.cursor[..ch.len_utf8()]);
                     self.new_line();
                 },
                 ('\r', _) => {
@@ -1215,21 +1346,33 @@ impl Lexer<'_> {
                     }
                 },
                 ('/', _) => {
-                    let is_comment = self.lex_comment().map_err(|(err, byte_len, char_len)| self.gen_err(err, byte_len, char_len))?;
+                    let is_comment = match self.lex_comment() {
+                        Ok(is_comment) => is_comment,
+                        Err((err, byte_len, _)) => {
+                            self.add_error(err, byte_len);
+                            true
+                        },
+                    };
                     if !is_comment {
-                        self.lex_punctuation(ch)?;
+                        self.lex_punctuation_or_error(ch);
                     }
                 },
                 ('\'', _) => {
-                    self.lex_character().map_err(|(err, byte_len, char_len)| self.gen_err(err, byte_len, char_len))?;
+                    if let Err((err, byte_len, _)) = self.lex_character() {
+                        self.add_error(err, byte_len);
+                    }
                 },
                 ('"', _) => {
-                    self.lex_string().map_err(|(err, byte_len, char_len)| self.gen_err(err, byte_len, char_len))?;
+                    if let Err((err, byte_len, _)) = self.lex_string() {
+                        self.add_error(err, byte_len);
+                    }
                 },
                 ('r', 1) => {
                     let bytes = self.cursor.as_bytes();
                     if self.cursor.len() > 1 && (bytes[1] == b'#' || bytes[1] == b'"') {
-                        self.lex_raw_string().map_err(|(err, byte_len, char_len)| self.gen_err(err, byte_len, char_len))?;
+                        if let Err((err, byte_len, _)) = self.lex_raw_string() {
+                            self.add_error(err, byte_len);
+                        }
                     } else {
                         self.add_name(sub_str);
                     }
@@ -1237,8 +1380,8 @@ impl Lexer<'_> {
                 ('1'..='9', _) => {
                     if sub_str.find(|ch: char| (ch < '0' || ch > '9') && ch != '_').is_some() {
                         self.add_name(sub_str);
-                    } else {
-                        self.lex_decimal(sub_str).map_err(|err| self.gen_err(err, sub_str.len() as u32, sub_str.chars().count() as u32))?;
+                    } else if let Err(err) = self.lex_decimal(sub_str) {
+                        self.add_error(err, sub_str.len() as u32);
                     }
                 },
                 ('!', _) => {
@@ -1249,20 +1392,20 @@ impl Lexer<'_> {
                     } else if sub_str == "!is" {
                         self.add_strong_keyword(StrongKeyword::ExclaimIs);
                     } else {
-                        self.lex_punctuation(ch)?;
+                        self.lex_punctuation_or_error(ch);
                     }
                 },
                 ('(', _) => {
                     self.add_token(Token::OpenSymbol(OpenCloseSymbol::Paren), 1, 1);
-                    open_close_stack.push(OpenCloseSymbol::Paren);
+                    open_close_stack.push((OpenCloseSymbol::Paren, self.last_span(), false));
                 },
                 ('{', _) => {
                     self.add_token(Token::OpenSymbol(OpenCloseSymbol::Brace), 1, 1);
-                    open_close_stack.push(OpenCloseSymbol::Brace);
+                    open_close_stack.push((OpenCloseSymbol::Brace, self.last_span(), false));
                 },
                 ('[', _) => {
                     self.add_token(Token::OpenSymbol(OpenCloseSymbol::Bracket), 1, 1);
-                    open_close_stack.push(OpenCloseSymbol::Bracket);
+                    open_close_stack.push((OpenCloseSymbol::Bracket, self.last_span(), false));
                 },
                 (')' | '}' | ']', _) => {
                     let sym = match ch {
@@ -1272,18 +1415,40 @@ impl Lexer<'_> {
                         _ => unreachable!(),
                     };
 
-                    if let Some(prev) = open_close_stack.pop() {
-                        if prev != sym {
-                            return Err(self.gen_err(LexErrorCode::MismatchCloseSym{ found: sym, expected: prev }, 1, 1));
-                        }
-                    } else {
-                        return Err(self.gen_err(LexErrorCode::NoOpeningSym{ sym }, 1, 1));
+                    // Stack elements track if a mismatch was already reported for the open symbol, to not report it twice
+                    match open_close_stack.iter().rposition(|(open, _, _)| *open == sym) {
+                        // Symbols opened after the matching one are never closed,
+                        // so close them here, to keep the token stream balanced for the parser
+                        Some(idx) => {
+                            let (expected, open_span, reported) = open_close_stack[open_close_stack.len() - 1];
+                            if expected != sym && !reported {
+                                let mut err = self.gen_err(LexErrorCode::MismatchCloseSym { found: sym, expected }, 1, 1);
+                                err.open_span = Some(open_span);
+                                self.errors.push(err);
+                            }
+                            for (unclosed, _, _) in open_close_stack.drain(idx + 1..).rev() {
+                                self.add_token(Token::CloseSymbol(unclosed), 0, 0);
+                            }
+                            open_close_stack.pop();
+                            self.add_token(Token::CloseSymbol(sym), 1, 1);
+                        },
+                        // Skip closing symbols without any matching open symbol
+                        None => match open_close_stack.last_mut() {
+                            Some((expected, open_span, reported)) => {
+                                *reported = true;
+                                let (expected, open_span) = (*expected, *open_span);
+                                let err = self.add_error(LexErrorCode::MismatchCloseSym{ found: sym, expected }, 1);
+                                err.open_span = Some(open_span);
+                            },
+                            None => {
+                                self.add_error(LexErrorCode::NoOpeningSym{ sym }, 1);
+                            },
+                        },
                     }
-                    self.add_token(Token::CloseSymbol(sym), 1, 1);
                 },
                 // character
                 (_, 0) => {
-                    self.lex_punctuation(ch)?;
+                    self.lex_punctuation_or_error(ch);
                 },
                 ('_', 1) => {
                     self.add_token(Token::Underscore, 1, 1);
@@ -1298,56 +1463,17 @@ impl Lexer<'_> {
         if !self.meta_elems.is_empty() {
             self.tokens.tail_meta_elems = mem::take(&mut self.meta_elems);
         }
-
-        Ok(())
     }
 
-    fn lex_bom(&mut self) -> Result<(), (LexErrorCode, u32)> {
-        let bytes = self.cursor.as_bytes();
-        match bytes[0] {
-            0xEF => if bytes[0..=2] == [0xEF, 0xBB, 0xBF] {
-                self.tokens.has_bom = true;
-                self.consume_bytes(3);
+    // Any other BOM has already been rejected when decoding the source
+    fn lex_bom(&mut self) {
+        if self.cursor.starts_with('\u{FEFF}') {
+            self.tokens.has_bom = true;
+            self.consume_bytes(3);
 
-                // make sure that we don't ever log any BOMs
-                self.source = self.cursor;
-            },
-            0xFE => if bytes[0..=1] == [0xFE, 0xFF] {
-                return Err((LexErrorCode::InvalidBOM("utf16 (be)"), 2));
-            }
-            0xFF => 
-            if bytes[0..=1] == [0xFF, 0xFE] {
-                if bytes[2..=3] == [0x00, 0x00] {
-                    return Err((LexErrorCode::InvalidBOM("utf32 (le)"), 4));
-                } else {
-                    return Err((LexErrorCode::InvalidBOM("utf16 (le)"), 2));
-                }
-            },
-            0x00 => if bytes[0..=3] == [0x00, 0x00, 0xFE, 0xFF] {
-                return Err((LexErrorCode::InvalidBOM("utf32 (be)"), 4));
-            },
-            0x2B => if bytes[0..=2] == [0x2B, 0x2F, 0x76] {
-                return Err((LexErrorCode::InvalidBOM("utf-7"), 3));
-            },
-            0xF7 => if bytes[0..=2] == [0xF7, 0x64, 0x4C] {
-                return Err((LexErrorCode::InvalidBOM("utf-1"), 3));
-            },
-            0xDD => if bytes[0..=3] == [0xDD, 0x73, 0x66, 0x73] {
-                return Err((LexErrorCode::InvalidBOM("utf-ecbdic"), 4));
-            },
-            0x0E => if bytes[0..=2] == [0x0E, 0xFE, 0xFF] {
-                return Err((LexErrorCode::InvalidBOM("scsu"), 3));
-            },
-            0xFB => if bytes[0..=2] ==[0xFB, 0xEE, 0x28] {
-                return Err((LexErrorCode::InvalidBOM("bocu-1"), 3));
-            },
-            0x84 => if bytes[0..=3] == [0x84, 0x31, 0x95, 0x33] {
-                return Err((LexErrorCode::InvalidBOM("gb18030"), 4));
-            },
-            _ => {},
+            // make sure that we don't ever log any BOMs
+            self.source = self.cursor;
         }
-
-        Ok(())
     }
 
     fn lex_shebang(&mut self) {
@@ -1355,17 +1481,14 @@ impl Lexer<'_> {
             return;
         }
 
-        let end = Self::find_next_newline(&self.cursor).unwrap_or(self.cursor.len());
-        let shebang_str = if end > 1 && self.cursor.as_bytes()[end - 1] == b'\r' {
-            self.cursor[2..end - 1].to_string()
-        } else {
-            self.cursor[2..end].to_string()
-        };
-
-        let nw_len = end + Self::next_char_len(&self.cursor[end..]);
+        let mut end = Self::find_next_newline(&self.cursor).unwrap_or(self.cursor.len());
+        if end > 1 && self.cursor.as_bytes()[end - 1] == b'\r' {
+            end -= 1;
+        }
 
-        self.consume_str(&self.cursor[..nw_len]);
-        self.tokens.shebang = Some(shebang_str);
+        // The line break is left to be lexed as whitespace, so the source can be reconstructed
+        self.tokens.shebang = Some(self.cursor[2..end].to_string());
+        self.consume_str(&self.cursor[..end]);
     }
 
     fn lex_binary_lit(&mut self, sub_str: &str) -> Result<(), LexErrorCode> {
@@ -1406,7 +1529,13 @@ impl Lexer<'_> {
         Ok(())
     }
 
-    fn lex_punctuation(&mut self, ch: char) -> Result<(), LexerErr> {
+    fn lex_punctuation_or_error(&mut self, ch: char) {
+        if let Err((err, byte_len)) = self.lex_punctuation(ch) {
+            self.add_error(err, byte_len);
+        }
+    }
+
+    fn lex_punctuation(&mut self, ch: char) -> Result<(), (LexErrorCode, u32)> {
         const ALLOWED_CHARACTERS: [char; 493] = [
             '!', '%', '&', '*', '+', '-', '.', '/', ':', '<', '=', '>', '?', '^', '|', '~', 
 
@@ -1465,18 +1594,24 @@ impl Lexer<'_> {
             } else {
                 self.add_punctuation(".");
             }
+        } else if self.cursor.starts_with("@!") {
+            self.add_punctuation("@!");
         } else if SINGLE_SYMBOLS.contains(&ch) {
             self.add_punctuation(&self.cursor[..1]);
         } else {
             let mut seq = String::new();
-            for ch in self.cursor.chars() {
+            for (idx, ch) in self.cursor.char_indices() {
                 if ch.is_alphanumeric() || ch.is_whitespace() || SINGLE_SYMBOLS.contains(&ch) || OPEN_CLOSE.contains(&ch) {
                     break;
                 } if ch == '\\' {
                     let tmp = self.convert_punct_sequence()?;
                     seq.push(tmp);
+                } else if is_combining_mark(ch) && seq.chars().next_back().is_some_and(|prev| compose(prev, ch).is_some()) {
+                    // Only allowed when it combines into another character, e.g. '=' + U+0338 becomes '≠' after normalization
+                    seq.push(ch);
                 } else if ALLOWED_CHARACTERS.binary_search(&ch).is_err() {
-                    return Err(self.gen_err(LexErrorCode::InvalidCharInOp { ch }, ch.len_utf8() as u32, 1));
+                    // Skip the operator up to and including the invalid character
+                    return Err((LexErrorCode::InvalidCharInOp { ch }, (idx + ch.len_utf8()) as u32));
                 } else {
                     seq.push(ch);
                 }
@@ -1487,12 +1622,12 @@ impl Lexer<'_> {
         Ok(())
     }
 
-    fn convert_punct_sequence(&mut self) -> Result<char, LexerErr> {
+    fn convert_punct_sequence(&mut self) -> Result<char, (LexErrorCode, u32)> {
         let non_alphanum_idx = self.cursor.find(|ch: char| !ch.is_alphanumeric() && ch != '_').unwrap_or(self.cursor.len());
         let name = &self.cursor[..non_alphanum_idx];
         let ch = match self.op_seq_map.get(name) {
             Some(ch) => *ch,
-            None => return Err(self.gen_err(LexErrorCode::InvalidOpSequence { name: name.to_string() }, name.len() as u32 + 1, name.chars().count() as u32 + 1)),
+            None => return Err((LexErrorCode::InvalidOpSequence { name: name.to_string() }, name.len() as u32 + 1)),
         };
 
         // Consume with enough space for the character left, as it will be consumed in the caller
@@ -1674,7 +1809,7 @@ impl Lexer<'_> {
             
             let end = Self::find_next_newline(&self.cursor).unwrap_or(self.cursor.len());
 
-            let (is_doc, is_top) = if end > 3 {
+            let (is_doc, is_top) = if end >= 3 {
                 let bytes = self.cursor.as_bytes();
                 if bytes[2] == b'/' {
                     (true, false)
@@ -1897,7 +2032,11 @@ impl Lexer<'_> {
 mod tests {
     use crate::{lexer::{MetaElem, NameTable, PuncutationTable, SpanRegistry}, literals::{Literal, LiteralTable}};
 
-    use super::{Lexer, Token};
+    use crate::error_warning::LexErrorCode;
+
+    use std::{fs, path::{Path, PathBuf}};
+
+    use super::{Lexer, OpenCloseSymbol, Punctuation, Token};
 
 
 
@@ -1917,12 +2056,13 @@ mod tests {
 0b0101_01010101
 "#;
 
-        let mut literals = LiteralTable::new();
-        let mut names = NameTable::new();
-        let mut punctuation = PuncutationTable::new();
-        let mut spans = SpanRegistry::new();
-        let mut lexer = Lexer::new("", &source, &mut literals, &mut names, &mut punctuation, &mut spans);
-        lexer.lex().unwrap();
+        let literals = LiteralTable::new();
+        let names = NameTable::new();
+        let punctuation = PuncutationTable::new();
+        let spans = SpanRegistry::new();
+        let mut lexer = Lexer::new("", &source, &literals, &names, &punctuation, &spans);
+        lexer.lex();
+        assert!(lexer.errors.is_empty());
 
         let token_store = &lexer.tokens;
 
@@ -1964,12 +2104,13 @@ mod tests {
 0o12345670
 "#;
 
-        let mut literals = LiteralTable::new();
-        let mut names = NameTable::new();
-        let mut punctuation = PuncutationTable::new();
-        let mut spans = SpanRegistry::new();
-        let mut lexer = Lexer::new("", &source, &mut literals, &mut names, &mut punctuation, &mut spans);
-        lexer.lex().unwrap();
+        let literals = LiteralTable::new();
+        let names = NameTable::new();
+        let punctuation = PuncutationTable::new();
+        let spans = SpanRegistry::new();
+        let mut lexer = Lexer::new("", &source, &literals, &names, &punctuation, &spans);
+        lexer.lex();
+        assert!(lexer.errors.is_empty());
 
         let token_store = &lexer.tokens;
 
@@ -2010,12 +2151,13 @@ mod tests {
 0x123456789ABCDEF0
 "#;
 
-        let mut literals = LiteralTable::new();
-        let mut names = NameTable::new();
-        let mut punctuation = PuncutationTable::new();
-        let mut spans = SpanRegistry::new();
-        let mut lexer = Lexer::new("", &source, &mut literals, &mut names, &mut punctuation, &mut spans);
-        lexer.lex().unwrap();
+        let literals = LiteralTable::new();
+        let names = NameTable::new();
+        let punctuation = PuncutationTable::new();
+        let spans = SpanRegistry::new();
+        let mut lexer = Lexer::new("", &source, &literals, &names, &punctuation, &spans);
+        lexer.lex();
+        assert!(lexer.errors.is_empty());
 
         let token_store = &lexer.tokens;
 
@@ -2049,12 +2191,13 @@ mod tests {
 0x1.0001p+1
 "#;
 
-        let mut literals = LiteralTable::new();
-        let mut names = NameTable::new();
-        let mut punctuation = PuncutationTable::new();
-        let mut spans = SpanRegistry::new();
-        let mut lexer = Lexer::new("", &source, &mut literals, &mut names, &mut punctuation, &mut spans);
-        lexer.lex().unwrap();
+        let literals = LiteralTable::new();
+        let names = NameTable::new();
+        let punctuation = PuncutationTable::new();
+        let spans = SpanRegistry::new();
+        let mut lexer = Lexer::new("", &source, &literals, &names, &punctuation, &spans);
+        lexer.lex();
+        assert!(lexer.errors.is_empty());
 
         let token_store = &lexer.tokens;
 
@@ -2087,12 +2230,13 @@ mod tests {
 7.8e+9
 "#;
 
-        let mut literals = LiteralTable::new();
-        let mut names = NameTable::new();
-        let mut punctuation = PuncutationTable::new();
-        let mut spans = SpanRegistry::new();
-        let mut lexer = Lexer::new("", &source, &mut literals, &mut names, &mut punctuation, &mut spans);
-        lexer.lex().unwrap();
+        let literals = LiteralTable::new();
+        let names = NameTable::new();
+        let punctuation = PuncutationTable::new();
+        let spans = SpanRegistry::new();
+        let mut lexer = Lexer::new("", &source, &literals, &names, &punctuation, &spans);
+        lexer.lex();
+        assert!(lexer.errors.is_empty());
 
         let token_store = &lexer.tokens;
 
@@ -2129,12 +2273,13 @@ mod tests {
 '\u{0085}'
 "#;
 
-        let mut literals = LiteralTable::new();
-        let mut names = NameTable::new();
-        let mut punctuation = PuncutationTable::new();
-        let mut spans = SpanRegistry::new();
-        let mut lexer = Lexer::new("", &source, &mut literals, &mut names, &mut punctuation, &mut spans);
-        lexer.lex().unwrap();
+        let literals = LiteralTable::new();
+        let names = NameTable::new();
+        let punctuation = PuncutationTable::new();
+        let spans = SpanRegistry::new();
+        let mut lexer = Lexer::new("", &source, &literals, &names, &punctuation, &spans);
+        lexer.lex();
+        assert!(lexer.errors.is_empty());
 
         let token_store = &lexer.tokens;
 
@@ -2174,12 +2319,13 @@ string
 "##
 "###;
 
-        let mut literals = LiteralTable::new();
-        let mut names = NameTable::new();
-        let mut punctuation = PuncutationTable::new();
-        let mut spans = SpanRegistry::new();
-        let mut lexer = Lexer::new("", &source, &mut literals, &mut names, &mut punctuation, &mut spans);
-        lexer.lex().unwrap();
+        let literals = LiteralTable::new();
+        let names = NameTable::new();
+        let punctuation = PuncutationTable::new();
+        let spans = SpanRegistry::new();
+        let mut lexer = Lexer::new("", &source, &literals, &names, &punctuation, &spans);
+        lexer.lex();
+        assert!(lexer.errors.is_empty());
 
         let token_store = &lexer.tokens;
 
@@ -2211,24 +2357,49 @@ string
 //! And another one, but this time a top-level doc comment
 "#;
 
-        let mut literals = LiteralTable::new();
-        let mut names = NameTable::new();
-        let mut punctuation = PuncutationTable::new();
-        let mut spans = SpanRegistry::new();
-        let mut lexer = Lexer::new("", &source, &mut literals, &mut names, &mut punctuation, &mut spans);
-        lexer.lex().unwrap();
+        let literals = LiteralTable::new();
+        let names = NameTable::new();
+        let punctuation = PuncutationTable::new();
+        let spans = SpanRegistry::new();
+        let mut lexer = Lexer::new("", &source, &literals, &names, &punctuation, &spans);
+        lexer.lex();
+        assert!(lexer.errors.is_empty());
 
         let token_store = &lexer.tokens;
 
         assert_eq!(token_store.tokens.len(), 0);
 
-        assert_eq!(token_store.tail_meta_elems.len(), 6);
+        // The line break after each comment is whitespace, including the one at the end of the source
+        assert_eq!(token_store.tail_meta_elems.len(), 7);
 
         assert_eq!(token_store.tail_meta_elems[1], MetaElem::LineComment(" A line comment".to_string()));
         assert_eq!(token_store.tail_meta_elems[3], MetaElem::LineDocComment(" Another line comment, but this time a doc comment".to_string()));
         assert_eq!(token_store.tail_meta_elems[5], MetaElem::LineTopDocComment(" And another one, but this time a top-level doc comment".to_string()));
     }
     
+    #[test]
+    fn test_empty_doc_comment_lex() {
+        let source = "/// Short\n///\n/// Long\n@!doc";
+
+        let literals = LiteralTable::new();
+        let names = NameTable::new();
+        let punctuation = PuncutationTable::new();
+        let spans = SpanRegistry::new();
+        let mut lexer = Lexer::new("", &source, &literals, &names, &punctuation, &spans);
+        lexer.lex();
+        assert!(lexer.errors.is_empty());
+
+        let token_store = &lexer.tokens;
+
+        assert_eq!(token_store.tokens.len(), 2);
+        assert_eq!(token_store.tokens[0], Token::Punctuation(Punctuation::AtExclaim));
+
+        let meta_elems = &token_store.metadata[0].meta_elems;
+        assert_eq!(meta_elems[0], MetaElem::LineDocComment(" Short".to_string()));
+        assert_eq!(meta_elems[2], MetaElem::LineDocComment(String::new()));
+        assert_eq!(meta_elems[4], MetaElem::LineDocComment(" Long".to_string()));
+    }
+
     #[test]
     fn test_block_comment_lex() {
         let source = r#"
@@ -2253,12 +2424,13 @@ Comment
 */
 "#;
 
-        let mut literals = LiteralTable::new();
-        let mut names = NameTable::new();
-        let mut punctuation = PuncutationTable::new();
-        let mut spans = SpanRegistry::new();
-        let mut lexer = Lexer::new("", &source, &mut literals, &mut names, &mut punctuation, &mut spans);
-        lexer.lex().unwrap();
+        let literals = LiteralTable::new();
+        let names = NameTable::new();
+        let punctuation = PuncutationTable::new();
+        let spans = SpanRegistry::new();
+        let mut lexer = Lexer::new("", &source, &literals, &names, &punctuation, &spans);
+        lexer.lex();
+        assert!(lexer.errors.is_empty());
 
         let token_store = &lexer.tokens;
 
@@ -2273,4 +2445,129 @@ Comment
         assert_eq!(token_store.tail_meta_elems[9], MetaElem::BlockTopDocComment(" A single line top doc block comment ".to_string()));
         assert_eq!(token_store.tail_meta_elems[11], MetaElem::BlockTopDocComment(" A multi-line\nTop doc block\nComment\n".to_string()));
     }
+
+    #[test]
+    fn test_error_recovery_lex() {
+        let source = r#"
+0xZZ
+0b102
+(1]
+0o9
+"#;
+
+        let literals = LiteralTable::new();
+        let names = NameTable::new();
+        let punctuation = PuncutationTable::new();
+        let spans = SpanRegistry::new();
+        let mut lexer = Lexer::new("", &source, &literals, &names, &punctuation, &spans);
+        lexer.lex();
+
+        let errors: Vec<_> = lexer.errors.iter().map(|err| &err.err).collect();
+        assert_eq!(errors.len(), 4);
+        assert!(matches!(errors[0], LexErrorCode::InvalidHexInLit));
+        assert!(matches!(errors[1], LexErrorCode::InvalidBinInLit));
+        assert!(matches!(errors[2], LexErrorCode::MismatchCloseSym { found: OpenCloseSymbol::Bracket, expected: OpenCloseSymbol::Paren }));
+        assert!(matches!(errors[3], LexErrorCode::InvalidOctInLit));
+
+        let token_store = &lexer.tokens;
+        assert_eq!(token_store.tokens.len(), 6);
+        assert_eq!(token_store.tokens[0], Token::Error);
+        assert_eq!(token_store.tokens[1], Token::Error);
+        assert_eq!(token_store.tokens[2], Token::OpenSymbol(OpenCloseSymbol::Paren));
+        assert!(matches!(token_store.tokens[3], Token::Literal(_)));
+        assert_eq!(token_store.tokens[4], Token::Error);
+        assert_eq!(token_store.tokens[5], Token::Error);
+    }
+
+    #[test]
+    fn test_nfc_normalization_lex() {
+        let source = "caf\u{E9} cafe\u{301} \"cafe\u{301}\"";
+
+        let literals = LiteralTable::new();
+        let names = NameTable::new();
+        let punctuation = PuncutationTable::new();
+        let spans = SpanRegistry::new();
+        let mut lexer = Lexer::new("", &source, &literals, &names, &punctuation, &spans);
+        lexer.lex();
+        assert!(lexer.errors.is_empty());
+        assert_eq!(lexer.warnings.len(), 1);
+
+        let token_store = &lexer.tokens;
+        assert_eq!(token_store.tokens.len(), 3);
+        assert!(matches!(token_store.tokens[0], Token::Name(_)));
+        assert_eq!(token_store.tokens[0], token_store.tokens[1]);
+
+        // String literals are never normalized
+        let Token::Literal(lit_id) = token_store.tokens[2] else { panic!("expected a literal") };
+        drop(lexer);
+        assert_eq!(literals[lit_id], Literal::String("cafe\u{301}".to_string()));
+    }
+
+    #[test]
+    fn test_reconstruct_source() {
+        let source = "\u{FEFF}#!/usr/bin/xenon\r\n// comment\r\nfn main() {\n\t/* block /* nested */ */ let a = 0x1F \u{2260} b;\u{0085}\t/// doc\n\t/*! top doc */let s = \"caf\u{E9}\\n\";\n} //! tail\n";
+
+        let literals = LiteralTable::new();
+        let names = NameTable::new();
+        let punctuation = PuncutationTable::new();
+        let spans = SpanRegistry::new();
+        let mut lexer = Lexer::new("", &source, &literals, &names, &punctuation, &spans);
+        lexer.lex();
+        assert!(lexer.errors.is_empty());
+
+        let Lexer { tokens, .. } = lexer;
+        assert_eq!(tokens.reconstruct_source(&names, &punctuation), source);
+    }
+
+    #[test]
+    fn test_reconstruct_source_with_errors() {
+        // Contains a name that isn't normalized, a mismatched close symbol and an unclosed string
+        let source = "fn cafe\u{301}() {\n    let a = f(b];\n    let s = \"unclosed\n}\n";
+
+        let literals = LiteralTable::new();
+        let names = NameTable::new();
+        let punctuation = PuncutationTable::new();
+        let spans = SpanRegistry::new();
+        let mut lexer = Lexer::new("", &source, &literals, &names, &punctuation, &spans);
+        lexer.lex();
+        assert!(!lexer.errors.is_empty());
+
+        let Lexer { tokens, .. } = lexer;
+        assert_eq!(tokens.reconstruct_source(&names, &punctuation), source);
+    }
+
+    #[test]
+    fn test_reconstruct_repo_sources() {
+        fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+            for entry in fs::read_dir(dir).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    collect_files(&path, files);
+                } else if path.extension().is_some_and(|ext| ext == "xn") {
+                    files.push(path);
+                }
+            }
+        }
+
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+        let mut files = Vec::new();
+        collect_files(&root.join("core"), &mut files);
+        collect_files(&root.join("tests"), &mut files);
+        assert!(!files.is_empty());
+
+        for path in files {
+            // Files in other encodings are covered by the source decoding tests
+            let Ok(source) = String::from_utf8(fs::read(&path).unwrap()) else { continue };
+
+            let literals = LiteralTable::new();
+            let names = NameTable::new();
+            let punctuation = PuncutationTable::new();
+            let spans = SpanRegistry::new();
+            let mut lexer = Lexer::new("", &source, &literals, &names, &punctuation, &spans);
+            lexer.lex();
+
+            let Lexer { tokens, .. } = lexer;
+            assert!(tokens.reconstruct_source(&names, &punctuation) == source, "{} does not round-trip", path.display());
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,430 @@
+use std::{borrow::Cow, fs::File, io::{self, Read}, ops::Deref, str};
+
+use memmap2::Mmap;
+
+use crate::{
+    common::{Span, SpanId, SpanRegistry},
+    error_warning::LexErrorCode,
+};
+
+use super::LexerErr;
+
+/// Raw bytes of a source file, either read into memory or memory-mapped
+pub enum SourceBuffer {
+    Owned(Vec<u8>),
+    Mapped(Mmap),
+}
+
+impl Deref for SourceBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            SourceBuffer::Owned(bytes) => bytes,
+            SourceBuffer::Mapped(mmap) => mmap,
+        }
+    }
+}
+
+/// Source file as it is stored on disk, before it has been decoded
+pub struct SourceFile {
+    buffer: SourceBuffer,
+}
+
+impl SourceFile {
+    /// Files of at least this size will be memory-mapped instead of read into memory
+    const MMAP_THRESHOLD: u64 = 1024 * 1024;
+    /// Maximum number of invalid sequences that will be reported for a single file
+    const MAX_INVALID_ERRORS: usize = 8;
+    // TODO: This is not guaranteed to be 4, needs to match the lexer
+    const TAB_WIDTH: u32 = 4;
+
+    pub fn open(path: &str) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let size = file.metadata()?.len();
+
+        let buffer = if size >= Self::MMAP_THRESHOLD {
+            // SAFETY: The compiler never writes to its input files, modifying the file while it is mapped is not supported
+            SourceBuffer::Mapped(unsafe { Mmap::map(&file)? })
+        } else {
+            let mut bytes = Vec::with_capacity(size as usize);
+            file.read_to_end(&mut bytes)?;
+            SourceBuffer::Owned(bytes)
+        };
+
+        Ok(Self { buffer })
+    }
+
+    /// Create a source file from bytes that are already in memory
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self { buffer: SourceBuffer::Owned(bytes) }
+    }
+
+    /// Decode the source to utf-8 like `decode`, but keep the file as the source text when it is already valid utf-8, so it doesn't need to be copied
+    pub fn into_text(self, file: &str, spans: &SpanRegistry) -> Result<SourceText, Vec<LexerErr>> {
+        let decoded = match self.decode(file, spans)? {
+            Cow::Borrowed(_)     => None,
+            Cow::Owned(decoded) => Some(decoded),
+        };
+        Ok(match decoded {
+            Some(decoded) => SourceText::Decoded(decoded),
+            None          => SourceText::Utf8(self),
+        })
+    }
+
+    /// Decode the source to utf-8.
+    ///
+    /// Utf-16 and utf-32 sources are detected by their BOM and transcoded, the BOM is kept so the lexer can handle it like a utf-8 BOM.
+    /// Files starting with the BOM of any other encoding are rejected as a whole,
+    /// otherwise every invalid sequence is reported at its exact line and column.
+    pub fn decode(&self, file: &str, spans: &SpanRegistry) -> Result<Cow<'_, str>, Vec<LexerErr>> {
+        let bytes = &*self.buffer;
+
+        let encoding = match Encoding::from_bom(bytes) {
+            Ok(encoding) => encoding,
+            Err((bom, len)) => {
+                let span = spans.add_span(file, Span {
+                    file_id: 0,
+                    row: 1,
+                    column: 1,
+                    char_offset: 0,
+                    byte_offset: 0,
+                    char_len: len as u64,
+                    byte_len: len as u64,
+                    row_end: 1,
+                    column_end: 1 + len,
+                });
+                return Err(vec![LexerErr { err: LexErrorCode::InvalidBOM(bom), span, open_span: None }]);
+            },
+        };
+
+        if encoding == Encoding::Utf8 {
+            if let Ok(source) = str::from_utf8(bytes) {
+                return Ok(Cow::Borrowed(source));
+            }
+        }
+
+        let mut source = String::with_capacity(bytes.len());
+        let mut errors = Vec::new();
+        let mut num_invalid = 0;
+
+        // Offsets of invalid sequences are reported in bytes of the original file, but in characters of the decoded source
+        let mut byte_offset = 0;
+        let mut char_offset = 0;
+        let mut line = 1;
+        let mut column = 1;
+        while byte_offset < bytes.len() {
+            let (ch, len) = encoding.decode_char(&bytes[byte_offset..]);
+            match ch {
+                Some(ch) => {
+                    source.push(ch);
+                    match ch {
+                        '\n' | '\u{0085}' => {
+                            line += 1;
+                            column = 1;
+                        },
+                        '\t' => column += Self::TAB_WIDTH,
+                        _ => column += 1,
+                    }
+                },
+                None => {
+                    num_invalid += 1;
+                    if num_invalid <= Self::MAX_INVALID_ERRORS {
+                        let span = spans.add_span(file, Span {
+                            file_id: 0,
+                            row: line,
+                            column,
+                            char_offset,
+                            byte_offset: byte_offset as u64,
+                            char_len: 1,
+                            byte_len: len as u64,
+                            row_end: line,
+                            column_end: column + 1,
+                        });
+                        let bytes = bytes[byte_offset..byte_offset + len].to_vec();
+                        errors.push(LexerErr { err: LexErrorCode::InvalidEncoding { encoding: encoding.name(), bytes }, span, open_span: None });
+                    }
+                    // Each invalid sequence is counted as a single character, which is how it would be displayed
+                    column += 1;
+                },
+            }
+            byte_offset += len;
+            char_offset += 1;
+        }
+
+        if num_invalid > Self::MAX_INVALID_ERRORS {
+            errors.push(LexerErr {
+                err: LexErrorCode::TooManyInvalidEncoding { encoding: encoding.name(), count: num_invalid - Self::MAX_INVALID_ERRORS },
+                span: SpanId::INVALID,
+                open_span: None,
+            });
+        }
+
+        if errors.is_empty() {
+            Ok(Cow::Owned(source))
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Decoded source text of a file
+pub enum SourceText {
+    /// File that is valid utf-8 as it is stored on disk
+    Utf8(SourceFile),
+    /// Source that needed to be transcoded, or that didn't come from a file
+    Decoded(String),
+}
+
+impl Deref for SourceText {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        match self {
+            // SAFETY: Only files that were validated to be utf-8 by `SourceFile::into_text` are stored as is
+            SourceText::Utf8(file) => unsafe { str::from_utf8_unchecked(&file.buffer) },
+            SourceText::Decoded(source) => source,
+        }
+    }
+}
+
+impl AsRef<str> for SourceText {
+    fn as_ref(&self) -> &str {
+        self
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Utf32Le,
+    Utf32Be,
+}
+
+impl Encoding {
+    /// Get the encoding from the BOM at the start of the file, files without a BOM are utf-8.
+    /// Unsupported encodings return their name and the length of their BOM in bytes.
+    fn from_bom(bytes: &[u8]) -> Result<Self, (&'static str, u32)> {
+        const UNSUPPORTED: [(&[u8], &str); 6] = [
+            (&[0x2B, 0x2F, 0x76],       "utf-7"),
+            (&[0xF7, 0x64, 0x4C],       "utf-1"),
+            (&[0xDD, 0x73, 0x66, 0x73], "utf-ecbdic"),
+            (&[0x0E, 0xFE, 0xFF],       "scsu"),
+            (&[0xFB, 0xEE, 0x28],       "bocu-1"),
+            (&[0x84, 0x31, 0x95, 0x33], "gb18030"),
+        ];
+
+        // utf-32 (le) needs to come before utf-16 (le), as it starts with the same bytes
+        if bytes.starts_with(&[0xFF, 0xFE, 0x00, 0x00]) {
+            Ok(Self::Utf32Le)
+        } else if bytes.starts_with(&[0x00, 0x00, 0xFE, 0xFF]) {
+            Ok(Self::Utf32Be)
+        } else if bytes.starts_with(&[0xFF, 0xFE]) {
+            Ok(Self::Utf16Le)
+        } else if bytes.starts_with(&[0xFE, 0xFF]) {
+            Ok(Self::Utf16Be)
+        } else if let Some((bom, name)) = UNSUPPORTED.iter().find(|(bom, _)| bytes.starts_with(bom)) {
+            Err((name, bom.len() as u32))
+        } else {
+            Ok(Self::Utf8)
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Utf8    => "utf-8",
+            Self::Utf16Le => "utf-16 (le)",
+            Self::Utf16Be => "utf-16 (be)",
+            Self::Utf32Le => "utf-32 (le)",
+            Self::Utf32Be => "utf-32 (be)",
+        }
+    }
+
+    /// Decode the first character in `bytes`, returning the character, or `None` if it is invalid, and the number of bytes it takes up
+    fn decode_char(self, bytes: &[u8]) -> (Option<char>, usize) {
+        match self {
+            Self::Utf8 => match str::from_utf8(&bytes[..bytes.len().min(4)]) {
+                Ok(s) => Self::first_char(s),
+                Err(err) if err.valid_up_to() > 0 => Self::first_char(str::from_utf8(&bytes[..err.valid_up_to()]).unwrap()),
+                Err(err) => (None, err.error_len().unwrap_or(bytes.len().min(4))),
+            },
+            Self::Utf16Le | Self::Utf16Be => {
+                let read_unit = |bytes: &[u8]| if self == Self::Utf16Le {
+                    u16::from_le_bytes([bytes[0], bytes[1]])
+                } else {
+                    u16::from_be_bytes([bytes[0], bytes[1]])
+                };
+
+                if bytes.len() < 2 {
+                    return (None, bytes.len());
+                }
+                let unit = read_unit(bytes);
+                let units = if bytes.len() >= 4 { [unit, read_unit(&bytes[2..])] } else { [unit, 0] };
+                match char::decode_utf16(units).next() {
+                    Some(Ok(ch)) => (Some(ch), ch.len_utf16() * 2),
+                    _ => (None, 2),
+                }
+            },
+            Self::Utf32Le | Self::Utf32Be => {
+                if bytes.len() < 4 {
+                    return (None, bytes.len());
+                }
+                let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+                let value = if self == Self::Utf32Le { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) };
+                (char::from_u32(value), 4)
+            },
+        }
+    }
+
+    fn first_char(s: &str) -> (Option<char>, usize) {
+        let ch = s.chars().next().unwrap();
+        (Some(ch), ch.len_utf8())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use crate::{common::SpanRegistry, error_warning::LexErrorCode};
+
+    use super::{SourceBuffer, SourceFile, SourceText};
+
+    /// Decode `bytes`, errors are returned with their row, column, byte offset and byte length
+    fn decode(bytes: &[u8]) -> Result<String, Vec<(LexErrorCode, Option<(u32, u32, u64, u64)>)>> {
+        let spans = SpanRegistry::new();
+        let file = SourceFile { buffer: SourceBuffer::Owned(bytes.to_vec()) };
+        file.decode("test.xn", &spans)
+            .map(|source| source.into_owned())
+            .map_err(|errors| errors.into_iter()
+                .map(|err| (err.err, spans.get(err.span).map(|span| (span.row, span.column, span.byte_offset, span.byte_len))))
+                .collect()
+            )
+    }
+
+    fn encode_utf16(bom: &[u8], text: &str, to_bytes: fn(u16) -> [u8; 2]) -> Vec<u8> {
+        let mut bytes = bom.to_vec();
+        bytes.extend(text.encode_utf16().flat_map(to_bytes));
+        bytes
+    }
+
+    fn encode_utf32(bom: &[u8], text: &str, to_bytes: fn(u32) -> [u8; 4]) -> Vec<u8> {
+        let mut bytes = bom.to_vec();
+        bytes.extend(text.chars().flat_map(|ch| to_bytes(ch as u32)));
+        bytes
+    }
+
+    #[test]
+    fn test_decode_utf8() {
+        let res = decode("fn a() {}\n\u{FEFF}".as_bytes());
+        assert_eq!(res.ok().as_deref(), Some("fn a() {}\n\u{FEFF}"));
+    }
+
+    #[test]
+    fn test_into_text_keeps_file() {
+        // Large files are memory-mapped, and used as the source text without copying them
+        let path = env::temp_dir().join(format!("xenon-source-test-{}.xn", process::id()));
+        fs::write(&path, "a".repeat(SourceFile::MMAP_THRESHOLD as usize)).unwrap();
+        let file = SourceFile::open(path.to_str().unwrap()).unwrap();
+        let mapped = file.buffer.as_ptr();
+        assert!(matches!(file.buffer, SourceBuffer::Mapped(_)));
+
+        let text = file.into_text("test.xn", &SpanRegistry::new()).ok().unwrap();
+        assert!(matches!(&text, SourceText::Utf8(_)));
+        assert_eq!(text.as_ptr(), mapped);
+        assert_eq!(text.len(), SourceFile::MMAP_THRESHOLD as usize);
+        drop(text);
+        _ = fs::remove_file(&path);
+
+        // Transcoded sources can't borrow the file
+        let text = SourceFile::from_bytes(vec![0xFF, 0xFE, b'a', 0]).into_text("test.xn", &SpanRegistry::new()).ok().unwrap();
+        assert!(matches!(&text, SourceText::Decoded(_)));
+        assert_eq!(&*text, "\u{FEFF}a");
+    }
+
+    #[test]
+    fn test_decode_invalid_utf8() {
+        let res = decode(b"ab\xFFc\n\td\xE2\x82e\xF0\x9F\x98\x80\x80");
+        let errors = res.unwrap_err();
+
+        // Positions are in the original bytes, an incomplete sequence is reported as a whole
+        assert_eq!(errors.len(), 3);
+        assert!(matches!(&errors[0].0, LexErrorCode::InvalidEncoding { encoding: "utf-8", bytes } if bytes == &[0xFF]));
+        assert_eq!(errors[0].1, Some((1, 3, 2, 1)));
+        assert!(matches!(&errors[1].0, LexErrorCode::InvalidEncoding { encoding: "utf-8", bytes } if bytes == &[0xE2, 0x82]));
+        assert_eq!(errors[1].1, Some((2, 6, 7, 2)));
+        assert!(matches!(&errors[2].0, LexErrorCode::InvalidEncoding { encoding: "utf-8", bytes } if bytes == &[0x80]));
+        assert_eq!(errors[2].1, Some((2, 9, 14, 1)));
+    }
+
+    #[test]
+    fn test_decode_too_many_invalid() {
+        let res = decode(&[0xFF; 10]);
+        let errors = res.unwrap_err();
+
+        assert_eq!(errors.len(), SourceFile::MAX_INVALID_ERRORS + 1);
+        assert!(matches!(errors.last().unwrap(), (LexErrorCode::TooManyInvalidEncoding { encoding: "utf-8", count: 2 }, None)));
+    }
+
+    #[test]
+    fn test_decode_utf16() {
+        // The BOM is kept, and characters outside of the BMP are decoded from their surrogate pair
+        let text = "a\u{1F600}b\n\u{00E9}";
+        let res = decode(&encode_utf16(&[0xFE, 0xFF], text, u16::to_be_bytes));
+        assert_eq!(res.ok().as_deref(), Some("\u{FEFF}a\u{1F600}b\n\u{00E9}"));
+
+        let res = decode(&encode_utf16(&[0xFF, 0xFE], text, u16::to_le_bytes));
+        assert_eq!(res.ok().as_deref(), Some("\u{FEFF}a\u{1F600}b\n\u{00E9}"));
+    }
+
+    #[test]
+    fn test_decode_invalid_utf16() {
+        // A high surrogate without a low surrogate, a lone low surrogate, and a trailing odd byte
+        let bytes = [0xFF, 0xFE, b'a', 0, 0x3D, 0xD8, b'b', 0, 0x00, 0xDE, b'\n', 0, 0x01];
+        let res = decode(&bytes);
+        let errors = res.unwrap_err();
+
+        assert_eq!(errors.len(), 3);
+        assert!(matches!(&errors[0].0, LexErrorCode::InvalidEncoding { encoding: "utf-16 (le)", bytes } if bytes == &[0x3D, 0xD8]));
+        assert_eq!(errors[0].1, Some((1, 3, 4, 2)));
+        assert!(matches!(&errors[1].0, LexErrorCode::InvalidEncoding { encoding: "utf-16 (le)", bytes } if bytes == &[0x00, 0xDE]));
+        assert_eq!(errors[1].1, Some((1, 5, 8, 2)));
+        assert!(matches!(&errors[2].0, LexErrorCode::InvalidEncoding { encoding: "utf-16 (le)", bytes } if bytes == &[0x01]));
+        assert_eq!(errors[2].1, Some((2, 1, 12, 1)));
+    }
+
+    #[test]
+    fn test_decode_utf32() {
+        let text = "a\u{1F600}b\n\u{00E9}";
+        let res = decode(&encode_utf32(&[0x00, 0x00, 0xFE, 0xFF], text, u32::to_be_bytes));
+        assert_eq!(res.ok().as_deref(), Some("\u{FEFF}a\u{1F600}b\n\u{00E9}"));
+
+        let res = decode(&encode_utf32(&[0xFF, 0xFE, 0x00, 0x00], text, u32::to_le_bytes));
+        assert_eq!(res.ok().as_deref(), Some("\u{FEFF}a\u{1F600}b\n\u{00E9}"));
+    }
+
+    #[test]
+    fn test_decode_invalid_utf32() {
+        // Values above the unicode range and surrogates are not valid characters
+        let bytes = [0x00, 0x00, 0xFE, 0xFF, 0x00, 0x11, 0x00, 0x00, 0x00, 0x00, 0xD8, 0x00, 0x00, 0x00, 0x00, b'a'];
+        let res = decode(&bytes);
+        let errors = res.unwrap_err();
+
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(&errors[0].0, LexErrorCode::InvalidEncoding { encoding: "utf-32 (be)", bytes } if bytes == &[0x00, 0x11, 0x00, 0x00]));
+        assert_eq!(errors[0].1, Some((1, 2, 4, 4)));
+        assert!(matches!(&errors[1].0, LexErrorCode::InvalidEncoding { encoding: "utf-32 (be)", bytes } if bytes == &[0x00, 0x00, 0xD8, 0x00]));
+        assert_eq!(errors[1].1, Some((1, 3, 8, 4)));
+    }
+
+    #[test]
+    fn test_decode_unsupported_bom() {
+        let res = decode(&[0x2B, 0x2F, 0x76, b'a']);
+        let errors = res.unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(errors[0], (LexErrorCode::InvalidBOM("utf-7"), Some((1, 1, 0, 3)))));
+    }
+}
@@ -1,6 +1,8 @@
 use core::ops::Index;
 use std::fmt::Display;
 
+use crate::common::Interner;
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
 pub struct PunctuationId(u32);
 
@@ -16,29 +18,20 @@ impl Display for PunctuationId {
 
 
 pub struct PuncutationTable {
-    punctuation: Vec<String>,
+    punctuation: Interner<String>,
 }
 
 impl PuncutationTable {
     pub fn new() -> Self {
-        Self { punctuation: Vec::new() }
+        Self { punctuation: Interner::new() }
     }
 
-    pub fn add(&mut self, punct: &str) -> PunctuationId {
-        // Naive implementation
-        let it = self.punctuation.iter().enumerate().find(|(_, val)| *val == punct);
-        match it {
-            Some((idx, _)) => PunctuationId(idx as u32),
-            None => {
-                let idx = self.punctuation.len() as u32;
-                self.punctuation.push(punct.to_string());
-                PunctuationId(idx)
-            }
-        }
+    pub fn add(&self, punct: &str) -> PunctuationId {
+        PunctuationId(self.punctuation.add(punct))
     }
 
     pub fn get(&self, index: PunctuationId) -> &str {
-        &self.punctuation[index.0 as usize]
+        self.punctuation.get(index.0).expect("invalid punctuation id")
     }
 }
 
@@ -2,12 +2,13 @@ use std::{
     fmt::{self, Write as _},
     io
 };
+use serde::{Deserialize, Serialize};
 use super::{NameId, NameTable, PunctuationId, PuncutationTable, SpanId, SpanRegistry};
 use crate::{lexer::FormatSpan, literals::{Literal, LiteralId, LiteralTable}};
 
 
 /// Strong keywords
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum StrongKeyword {
     // Primitive types
     B8,
@@ -239,7 +240,7 @@ impl StrongKeyword {
 }
 
 // Weak keywords
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum WeakKeyword {
     Assign,
     Associativity,
@@ -302,7 +303,7 @@ impl WeakKeyword {
     ];
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Serialize, Deserialize)]
 pub enum Punctuation {
     Dot,
     DotDot,
@@ -376,7 +377,7 @@ impl Punctuation {
         }
     }
 
-    pub fn from_str(s: &str, punctuations: &mut PuncutationTable) -> Self {
+    pub fn from_str(s: &str, punctuations: &PuncutationTable) -> Self {
         match s {
             "."   => Punctuation::Dot,
             ".."  => Punctuation::DotDot,
@@ -402,6 +403,9 @@ impl Punctuation {
             "<-"  => Punctuation::SingleArrowL,
             "=>"  => Punctuation::DoubleArrow,
 
+            "in"  => Punctuation::Contains,
+            "!in" => Punctuation::NotContains,
+
             _ => {
                 let id = punctuations.add(s);
                 Punctuation::Custom(id)
@@ -411,7 +415,7 @@ impl Punctuation {
 }
 
 #[allow(unused)]
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum OpenCloseSymbol {
     Paren,
     Brace,
@@ -460,7 +464,7 @@ impl OpenCloseSymbol {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum Token {
     StrongKw(StrongKeyword),
     WeakKw(WeakKeyword),
@@ -469,7 +473,9 @@ pub enum Token {
     OpenSymbol(OpenCloseSymbol),
     CloseSymbol(OpenCloseSymbol),
     Literal(LiteralId),
-    Underscore
+    Underscore,
+    // Malformed source text, the error has already been reported by the lexer
+    Error,
 }
 
 impl Token {
@@ -484,6 +490,22 @@ impl Token {
             Self::CloseSymbol(sym) => write!(f, "{}", sym.as_close_display_str()),
             Self::Literal(lit_id) => write!(f, "{}", &literals[*lit_id]),
             Self::Underscore => write!(f, "_"),
+            Self::Error => write!(f, "<error>"),
+        }
+    }
+
+    /// Get the text the token is written as, `None` for literals and errors, as their text can't be derived from the token
+    pub fn source_text<'a>(&'a self, names: &'a NameTable, punctuations: &'a PuncutationTable) -> Option<&'a str> {
+        match self {
+            Self::StrongKw(kw) => Some(kw.as_str()),
+            Self::WeakKw(kw) => Some(kw.as_str()),
+            Self::Name(name_id) => Some(&names[*name_id]),
+            Self::Punctuation(punct) => Some(punct.as_str(punctuations)),
+            Self::OpenSymbol(sym) => Some(sym.as_open_str(punctuations)),
+            Self::CloseSymbol(sym) => Some(sym.as_close_str(punctuations)),
+            Self::Underscore => Some("_"),
+            Self::Literal(_) |
+            Self::Error => None,
         }
     }
 
@@ -497,11 +519,12 @@ impl Token {
             Self::CloseSymbol(sym) => sym.as_close_display_str(),
             Self::Literal(_) => "literal",
             Self::Underscore => "_",
+            Self::Error => "<error>",
         }
     }
 }
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Serialize, Deserialize)]
 pub enum MetaElem {
     Whitespace(String),
     LineComment(String),
@@ -512,11 +535,33 @@ pub enum MetaElem {
     BlockTopDocComment(String),
 }
 
+impl MetaElem {
+    /// Write the element exactly as it appeared in the source
+    pub fn write_source(&self, out: &mut String) {
+        let (open, text, close) = match self {
+            Self::Whitespace(text)         => ("",    text, ""),
+            Self::LineComment(text)        => ("//",  text, ""),
+            Self::LineDocComment(text)     => ("///", text, ""),
+            Self::LineTopDocComment(text)  => ("//!", text, ""),
+            Self::BlockComment(text)       => ("/*",  text, "*/"),
+            Self::BlockDocComment(text)    => ("/**", text, "*/"),
+            Self::BlockTopDocComment(text) => ("/*!", text, "*/"),
+        };
+        out.push_str(open);
+        out.push_str(text);
+        out.push_str(close);
+    }
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct TokenMetadata {
     pub span_id:     SpanId,
     pub meta_elems:  Vec<MetaElem>,
+    /// Text of the token as written in the source, only stored when it differs from `Token::source_text`, e.g. for literals, errors and names that are not NFC normalized
+    pub raw_text:    Option<String>,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct TokenStore {
     pub has_bom:          bool,
     pub shebang:          Option<String>,
@@ -528,7 +573,7 @@ pub struct TokenStore {
 }
 
 impl TokenStore {
-    pub fn new(names: &mut NameTable) -> Self {
+    pub fn new(names: &NameTable) -> Self {
         let mut weak_kw_name_map = Vec::with_capacity(WeakKeyword::WEAK_KEYWORD_NAMES.len());
         for kw_name in &WeakKeyword::WEAK_KEYWORD_NAMES {
             weak_kw_name_map.push(names.add(kw_name));
@@ -568,6 +613,38 @@ impl TokenStore {
         self.metadata.push(meta);
     }
 
+    /// Get the exact source text of a token
+    pub fn token_text<'a>(&'a self, idx: usize, names: &'a NameTable, punctuations: &'a PuncutationTable) -> &'a str {
+        match &self.metadata[idx].raw_text {
+            Some(text) => text,
+            None       => self.tokens[idx].source_text(names, punctuations).unwrap_or_default(),
+        }
+    }
+
+    /// Rebuild the exact source the tokens were lexed from, using the text of the tokens and the whitespace and comments in their metadata
+    #[allow(unused)]
+    pub fn reconstruct_source(&self, names: &NameTable, punctuations: &PuncutationTable) -> String {
+        let mut out = String::new();
+        if self.has_bom {
+            out.push('\u{FEFF}');
+        }
+        if let Some(shebang) = &self.shebang {
+            out.push_str("#!");
+            out.push_str(shebang);
+        }
+
+        for (idx, meta) in self.metadata.iter().enumerate() {
+            for elem in &meta.meta_elems {
+                elem.write_source(&mut out);
+            }
+            out.push_str(self.token_text(idx, names, punctuations));
+        }
+        for elem in &self.tail_meta_elems {
+            elem.write_source(&mut out);
+        }
+        out
+    }
+
     pub fn get_name_from_weak_keyword(&self, kw: WeakKeyword) -> NameId {
         self.weak_kw_name_map[kw as usize]
     }
@@ -582,12 +659,12 @@ impl TokenStore {
     }
 
     pub fn log(&self, literals: &LiteralTable, names: &NameTable, punctuations: &PuncutationTable, spans: &SpanRegistry) {
-        println!("Lexer output");
-        println!("has BOM: {}", self.has_bom);
+        crate::outputln!("Lexer output");
+        crate::outputln!("has BOM: {}", self.has_bom);
         if let Some(shebang) = &self.shebang {
-            println!("shebang: Some(\"{shebang}\")");
+            crate::outputln!("shebang: Some(\"{shebang}\")");
         } else {
-            println!("shebang: None");
+            crate::outputln!("shebang: None");
         }
 
         let mut print_buf = String::with_capacity(64);
@@ -619,9 +696,10 @@ impl TokenStore {
                 },
                 Token::Literal(lit_id) => write!(&mut print_buf, "Literal({lit_id}): {}", &literals[*lit_id]),
                 Token::Underscore => write!(&mut print_buf, "Underscore"),
+                Token::Error => write!(&mut print_buf, "Error"),
             };
 
-            println!("{print_buf:64} | {}", FormatSpan::new(spans, meta.span_id));
+            crate::outputln!("{print_buf:64} | {}", FormatSpan::new(spans, meta.span_id));
         }
     }
 
@@ -647,6 +725,7 @@ impl TokenStore {
 
                 },
                 Token::Underscore => write!(writer, "underscore,_,")?,
+                Token::Error => write!(writer, "error,,")?,
             }
 
             let span = &spans[meta.span_id];
@@ -0,0 +1,21 @@
+pub mod error_warning;
+pub mod literals;
+
+pub mod common;
+
+pub mod type_system;
+
+pub mod lexer;
+pub mod ast;
+
+pub mod hir;
+
+pub mod formatter;
+
+pub mod manifest;
+pub mod incremental;
+pub mod metadata;
+pub mod prelude;
+pub mod frontend;
+pub mod target;
+pub mod doc;
@@ -1,8 +1,12 @@
 use core::ops::Index;
 use std::fmt::Display;
 
+use serde::{Deserialize, Serialize};
 
-#[derive(PartialEq, Debug)]
+use crate::common::Interner;
+
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 pub enum Literal {
     Decimal {
         int_digits: Vec<u8>,
@@ -89,7 +93,7 @@ fn print_digits(f: &mut std::fmt::Formatter<'_>, digits: &Vec<u8>, show_precedin
     Ok(())
 }
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
 pub struct LiteralId(u32);
 
 impl LiteralId {
@@ -103,31 +107,22 @@ impl Display for LiteralId {
 }
 
 pub struct LiteralTable {
-    literals: Vec<Literal>,
+    literals: Interner<Literal>,
 }
 
 impl LiteralTable {
     pub fn new() -> Self {
         Self {
-            literals: Vec::new(),
+            literals: Interner::new(),
         }
     }
 
-    pub fn add(&mut self, lit: Literal) -> LiteralId {
-        // Naive implementation
-        let it = self.literals.iter().enumerate().find(|(_, val)| **val == lit);
-        match it {
-            Some((idx, _)) => LiteralId(idx as u32),
-            None => {
-                let idx = self.literals.len() as u32;
-                self.literals.push(lit);
-                LiteralId(idx)
-            }
-        }
+    pub fn add(&self, lit: Literal) -> LiteralId {
+        LiteralId(self.literals.add(&lit))
     }
 
     pub fn get(&self, id: LiteralId) -> &Literal {
-        &self.literals[id.0 as usize]
+        self.literals.get(id.0).expect("invalid literal id")
     }
 }
 
@@ -1,143 +1,189 @@
 use std::{
+    collections::HashMap,
     fs::{self, File},
-    io::Read,
     path::{Path, PathBuf},
-    sync::Arc,
     env,
     time,
 };
-use parking_lot::RwLock;
 
 use clap::Parser as _;
-use ast::{Parser, Visitor as _};
-use cli::Cli;
-use common::{CompilerStats, FormatSpanLoc, LibraryPath, NameTable, OperatorTable, PrecedenceDAG, RootSymbolTable, RootUseTable, Scope, SpanId, SpanRegistry, Symbol, TraitDag, VarInfoMap};
-use hir::{FormatHirError, Visitor as _};
-use lexer::{Lexer, PuncutationTable};
-use literals::LiteralTable;
-use type_system::TypeRegistry;
+use ast::passes::AstPass as _;
+use cli::{Cli, Command, DocArgs, ErrorFormat, FmtArgs};
+use common::{CompilerStats, Diagnostic, FormatDiagnostic, FormatDiagnosticJson, PassManager, PassSkip, Severity, SpanRegistry};
+use frontend::{file_trace_args, FrontEnd, ProcessedFile};
+use hir::Visitor as _;
+use lexer::{ConfusablesLint, SourceFile};
+use metadata::LibraryMetadata;
+use target::BuildTarget;
 
-mod error_warning;
-mod literals;
-
-mod common;
+use bootstrap::{ast, common, doc, formatter, frontend, hir, lexer, metadata, outputln, target};
 
 mod cli;
 
-mod type_system;
-
-mod lexer;
-mod ast;
-
-mod hir;
+#[global_allocator]
+static ALLOCATOR: common::memory::TrackingAllocator = common::memory::TrackingAllocator;
 
 fn main() {
-    let cwd = env::current_dir().unwrap().to_str().unwrap().to_string();
-    println!("cwd: {cwd}");
-
+    let start = time::Instant::now();
     let cli = Cli::parse();
 
-    let library = match &cli.library {
-        Some(library) => library.clone(),
-        None => cli.package.clone(),
+    let error_format = match &cli.command {
+        Some(Command::Fmt(args)) => args.error_format,
+        Some(Command::Doc(args)) => args.error_format,
+        None                     => cli.error_format,
     };
+    common::set_output_to_stderr(error_format == ErrorFormat::Json);
+
+    match &cli.command {
+        Some(Command::Fmt(args)) => {
+            run_fmt(args);
+            return;
+        },
+        Some(Command::Doc(args)) => {
+            run_doc(args);
+            return;
+        },
+        None => {},
+    }
 
-    let library_path = LibraryPath {
-        group: cli.group.clone(),
-        package: cli.package.clone(),
-        library,
+    let cwd = env::current_dir().unwrap().to_str().unwrap().to_string();
+    outputln!("cwd: {cwd}");
+
+    let (passes, targets) = match pass_manager(&cli).and_then(|passes| Ok((passes, build_targets(&cli)?))) {
+        Ok(res)     => res,
+        Err(errors) => {
+            let spans = SpanRegistry::new();
+            for err in &errors {
+                report_diagnostic(&cli, &spans, err);
+            }
+            std::process::exit(1);
+        },
     };
+    let all_passes = PassManager::all(frontend::pass_infos());
+
+    let mut lib_stats = Vec::new();
+    for target in targets {
+        outputln!("Building {} `{}` from '{}'", target.kind, target.lib_path, target.root);
+        // Dependent libraries need the metadata of fully processed libraries
+        let passes = if target.is_dependency { &all_passes } else { &passes };
+        let stats = compile(&cli, &cwd, &target, passes);
+        lib_stats.push((target.lib_path.to_string(), stats));
+    }
 
-    let total_start = time::Instant::now();
-
-    let mut files_to_process = Vec::new();
-    for input_file in &cli.input_files {
-        files_to_process.push((input_file.clone(), Scope::new()));
+    if let Some(path) = &cli.timings_json {
+        let trace = common::chrome_trace(start, &lib_stats);
+        match fs::write(path, trace.to_string()) {
+            Ok(())   => outputln!("Wrote timings to '{path}'"),
+            Err(err) => report_diagnostic(&cli, &SpanRegistry::new(), &Diagnostic::new(Severity::Error, format!("Failed to write timings to '{path}': {err}"))),
+        }
     }
-    
-    let symbol_table = RootSymbolTable::new(library_path.clone());
-    let symbol_table = Arc::new(RwLock::new(symbol_table));
+}
 
-    let type_registry = Arc::new(RwLock::new(TypeRegistry::new()));
+/// Get the passes to run, based on the passes selected on the command line
+fn pass_manager(cli: &Cli) -> Result<PassManager, Vec<Diagnostic>> {
+    let passes = PassManager::new(frontend::pass_infos(), cli.stop_after.as_deref(), &cli.skip, &cli.dump_hir_after)?;
+
+    let lowering = ast::passes::AstToHirLowering::NAME;
+    let errors: Vec<_> = passes.dumped_passes()
+        .filter(|pass| passes.runs_before(pass, lowering))
+        .map(|pass| Diagnostic::new(Severity::Error, format!("Cannot dump the HIR after '{pass}', the HIR is only created by '{lowering}'")))
+        .collect();
+    if !errors.is_empty() {
+        return Err(errors);
+    }
 
-    let trait_dag = Arc::new(RwLock::new(TraitDag::new()));
+    for (pass, skip) in passes.skipped_passes() {
+        match skip {
+            PassSkip::Dependency(dep) => outputln!("Skipping pass '{pass}', as it depends on '{dep}'"),
+            _                         => outputln!("Skipping pass '{pass}'"),
+        }
+    }
+    Ok(passes)
+}
 
-    let precedences = PrecedenceDAG::new();
-    let precedences = Arc::new(RwLock::new(precedences));
+/// Get the artifacts to build for the input, the core library isn't needed when nothing is lowered
+fn build_targets(cli: &Cli) -> Result<Vec<BuildTarget>, Vec<Diagnostic>> {
+    let input = Path::new(cli.input.as_deref().unwrap_or("."));
+    let no_core = cli.no_core || cli.lex_only || cli.parse_only;
+    target::build_targets(input, cli.core.as_deref().map(Path::new), no_core)
+}
 
-    let operators = OperatorTable::new();
-    let operators = Arc::new(RwLock::new(operators));
+fn compile(cli: &Cli, cwd: &str, target: &BuildTarget, passes: &PassManager) -> CompilerStats {
+    let total_start = time::Instant::now();
+    let library_path = target.lib_path.clone();
 
-    let span_registry = SpanRegistry::new();
-    let span_registry = Arc::new(RwLock::new(span_registry));
+    let ctx = hir::passes::PassContext::new(library_path.clone());
+    let mut stats = CompilerStats::new();
 
-    let var_info_map = Arc::new(RwLock::new(VarInfoMap::new()));
+    let options = frontend::FrontEndOptions {
+        jobs: cli.jobs.unwrap_or_else(frontend::default_jobs),
+        skip_normalization: cli.skip_normalization,
+        incremental: !cli.no_incremental,
+        lex_only: cli.lex_only,
+        parse_only: cli.parse_only,
+    };
+    let phase_start = stats.begin_phase();
+    let processed = frontend::process_files(target, &options, passes, &ctx, &HashMap::new(), &stats.trace);
+    stats.end_phase("Front-end", phase_start);
 
     let mut asts = Vec::new();
+    let mut confusables = ConfusablesLint::new();
 
-    let literal_table = Arc::new(RwLock::new(LiteralTable::new()));
-    let name_table = Arc::new(RwLock::new(NameTable::new()));
-    let punct_table = Arc::new(RwLock::new(PuncutationTable::new()));
-
-    let mut stats = CompilerStats::new();
-
-    while let Some((input_file, base_scope)) = files_to_process.pop() {
-        println!("================================================================");
-        println!("File path: {cwd}/{input_file}");
+    for file in processed {
+        let ProcessedFile { input_file, .. } = &file;
+        outputln!("================================================================");
+        outputln!("File path: {cwd}/{input_file}");
 
         stats.add_file();
 
-        let mut file = File::open(&input_file).unwrap();
+        let spans = ctx.spans.read();
+        let names = ctx.names.read();
+        let puncts = ctx.puncts.read();
+        let lits = ctx.lits.read();
 
-        let mut buf = Vec::new();
-        _ = file.read_to_end(&mut buf).unwrap();
-    
-        let file_content = unsafe { String::from_utf8_unchecked(buf) };
-    
-        let lex_start = time::Instant::now();
-    
-        let tokens = {
-            let mut spans = span_registry.write();
-            let mut names = name_table.write();
-            let mut puncts = punct_table.write();
-            let mut lits = literal_table.write();
-            let mut lexer = Lexer::new(&input_file, &file_content, &mut lits, &mut names, &mut puncts, &mut spans);
-            match lexer.lex() {
-                Ok(()) => {},
-                Err(mut err) => {
-                    err.set_path(input_file.clone());
-                    println!("{err}");
-                    return;
-                },
-            };
-            let (num_lexed_bytes, num_lexed_chars, num_lexed_lines) = lexer.stats();
-            let tokens = lexer.tokens;
-            
+        for err in &file.read_errors {
+            report_diagnostic(cli, &spans, err);
+        }
+        // Files loaded from the incremental cache weren't lexed, parsed and processed, so they only report the time it took to load them
+        if let Some(load_duration) = file.cache_load {
+            stats.add_cached_file();
             if cli.timings {
-                let lex_dur = time::Instant::now() - lex_start;
-                println!("Lexing {input_file} took {:.2} ms, generating {} tokens", lex_dur.as_secs_f32() * 1000.0, tokens.tokens.len());
-                
-                stats.add_lex(
-                    lex_dur,
-                    num_lexed_bytes,
-                    num_lexed_chars,
-                    num_lexed_lines,
-                    tokens.tokens.len() as u64
-                );
+                outputln!("Loaded {input_file} from the incremental cache in {:.2} ms", load_duration.as_secs_f32() * 1000.0);
             }
-            tokens
+        }
+        let is_cached = file.cache_load.is_some();
+
+        let Some(lex) = &file.lex else { continue };
+        let tokens = match (&file.tokens, &file.ast) {
+            (Some(tokens), _) => tokens,
+            (None, Some(ast)) => &ast.tokens,
+            (None, None)      => continue,
         };
-            
+
+        for err in &lex.diagnostics {
+            report_diagnostic(cli, &spans, err);
+        }
+        if !is_cached {
+            let (num_lexed_bytes, num_lexed_chars, num_lexed_lines) = file.lex_counts;
+            stats.add_lex(
+                lex.duration,
+                num_lexed_bytes,
+                num_lexed_chars,
+                num_lexed_lines,
+                tokens.tokens.len() as u64
+            );
+            if cli.timings {
+                outputln!("Lexing {input_file} took {:.2} ms, generating {} tokens", lex.duration.as_secs_f32() * 1000.0, tokens.tokens.len());
+            }
+        }
+        confusables.collect(tokens, &names, &puncts);
+
         if cli.print_lex_output {
-            let spans = span_registry.read();
-            let names = name_table.read();
-            let puncts = punct_table.read();
-            let lits = literal_table.read();
             tokens.log(&lits, &names, &puncts, &spans);
         }
     
         if cli.output_lex_csv {
-            let name = Path::new(&input_file).strip_prefix("../").unwrap();
+            let name = Path::new(input_file).strip_prefix("../").unwrap();
             let mut lex_csv_out_path = PathBuf::new();
             lex_csv_out_path.push("..");
             lex_csv_out_path.push("lex_csv");
@@ -149,124 +195,67 @@ fn main() {
                 fs::create_dir_all(parent_dir_path).unwrap();
             }
 
-            let spans = span_registry.read();
-            let names = name_table.read();
-            let puncts = punct_table.read();
-            let lits = literal_table.read();
             let mut csv_file = File::create(lex_csv_out_path).unwrap();
             _ = tokens.log_csv(&mut csv_file, &lits, &names, &puncts, &spans);
         }
-        
-        if cli.lex_only {
-            continue;
-        }
-
-        let parse_start = time::Instant::now();
 
-        let mut ast = {
-            let mut spans = span_registry.write();
+        let (Some(parse), Some(ast)) = (&file.parse, &file.ast) else { continue };
+        for err in &parse.diagnostics {
+            report_diagnostic(cli, &spans, err);
+        }
 
-            let names = name_table.read();
-            let mut parser = Parser::new(&tokens, &names, &mut spans);
-            match parser.parse() {
-                Ok(_) => {},
-                Err(err) => {
-                    let tok_meta = &tokens.metadata[err.tok_idx];
-                    println!("{}({}): {err}", input_file, FormatSpanLoc{ registry: &spans, span: tok_meta.span_id });
-                    return;
-                },
+        if !is_cached {
+            stats.add_parse(parse.duration, ast.nodes.len() as u64);
+            if cli.timings {
+                outputln!("Parsing {input_file} took {:.2} ms, generating {} nodes", parse.duration.as_secs_f32() * 1000.0, ast.nodes.len() );
             }
-            
-            parser.ast
-        };
-            ast.tokens = tokens;
-        ast.file = input_file.clone().into();
-
-        if cli.timings {
-            let parse_dur = time::Instant::now() - parse_start;
-            stats.add_parse(parse_dur, ast.nodes.len() as u64);
-            println!("Parsing {input_file} took {:.2} ms, generating {} nodes", parse_dur.as_secs_f32() * 1000.0, ast.nodes.len() );
         }
 
         if cli.print_parse_output {
-            let names = name_table.read();
-            let puncts = punct_table.read();
-            let lits = literal_table.read();
             ast.log(&names, &lits, &puncts);
         }
 
-        if cli.parse_only {
-            continue;
+        let Some(ast_passes) = &file.ast_passes else { continue };
+        for (pass_name, pass_dur) in &file.pass_timings {
+            stats.add_ast_pass(*pass_dur);
+            if cli.pass_timings {
+                outputln!("Processing AST Pass '{pass_name:32}' for '{input_file}' took {:.2} ms", pass_dur.as_secs_f32() * 1000.0);
+            }
         }
-
-        let ast_start = time::Instant::now();
-    
-        let mut ast_ctx = ast::passes::Context::new(
-            library_path.clone(),
-            symbol_table.clone(),
-            base_scope.clone(),
-            &ast,
-            precedences.clone(),
-        );
-        
-        let mut sub_paths = Vec::new();
-        {
-            let names = name_table.read();
-            let lits = literal_table.read();
-
-            do_ast_pass(&cli, &mut stats, &input_file, "Context Setup", || {
-                let mut pass = ast::passes::ContextSetup::new(&mut ast_ctx);
-                pass.visit(&ast);
-            });
-            
-            do_ast_pass(&cli, &mut stats, &input_file, "Module Scoping", || {
-                let mut pass = ast::passes::ModuleScopePass::new(&mut ast_ctx, base_scope.clone(), &names);
-                pass.visit(&ast);
-            });
-            
-            do_ast_pass(&cli, &mut stats, &input_file, "Module Attribute Resolve", || {
-                let mut pass = ast::passes::ModuleAttributeResolver::new(&mut ast_ctx, &names, &lits);
-                pass.visit(&ast);
-            });
-            
-            do_ast_pass(&cli, &mut stats, &input_file, "Module Symbol Generation + Path Collection", || {
-                let mut input_path = PathBuf::from(input_file.clone());
-                input_path.pop();
-                let mut pass = ast::passes::ModulePathResolution::new(&mut ast_ctx, &names, input_path);
-                pass.visit(&ast);
-                
-                sub_paths = pass.collected_paths;
-            });
+        for err in ast_passes.diagnostics.iter().chain(&file.cache_errors) {
+            report_diagnostic(cli, &spans, err);
         }
 
-        for err in &*ast_ctx.errors.lock() {
-            println!("{err}");
+        if cli.timings && !is_cached {
+            outputln!("Processing all AST passes for {input_file} took {:.2} ms", ast_passes.duration.as_secs_f32() * 1000.0);
         }
 
-        if cli.timings {
-            let parse_dur = time::Instant::now() - ast_start;
-            println!("Processing all AST passes for {input_file} took {:.2} ms", parse_dur.as_secs_f32() * 1000.0);
+        for path in &file.sub_modules {
+            outputln!("Found sub-module at '{path}'");
         }
 
-        for (path, scope) in sub_paths {
-            println!("Found sub-module at '{}'", path.to_str().unwrap());
-            files_to_process.push((path.to_str().unwrap().to_string(), scope));
+        if let (Some(ast), Some(ast_ctx)) = (file.ast, file.ast_ctx) {
+            asts.push((ast, ast_ctx));
         }
-
-        asts.push((ast, ast_ctx));
     }
 
+    {
+        let spans = ctx.spans.read();
+        for warning in confusables.check() {
+            report_diagnostic(cli, &spans, &warning);
+        }
+    }
 
-    println!("================================================================");
-    println!("Post-parse AST passes:");
+    outputln!("================================================================");
+    outputln!("Post-parse AST passes:");
 
     // Precedence
 
     // Operators
 
     // let mut imported_operators = Vec::new();
-    // do_ast_for_all_passes(&cli, &mut stats, "Operator Import", &mut asts, |ast, ast_ctx| {
-    //     let mut pass = ast::passes::OperatorImport::new(ast_ctx, &name_table);
+    // do_ast_for_all_passes(cli, &mut stats, "Operator Import", &mut asts, |ast, ast_ctx| {
+    //     let mut pass = ast::passes::OperatorImport::new(ast_ctx, &ctx.names);
     //     pass.visit(ast);
 
     //     if !pass.imports.is_empty() {
@@ -274,137 +263,156 @@ fn main() {
     //     }
     // });
 
-    // TODO: External operator importing happens here
-
-    let use_table = Arc::new(RwLock::new(RootUseTable::new(library_path.clone())));
-
+    // The root file is always processed first, and can opt out of the core library or its prelude
+    let phase_start = stats.begin_phase();
+    let import_errors = frontend::import_libraries(target, asts.first().map(|(ast, _)| ast), &ctx, &HashMap::new());
+    for err in &import_errors {
+        report_diagnostic(cli, &ctx.spans.read(), err);
+    }
+    let import_failed = !import_errors.is_empty();
+    stats.end_phase("Library import", phase_start);
 
+    let phase_start = stats.begin_phase();
     let mut hir = hir::Hir::new();
-    do_ast_for_all_passes(&cli, &mut stats, "AST to HIR lowering", &mut asts, |ast, ast_ctx| {
-        let spans = span_registry.read();
-        let mut names = name_table.write();
-        let lits = literal_table.read();
-        let mut uses = use_table.write();
-        let mut pass = ast::passes::AstToHirLowering::new(ast_ctx, &mut names, &lits, &spans, &mut hir, &mut uses, library_path.clone());
-        pass.visit(ast);
+    do_ast_for_all_passes(cli, &mut stats, passes, ast::passes::AstToHirLowering::NAME, &mut asts, |ast, ast_ctx| {
+        frontend::lower_file(&ctx, &mut hir, ast, ast_ctx);
     });
     stats.add_ast_hir_lower(&hir);
+    stats.end_phase("AST to HIR lowering", phase_start);
+    {
+        let spans = ctx.spans.read();
+        for (ast, ast_ctx) in &asts {
+            for err in &*ast_ctx.errors.lock() {
+                report_diagnostic(cli, &spans, &err.to_diagnostic(ast));
+            }
+        }
+    }
 
-    println!("================================================================");
-
-    // TODO: implicit prelude
+    outputln!("================================================================");
 
     if cli.print_lowered_hir_nodes {
-        let names = name_table.read();
-        let puncts = punct_table.read();
-        let lits = literal_table.read();
+        let names = ctx.names.read();
+        let puncts = ctx.puncts.read();
+        let lits = ctx.lits.read();
 
-        println!("Lowered HIR:");
+        outputln!("Lowered HIR:");
         let mut hir_logger = hir::NodeLogger::new(&names, &lits, &puncts);
         hir_logger.visit(&mut hir, hir::VisitFlags::all());
-        println!("--------------------------------")
+        outputln!("--------------------------------")
     }
 
-    if cli.print_lowered_hir_code {
-        let names = name_table.read();
-        let puncts = punct_table.read();
-        let lits = literal_table.read();
+    if cli.print_lowered_hir_code || passes.dump_after(ast::passes::AstToHirLowering::NAME) {
+        let names = ctx.names.read();
+        let puncts = ctx.puncts.read();
+        let lits = ctx.lits.read();
 
-        println!("Lowered HIR pseudo-code:");
+        outputln!("Lowered HIR pseudo-code:");
         let mut hir_printer = hir::CodePrinter::new(&names, &lits, &puncts);
         hir_printer.visit(&mut hir, hir::VisitFlags::all());
-        println!("--------------------------------")
+        outputln!("--------------------------------")
     }
 
     {
-        let mut ctx = hir::passes::PassContext {
-            names: name_table.clone(),
-            puncts: punct_table.clone(),
-            lits: literal_table.clone(),
-            spans: span_registry.clone(),
-            syms: symbol_table.clone(),
-            type_reg: type_registry.clone(),
-            trait_dag: trait_dag.clone(),
-            uses: use_table.clone(),
-            precedence_dag: precedences.clone(),
-            op_table: operators.clone(),
-            var_infos: var_info_map.clone(),
-            lib_path: library_path.clone(),
-            errors: Arc::new(RwLock::new(Vec::new())),
-        };
-        process_hir(&mut hir, &cli, &mut stats, &mut ctx);
+        let phase_start = stats.begin_phase();
+        let hir_failed = hir::process_hir(&mut hir, cli.pass_timings, cli.verify_hir, &mut stats, &ctx, options.jobs, passes);
+        stats.end_phase("HIR passes", phase_start);
+
+        stats.num_types_registered = ctx.type_reg.read().type_count();
+
+        if cli.print_hir_nodes {
+            let names = ctx.names.read();
+            let puncts = ctx.puncts.read();
+            let lits = ctx.lits.read();
 
-        stats.num_types_registered = type_registry.read().type_count();
+            outputln!("--------------------------------");
+            outputln!("Processed HIR:");
+            let mut hir_logger = hir::NodeLogger::new(&names, &lits, &puncts);
+            hir_logger.visit(&mut hir, hir::VisitFlags::all());
+        }
 
         if cli.print_hir_code {
-            let names = name_table.read();
-            let puncts = punct_table.read();
-            let lits = literal_table.read();
+            let names = ctx.names.read();
+            let puncts = ctx.puncts.read();
+            let lits = ctx.lits.read();
 
-            println!("--------------------------------");
-            println!("Processed HIR pseudo-code:");
+            outputln!("--------------------------------");
+            outputln!("Processed HIR pseudo-code:");
             let mut hir_printer = hir::CodePrinter::new(&names, &lits, &puncts);
             hir_printer.visit(&mut hir, hir::VisitFlags::all());
         }
 
         {
-            let spans = span_registry.read();
+            let spans = ctx.spans.read();
             
             for err in &*ctx.errors.read() {
-                println!("{}", FormatHirError::new(&spans, err.clone()));
+                report_diagnostic(cli, &spans, &err.to_diagnostic());
+            }
+        }
+
+        if let Some(metadata_out) = &target.metadata_out {
+            if hir_failed || import_failed || !passes.runs_all() {
+                // Don't leave metadata of a previous build around, which dependent libraries would otherwise pick up
+                _ = fs::remove_file(metadata_out);
+            } else {
+                let puncts = ctx.puncts.read();
+                let metadata = LibraryMetadata::export(&library_path, &ctx.syms.read(), &ctx.trait_dag.read(), &puncts);
+                match metadata.store(metadata_out) {
+                    Ok(())   => outputln!("Wrote library metadata to '{}'", metadata_out.display()),
+                    Err(err) => report_diagnostic(cli, &ctx.spans.read(), &err),
+                }
             }
         }
     }
     
-    println!("================================================================");
+    outputln!("================================================================");
     
-    if cli.print_use_table {
-        println!("-[use table]--------------------");
-        use_table.read().log();
+    if cli.print_hir_use_table {
+        outputln!("-[use table]--------------------");
+        ctx.uses.read().log();
     }
 
 
     if cli.print_sym_table {
-        println!("-[symbol table]-----------------");
-        let puncts = punct_table.read();
-        symbol_table.read().log(&puncts);
+        outputln!("-[symbol table]-----------------");
+        let puncts = ctx.puncts.read();
+        ctx.syms.read().log(&puncts);
     }
 
     if cli.print_precedence {
-        println!("-[precedence DAG]---------------");
-        precedences.read().log_unordered();
+        outputln!("-[precedence DAG]---------------");
+        ctx.precedence_dag.read().log_unordered();
     }
 
     if cli.print_op_table {
-        let puncts = punct_table.read();
+        let puncts = ctx.puncts.read();
 
-        println!("-[operator table]---------------");
-        operators.read().log(&puncts);
+        outputln!("-[operator table]---------------");
+        ctx.op_table.read().log(&puncts);
     }
 
     if cli.print_trait_dag {
-        println!("-[trait DAG]--------------------"); 
-        trait_dag.read().log_unordered();
+        outputln!("-[trait DAG]--------------------"); 
+        ctx.trait_dag.read().log_unordered();
     }
 
     if cli.print_type_registry {
-        println!("-[types]------------------------");
-        type_registry.read().log();
+        outputln!("-[types]------------------------");
+        ctx.type_reg.read().log();
     }
     if cli.print_type_dependencies {
-        println!("-[type dependencies]------------");
-        type_registry.read().log_dependencies();
+        outputln!("-[type dependencies]------------");
+        ctx.type_reg.read().log_dependencies();
     }
 
     if cli.print_var_info {
-        println!("-[Variable Info]----------------");
-        var_info_map.read().log();
+        outputln!("-[Variable Info]----------------");
+        ctx.var_infos.read().log();
     }
 
     if cli.timings {
         let total_dur = time::Instant::now() - total_start;
 
-        println!("=[stats]========================================================");
+        outputln!("=[stats]========================================================");
         stats.log();
 
         let mut total_time = total_dur.as_secs_f32();
@@ -412,237 +420,181 @@ fn main() {
         total_time -= hours * 3600.0;
         let minutes = (total_time / 60.0).floor();
         total_time -= minutes * 60.0;
-        println!("Compiler took {hours}:{minutes}:{total_time:.3}");
+        outputln!("Compiler took {hours}:{minutes}:{total_time:.3}");
     }
-}
 
-fn do_ast_pass<F>(cli: &Cli, stats: &mut CompilerStats, input_file: &str, pass_name: &str, f: F) where 
-    F: FnOnce()
-{
-    let start = time::Instant::now();
-    f();
-    if cli.pass_timings {
-        let pass_dur = time::Instant::now() - start;
-        stats.add_ast_pass(pass_dur);
-        println!("Processing AST Pass '{pass_name:32}' for '{input_file}' took {:.2} ms", pass_dur.as_secs_f32() * 1000.0);
-    }
+    stats
 }
 
-fn do_ast_for_all_passes<F>(cli: &Cli, stats: &mut CompilerStats, pass_name: &str, asts: &mut Vec<(ast::Ast, ast::passes::Context)>, mut f: F) where
-    F: FnMut(&ast::Ast, &mut ast::passes::Context)
-{
-    for (ast, ctx) in asts {
-        let start = time::Instant::now();
-
-        f(ast, ctx);
-        
-        if cli.pass_timings {
-            let pass_dur = time::Instant::now() - start;
-            stats.add_ast_pass(pass_dur);
-            let input_file = ast.file.to_str().unwrap();
-            println!("Processing AST Pass '{pass_name:32}' for '{input_file}' took {:.2} ms", pass_dur.as_secs_f32() * 1000.0);
-        }
-    }
+fn report_diagnostic(cli: &Cli, spans: &SpanRegistry, diag: &Diagnostic) {
+    report_diagnostic_as(cli.error_format, spans, diag);
 }
 
-fn process_hir(hir: &mut hir::Hir, cli: &Cli, stats: &mut CompilerStats, ctx: &hir::passes::PassContext) -> bool {
-    //do_hir_pass(hir, cli, stats, hir::passes::);
-
-    use hir::passes::*;
-    
-    // Base pass
-    do_hir_pass(hir, cli, stats, SimplePathGen::new(ctx));
-
-    // Precedences
-    do_hir_pass(hir, cli, stats, PrecedenceSymGen::new(ctx));
-    {
-        let start = time::Instant::now();
-
-        let mut uses = ctx.uses.write();
-        let syms = ctx.syms.read();
-        if let Err(err) = uses.finalize_precedences(&syms) {
-            ctx.add_error(hir::HirError {
-                span: SpanId::INVALID,
-                err: error_warning::HirErrorCode::UseTable { err },
-            });
-            return false;
-        }
-
-        log_hir_pass_time(cli, stats, start, "Finalizing use table precedences", false);
+fn report_diagnostic_as(format: ErrorFormat, spans: &SpanRegistry, diag: &Diagnostic) {
+    match format {
+        ErrorFormat::Human => eprintln!("{}", FormatDiagnostic::new(spans, diag)),
+        // Json diagnostics are the only output on stdout, so it can be parsed as a whole
+        ErrorFormat::Json  => println!("{}", FormatDiagnosticJson::new(spans, diag)),
     }
+}
 
-    do_hir_pass(hir, cli, stats, PrecedenceAttrib::new(ctx));
-    do_hir_pass(hir, cli, stats, PrecedenceConnect::new(ctx));
-    {
-        let start = time::Instant::now();
-
-        let mut precedence_dag = ctx.precedence_dag.write();
-        let syms = ctx.syms.read();
-        let uses = ctx.uses.read();
-        precedence_dag.build_from_syms(&syms, &uses);
-
-        let cycles = precedence_dag.check_cycles();
-        if !cycles.is_empty() {
-            for cycle in cycles {
-                let mut cycle_str = String::new();
+fn run_fmt(args: &FmtArgs) {
+    let config = formatter::FormatConfig {
+        indent_width: args.indent_width,
+        hard_tabs: args.hard_tabs,
+        max_width: args.max_width,
+        align: !args.no_align,
+    };
+    let formatter = formatter::Formatter::new(&config);
+
+    let spans = SpanRegistry::new();
+    let mut failed = false;
+    let mut unformatted = false;
+    for file in &args.files {
+        let source_file = match SourceFile::open(file) {
+            Ok(source_file) => source_file,
+            Err(err) => {
+                report_diagnostic_as(args.error_format, &spans, &Diagnostic::new(Severity::Error, format!("Failed to read {file}: {err}")));
+                failed = true;
+                continue;
+            },
+        };
+        let source = match source_file.decode(file, &spans) {
+            Ok(source) => source,
+            Err(errors) => {
+                for err in &errors {
+                    report_diagnostic_as(args.error_format, &spans, &err.to_diagnostic());
+                }
+                failed = true;
+                continue;
+            },
+        };
 
-                for idx in &cycle {
-                    let name = precedence_dag.get_name(*idx).unwrap();
-                    cycle_str.push_str(&format!("{name} -> "));
+        let formatted = match formatter.format(file, &source, &spans) {
+            Ok(formatted) => formatted,
+            Err(errors) => {
+                for err in &errors {
+                    report_diagnostic_as(args.error_format, &spans, err);
                 }
-                let name = precedence_dag.get_name(cycle[0]).unwrap();
-                cycle_str.push_str(&format!("{name}"));
+                failed = true;
+                continue;
+            },
+        };
+        if formatted == source {
+            continue;
+        }
 
-                ctx.add_error(hir::HirError {
-                    span: SpanId::INVALID,
-                    err: error_warning::HirErrorCode::CycleInPrecedenceDag { cycle: cycle_str },
-                })
+        if args.check {
+            outputln!("Would reformat: {file}");
+            unformatted = true;
+        } else {
+            // The source needs to be closed first, as it might be memory-mapped
+            drop(source);
+            drop(source_file);
+            if let Err(err) = fs::write(file, formatted) {
+                report_diagnostic_as(args.error_format, &spans, &Diagnostic::new(Severity::Error, format!("Failed to write {file}: {err}")));
+                failed = true;
             }
-            
-            return false;
         }
-        precedence_dag.calculate_order();
-
-        log_hir_pass_time(cli, stats, start, "Building and checking precedence dag", false);
     }
 
-    
-    // Operators
-    do_hir_pass(hir, cli, stats, OperatorSymbolGen::new(ctx));
-    {
-        let start = time::Instant::now();
-
-        let mut uses = ctx.uses.write();
-        let syms = ctx.syms.read();
-        if let Err(err) = uses.finalize_operators(&syms) {
-            ctx.add_error(hir::HirError {
-                span: SpanId::INVALID,
-                err: error_warning::HirErrorCode::UseTable { err },
-            });
-            return false;
-        }
-
-        log_hir_pass_time(cli, stats, start, "Finalizing use table operators", false);
+    if failed || unformatted {
+        std::process::exit(1);
     }
+}
 
-    do_hir_pass(hir, cli, stats, OperatorSetDependencyProcess::new(ctx)); 
-    do_hir_pass(hir, cli, stats, OpSetConnect::new(ctx));
-    do_hir_pass(hir, cli, stats, OpTagging::new(ctx));
-    do_hir_pass(hir, cli, stats, OpTraitGen::new(ctx));
-    {
-        let start = time::Instant::now();
-
-        let mut op_table = ctx.op_table.write();
-        let syms = ctx.syms.read();
-        let uses = ctx.uses.read();
-
-        op_table.build_from_symbols(&syms, &uses);
+fn run_doc(args: &DocArgs) {
+    let input = Path::new(&args.input);
+    let targets = match target::build_targets(input, args.core.as_deref().map(Path::new), args.no_core) {
+        Ok(targets) => targets,
+        Err(errors) => {
+            for err in &errors {
+                report_diagnostic_as(args.error_format, &SpanRegistry::new(), err);
+            }
+            std::process::exit(1);
+        },
+    };
+    // A single root file is documented even when it is a binary
+    let is_file = input.extension().is_some_and(|ext| ext == "xn");
+    let (dependencies, libraries): (Vec<_>, Vec<_>) = targets.into_iter().partition(|target| target.is_dependency);
+    let libraries: Vec<_> = libraries.into_iter().filter(|target| is_file || target.kind.is_library()).collect();
+    if libraries.is_empty() {
+        report_diagnostic_as(args.error_format, &SpanRegistry::new(), &Diagnostic::new(Severity::Error, "Package has no libraries to document".to_string()));
+        std::process::exit(1);
+    }
 
-        log_hir_pass_time(cli, stats, start, "Building operator table", false);
+    // Documentation doesn't write metadata, so dependencies that aren't up to date are only built in memory
+    let (built, errors) = FrontEnd::build_dependencies(&dependencies);
+    let mut failed = !errors.is_empty();
+    for err in &errors {
+        report_diagnostic_as(args.error_format, &SpanRegistry::new(), err);
     }
-    do_hir_pass(hir, cli, stats, InfixReorder::new(ctx));
-    
-    // Symbol gen
-    do_hir_pass(hir, cli, stats, SymbolGeneration::new(ctx));
-    {
-        let start = time::Instant::now();
-        
-        let mut uses = ctx.uses.write();
-        let syms = ctx.syms.read();
-        if let Err(err) = uses.finalize(&syms) {
-            ctx.add_error(hir::HirError {
-                span: SpanId::INVALID,
-                err: error_warning::HirErrorCode::UseTable { err },
-            });
-            return false;
-        }
-        
-        log_hir_pass_time(cli, stats, start, "Finalizing use table", false);
+    for target in &libraries {
+        failed |= !document_library(args, target, &built);
     }
-    do_hir_pass(hir, cli, stats, OpSetTraitAssociation::new(ctx));
-
-    // Trait
-    do_hir_pass(hir, cli, stats, TraitDagGen::new(ctx));
-    {
-        let mut trait_dag = ctx.trait_dag.write();
+    if failed {
+        std::process::exit(1);
+    }
+}
 
-        let cycles = trait_dag.check_cycles();
-        if !cycles.is_empty() {
-            for cycle in cycles {
-                let mut cycle_str = String::new();
+/// Generate the documentation of a single library, returns whether it was written
+fn document_library(args: &DocArgs, target: &BuildTarget, built: &HashMap<PathBuf, LibraryMetadata>) -> bool {
+    let mut front_end = FrontEnd::run(target, &HashMap::new(), built);
+    let config = doc::DocConfig {
+        document_private: args.document_private,
+        document_hidden: args.document_hidden,
+    };
+    let documentation = doc::Documentation::collect(&mut front_end, &config);
 
-                for idx in &cycle {
-                    let sym = trait_dag.get(*idx).unwrap().symbol.read();
-                    let Symbol::Trait(sym) = &*sym else { unreachable!() };
-                    cycle_str.push_str(&format!("{} -> ", sym.path));
-                }
-                let sym = trait_dag.get(cycle[0]).unwrap().symbol.read();
-                let Symbol::Trait(sym) = &*sym else { unreachable!() };
-                cycle_str.push_str(&format!("{}", sym.path));
-
-                ctx.add_error(hir::HirError {
-                    span: SpanId::INVALID,
-                    err: error_warning::HirErrorCode::CycleInTraitDag { cycle: cycle_str },
-                })
-            }
-            
-            return false;
+    let mut has_errors = false;
+    {
+        let spans = front_end.ctx.spans.read();
+        for diag in front_end.diagnostics.iter().chain(&documentation.diagnostics) {
+            has_errors |= diag.severity == Severity::Error;
+            report_diagnostic_as(args.error_format, &spans, diag);
         }
-        trait_dag.calculate_predecessors();
     }
-    
-    // Trait
-    do_hir_pass(hir, cli, stats, TraitItemProcess::new(ctx));
-    
-    // Impl trait processing
-    do_hir_pass(hir, cli, stats, ImplTraitPathGen::new(ctx));
-    do_hir_pass(hir, cli, stats, ImplTraitSymResolve::new(ctx));
-    do_hir_pass(hir, cli, stats, ImplTraitItemCollection::new(ctx));
-    do_hir_pass(hir, cli, stats, TraitImpl::new(ctx));
-    
-    // Misc
-    do_hir_pass(hir, cli, stats, VisibilityProcess::new(ctx.lib_path.clone()));
-    do_hir_pass(hir, cli, stats, SelfTyReplacePass::new(ctx));
-    do_hir_pass(hir, cli, stats, PathGen::new(ctx));
-
-    // Variable collection
-    do_hir_pass(hir, cli, stats, VariableScopeCollection::new(ctx));
-    do_hir_pass(hir, cli, stats, VariableCollection::new(ctx));
-
-    // Types
-    do_hir_pass(hir, cli, stats, ItemLevelTypeGen::new(ctx));
-    do_hir_pass(hir, cli, stats, TypeImplSymbolAssoc::new(ctx));
-    
-    !ctx.errors.read().is_empty()
-}
+    if front_end.files.is_empty() {
+        return false;
+    }
+    if has_errors {
+        outputln!("Documentation is generated from a library with errors, and might be incomplete");
+    }
 
-fn do_hir_pass<T: hir::Pass>(hir: &mut hir::Hir, cli: &Cli, stats: &mut CompilerStats, mut pass: T) {
-    let start = time::Instant::now();
-    pass.process(hir);
-    log_hir_pass_time(cli, stats, start, T::NAME, true);
+    let format = match args.format {
+        cli::DocFormat::Html     => doc::DocFormat::Html,
+        cli::DocFormat::Markdown => doc::DocFormat::Markdown,
+    };
+    match documentation.write(Path::new(&args.out_dir), format) {
+        Ok(index) => {
+            outputln!("Documentation written to {}", index.display());
+            true
+        },
+        Err(err) => {
+            report_diagnostic_as(args.error_format, &front_end.ctx.spans.read(), &Diagnostic::new(Severity::Error, format!("Failed to write documentation: {err}")));
+            false
+        },
+    }
 }
 
-fn log_hir_pass_time(cli: &Cli, stats: &mut CompilerStats, start: time::Instant, name: &str, is_direct_pass: bool) {
-    if !cli.pass_timings {
+fn do_ast_for_all_passes<F>(cli: &Cli, stats: &mut CompilerStats, passes: &PassManager, pass_name: &str, asts: &mut Vec<(ast::Ast, ast::passes::Context)>, mut f: F) where
+    F: FnMut(&ast::Ast, &mut ast::passes::Context)
+{
+    if !passes.should_run(pass_name) {
         return;
     }
 
-    let dur = time::Instant::now() - start;
-    stats.add_hir_pass(dur);
+    for (ast, ctx) in asts {
+        let start = time::Instant::now();
 
-    const NAME_WIDTH: usize = 40;
-    let name = if is_direct_pass {
-        format!("HIR pass '{name:NAME_WIDTH$}' took ")
-    } else {
-        format!("{name:0$} took ", NAME_WIDTH + 11)
-    };
-    
-    let dur = dur.as_secs_f32() * 1000.0;
-    if dur < 10.0 {
-        println!("{name}{:.2} ms", dur)
-    } else if dur < 1000.0 {
-        println!("{name}{:.1} ms", dur)
-    } else {
-        println!("{name}{:.2} s", dur / 1000.0);
+        f(ast, ctx);
+
+        let pass_dur = time::Instant::now() - start;
+        let input_file = ast.file.to_str().unwrap();
+        stats.add_ast_pass(pass_dur);
+        stats.trace.add_span(pass_name, "ast pass", start, file_trace_args(input_file));
+        if cli.pass_timings {
+            outputln!("Processing AST Pass '{pass_name:32}' for '{input_file}' took {:.2} ms", pass_dur.as_secs_f32() * 1000.0);
+        }
     }
 }
@@ -0,0 +1,272 @@
+// Package manifests, describing a package, its artifacts and its dependencies.
+//
+// ```toml
+// [package]
+// name = "foo"
+// group = "bar"              # optional
+// core = "../core"           # optional, core library package or prebuilt `.xnlib` metadata that is implicitly imported
+//
+// [[artifact]]
+// kind = "static-library"    # binary, static-library or dynamic-library
+// name = "foo"               # optional, defaults to the name of the package
+// root = "src/lib.xn"        # optional, defaults to `src/main.xn` for binaries and `src/lib.xn` for libraries
+//
+// [dependencies]
+// baz = { path = "../baz" }
+// ```
+//
+// When no artifacts are declared, a binary is added if `src/main.xn` exists, and a static library if `src/lib.xn` exists.
+// The core library is only imported when the package names it, or when it is passed on the command line.
+
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::common::{Diagnostic, LibraryPath, Severity};
+
+pub const MANIFEST_FILE_NAME: &str = "package.toml";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ArtifactKind {
+    Binary,
+    StaticLibrary,
+    DynamicLibrary,
+}
+
+impl ArtifactKind {
+    pub fn default_root(self) -> &'static str {
+        match self {
+            ArtifactKind::Binary => "src/main.xn",
+            _                    => "src/lib.xn",
+        }
+    }
+
+    pub fn is_library(self) -> bool {
+        self != ArtifactKind::Binary
+    }
+}
+
+impl fmt::Display for ArtifactKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArtifactKind::Binary         => write!(f, "binary"),
+            ArtifactKind::StaticLibrary  => write!(f, "static library"),
+            ArtifactKind::DynamicLibrary => write!(f, "dynamic library"),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawManifest {
+    package:      RawPackage,
+    #[serde(default, rename = "artifact")]
+    artifacts:    Vec<RawArtifact>,
+    #[serde(default)]
+    dependencies: BTreeMap<String, RawDependency>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawPackage {
+    name:  String,
+    group: Option<String>,
+    core:  Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawArtifact {
+    kind: ArtifactKind,
+    name: Option<String>,
+    root: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawDependency {
+    path: PathBuf,
+}
+
+#[derive(Clone, Debug)]
+pub struct Artifact {
+    pub name: String,
+    pub kind: ArtifactKind,
+    /// Root file of the main module
+    pub root: PathBuf,
+}
+
+/// Dependency on another local package
+#[derive(Clone, Debug)]
+pub struct Dependency {
+    pub name: String,
+    /// Directory containing the manifest of the dependency
+    pub dir:  PathBuf,
+}
+
+#[derive(Clone, Debug)]
+pub struct Manifest {
+    /// Path to the manifest file
+    pub path:         PathBuf,
+    pub group:        Option<String>,
+    pub name:         String,
+    /// Core library package, or its metadata
+    pub core:         Option<PathBuf>,
+    pub artifacts:    Vec<Artifact>,
+    pub dependencies: Vec<Dependency>,
+}
+
+impl Manifest {
+    /// Load the manifest at `path`, which is either the manifest file or the directory containing it
+    pub fn load(path: &Path) -> Result<Self, Diagnostic> {
+        let path = if path.is_dir() { path.join(MANIFEST_FILE_NAME) } else { path.to_path_buf() };
+        let error = |msg: String| Diagnostic::new(Severity::Error, format!("{}: {msg}", path.display()));
+
+        let text = fs::read_to_string(&path).map_err(|err| error(format!("Failed to read package manifest: {err}")))?;
+        let raw: RawManifest = toml::from_str(&text).map_err(|err| {
+            let location = err.span().map_or_else(String::new, |span| {
+                let before = &text[..span.start.min(text.len())];
+                let line = before.matches('\n').count() + 1;
+                let column = before.len() - before.rfind('\n').map_or(0, |idx| idx + 1) + 1;
+                format!(" at {line}:{column}")
+            });
+            error(format!("Invalid package manifest{location}: {}", err.message()))
+        })?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+
+        Self::check_name(&raw.package.name, "package").map_err(error)?;
+        if let Some(group) = &raw.package.group {
+            Self::check_name(group, "group").map_err(error)?;
+        }
+
+        let mut artifacts: Vec<Artifact> = Vec::new();
+        for raw_artifact in raw.artifacts {
+            let name = raw_artifact.name.unwrap_or_else(|| raw.package.name.clone());
+            Self::check_name(&name, "artifact").map_err(error)?;
+            if artifacts.iter().any(|artifact| artifact.name == name) {
+                return Err(error(format!("Artifact `{name}` is declared more than once")));
+            }
+
+            let root = dir.join(raw_artifact.root.as_deref().unwrap_or(Path::new(raw_artifact.kind.default_root())));
+            artifacts.push(Artifact { name, kind: raw_artifact.kind, root });
+        }
+
+        if artifacts.is_empty() {
+            for kind in [ArtifactKind::StaticLibrary, ArtifactKind::Binary] {
+                let root = dir.join(kind.default_root());
+                if root.is_file() {
+                    artifacts.push(Artifact { name: raw.package.name.clone(), kind, root });
+                }
+            }
+            if artifacts.is_empty() {
+                return Err(error(format!("Package has no artifacts, and neither `{}` nor `{}` exist", ArtifactKind::StaticLibrary.default_root(), ArtifactKind::Binary.default_root())));
+            }
+        }
+
+        let dependencies = raw.dependencies.into_iter()
+            .map(|(name, dep)| Dependency { name, dir: dir.join(dep.path) })
+            .collect();
+        let core = raw.package.core.map(|core| dir.join(core));
+
+        Ok(Self {
+            path,
+            group: raw.package.group,
+            name: raw.package.name,
+            core,
+            artifacts,
+            dependencies,
+        })
+    }
+
+    /// Find the manifest of the package containing `path`, by looking in its directory and all of its parents
+    pub fn find(path: &Path) -> Option<PathBuf> {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        path.ancestors()
+            .map(|dir| dir.join(MANIFEST_FILE_NAME))
+            .find(|manifest| manifest.is_file())
+    }
+
+    pub fn dir(&self) -> &Path {
+        self.path.parent().unwrap_or(Path::new("."))
+    }
+
+    pub fn lib_path(&self, artifact: &Artifact) -> LibraryPath {
+        LibraryPath {
+            group: self.group.clone(),
+            package: self.name.clone(),
+            library: artifact.name.clone(),
+        }
+    }
+
+    /// Get the artifact with `root` as the root file of its main module
+    pub fn artifact_for_root(&self, root: &Path) -> Option<&Artifact> {
+        let root = fs::canonicalize(root).ok()?;
+        self.artifacts.iter().find(|artifact| fs::canonicalize(&artifact.root).is_ok_and(|path| path == root))
+    }
+
+    fn check_name(name: &str, what: &str) -> Result<(), String> {
+        let valid = name.starts_with(|ch: char| ch.is_alphabetic() || ch == '_') &&
+            name.chars().all(|ch| ch.is_alphanumeric() || ch == '_');
+        if valid {
+            Ok(())
+        } else {
+            Err(format!("Invalid {what} name `{name}`, names may only contain letters, digits and underscores, and can't start with a digit"))
+        }
+    }
+}
+
+/// Load the manifest at `path` and those of all its dependencies, ordered so that each package comes after its dependencies
+pub fn resolve_packages(path: &Path) -> Result<Vec<Manifest>, Vec<Diagnostic>> {
+    let mut packages = Vec::new();
+    let mut stack = Vec::new();
+    let mut errors = Vec::new();
+    resolve_package(path, None, &mut stack, &mut packages, &mut errors);
+    if errors.is_empty() {
+        Ok(packages)
+    } else {
+        Err(errors)
+    }
+}
+
+fn resolve_package(path: &Path, dep_name: Option<&str>, stack: &mut Vec<PathBuf>, packages: &mut Vec<Manifest>, errors: &mut Vec<Diagnostic>) {
+    let manifest = match Manifest::load(path) {
+        Ok(manifest) => manifest,
+        Err(err) => {
+            errors.push(err);
+            return;
+        },
+    };
+    let manifest_path = fs::canonicalize(&manifest.path).unwrap_or_else(|_| manifest.path.clone());
+
+    if let Some(dep_name) = dep_name {
+        if dep_name != manifest.name {
+            errors.push(Diagnostic::new(Severity::Error, format!("{}: Dependency `{dep_name}` refers to package `{}`", manifest.path.display(), manifest.name)));
+            return;
+        }
+    }
+    if let Some(pos) = stack.iter().position(|path| *path == manifest_path) {
+        let cycle: Vec<_> = stack[pos..].iter().chain([&manifest_path]).map(|path| path.display().to_string()).collect();
+        errors.push(Diagnostic::new(Severity::Error, format!("Cyclic package dependency: {}", cycle.join(" -> "))));
+        return;
+    }
+    if packages.iter().any(|package: &Manifest| fs::canonicalize(&package.path).is_ok_and(|path| path == manifest_path)) {
+        return;
+    }
+
+    stack.push(manifest_path);
+    for dep in &manifest.dependencies {
+        resolve_package(&dep.dir, Some(&dep.name), stack, packages, errors);
+    }
+    stack.pop();
+
+    if packages.iter().any(|package| package.name == manifest.name && package.group == manifest.group) {
+        errors.push(Diagnostic::new(Severity::Error, format!("{}: Package `{}` is also declared by another manifest", manifest.path.display(), manifest.name)));
+        return;
+    }
+    packages.push(manifest);
+}
@@ -0,0 +1,894 @@
+// Library metadata, containing everything needed to use a library from other code.
+//
+// The metadata contains the public symbols of a library, the types they refer to, the traits in the trait DAG, and all precedences and operator sets with their operators.
+// Symbols are referred to by their path, and types by their index in the metadata, as the tables they live in are filled in a different order by every library that imports them.
+// The precedence DAG and operator table are not stored, they are rebuilt by the importing library from the imported precedence and operator set symbols, and its own uses.
+//
+// The libraries a library depends on need to be imported before it, so paths that point into them can be resolved.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io,
+    path::Path,
+    process,
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::{
+        Diagnostic, LibraryPath, OpType, PathGeneric, PathIden, PrecedenceAssocKind, PrecedenceOrderKind, RootSymbolTable, Scope, Severity, StaticKind,
+        StructKind, Symbol, SymbolPath, SymbolRef, TraitDag, TraitItemRecord, Visibility, WeakSymbolRef, BUILD_ID,
+    },
+    lexer::{Punctuation, PuncutationTable},
+    type_system::{ArrayType, ImplTraitType, PathType, PointerType, PrimitiveType, ReferenceType, SliceType, StringSliceType, TraitObjectType, TupleType, Type, TypeHandle, TypeRegistry},
+};
+
+pub const METADATA_EXTENSION: &str = "xnlib";
+
+const MAGIC: [u8; 4] = *b"XNLB";
+/// Version of the layout of the metadata, needs to be bumped when the layout of any stored data changes
+const FORMAT_VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize)]
+struct Header {
+    magic:    [u8; 4],
+    version:  u32,
+    compiler: String,
+}
+
+impl Header {
+    fn new() -> Self {
+        Self {
+            magic: MAGIC,
+            version: FORMAT_VERSION,
+            compiler: BUILD_ID.to_string(),
+        }
+    }
+}
+
+// =============================================================
+
+#[derive(Serialize, Deserialize)]
+struct IdenMeta {
+    name:     String,
+    params:   Vec<String>,
+    /// Index of the type of each generic argument, value generics are not stored
+    gen_args: Vec<Option<u32>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PathMeta {
+    lib:   LibraryPath,
+    scope: Vec<IdenMeta>,
+    iden:  IdenMeta,
+}
+
+#[derive(Serialize, Deserialize)]
+enum TypeMeta {
+    Primitive(PrimitiveType),
+    StringSlice(StringSliceType),
+    Unit,
+    Never,
+    Path {
+        path:     PathMeta,
+        /// Whether the path was resolved to a symbol
+        resolved: bool,
+    },
+    Tuple(Vec<u32>),
+    Array {
+        ty:   u32,
+        size: Option<usize>,
+    },
+    Slice(u32),
+    Pointer {
+        ty:       u32,
+        is_multi: bool,
+    },
+    Reference {
+        ty:     u32,
+        is_mut: bool,
+    },
+    TraitObject(Vec<PathMeta>),
+    ImplTrait(Vec<PathMeta>),
+    Placeholder,
+}
+
+#[derive(Serialize, Deserialize)]
+enum SymbolKindMeta {
+    Module {
+        file_path: String,
+    },
+    Function,
+    TypeAlias,
+    DistinctType,
+    OpaqueType,
+    Struct(StructKind),
+    Union,
+    AdtEnum,
+    FlagEnum,
+    Bitfield,
+    Const,
+    Static(StaticKind),
+    Property,
+    Trait {
+        items: Vec<TraitItemRecord>,
+    },
+    Impl,
+    TypeGeneric {
+        in_pack: bool,
+    },
+    ValueGeneric {
+        in_pack: bool,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+struct SymbolMeta {
+    path: PathMeta,
+    vis:  Option<Visibility>,
+    ty:   Option<u32>,
+    kind: SymbolKindMeta,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TraitMeta {
+    path:  PathMeta,
+    bases: Vec<PathMeta>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PrecedenceMeta {
+    name:        String,
+    order_kind:  PrecedenceOrderKind,
+    assoc:       PrecedenceAssocKind,
+    lower_than:  Option<(LibraryPath, String)>,
+    higher_than: Option<(LibraryPath, String)>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct OperatorMeta {
+    name:         String,
+    op_ty:        OpType,
+    op:           String,
+    assoc_method: Option<PathMeta>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct OpSetMeta {
+    name:              String,
+    assoc_trait:       Option<PathMeta>,
+    precedence:        Option<(LibraryPath, String)>,
+    bases:             Vec<(LibraryPath, String)>,
+    has_generics:      bool,
+    has_output_alias:  bool,
+    parent_has_output: bool,
+    operators:         Vec<OperatorMeta>,
+}
+
+/// Metadata of a library
+#[derive(Serialize, Deserialize)]
+pub struct LibraryMetadata {
+    pub lib:     LibraryPath,
+    /// Types, ordered so that a type only refers to types before it
+    types:       Vec<TypeMeta>,
+    /// Symbols, ordered so that a symbol comes after the symbol it is nested in
+    symbols:     Vec<SymbolMeta>,
+    /// Types with the paths of the impls on them
+    impls:       Vec<(u32, PathMeta)>,
+    traits:      Vec<TraitMeta>,
+    precedences: Vec<PrecedenceMeta>,
+    op_sets:     Vec<OpSetMeta>,
+}
+
+impl LibraryMetadata {
+    /// Collect the metadata of `lib`, this needs to be done after all HIR passes have run.
+    /// Only the types that exported symbols refer to are stored
+    pub fn export(lib: &LibraryPath, syms: &RootSymbolTable, trait_dag: &TraitDag, puncts: &PuncutationTable) -> Self {
+        let mut exporter = TypeExporter::new();
+
+        // Modules are always exported, so the paths of public items in private modules can still be resolved
+        let exported_syms = syms.get_symbols_for_lib(lib, |sym| match sym.vis() {
+            Some(vis) => matches!(vis, Visibility::Public | Visibility::Package { .. }),
+            None      => true,
+        });
+
+        let mut symbols = Vec::with_capacity(exported_syms.len());
+        for sym in &exported_syms {
+            let sym = sym.read();
+            let kind = match &*sym {
+                Symbol::Module(sym)       => SymbolKindMeta::Module { file_path: sym.file_path.to_string_lossy().to_string() },
+                Symbol::Function(_)       => SymbolKindMeta::Function,
+                Symbol::TypeAlias(_)      => SymbolKindMeta::TypeAlias,
+                Symbol::DistinctType(_)   => SymbolKindMeta::DistinctType,
+                Symbol::OpaqueType(_)     => SymbolKindMeta::OpaqueType,
+                Symbol::Struct(sym)       => SymbolKindMeta::Struct(sym.kind),
+                Symbol::Union(_)          => SymbolKindMeta::Union,
+                Symbol::AdtEnum(_)        => SymbolKindMeta::AdtEnum,
+                Symbol::FlagEnum(_)       => SymbolKindMeta::FlagEnum,
+                Symbol::Bitfield(_)       => SymbolKindMeta::Bitfield,
+                Symbol::Const(_)          => SymbolKindMeta::Const,
+                Symbol::Static(sym)       => SymbolKindMeta::Static(sym.kind),
+                Symbol::Property(_)       => SymbolKindMeta::Property,
+                Symbol::Trait(sym)        => SymbolKindMeta::Trait { items: sym.items.clone() },
+                Symbol::Impl(_)           => SymbolKindMeta::Impl,
+                Symbol::TypeGeneric(sym)  => SymbolKindMeta::TypeGeneric { in_pack: sym.in_pack },
+                Symbol::ValueGeneric(sym) => SymbolKindMeta::ValueGeneric { in_pack: sym.in_pack },
+                // Precedences and operators live outside of the symbol tree
+                Symbol::Precedence(_) |
+                Symbol::OpSet(_)      |
+                Symbol::Operator(_)       => continue,
+            };
+
+            symbols.push(SymbolMeta {
+                path: exporter.export_path(sym.path(), false),
+                vis: sym.vis().cloned(),
+                ty: sym.get_type().and_then(|ty| exporter.export_type(ty)),
+                kind,
+            });
+        }
+
+        let mut impls = Vec::new();
+        for (ty, impl_syms) in syms.type_impls() {
+            for sym in impl_syms {
+                if !exported_syms.iter().any(|exported| Arc::ptr_eq(exported, sym)) {
+                    continue;
+                }
+                if let Some(ty) = exporter.export_type(ty) {
+                    impls.push((ty, exporter.export_path(sym.read().path(), false)));
+                }
+            }
+        }
+        // The impl table is a hash map, so sort the impls to keep the output stable
+        impls.sort_by_cached_key(|(ty, path)| (*ty, path.iden.name.clone(), path.scope.iter().map(|iden| iden.name.clone()).collect::<Vec<_>>()));
+
+        let mut traits = Vec::new();
+        for node in trait_dag.iter() {
+            let sym = node.symbol.read();
+            let Symbol::Trait(trait_sym) = &*sym else { unreachable!() };
+            if trait_sym.path.lib() != lib || !exported_syms.iter().any(|exported| Arc::ptr_eq(exported, &node.symbol)) {
+                continue;
+            }
+
+            let bases = trait_dag.get_base_ids(trait_sym.dag_idx).iter()
+                .filter_map(|idx| trait_dag.get(*idx))
+                .map(|base| exporter.export_path(base.symbol.read().path(), false))
+                .collect();
+            traits.push(TraitMeta {
+                path: exporter.export_path(&trait_sym.path, false),
+                bases,
+            });
+        }
+
+        let mut precedences = Vec::new();
+        if let Some(lib_precedences) = syms.get_precedences_for_lib(lib) {
+            for sym in lib_precedences.values() {
+                let sym = sym.read();
+                let Symbol::Precedence(sym) = &*sym else { unreachable!() };
+                precedences.push(PrecedenceMeta {
+                    name: sym.path.iden().name.clone(),
+                    order_kind: sym.order_kind,
+                    assoc: sym.assoc,
+                    lower_than: sym.lower_than.as_ref().and_then(symbol_ref),
+                    higher_than: sym.higher_than.as_ref().and_then(symbol_ref),
+                });
+            }
+        }
+        precedences.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut op_sets = Vec::new();
+        if let Some(lib_op_sets) = syms.get_op_set_and_ops_for_lib(lib) {
+            for (op_set, ops) in lib_op_sets.values() {
+                let op_set = op_set.read();
+                let Symbol::OpSet(op_set) = &*op_set else { unreachable!() };
+
+                let mut operators = Vec::with_capacity(ops.len());
+                for op in ops.values() {
+                    let op = op.read();
+                    let Symbol::Operator(op) = &*op else { unreachable!() };
+                    operators.push(OperatorMeta {
+                        name: op.path.iden().name.clone(),
+                        op_ty: op.op_ty,
+                        op: op.op.as_str(puncts).to_string(),
+                        assoc_method: op.assoc_method.as_ref()
+                            .and_then(|method| method.upgrade())
+                            .map(|method| exporter.export_path(method.read().path(), false)),
+                    });
+                }
+                operators.sort_by(|a, b| a.name.cmp(&b.name));
+
+                op_sets.push(OpSetMeta {
+                    name: op_set.path.iden().name.clone(),
+                    assoc_trait: op_set.assoc_trait.as_ref()
+                        .and_then(|assoc_trait| assoc_trait.upgrade())
+                        .map(|assoc_trait| exporter.export_path(assoc_trait.read().path(), false)),
+                    precedence: op_set.precedence.as_ref().and_then(symbol_ref),
+                    bases: op_set.bases.iter().filter_map(symbol_ref).collect(),
+                    has_generics: op_set.has_generics,
+                    has_output_alias: op_set.has_output_alias,
+                    parent_has_output: op_set.parent_has_output,
+                    operators,
+                });
+            }
+        }
+        op_sets.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Self {
+            lib: lib.clone(),
+            types: exporter.types,
+            symbols,
+            impls,
+            traits,
+            precedences,
+            op_sets,
+        }
+    }
+
+    /// Add the symbols, types, traits, precedences and operator sets in the metadata to the tables of the current library.
+    /// The metadata is validated first, so nothing is added when it can't be imported
+    pub fn import(&self, syms: &mut RootSymbolTable, type_reg: &mut TypeRegistry, trait_dag: &mut TraitDag, puncts: &PuncutationTable) -> Result<(), Diagnostic> {
+        self.validate(syms)?;
+        let lib = &self.lib;
+
+        let mut imported_syms = Vec::with_capacity(self.symbols.len());
+        for sym_meta in &self.symbols {
+            let scope = import_scope(&sym_meta.path.scope);
+            let iden = PathIden::new(sym_meta.path.iden.name.clone(), sym_meta.path.iden.params.clone(), Vec::new());
+            let name = &iden.name;
+            let sym = match &sym_meta.kind {
+                SymbolKindMeta::Module { file_path }     => syms.add_module(Some(lib), &scope, name, file_path.into()),
+                SymbolKindMeta::Function                 => syms.add_function(Some(lib), &scope, iden),
+                SymbolKindMeta::TypeAlias                => syms.add_type_alias(Some(lib), &scope, iden),
+                SymbolKindMeta::DistinctType             => syms.add_distinct_type(Some(lib), &scope, iden),
+                SymbolKindMeta::OpaqueType               => syms.add_opaque_type(Some(lib), &scope, iden),
+                SymbolKindMeta::Struct(kind)             => syms.add_struct(Some(lib), &scope, iden, *kind),
+                SymbolKindMeta::Union                    => syms.add_union(Some(lib), &scope, iden),
+                SymbolKindMeta::AdtEnum                  => syms.add_adt_enum(Some(lib), &scope, iden),
+                SymbolKindMeta::FlagEnum                 => syms.add_flag_enum(Some(lib), &scope, iden),
+                SymbolKindMeta::Bitfield                 => syms.add_bitfield(Some(lib), &scope, iden),
+                SymbolKindMeta::Const                    => syms.add_const(Some(lib), &scope, iden),
+                SymbolKindMeta::Static(kind)             => syms.add_static(Some(lib), &scope, name, *kind),
+                SymbolKindMeta::Property                 => syms.add_property(Some(lib), &scope, iden),
+                SymbolKindMeta::Trait { items }          => {
+                    let sym = syms.add_trait(Some(lib), &scope, iden);
+                    if let Symbol::Trait(trait_sym) = &mut *sym.write() {
+                        trait_sym.items = items.clone();
+                    }
+                    sym
+                },
+                SymbolKindMeta::Impl                     => syms.add_impl(Some(lib), &scope, iden),
+                SymbolKindMeta::TypeGeneric { in_pack }  => syms.add_type_generic(Some(lib), &scope, name, *in_pack),
+                SymbolKindMeta::ValueGeneric { in_pack } => syms.add_value_generic(Some(lib), &scope, name, *in_pack),
+            };
+            if let Some(vis) = &sym_meta.vis {
+                sym.write().set_vis(vis.clone());
+            }
+            imported_syms.push(sym);
+        }
+
+        // Types are imported after the symbols, as path types can refer to them
+        let mut types: Vec<Option<TypeHandle>> = Vec::with_capacity(self.types.len());
+        for ty_meta in &self.types {
+            let get_ty = |idx: &u32| types.get(*idx as usize).cloned().flatten();
+            let ty = match ty_meta {
+                TypeMeta::Primitive(ty)                  => Some(type_reg.create_primitive_type(*ty)),
+                TypeMeta::StringSlice(ty)                => Some(type_reg.create_str_slice_type(*ty)),
+                TypeMeta::Unit                           => Some(type_reg.create_unit_type()),
+                TypeMeta::Never                          => Some(type_reg.create_never_type()),
+                TypeMeta::Path { path, resolved }        => {
+                    // A resolved path is the path of its symbol, so it can be recreated from the symbol
+                    let path = import_path(path, &types);
+                    match syms.get_symbol_from_path(&path) {
+                        Some(sym) if *resolved => {
+                            // Symbols are imported without generic arguments, which are restored from the path, as it is the full path of the symbol
+                            *sym.write().path_mut() = path;
+                            Some(type_reg.create_sym_path_type(sym))
+                        },
+                        _                      => Some(type_reg.create_path_type(path)),
+                    }
+                },
+                TypeMeta::Tuple(elems)                   => elems.iter().map(get_ty).collect::<Option<Vec<_>>>().map(|elems| type_reg.create_tuple_type(&elems)),
+                TypeMeta::Array { ty: elem, size }       => get_ty(elem).map(|elem| type_reg.create_array_type(elem, *size)),
+                TypeMeta::Slice(elem)                    => get_ty(elem).map(|elem| type_reg.create_slice_type(elem)),
+                TypeMeta::Pointer { ty: elem, is_multi } => get_ty(elem).map(|elem| type_reg.create_pointer_type(elem, *is_multi)),
+                TypeMeta::Reference { ty: elem, is_mut } => get_ty(elem).map(|elem| type_reg.create_reference_type(elem, *is_mut)),
+                TypeMeta::TraitObject(traits)            => Some(type_reg.create_trait_object_type(traits.iter().map(|path| import_path(path, &types)).collect())),
+                TypeMeta::ImplTrait(traits)              => Some(type_reg.create_impl_trait_type(traits.iter().map(|path| import_path(path, &types)).collect())),
+                TypeMeta::Placeholder                    => Some(type_reg.create_placeholder_type()),
+            };
+            types.push(ty);
+        }
+
+        for (sym_meta, sym) in self.symbols.iter().zip(&imported_syms) {
+            if let Some(ty) = sym_meta.ty.and_then(|idx| types.get(idx as usize).cloned().flatten()) {
+                sym.write().set_type(ty);
+            }
+        }
+        for (ty, path) in &self.impls {
+            let ty = types.get(*ty as usize).cloned().flatten();
+            let sym = syms.get_symbol_from_path(&import_path(path, &types));
+            if let (Some(ty), Some(sym)) = (ty, sym) {
+                syms.associate_impl_with_ty(ty, sym);
+            }
+        }
+
+        // Traits are added to the DAG first, so bases within the library can be linked regardless of their order
+        let mut trait_syms = Vec::with_capacity(self.traits.len());
+        for trait_meta in &self.traits {
+            let sym = get_symbol(syms, &trait_meta.path, &types)?;
+            let dag_idx = trait_dag.add(sym.clone());
+            let Symbol::Trait(trait_sym) = &mut *sym.write() else {
+                return Err(Diagnostic::new(Severity::Error, format!("Library metadata of `{lib}` is invalid: `{}` is not a trait", import_path(&trait_meta.path, &types))));
+            };
+            trait_sym.dag_idx = dag_idx;
+            trait_syms.push(dag_idx);
+        }
+        for (trait_meta, dag_idx) in self.traits.iter().zip(trait_syms) {
+            for base in &trait_meta.bases {
+                let base = get_symbol(syms, base, &types)?;
+                let Symbol::Trait(base) = &*base.read() else { continue };
+                if base.dag_idx != u32::MAX {
+                    trait_dag.set_base_dependency(dag_idx, base.dag_idx);
+                }
+            }
+        }
+
+        for prec_meta in &self.precedences {
+            syms.add_precedence(Some(lib), prec_meta.name.clone(), prec_meta.order_kind, prec_meta.assoc);
+        }
+        for prec_meta in &self.precedences {
+            let lower_than = prec_meta.lower_than.as_ref().map(|(lib, name)| get_precedence(syms, lib, name)).transpose()?;
+            let higher_than = prec_meta.higher_than.as_ref().map(|(lib, name)| get_precedence(syms, lib, name)).transpose()?;
+
+            let sym = syms.get_direct_precedence(lib, &prec_meta.name).unwrap();
+            let Symbol::Precedence(sym) = &mut *sym.write() else { unreachable!() };
+            sym.lower_than = lower_than.as_ref().map(Arc::downgrade);
+            sym.higher_than = higher_than.as_ref().map(Arc::downgrade);
+        }
+
+        for op_set_meta in &self.op_sets {
+            let op_set = syms.add_op_set(Some(lib), op_set_meta.name.clone());
+            for op_meta in &op_set_meta.operators {
+                let op = Punctuation::from_str(&op_meta.op, puncts);
+                let op_sym = syms.add_operator(Some(lib), &op_set_meta.name, op_meta.name.clone(), op_meta.op_ty, op);
+                if let Some(method) = &op_meta.assoc_method {
+                    let method = get_symbol(syms, method, &types)?;
+                    let Symbol::Operator(op_sym) = &mut *op_sym.write() else { unreachable!() };
+                    op_sym.assoc_method = Some(Arc::downgrade(&method));
+                }
+            }
+
+            let Symbol::OpSet(op_set) = &mut *op_set.write() else { unreachable!() };
+            op_set.has_generics = op_set_meta.has_generics;
+            op_set.has_output_alias = op_set_meta.has_output_alias;
+            op_set.parent_has_output = op_set_meta.parent_has_output;
+            if let Some(assoc_trait) = &op_set_meta.assoc_trait {
+                op_set.assoc_trait = Some(Arc::downgrade(&get_symbol(syms, assoc_trait, &types)?));
+            }
+            if let Some((lib, name)) = &op_set_meta.precedence {
+                op_set.precedence = Some(Arc::downgrade(&get_precedence(syms, lib, name)?));
+            }
+        }
+        // Bases can be declared after the sets that extend them, so they are linked once all sets are added
+        for op_set_meta in &self.op_sets {
+            let mut bases = Vec::with_capacity(op_set_meta.bases.len());
+            for (base_lib, name) in &op_set_meta.bases {
+                let Some(base) = syms.get_direct_op_set(base_lib, name) else {
+                    return Err(Diagnostic::new(Severity::Error, format!("Library `{lib}` requires operator set `{base_lib}.{name}`, which is not loaded")));
+                };
+                bases.push(Arc::downgrade(&base));
+            }
+
+            let op_set = syms.get_direct_op_set(lib, &op_set_meta.name).unwrap();
+            let Symbol::OpSet(op_set) = &mut *op_set.write() else { unreachable!() };
+            op_set.bases = bases;
+        }
+
+        Ok(())
+    }
+
+    /// Check that the metadata can be imported: the library is not loaded yet, all indices are in range,
+    /// and everything it refers to is either part of the metadata or of an already loaded library
+    fn validate(&self, syms: &RootSymbolTable) -> Result<(), Diagnostic> {
+        let lib = &self.lib;
+        if syms.has_lib(lib) || syms.has_precedence_for_lib(lib) || syms.has_op_set_for_lib(lib) {
+            return Err(Diagnostic::new(Severity::Error, format!("Library `{lib}` is already loaded")));
+        }
+        let invalid = |msg: String| Diagnostic::new(Severity::Error, format!("Library metadata of `{lib}` is invalid: {msg}"));
+
+        let mut own_syms = HashMap::with_capacity(self.symbols.len());
+        for sym_meta in &self.symbols {
+            let path = &sym_meta.path;
+            if path.lib != *lib {
+                return Err(invalid(format!("`{}` is not part of the library", import_path(path, &[]))));
+            }
+            if !path.scope.is_empty() && !own_syms.contains_key(&lookup_key(&path.scope)) {
+                return Err(invalid(format!("`{}` comes before the symbol it is nested in", import_path(path, &[]))));
+            }
+            if sym_meta.ty.is_some_and(|idx| idx as usize >= self.types.len()) {
+                return Err(invalid(format!("the type of `{}` is out of range", import_path(path, &[]))));
+            }
+            let mut key = lookup_key(&path.scope);
+            key.push((&path.iden.name, &path.iden.params));
+            own_syms.insert(key, &sym_meta.kind);
+        }
+
+        // Check that a path refers to a symbol of the expected kind, in the metadata or in a loaded library
+        let check_path = |path: &PathMeta, kind: &str, is_kind: fn(&SymbolKindMeta) -> bool, is_sym: fn(&Symbol) -> bool| {
+            let mut key = lookup_key(&path.scope);
+            key.push((&path.iden.name, &path.iden.params));
+            let found = match path.lib == *lib {
+                true  => own_syms.get(&key).map(|sym_kind| is_kind(sym_kind)),
+                false => syms.get_symbol_from_path(&import_path(path, &[])).map(|sym| is_sym(&sym.read())),
+            };
+            match found {
+                Some(true)  => Ok(()),
+                Some(false) => Err(invalid(format!("`{}` is not {kind}", import_path(path, &[])))),
+                None        => Err(Diagnostic::new(Severity::Error, format!("Library metadata refers to `{}`, which is not loaded", import_path(path, &[])))),
+            }
+        };
+        let check_precedence = |(prec_lib, name): &(LibraryPath, String)| match prec_lib == lib {
+            true  => self.precedences.iter().any(|prec| prec.name == *name),
+            false => syms.get_direct_precedence(prec_lib, name).is_some(),
+        }.then_some(()).ok_or_else(|| Diagnostic::new(Severity::Error, format!("Library metadata refers to precedence `{prec_lib}.{name}`, which is not loaded")));
+
+        let check_gen_args = |path: &PathMeta, max: usize| path.scope.iter().chain([&path.iden])
+            .flat_map(|iden| &iden.gen_args)
+            .all(|idx| idx.is_none_or(|idx| (idx as usize) < max));
+        for (idx, ty_meta) in self.types.iter().enumerate() {
+            // Types only refer to types before them
+            let valid = match ty_meta {
+                TypeMeta::Path { path, .. }      => check_gen_args(path, idx),
+                TypeMeta::Tuple(elems)           => elems.iter().all(|elem| (*elem as usize) < idx),
+                TypeMeta::Array { ty, .. }       |
+                TypeMeta::Slice(ty)              |
+                TypeMeta::Pointer { ty, .. }     |
+                TypeMeta::Reference { ty, .. }   => (*ty as usize) < idx,
+                TypeMeta::TraitObject(paths)     |
+                TypeMeta::ImplTrait(paths)       => paths.iter().all(|path| check_gen_args(path, idx)),
+                TypeMeta::Primitive(_)           |
+                TypeMeta::StringSlice(_)         |
+                TypeMeta::Unit                   |
+                TypeMeta::Never                  |
+                TypeMeta::Placeholder            => true,
+            };
+            if !valid {
+                return Err(invalid(format!("type {idx} refers to a type that comes after it")));
+            }
+        }
+
+        for (ty, path) in &self.impls {
+            if *ty as usize >= self.types.len() {
+                return Err(invalid(format!("the type of `{}` is out of range", import_path(path, &[]))));
+            }
+            check_path(path, "an impl", |kind| matches!(kind, SymbolKindMeta::Impl), |sym| matches!(sym, Symbol::Impl(_)))?;
+        }
+
+        for trait_meta in &self.traits {
+            if trait_meta.path.lib != *lib {
+                return Err(invalid(format!("`{}` is not part of the library", import_path(&trait_meta.path, &[]))));
+            }
+            for path in [&trait_meta.path].into_iter().chain(&trait_meta.bases) {
+                check_path(path, "a trait", |kind| matches!(kind, SymbolKindMeta::Trait { .. }), |sym| matches!(sym, Symbol::Trait(_)))?;
+            }
+        }
+
+        for (idx, prec_meta) in self.precedences.iter().enumerate() {
+            if self.precedences[..idx].iter().any(|prec| prec.name == prec_meta.name) {
+                return Err(invalid(format!("precedence `{}` is defined more than once", prec_meta.name)));
+            }
+            prec_meta.lower_than.as_ref().map(check_precedence).transpose()?;
+            prec_meta.higher_than.as_ref().map(check_precedence).transpose()?;
+        }
+
+        for (idx, op_set_meta) in self.op_sets.iter().enumerate() {
+            if self.op_sets[..idx].iter().any(|op_set| op_set.name == op_set_meta.name) {
+                return Err(invalid(format!("operator set `{}` is defined more than once", op_set_meta.name)));
+            }
+            if let Some(assoc_trait) = &op_set_meta.assoc_trait {
+                check_path(assoc_trait, "a trait", |kind| matches!(kind, SymbolKindMeta::Trait { .. }), |sym| matches!(sym, Symbol::Trait(_)))?;
+            }
+            for op_meta in &op_set_meta.operators {
+                if let Some(method) = &op_meta.assoc_method {
+                    check_path(method, "a function", |kind| matches!(kind, SymbolKindMeta::Function), |sym| matches!(sym, Symbol::Function(_)))?;
+                }
+            }
+            op_set_meta.precedence.as_ref().map(check_precedence).transpose()?;
+            for (base_lib, name) in &op_set_meta.bases {
+                let found = match base_lib == lib {
+                    true  => self.op_sets.iter().any(|op_set| op_set.name == *name),
+                    false => syms.get_direct_op_set(base_lib, name).is_some(),
+                };
+                if !found {
+                    return Err(Diagnostic::new(Severity::Error, format!("Library `{lib}` requires operator set `{base_lib}.{name}`, which is not loaded")));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check whether the metadata at `path` was written by this build of the compiler, without loading all of it
+    pub fn is_from_current_build(path: &Path) -> bool {
+        let Ok(file) = fs::File::open(path) else { return false };
+        match bincode::deserialize_from::<_, Header>(io::BufReader::new(file)) {
+            Ok(header) => header.magic == MAGIC && header.version == FORMAT_VERSION && header.compiler == BUILD_ID,
+            Err(_)     => false,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Diagnostic> {
+        let error = |msg: String| Diagnostic::new(Severity::Error, format!("{}: {msg}", path.display()));
+
+        let bytes = fs::read(path).map_err(|err| error(format!("Failed to read library metadata: {err}")))?;
+        let mut reader = bytes.as_slice();
+        let header: Header = bincode::deserialize_from(&mut reader).map_err(|_| error("Not a library metadata file".to_string()))?;
+        if header.magic != MAGIC {
+            return Err(error("Not a library metadata file".to_string()));
+        }
+        if header.version != FORMAT_VERSION || header.compiler != BUILD_ID {
+            return Err(error(format!("Library metadata was created by a different compiler build ({}, this is {BUILD_ID}), the library needs to be rebuilt", header.compiler)));
+        }
+        bincode::deserialize_from(&mut reader).map_err(|err| error(format!("Invalid library metadata: {err}")))
+    }
+
+    pub fn store(&self, path: &Path) -> Result<(), Diagnostic> {
+        let error = |msg: String| Diagnostic::new(Severity::Error, format!("{}: {msg}", path.display()));
+
+        let mut bytes = bincode::serialize(&Header::new()).map_err(|err| error(format!("Failed to serialize library metadata: {err}")))?;
+        bincode::serialize_into(&mut bytes, self).map_err(|err| error(format!("Failed to serialize library metadata: {err}")))?;
+
+        // Write to a temporary file first, so a library that is being built never sees partially written metadata
+        let write = || {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let tmp_path = path.with_extension(format!("tmp{}", process::id()));
+            fs::write(&tmp_path, &bytes)?;
+            fs::rename(&tmp_path, path)
+        };
+        write().map_err(|err| error(format!("Failed to write library metadata: {err}")))
+    }
+}
+
+// =============================================================
+
+/// Collects the types referred to by the metadata, each type is stored once, after the types it refers to
+struct TypeExporter {
+    types:   Vec<TypeMeta>,
+    indices: HashMap<*const Type, Option<u32>>,
+}
+
+impl TypeExporter {
+    fn new() -> Self {
+        Self {
+            types: Vec::new(),
+            indices: HashMap::new(),
+        }
+    }
+
+    /// Export a type, `None` is returned for types that can't be stored, i.e. types that only exist within a function
+    fn export_type(&mut self, ty: &TypeHandle) -> Option<u32> {
+        let ty = ty.get();
+        if let Some(idx) = self.indices.get(&Arc::as_ptr(&ty)) {
+            return *idx;
+        }
+
+        let meta = match &*ty {
+            Type::Primitive(ty)                            => Some(TypeMeta::Primitive(*ty)),
+            Type::StringSlice(ty)                          => Some(TypeMeta::StringSlice(*ty)),
+            Type::Unit(_)                                  => Some(TypeMeta::Unit),
+            Type::Never(_)                                 => Some(TypeMeta::Never),
+            Type::Path(PathType { path, sym })             => {
+                let path = match sym {
+                    Some(sym) => self.export_path(sym.read().path(), true),
+                    None      => self.export_path(path, true),
+                };
+                Some(TypeMeta::Path { path, resolved: sym.is_some() })
+            },
+            Type::Tuple(TupleType { types })               => types.iter()
+                .map(|ty| self.export_type(ty))
+                .collect::<Option<Vec<_>>>()
+                .map(TypeMeta::Tuple),
+            Type::Array(ArrayType { ty, size })            => self.export_type(ty).map(|ty| TypeMeta::Array { ty, size: *size }),
+            Type::Slice(SliceType { ty })                  => self.export_type(ty).map(TypeMeta::Slice),
+            Type::Pointer(PointerType { ty, is_multi })    => self.export_type(ty).map(|ty| TypeMeta::Pointer { ty, is_multi: *is_multi }),
+            Type::Reference(ReferenceType { ty, is_mut })  => self.export_type(ty).map(|ty| TypeMeta::Reference { ty, is_mut: *is_mut }),
+            Type::TraitObject(TraitObjectType { traits })  => Some(TypeMeta::TraitObject(traits.iter().map(|path| self.export_path(path, true)).collect())),
+            Type::ImplTrait(ImplTraitType { traits })      => Some(TypeMeta::ImplTrait(traits.iter().map(|path| self.export_path(path, true)).collect())),
+            Type::Placeholder                              => Some(TypeMeta::Placeholder),
+            _                                              => None,
+        };
+
+        let idx = meta.map(|meta| {
+            self.types.push(meta);
+            self.types.len() as u32 - 1
+        });
+        self.indices.insert(Arc::as_ptr(&ty), idx);
+        idx
+    }
+
+    /// Export a path, generic arguments are only stored when `with_gen_args` is set, as symbols are identified by only their name and parameters
+    fn export_path(&mut self, path: &SymbolPath, with_gen_args: bool) -> PathMeta {
+        let mut export_iden = |iden: &PathIden| IdenMeta {
+            name: iden.name.clone(),
+            params: iden.params.clone(),
+            gen_args: match with_gen_args {
+                true  => iden.gen_args.iter().map(|arg| match arg {
+                    PathGeneric::Type { ty } => self.export_type(ty),
+                    PathGeneric::Value {  }  => None,
+                }).collect(),
+                false => Vec::new(),
+            },
+        };
+
+        PathMeta {
+            lib: path.lib().clone(),
+            scope: path.scope().idens().iter().map(&mut export_iden).collect(),
+            iden: export_iden(path.iden()),
+        }
+    }
+}
+
+fn symbol_ref(sym: &WeakSymbolRef) -> Option<(LibraryPath, String)> {
+    let sym = sym.upgrade()?;
+    let sym = sym.read();
+    let path = sym.path();
+    Some((path.lib().clone(), path.iden().name.clone()))
+}
+
+/// Key of a path in the symbol table, symbols are identified by the names and parameters of the path
+fn lookup_key(idens: &[IdenMeta]) -> Vec<(&str, &[String])> {
+    idens.iter().map(|iden| (iden.name.as_str(), iden.params.as_slice())).collect()
+}
+
+fn import_iden(iden: &IdenMeta, types: &[Option<TypeHandle>]) -> PathIden {
+    let gen_args = iden.gen_args.iter()
+        .map(|arg| match arg.and_then(|idx| types.get(idx as usize).cloned().flatten()) {
+            Some(ty) => PathGeneric::Type { ty },
+            None     => PathGeneric::Value {},
+        })
+        .collect();
+    PathIden::new(iden.name.clone(), iden.params.clone(), gen_args)
+}
+
+fn import_scope(idens: &[IdenMeta]) -> Scope {
+    let mut scope = Scope::new();
+    for iden in idens {
+        scope.push_with_params(iden.name.clone(), iden.params.clone());
+    }
+    scope
+}
+
+fn import_path(path: &PathMeta, types: &[Option<TypeHandle>]) -> SymbolPath {
+    let mut scope = Scope::new();
+    for iden in &path.scope {
+        scope.push_iden(import_iden(iden, types));
+    }
+    SymbolPath::new(path.lib.clone(), scope, import_iden(&path.iden, types))
+}
+
+fn get_symbol(syms: &RootSymbolTable, path: &PathMeta, types: &[Option<TypeHandle>]) -> Result<SymbolRef, Diagnostic> {
+    let path = import_path(path, types);
+    syms.get_symbol_from_path(&path)
+        .ok_or_else(|| Diagnostic::new(Severity::Error, format!("Library metadata refers to `{path}`, which is not loaded")))
+}
+
+fn get_precedence(syms: &RootSymbolTable, lib: &LibraryPath, name: &str) -> Result<SymbolRef, Diagnostic> {
+    syms.get_direct_precedence(lib, name)
+        .ok_or_else(|| Diagnostic::new(Severity::Error, format!("Library metadata refers to precedence `{lib}.{name}`, which is not loaded")))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs, path::Path};
+
+    use crate::{
+        common::{LibraryPath, RootSymbolTable, TraitDag},
+        frontend::FrontEnd,
+        lexer::PuncutationTable,
+        type_system::TypeRegistry,
+    };
+
+    use super::{Header, IdenMeta, LibraryMetadata, PathMeta, SymbolKindMeta, SymbolMeta};
+
+    fn export_core() -> LibraryMetadata {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap().join("core/src/lib.xn");
+        let front_end = FrontEnd::for_root(&root, &HashMap::new());
+        assert!(!front_end.has_errors);
+        let ctx = &front_end.ctx;
+        let metadata = LibraryMetadata::export(&front_end.lib_path, &ctx.syms.read(), &ctx.trait_dag.read(), &ctx.puncts.read());
+        metadata
+    }
+
+    struct Tables {
+        syms:      RootSymbolTable,
+        type_reg:  TypeRegistry,
+        trait_dag: TraitDag,
+        puncts:    PuncutationTable,
+    }
+
+    impl Tables {
+        fn new() -> Self {
+            let lib = LibraryPath { group: None, package: "user".to_string(), library: "user".to_string() };
+            Self { syms: RootSymbolTable::new(lib), type_reg: TypeRegistry::new(), trait_dag: TraitDag::new(), puncts: PuncutationTable::new() }
+        }
+
+        fn import(&mut self, metadata: &LibraryMetadata) -> Result<(), String> {
+            metadata.import(&mut self.syms, &mut self.type_reg, &mut self.trait_dag, &self.puncts).map_err(|err| err.message)
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let metadata = export_core();
+        assert!(!metadata.symbols.is_empty() && !metadata.traits.is_empty() && !metadata.precedences.is_empty() && !metadata.op_sets.is_empty());
+
+        let path = std::env::temp_dir().join(format!("xenon-metadata-{}", std::process::id())).join("core.xnlib");
+        metadata.store(&path).unwrap();
+        let loaded = LibraryMetadata::load(&path).unwrap();
+        assert!(LibraryMetadata::is_from_current_build(&path));
+
+        // Metadata written by a different build of the compiler is rejected, even when the package version is the same
+        let header = Header { compiler: env!("CARGO_PKG_VERSION").to_string(), ..Header::new() };
+        let mut bytes = bincode::serialize(&header).unwrap();
+        bincode::serialize_into(&mut bytes, &metadata).unwrap();
+        fs::write(&path, bytes).unwrap();
+        let err = LibraryMetadata::load(&path).err().unwrap().message;
+        assert!(err.contains("different compiler build"), "{err}");
+        assert!(!LibraryMetadata::is_from_current_build(&path));
+        _ = fs::remove_dir_all(path.parent().unwrap());
+
+        let mut tables = Tables::new();
+        tables.import(&loaded).unwrap();
+        assert!(tables.syms.has_lib(&metadata.lib));
+
+        // Exporting the imported library gives back the same metadata
+        let reexported = LibraryMetadata::export(&metadata.lib, &tables.syms, &tables.trait_dag, &tables.puncts);
+        assert_eq!(bincode::serialize(&reexported).unwrap(), bincode::serialize(&metadata).unwrap());
+
+        let err = tables.import(&loaded).unwrap_err();
+        assert!(err.contains("is already loaded"), "{err}");
+    }
+
+    #[test]
+    fn test_invalid_metadata_is_not_imported() {
+        let iden = |name: &str| IdenMeta { name: name.to_string(), params: Vec::new(), gen_args: Vec::new() };
+
+        let mut metadata = export_core();
+        let num_types = metadata.types.len() as u32;
+        metadata.symbols.push(SymbolMeta {
+            path: PathMeta { lib: metadata.lib.clone(), scope: Vec::new(), iden: iden("Broken") },
+            vis: None,
+            ty: Some(num_types),
+            kind: SymbolKindMeta::Const,
+        });
+        let mut tables = Tables::new();
+        let err = tables.import(&metadata).unwrap_err();
+        assert!(err.contains("the type of `") && err.contains("Broken` is out of range"), "{err}");
+        assert!(!tables.syms.has_lib(&metadata.lib));
+        assert!(tables.trait_dag.iter().next().is_none());
+
+        let mut metadata = export_core();
+        metadata.symbols.push(SymbolMeta {
+            path: PathMeta { lib: metadata.lib.clone(), scope: vec![iden("missing")], iden: iden("Nested") },
+            vis: None,
+            ty: None,
+            kind: SymbolKindMeta::Const,
+        });
+        let err = tables.import(&metadata).unwrap_err();
+        assert!(err.contains("comes before the symbol it is nested in"), "{err}");
+        assert!(!tables.syms.has_lib(&metadata.lib));
+
+        let mut metadata = export_core();
+        metadata.op_sets[0].bases.push((LibraryPath { group: None, package: "dep".to_string(), library: "dep".to_string() }, "Missing".to_string()));
+        let err = tables.import(&metadata).unwrap_err();
+        assert!(err.contains("requires operator set `dep:dep.Missing`"), "{err}");
+        assert!(!tables.syms.has_lib(&metadata.lib) && !tables.syms.has_precedence_for_lib(&metadata.lib));
+
+        // The unmodified metadata can still be imported afterwards
+        tables.import(&export_core()).unwrap();
+    }
+}
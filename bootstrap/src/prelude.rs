@@ -0,0 +1,90 @@
+// Implicit core prelude
+//
+// Every library, except the core library itself, implicitly imports the core library, which makes its precedences and operator traits available,
+// and uses all items in the prelude modules of the core library.
+//
+// The root module of a library can opt out of this using a module attribute:
+// - `@!no_prelude`: the core library is still imported, but none of its precedences, operators or items are used
+// - `@!no_core`: the core library is not imported at all
+
+use crate::{
+    ast::*,
+    common::{LibraryPath, NameId, NameTable, OpUsePath, PrecedenceUsePath, RootUseTable, Scope, UsePath, UsePathKind},
+};
+
+/// Modules in the core library of which all items are used by every library
+pub const PRELUDE_MODULES: &[&str] = &[
+    "ops",
+    "cmp",
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PreludeMode {
+    /// Import the core library and use its prelude
+    Full,
+    /// Import the core library, but don't use its prelude
+    NoPrelude,
+    /// Don't import the core library
+    NoCore,
+}
+
+impl PreludeMode {
+    /// Get the prelude mode requested by the module attributes in the root file of a library
+    pub fn from_attributes(ast: &Ast, names: &NameTable) -> Self {
+        let mut scan = PreludeAttributeScan {
+            no_prelude_name_id: names.get_id_for_str("no_prelude"),
+            no_core_name_id:    names.get_id_for_str("no_core"),
+            mode:               PreludeMode::Full,
+        };
+        scan.visit(ast);
+        scan.mode
+    }
+}
+
+struct PreludeAttributeScan {
+    no_prelude_name_id: NameId,
+    no_core_name_id:    NameId,
+    mode:               PreludeMode,
+}
+
+impl Visitor for PreludeAttributeScan {
+    // Attributes inside of inline modules belong to that module, not to the root
+    fn visit_item(&mut self, item: &Item) where Self: Sized {
+        match item {
+            Item::Module(module) => for attr in &module.attrs {
+                self.visit_attribute(attr);
+            },
+            _ => helpers::visit_item(self, item),
+        }
+    }
+
+    fn visit_attribute(&mut self, node: &AstNodeRef<Attribute>) where Self: Sized {
+        if !node.is_mod || node.path.names.len() != 1 {
+            return;
+        }
+
+        let name = node.path.names[0].0;
+        if name == self.no_core_name_id {
+            self.mode = PreludeMode::NoCore;
+        } else if name == self.no_prelude_name_id && self.mode == PreludeMode::Full {
+            self.mode = PreludeMode::NoPrelude;
+        }
+    }
+}
+
+/// Use the precedences, operators and prelude items of the core library in the library the use table belongs to
+pub fn add_prelude_uses(uses: &mut RootUseTable, core: &LibraryPath) {
+    uses.add_precedence_use(PrecedenceUsePath { lib: core.clone(), precedence: None });
+    uses.add_op_use(OpUsePath { lib: core.clone(), op_set: None });
+
+    for module in PRELUDE_MODULES {
+        let mut path = Scope::new();
+        path.push(module.to_string());
+        uses.add_use(&Scope::new(), UsePath {
+            lib_path: core.clone(),
+            path,
+            kind: UsePathKind::Wildcard,
+            last_in_scope: false,
+        });
+    }
+}
@@ -0,0 +1,283 @@
+// Build targets, the libraries and binaries that are built for an input, in the order they need to be built in.
+//
+// A package is built after the libraries of the packages it depends on, which are always rebuilt.
+// The core library is only imported when it is passed explicitly, or named by the manifest of the package that is built,
+// and is only rebuilt when its metadata is out of date.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time,
+};
+
+use crate::{
+    common::{Diagnostic, LibraryPath, Severity},
+    frontend::FrontEnd,
+    manifest::{self, ArtifactKind, Manifest},
+    metadata::{LibraryMetadata, METADATA_EXTENSION},
+};
+
+/// Artifact to build
+#[derive(Clone)]
+pub struct BuildTarget {
+    pub lib_path:      LibraryPath,
+    pub kind:          ArtifactKind,
+    pub root:          String,
+    /// Directory of the incremental cache
+    pub cache_dir:     PathBuf,
+    /// Path the library metadata is written to, only libraries in a package emit metadata
+    pub metadata_out:  Option<PathBuf>,
+    /// Metadata of the libraries in the packages this artifact depends on, ordered so that each library comes after its dependencies
+    pub dependencies:  Vec<PathBuf>,
+    /// Core library that is implicitly imported, if any
+    pub core:          Option<CoreLibrary>,
+    /// Whether the artifact is a library of a dependency, including the core library
+    pub is_dependency: bool,
+}
+
+impl BuildTarget {
+    /// Whether the metadata of the library was written by this build of the compiler, after the last change to its sources
+    pub fn is_up_to_date(&self) -> bool {
+        let Some(metadata) = &self.metadata_out else { return false };
+        let src_dir = Path::new(&self.root).parent().unwrap_or(Path::new("."));
+        match (modified_time(metadata), newest_modified_time(src_dir)) {
+            (Some(built), Some(changed)) => built >= changed && LibraryMetadata::is_from_current_build(metadata),
+            _                            => false,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct CoreLibrary {
+    pub lib_path: LibraryPath,
+    pub metadata: PathBuf,
+}
+
+/// Get the artifacts to build, a single root file is built on its own, otherwise all artifacts in the package manifest are built, after the libraries of its dependencies.
+///
+/// The core library is `core` when it is passed, otherwise the one named by the manifest of the package, or of the package containing the root file.
+/// If the core library needs to be built from its sources, it is built first
+pub fn build_targets(input: &Path, core: Option<&Path>, no_core: bool) -> Result<Vec<BuildTarget>, Vec<Diagnostic>> {
+    let (mut targets, package_core) = package_targets(input)?;
+
+    let core = match no_core {
+        true  => None,
+        false => core.map(Path::to_path_buf).or(package_core),
+    };
+    let (core, core_target) = match core.map(|core| locate_core(&core)).transpose() {
+        Ok(Some((core, core_target))) => (Some(core), core_target),
+        Ok(None)                      => (None, None),
+        Err(err)                      => return Err(vec![err]),
+    };
+
+    for target in &mut targets {
+        target.core = core.clone().filter(|core| core.lib_path != target.lib_path);
+    }
+    if let Some(core_target) = core_target {
+        if !targets.iter().any(|target| target.lib_path == core_target.lib_path) {
+            targets.insert(0, core_target);
+        }
+    }
+    Ok(targets)
+}
+
+/// Get the targets of the library or binary with `root` as its root file, split into the libraries it depends on and the target itself.
+/// When the file is the root of an artifact in a package, the package is resolved like it would be when it is built, otherwise the file is built on its own
+pub fn targets_for_root(root: &Path, core: Option<&Path>, no_core: bool) -> Result<(Vec<BuildTarget>, BuildTarget), Vec<Diagnostic>> {
+    let package = Manifest::find(root)
+        .and_then(|path| Manifest::load(&path).ok())
+        .filter(|package| package.artifact_for_root(root).is_some());
+    let input = package.as_ref().map_or(root, |package| package.path.as_path());
+
+    let mut targets = build_targets(input, core, no_core)?;
+    let canonical = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let root = canonical(root);
+    let Some(idx) = targets.iter().position(|target| !target.is_dependency && canonical(Path::new(&target.root)) == root) else {
+        return Err(vec![Diagnostic::new(Severity::Error, format!("{} is not the root of an artifact of its package", root.display()))]);
+    };
+    let target = targets.remove(idx);
+    targets.retain(|target| target.is_dependency);
+    Ok((targets, target))
+}
+
+/// Get the target to build a single root file on its own, a file named `main.xn` is a binary, other files are libraries
+pub fn file_target(root: &Path) -> BuildTarget {
+    let kind = match root.file_name() {
+        Some(name) if name == "main.xn" => ArtifactKind::Binary,
+        _                               => ArtifactKind::StaticLibrary,
+    };
+    let lib_path = FrontEnd::lib_path_for_root(root);
+    BuildTarget {
+        cache_dir: incremental_cache_dir(root.parent().unwrap_or(Path::new(".")), &lib_path),
+        metadata_out: None,
+        dependencies: Vec::new(),
+        core: None,
+        is_dependency: false,
+        lib_path,
+        kind,
+        root: root.to_string_lossy().to_string(),
+    }
+}
+
+/// Get the targets of a package, or of a single root file, together with the core library named by the manifest of the package
+fn package_targets(input_path: &Path) -> Result<(Vec<BuildTarget>, Option<PathBuf>), Vec<Diagnostic>> {
+    if input_path.extension().is_some_and(|ext| ext == "xn") {
+        let package_core = Manifest::find(input_path)
+            .and_then(|path| Manifest::load(&path).ok())
+            .and_then(|package| package.core);
+        return Ok((vec![file_target(input_path)], package_core));
+    }
+
+    let packages = manifest::resolve_packages(input_path)?;
+    let num_packages = packages.len();
+    let mut targets = Vec::new();
+    for (idx, package) in packages.iter().enumerate() {
+        let is_dependency = idx + 1 != num_packages;
+        let dependencies = dependency_metadata(&packages[..idx], package);
+        for artifact in &package.artifacts {
+            if is_dependency && !artifact.kind.is_library() {
+                continue;
+            }
+            let lib_path = package.lib_path(artifact);
+            targets.push(BuildTarget {
+                cache_dir: incremental_cache_dir(package.dir(), &lib_path),
+                metadata_out: artifact.kind.is_library().then(|| metadata_path(package.dir(), &lib_path)),
+                dependencies: dependencies.clone(),
+                core: None,
+                is_dependency,
+                lib_path,
+                kind: artifact.kind,
+                root: artifact.root.to_string_lossy().to_string(),
+            });
+        }
+    }
+    // Packages are resolved in dependency order, so the package that is built comes last
+    let package_core = packages.last().and_then(|package| package.core.clone());
+    Ok((targets, package_core))
+}
+
+/// Get the core library from its package, or its prebuilt metadata.
+/// If the metadata of the core package is missing, older than its sources, or written by a different build of the compiler, a target to build it is returned as well
+fn locate_core(core: &Path) -> Result<(CoreLibrary, Option<BuildTarget>), Diagnostic> {
+    if core.extension().is_some_and(|ext| ext == METADATA_EXTENSION) {
+        let metadata = LibraryMetadata::load(core)?;
+        return Ok((CoreLibrary { lib_path: metadata.lib, metadata: core.to_path_buf() }, None));
+    }
+
+    let package = Manifest::load(core)?;
+    let Some(artifact) = package.artifacts.iter().find(|artifact| artifact.kind.is_library()) else {
+        return Err(Diagnostic::new(Severity::Error, format!("Core package '{}' does not contain a library", package.dir().display())));
+    };
+
+    let lib_path = package.lib_path(artifact);
+    let metadata = metadata_path(package.dir(), &lib_path);
+    let core = CoreLibrary { lib_path: lib_path.clone(), metadata: metadata.clone() };
+
+    let target = BuildTarget {
+        cache_dir: incremental_cache_dir(package.dir(), &lib_path),
+        metadata_out: Some(metadata),
+        dependencies: Vec::new(),
+        core: None,
+        is_dependency: true,
+        lib_path,
+        kind: artifact.kind,
+        root: artifact.root.to_string_lossy().to_string(),
+    };
+    match target.is_up_to_date() {
+        true  => Ok((core, None)),
+        false => Ok((core, Some(target))),
+    }
+}
+
+fn modified_time(path: &Path) -> Option<time::SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// Get the last time any source file in `dir` or its sub-directories was modified
+fn newest_modified_time(dir: &Path) -> Option<time::SystemTime> {
+    let mut newest = None;
+    for entry in fs::read_dir(dir).ok()?.flatten() {
+        let path = entry.path();
+        let modified = if path.is_dir() {
+            newest_modified_time(&path)
+        } else if path.extension().is_some_and(|ext| ext == "xn") {
+            modified_time(&path)
+        } else {
+            None
+        };
+        newest = newest.max(modified);
+    }
+    newest
+}
+
+/// Get the metadata paths of all libraries in the packages `package` depends on, directly or indirectly, `resolved` contains the packages before it
+fn dependency_metadata(resolved: &[Manifest], package: &Manifest) -> Vec<PathBuf> {
+    let canonical = |dir: &Path| fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf());
+
+    let mut is_dependency = vec![false; resolved.len()];
+    let mut to_visit: Vec<PathBuf> = package.dependencies.iter().map(|dep| canonical(&dep.dir)).collect();
+    while let Some(dir) = to_visit.pop() {
+        let Some(idx) = resolved.iter().position(|dep| canonical(dep.dir()) == dir) else { continue };
+        if !is_dependency[idx] {
+            is_dependency[idx] = true;
+            to_visit.extend(resolved[idx].dependencies.iter().map(|dep| canonical(&dep.dir)));
+        }
+    }
+
+    // Packages are resolved in dependency order, so keeping that order makes sure libraries are imported after their dependencies
+    let mut paths = Vec::new();
+    for (dep, _) in resolved.iter().zip(is_dependency).filter(|(_, is_dependency)| *is_dependency) {
+        for artifact in dep.artifacts.iter().filter(|artifact| artifact.kind.is_library()) {
+            paths.push(metadata_path(dep.dir(), &dep.lib_path(artifact)));
+        }
+    }
+    paths
+}
+
+fn incremental_cache_dir(package_dir: &Path, lib_path: &LibraryPath) -> PathBuf {
+    package_dir.join("target").join("incremental").join(format!("{}-{}", lib_path.package, lib_path.library))
+}
+
+fn metadata_path(package_dir: &Path, lib_path: &LibraryPath) -> PathBuf {
+    package_dir.join("target").join("lib").join(format!("{}-{}.{METADATA_EXTENSION}", lib_path.package, lib_path.library))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::Path, process};
+
+    use super::build_targets;
+
+    #[test]
+    fn test_core_is_explicit() {
+        let dir = env::temp_dir().join(format!("xenon-target-test-{}", process::id()));
+        for (name, manifest) in [
+            ("core", "[package]\nname = \"core\"\n"),
+            ("with_core", "[package]\nname = \"with_core\"\ncore = \"../core\"\n"),
+            // A `core` package next to a package isn't used when the package doesn't name it
+            ("without_core", "[package]\nname = \"without_core\"\n"),
+        ] {
+            fs::create_dir_all(dir.join(name).join("src")).unwrap();
+            fs::write(dir.join(name).join("package.toml"), manifest).unwrap();
+            fs::write(dir.join(name).join("src/lib.xn"), "").unwrap();
+        }
+
+        let core_of = |input: &Path, core: Option<&Path>, no_core: bool| {
+            let targets = build_targets(input, core, no_core).unwrap();
+            let target = targets.iter().find(|target| !target.is_dependency).unwrap();
+            target.core.as_ref().map(|core| core.lib_path.package.clone())
+        };
+
+        assert_eq!(core_of(&dir.join("with_core"), None, false).as_deref(), Some("core"));
+        assert_eq!(core_of(&dir.join("with_core/src/lib.xn"), None, false).as_deref(), Some("core"));
+        assert_eq!(core_of(&dir.join("with_core"), None, true), None);
+        assert_eq!(core_of(&dir.join("without_core"), None, false), None);
+        assert_eq!(core_of(&dir.join("without_core"), Some(&dir.join("core")), false).as_deref(), Some("core"));
+
+        // The core library is built first, as it has no metadata yet
+        let targets = build_targets(&dir.join("with_core"), None, false).unwrap();
+        assert!(targets[0].is_dependency && targets[0].lib_path.package == "core");
+
+        _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -21,6 +21,8 @@ pub struct TypeRegistry {
     slice_types:     Vec<TypeHandle>,
     pointer_types:   Vec<TypeHandle>,
     reference_types: Vec<TypeHandle>,
+    trait_objects:   Vec<TypeHandle>,
+    impl_traits:     Vec<TypeHandle>,
     placeholders:    Vec<TypeHandle>,
 }
 
@@ -39,6 +41,8 @@ impl TypeRegistry {
             slice_types: Vec::new(),
             pointer_types: Vec::new(),
             reference_types: Vec::new(),
+            trait_objects: Vec::new(),
+            impl_traits: Vec::new(),
             placeholders: Vec::new(),
         }
     }
@@ -61,6 +65,8 @@ impl TypeRegistry {
         count += self.slice_types.len();
         count += self.pointer_types.len();
         count += self.reference_types.len();
+        count += self.trait_objects.len();
+        count += self.impl_traits.len();
 
         count as u32
     }
@@ -148,6 +154,20 @@ impl TypeRegistry {
                 logger.log_fmt(format_args!("    - {ref_ty}\n"));
             }
         }
+
+        if !self.trait_objects.is_empty() {
+            logger.logln("- Trait object types:");
+            for dyn_ty in &self.trait_objects {
+                logger.log_fmt(format_args!("    - {dyn_ty}\n"));
+            }
+        }
+
+        if !self.impl_traits.is_empty() {
+            logger.logln("- Impl trait types:");
+            for impl_ty in &self.impl_traits {
+                logger.log_fmt(format_args!("    - {impl_ty}\n"));
+            }
+        }
     }
 
     pub fn log_dependencies(&self) {
@@ -386,6 +406,22 @@ impl TypeRegistry {
         ty
     }
 
+    // Like path types, the trait paths can't be resolved yet, so each trait object gets its own type
+    pub fn create_trait_object_type(&mut self, traits: Vec<SymbolPath>) -> TypeHandle {
+        let ty = TypeHandle::new(Type::TraitObject(TraitObjectType { traits }));
+        self.trait_objects.push(ty.clone());
+        self.dependencies.add(ty.clone());
+        ty
+    }
+
+    /// Each abstract return type stands in for a different concrete type, so they are never shared
+    pub fn create_impl_trait_type(&mut self, traits: Vec<SymbolPath>) -> TypeHandle {
+        let ty = TypeHandle::new(Type::ImplTrait(ImplTraitType { traits }));
+        self.impl_traits.push(ty.clone());
+        self.dependencies.add(ty.clone());
+        ty
+    }
+
     pub fn create_placeholder_type(&mut self) -> TypeHandle {
         let ty = TypeHandle::new(Type::Placeholder);
         self.placeholders.push(ty.clone());
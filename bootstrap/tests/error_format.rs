@@ -0,0 +1,61 @@
+// Tests for `--error-format=json`, whose stdout needs to be parseable as json lines, without any of the compiler's other output mixed in.
+
+use std::{
+    env,
+    fs,
+    process::{self, Command},
+};
+
+use serde_json::Value;
+
+/// Run the compiler on `source` with json diagnostics and any `flags`, returns the diagnostics parsed from stdout, and stderr
+fn run_json(name: &str, source: &str, flags: &[&str]) -> (Vec<Value>, String) {
+    let dir = env::temp_dir().join(format!("xenon-error-format-test-{name}-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("main.xn"), source).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_bootstrap"))
+        .current_dir(&dir)
+        .args(["--no-incremental", "--no-core", "--error-format=json"])
+        .args(flags)
+        .arg("main.xn")
+        .output()
+        .unwrap();
+    _ = fs::remove_dir_all(&dir);
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let diags = stdout.lines()
+        .map(|line| serde_json::from_str(line).unwrap_or_else(|err| panic!("stdout line is not json ({err}): {line}")))
+        .collect();
+    (diags, String::from_utf8_lossy(&output.stderr).into_owned())
+}
+
+fn codes(diags: &[Value]) -> Vec<&str> {
+    diags.iter().map(|diag| diag["code"].as_str().unwrap()).collect()
+}
+
+#[test]
+fn test_json_stdout() {
+    // Timings and dumps are requested as well, as they are printed in between the diagnostics
+    let (diags, stderr) = run_json("syntax", "fn main() {\n    a := 1 \u{00A7} 2;\n    b := ;\n}\n", &["--pass-timings", "--print-parse-output"]);
+    assert_eq!(codes(&diags), ["E1020", "E2202"]);
+    for diag in &diags {
+        assert_eq!(diag["severity"], "error");
+        assert_eq!(diag["file"], "main.xn");
+        assert!(diag["spans"].as_array().is_some_and(|spans| !spans.is_empty()));
+    }
+    assert!(diags[1]["line_start"] == 3 && diags[1]["column_start"] == 10);
+
+    // The banners are still written, but to stderr
+    assert!(stderr.contains("cwd: "));
+    assert!(stderr.contains("File path: "));
+    assert!(stderr.contains("===="));
+}
+
+#[test]
+fn test_json_stdout_hir() {
+    let source = "trait Trait {}\n\nfn main() {\n    let a: impl Trait = 0;\n}\n";
+    let (diags, stderr) = run_json("hir", source, &["--print-lowered-hir-code", "--stop-after", "ast-to-hir-lowering"]);
+    assert_eq!(codes(&diags), ["E3018"]);
+    assert!(stderr.contains("Lowered HIR pseudo-code:"));
+}
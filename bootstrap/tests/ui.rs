@@ -0,0 +1,535 @@
+// UI tests, runs the compiler on every `.xn` file in the `tests` directory at the root of the repository, and compares the output against the expectation files next to it.
+//
+// - `<name>.stderr` contains the expected diagnostics
+// - `<name>.out` contains the expected output of any `--print-*` flags passed to the compiler
+//
+// Tests are configured using `//@` directives:
+// - `//@ phase: lex|parse|check`: the last phase that is run, `parse` by default
+// - `//@ compile-flags: <flags>`: additional flags passed to the compiler
+// - `//@ known-bug: <description>`: the compiler is expected to panic on this file
+//
+// Diagnostics can also be annotated inline using `//~ ERROR E2001 <message>`, or `//~^ WARN ...` to refer to the line above it.
+// The code and message are optional, the message only needs to be part of the diagnostic's message.
+// When a file contains annotations, all diagnostics in the file need to be annotated.
+//
+// Tests are run with `-j4`, tests that reach the `check` phase are also run with `-j1`, and both runs need to give the same output,
+// so the order of the HIR and of the diagnostics does not depend on how items are split over the threads.
+//
+// Run with `cargo test --test ui -- --bless` to regenerate the expectation files, any other argument filters the tests by path.
+
+use std::{
+    env,
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    process::{self, Command, Stdio},
+    sync::{atomic::{AtomicUsize, Ordering}, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+/// Number of threads used for the main run, fixed so the output does not depend on the number of cores of the machine
+const JOBS: usize = 4;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Phase {
+    Lex,
+    Parse,
+    Check,
+}
+
+impl Phase {
+    fn flag(self) -> Option<&'static str> {
+        match self {
+            Phase::Lex   => Some("--lex-only"),
+            Phase::Parse => Some("--parse-only"),
+            Phase::Check => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "ERROR" | "error"                => Some(Severity::Error),
+            "WARN" | "WARNING" | "warning"   => Some(Severity::Warning),
+            _                                => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Severity::Error   => "ERROR",
+            Severity::Warning => "WARN",
+        }
+    }
+}
+
+/// Diagnostic expected by a `//~` annotation
+struct Annotation {
+    line:     usize,
+    severity: Severity,
+    code:     Option<String>,
+    message:  String,
+}
+
+/// Diagnostic reported by the compiler
+struct ReportedDiagnostic {
+    line:     Option<usize>,
+    severity: Severity,
+    code:     Option<String>,
+    message:  String,
+}
+
+struct TestConfig {
+    phase:       Phase,
+    flags:       Vec<String>,
+    known_bug:   bool,
+    annotations: Vec<Annotation>,
+}
+
+impl TestConfig {
+    fn parse(source: &str) -> Result<Self, String> {
+        let mut config = TestConfig {
+            phase: Phase::Parse,
+            flags: Vec::new(),
+            known_bug: false,
+            annotations: Vec::new(),
+        };
+
+        for (idx, line) in source.lines().enumerate() {
+            if let Some(directive) = line.trim_start().strip_prefix("//@") {
+                let (name, value) = directive.split_once(':').unwrap_or((directive, ""));
+                let value = value.trim();
+                match name.trim() {
+                    "phase" => config.phase = match value {
+                        "lex"   => Phase::Lex,
+                        "parse" => Phase::Parse,
+                        "check" => Phase::Check,
+                        _       => return Err(format!("line {}: unknown phase '{value}'", idx + 1)),
+                    },
+                    "compile-flags" => config.flags.extend(value.split_whitespace().map(str::to_string)),
+                    "known-bug"     => config.known_bug = true,
+                    name            => return Err(format!("line {}: unknown directive '{name}'", idx + 1)),
+                }
+            }
+
+            if let Some(pos) = line.find("//~") {
+                let annotation = &line[pos + 3..];
+                let offset = annotation.len() - annotation.trim_start_matches('^').len();
+                let mut parts = annotation[offset..].trim().splitn(2, char::is_whitespace);
+
+                let kind = parts.next().unwrap_or_default();
+                let Some(severity) = Severity::from_str(kind) else {
+                    return Err(format!("line {}: unknown annotation kind '{kind}'", idx + 1));
+                };
+                let mut rest = parts.next().unwrap_or_default().trim();
+                let mut code = None;
+                if let Some((first, tail)) = rest.split_once(char::is_whitespace).or(Some((rest, ""))) {
+                    if is_error_code(first) {
+                        code = Some(first.to_string());
+                        rest = tail.trim();
+                    }
+                }
+                if offset > idx {
+                    return Err(format!("line {}: annotation refers to a line before the start of the file", idx + 1));
+                }
+
+                config.annotations.push(Annotation {
+                    line: idx + 1 - offset,
+                    severity,
+                    code,
+                    message: rest.to_string(),
+                });
+            }
+        }
+        Ok(config)
+    }
+
+    /// Output is only compared when any output is explicitly requested
+    fn checks_stdout(&self) -> bool {
+        self.flags.iter().any(|flag| flag.starts_with("--print-"))
+    }
+}
+
+fn is_error_code(s: &str) -> bool {
+    s.len() > 1 && s.starts_with('E') && s[1..].chars().all(|ch| ch.is_ascii_digit())
+}
+
+// =============================================================================================================================
+
+struct TestOutput {
+    stdout:   String,
+    stderr:   String,
+    panicked: bool,
+}
+
+fn run_compiler(tests_dir: &Path, rel_path: &str, config: &TestConfig, jobs: usize) -> Result<TestOutput, String> {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_bootstrap"));
+    cmd.current_dir(tests_dir)
+        .env("RUST_BACKTRACE", "0")
+        // Tests are run from the source tree, so don't leave cache entries next to them
+        .arg("--no-incremental")
+        // Tests declare the precedences and operators they use themselves
+        .arg("--no-core")
+        // Catch passes that break HIR invariants at the pass itself, instead of at a later panic
+        .arg("--verify-hir")
+        .arg(format!("-j{jobs}"))
+        .args(config.phase.flag())
+        .args(&config.flags)
+        .arg(rel_path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    let mut child = cmd.spawn().map_err(|err| format!("failed to run the compiler: {err}"))?;
+    let read_pipe = |pipe: Option<Box<dyn Read + Send>>| thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            _ = pipe.read_to_end(&mut buf);
+        }
+        String::from_utf8_lossy(&buf).into_owned()
+    });
+    let stdout = read_pipe(child.stdout.take().map(|pipe| Box::new(pipe) as Box<dyn Read + Send>));
+    let stderr = read_pipe(child.stderr.take().map(|pipe| Box::new(pipe) as Box<dyn Read + Send>));
+
+    let start = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if start.elapsed() > TIMEOUT => {
+                _ = child.kill();
+                _ = child.wait();
+                return Err(format!("the compiler did not finish within {} seconds", TIMEOUT.as_secs()));
+            },
+            Ok(None) => thread::sleep(Duration::from_millis(5)),
+            Err(err) => return Err(format!("failed to wait for the compiler: {err}")),
+        }
+    };
+
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    let panicked = status.code() == Some(101) || stderr.contains("panicked at ");
+    Ok(TestOutput {
+        stdout: normalize(&stdout, tests_dir),
+        stderr: normalize(&stderr, tests_dir),
+        panicked,
+    })
+}
+
+/// Remove anything from the output that depends on the machine or on unrelated changes to the compiler
+fn normalize(output: &str, tests_dir: &Path) -> String {
+    let output = output.replace(&tests_dir.display().to_string(), "$DIR").replace('\r', "");
+
+    let mut out = String::new();
+    for line in output.lines() {
+        match normalize_panic(line) {
+            Some(line) => out.push_str(&line),
+            None       => out.push_str(line),
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Remove the thread id, line and column from `thread 'main' (1234) panicked at src/file.rs:12:34:`
+fn normalize_panic(line: &str) -> Option<String> {
+    let rest = line.strip_prefix("thread '")?;
+    let (thread, rest) = rest.split_once('\'')?;
+    let (_, location) = rest.split_once("panicked at ")?;
+    let location = location.strip_suffix(':')?;
+    let mut parts = location.rsplitn(3, ':');
+    let (_, _, file) = (parts.next()?, parts.next()?, parts.next()?);
+    Some(format!("thread '{thread}' panicked at {file}:LL:CC:"))
+}
+
+/// Collect the diagnostics from the human readable output of the compiler
+fn parse_diagnostics(stderr: &str, rel_path: &str) -> Vec<ReportedDiagnostic> {
+    let mut diags: Vec<ReportedDiagnostic> = Vec::new();
+    let mut has_location = false;
+    for line in stderr.lines() {
+        if let Some((header, message)) = line.split_once(": ") {
+            let (severity, code) = match header.split_once('[') {
+                Some((severity, code)) => (severity, code.strip_suffix(']').filter(|code| is_error_code(code))),
+                None                   => (header, None),
+            };
+            if let Some(severity) = Severity::from_str(severity).filter(|_| !severity.contains(char::is_whitespace)) {
+                diags.push(ReportedDiagnostic {
+                    line: None,
+                    severity,
+                    code: code.map(str::to_string),
+                    message: message.to_string(),
+                });
+                has_location = false;
+                continue;
+            }
+        }
+
+        // Only the first location is the primary one
+        if let Some(location) = line.trim_start().strip_prefix("--> ") {
+            if has_location {
+                continue;
+            }
+            has_location = true;
+
+            let mut parts = location.rsplitn(3, ':');
+            let (_, row, file) = (parts.next(), parts.next(), parts.next());
+            if let (Some(diag), Some(row), Some(file)) = (diags.last_mut(), row, file) {
+                if file == rel_path {
+                    diag.line = row.parse().ok();
+                }
+            }
+        }
+    }
+    diags
+}
+
+fn check_annotations(config: &TestConfig, diags: Vec<ReportedDiagnostic>, errors: &mut Vec<String>) {
+    if config.annotations.is_empty() {
+        return;
+    }
+
+    let mut unmatched: Vec<ReportedDiagnostic> = diags.into_iter().filter(|diag| diag.line.is_some()).collect();
+    for annotation in &config.annotations {
+        let pos = unmatched.iter().position(|diag|
+            diag.line == Some(annotation.line) &&
+            diag.severity == annotation.severity &&
+            (annotation.code.is_none() || diag.code == annotation.code) &&
+            diag.message.contains(&annotation.message)
+        );
+        match pos {
+            Some(pos) => _ = unmatched.remove(pos),
+            None => errors.push(format!(
+                "line {}: expected {} {}{} was not reported",
+                annotation.line,
+                annotation.severity.as_str(),
+                annotation.code.as_deref().unwrap_or(""),
+                if annotation.message.is_empty() { String::new() } else { format!(" '{}'", annotation.message) },
+            )),
+        }
+    }
+
+    for diag in unmatched {
+        errors.push(format!(
+            "line {}: unexpected {} {}'{}'",
+            diag.line.unwrap_or_default(),
+            diag.severity.as_str(),
+            diag.code.map_or(String::new(), |code| format!("{code} ")),
+            diag.message,
+        ));
+    }
+}
+
+/// Compare the output against an expectation file, or update the file when blessing
+fn check_output(expected_path: &Path, actual: &str, required: bool, bless: bool, errors: &mut Vec<String>) {
+    if bless {
+        let result = if actual.is_empty() || !required {
+            match fs::remove_file(expected_path) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+                _ => Ok(()),
+            }
+        } else {
+            fs::write(expected_path, actual)
+        };
+        if let Err(err) = result {
+            errors.push(format!("failed to update {}: {err}", expected_path.display()));
+        }
+        return;
+    }
+
+    let expected = fs::read_to_string(expected_path).unwrap_or_default().replace('\r', "");
+    let actual = if required { actual } else { "" };
+    if expected != actual {
+        let name = expected_path.file_name().unwrap_or_default().to_string_lossy();
+        errors.push(format!("{name} does not match the expected output:\n{}", diff(&expected, actual)));
+    }
+}
+
+/// Line based diff of the expected and actual output
+fn diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+
+    // Longest common subsequence, filled from the back
+    let mut lcs = vec![vec![0usize; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lcs[i][j] = if expected[i] == actual[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            out.push_str(&format!("  {}\n", expected[i]));
+            i += 1;
+            j += 1;
+        } else if i < expected.len() && (j == actual.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            out.push_str(&format!("- {}\n", expected[i]));
+            i += 1;
+        } else {
+            out.push_str(&format!("+ {}\n", actual[j]));
+            j += 1;
+        }
+    }
+    out
+}
+
+// =============================================================================================================================
+
+fn run_test(tests_dir: &Path, path: &Path, bless: bool) -> Result<(), Vec<String>> {
+    let rel_path = path.strip_prefix(tests_dir).unwrap_or(path).to_string_lossy().replace('\\', "/");
+    let source = fs::read(path).map_err(|err| vec![format!("failed to read the test: {err}")])?;
+    let config = TestConfig::parse(&decode_source(&source)).map_err(|err| vec![err])?;
+
+    let output = run_compiler(tests_dir, &rel_path, &config, JOBS).map_err(|err| vec![err])?;
+
+    let mut errors = Vec::new();
+    match (output.panicked, config.known_bug) {
+        (true, false) => errors.push("the compiler panicked".to_string()),
+        (false, true) => errors.push("the known bug no longer reproduces, remove the `known-bug` directive".to_string()),
+        _ => {},
+    }
+
+    check_annotations(&config, parse_diagnostics(&output.stderr, &rel_path), &mut errors);
+    check_output(&path.with_extension("stderr"), &output.stderr, true, bless, &mut errors);
+    check_output(&path.with_extension("out"), &output.stdout, config.checks_stdout(), bless, &mut errors);
+
+    // Lexing and parsing are done per file, so only the HIR passes can split a single test over multiple threads
+    if config.phase == Phase::Check && !config.known_bug {
+        match run_compiler(tests_dir, &rel_path, &config, 1) {
+            Ok(serial) => {
+                if serial.stderr != output.stderr {
+                    errors.push(format!("stderr with -j1 differs from -j{JOBS}:\n{}", diff(&serial.stderr, &output.stderr)));
+                }
+                if serial.stdout != output.stdout {
+                    errors.push(format!("stdout with -j1 differs from -j{JOBS}:\n{}", diff(&serial.stdout, &output.stdout)));
+                }
+            },
+            Err(err) => errors.push(err),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Decode a test to find its directives, utf-16 and utf-32 tests are recognized by their BOM.
+/// Anything else is decoded as utf-8, so tests with invalid utf-8 can still contain directives.
+fn decode_source(bytes: &[u8]) -> String {
+    let utf16 = |bytes: &[u8], from_bytes: fn([u8; 2]) -> u16| {
+        let units = bytes.chunks_exact(2).map(|unit| from_bytes([unit[0], unit[1]]));
+        char::decode_utf16(units).map(|ch| ch.unwrap_or(char::REPLACEMENT_CHARACTER)).collect()
+    };
+    let utf32 = |bytes: &[u8], from_bytes: fn([u8; 4]) -> u32| {
+        bytes.chunks_exact(4)
+            .map(|unit| char::from_u32(from_bytes([unit[0], unit[1], unit[2], unit[3]])).unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect()
+    };
+
+    // utf-32 (le) needs to come before utf-16 (le), as it starts with the same bytes
+    if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE, 0x00, 0x00]) {
+        utf32(rest, u32::from_le_bytes)
+    } else if let Some(rest) = bytes.strip_prefix(&[0x00, 0x00, 0xFE, 0xFF]) {
+        utf32(rest, u32::from_be_bytes)
+    } else if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        utf16(rest, u16::from_le_bytes)
+    } else if let Some(rest) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        utf16(rest, u16::from_be_bytes)
+    } else {
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+}
+
+fn collect_tests(dir: &Path, tests: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_tests(&path, tests);
+        } else if path.extension().is_some_and(|ext| ext == "xn") {
+            tests.push(path);
+        }
+    }
+}
+
+fn main() {
+    let mut bless = false;
+    let mut filters = Vec::new();
+    for arg in env::args().skip(1) {
+        if arg == "--bless" {
+            bless = true;
+        } else if !arg.starts_with('-') {
+            filters.push(arg);
+        }
+    }
+
+    let tests_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../tests");
+    let tests_dir = tests_dir.canonicalize().unwrap_or(tests_dir);
+
+    let mut tests = Vec::new();
+    collect_tests(&tests_dir, &mut tests);
+    tests.retain(|path| filters.is_empty() || filters.iter().any(|filter| path.to_string_lossy().replace('\\', "/").contains(filter.as_str())));
+    tests.sort();
+
+    println!("\nrunning {} ui tests", tests.len());
+
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::new());
+    let num_threads = thread::available_parallelism().map_or(1, |num| num.get());
+    thread::scope(|scope| {
+        for _ in 0..num_threads {
+            scope.spawn(|| loop {
+                let idx = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = tests.get(idx) else { break };
+                let result = run_test(&tests_dir, path, bless);
+                results.lock().unwrap().push((idx, result));
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(idx, _)| *idx);
+
+    let mut failures = Vec::new();
+    for (idx, result) in results {
+        let rel_path = tests[idx].strip_prefix(&tests_dir).unwrap_or(&tests[idx]).display().to_string();
+        match result {
+            Ok(()) => println!("test {rel_path} ... ok"),
+            Err(errors) => {
+                println!("test {rel_path} ... FAILED");
+                failures.push((rel_path, errors));
+            },
+        }
+    }
+
+    for (rel_path, errors) in &failures {
+        println!("\n---- {rel_path} ----");
+        for error in errors {
+            println!("{error}");
+        }
+    }
+
+    let num_passed = tests.len() - failures.len();
+    println!("\nui test result: {}. {num_passed} passed; {} failed", if failures.is_empty() { "ok" } else { "FAILED" }, failures.len());
+    if !failures.is_empty() {
+        if !bless {
+            println!("To update the expected output, run `cargo test --test ui -- --bless`");
+        }
+        process::exit(1);
+    }
+}